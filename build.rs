@@ -0,0 +1,19 @@
+// Records the git commit the binary is built from, for session manifests
+// Builds outside a git checkout (e.g. from crates.io) simply have no hash
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+
+    let describe = Command::new("git")
+        .args(["describe", "--always", "--dirty", "--abbrev=12"])
+        .output();
+    if let Ok(output) = describe {
+        let hash = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !hash.is_empty() {
+            println!("cargo:rustc-env=SOULWHISTLE_GIT_HASH={}", hash);
+        }
+    }
+}
@@ -0,0 +1,471 @@
+// Recording analysis: finds the carriers and binaural beat of a stereo recording over time and
+// splits it into sections, producing the same JSON as tools/coherence_analyzer.py (data/*_analysis.json)
+// The steps, thresholds and their quirks follow the Python tool so the two can be cross-checked
+
+use std::collections::HashMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::audio::AudioParams;
+use crate::coherence::{brainwave_state_name, BeingType, CoherenceParams};
+use crate::constants::*;
+use crate::dsp::DftPlan;
+use crate::wav::WavAudio;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AnalysisChunk {
+    pub time: f64,
+    pub left_freq: f64,
+    pub right_freq: f64,
+    pub binaural_beat: f64,
+    pub wave_type: String,
+    pub left_top_freqs: Vec<(f64, f64)>, // (Hz, magnitude), largest first
+    pub right_top_freqs: Vec<(f64, f64)>,
+    pub amplitude: f64, // Mean RMS of both channels (16-bit PCM scale)
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SectionAverage {
+    pub left_freq: f64,
+    pub right_freq: f64,
+    pub binaural_beat: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AnalysisSection {
+    pub section_type: String,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub chunks: Vec<AnalysisChunk>,
+    pub average: SectionAverage,
+}
+
+/// A recording's analysis, as written to JSON
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Analysis {
+    pub file: String,
+    pub duration: f64,
+    pub sample_rate: u32,
+    pub sections: Vec<AnalysisSection>,
+}
+
+impl Analysis {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read analysis {}: {}", path.display(), e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid analysis {}: {}", path.display(), e))
+    }
+}
+
+/// Chunk length and overlap of the per-section analysis
+#[derive(Clone, Copy, Debug)]
+pub struct AnalysisSettings {
+    pub chunk_secs: f64,
+    pub overlap_secs: f64,
+}
+
+impl Default for AnalysisSettings {
+    fn default() -> Self {
+        Self { chunk_secs: ANALYZE_CHUNK_SECS, overlap_secs: ANALYZE_OVERLAP_SECS }
+    }
+}
+
+/// Dominant frequency, strongest peaks and level of one channel of a segment
+struct ChannelSpectrum {
+    dominant: f64,
+    top: Vec<(f64, f64)>,
+    rms: f64,
+}
+
+/// Spectra of both channels of equal-length segments, with a DFT plan and window per length
+struct Spectrum {
+    sample_rate: f64,
+    plans: HashMap<usize, (DftPlan, Vec<f64>)>,
+}
+
+impl Spectrum {
+    fn new(sample_rate: u32) -> Self {
+        Self { sample_rate: sample_rate as f64, plans: HashMap::new() }
+    }
+
+    /// Hann-windowed spectra of left and right over [start, end)
+    fn analyze(&mut self, audio: &WavAudio, start: usize, end: usize) -> (ChannelSpectrum, ChannelSpectrum) {
+        let (left, right) = (&audio.left[start..end], &audio.right[start..end]);
+        let n = left.len();
+        let (plan, window) = self.plans.entry(n).or_insert_with(|| {
+            // Symmetric Hann, as scipy.signal.windows.hann
+            let window = (0..n)
+                .map(|i| if n < 2 { 1.0 } else { 0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / (n - 1) as f64).cos() })
+                .collect();
+            (DftPlan::new(n), window)
+        });
+
+        let windowed = |channel: &[f32]| -> Vec<f64> { channel.iter().zip(window.iter()).map(|(&x, &w)| x as f64 * w).collect() };
+        let (left_bins, right_bins) = plan.real_pair(&windowed(left), &windowed(right));
+        let left_mag: Vec<f64> = left_bins.iter().map(|c| c.norm()).collect();
+        let right_mag: Vec<f64> = right_bins.iter().map(|c| c.norm()).collect();
+
+        let bin_hz = self.sample_rate / n as f64;
+        (channel_spectrum(&left_mag, bin_hz, left), channel_spectrum(&right_mag, bin_hz, right))
+    }
+}
+
+fn channel_spectrum(magnitude: &[f64], bin_hz: f64, segment: &[f32]) -> ChannelSpectrum {
+    let rms = (segment.iter().map(|&x| x as f64 * x as f64).sum::<f64>() / segment.len().max(1) as f64).sqrt();
+    let in_range: Vec<(f64, f64)> = magnitude.iter().enumerate()
+        .map(|(k, &m)| (k as f64 * bin_hz, m))
+        .filter(|(f, _)| (ANALYZE_MIN_FREQ_HZ..=ANALYZE_MAX_FREQ_HZ).contains(f))
+        .collect();
+    let Some(&(dominant, peak)) = in_range.iter().reduce(|best, bin| if bin.1 > best.1 { bin } else { best }) else {
+        return ChannelSpectrum { dominant: 0.0, top: Vec::new(), rms };
+    };
+
+    let mags: Vec<f64> = in_range.iter().map(|(_, m)| *m).collect();
+    let mut top: Vec<(f64, f64)> = local_maxima(&mags).into_iter()
+        .map(|i| in_range[i])
+        .filter(|(_, m)| *m >= peak * ANALYZE_PEAK_MIN_RATIO)
+        .collect();
+    top.sort_by(|a, b| b.1.total_cmp(&a.1));
+    top.truncate(ANALYZE_TOP_PEAKS);
+    ChannelSpectrum { dominant, top, rms }
+}
+
+/// Local maxima excluding the ends; a flat top counts once, at its middle (as scipy.signal.find_peaks)
+fn local_maxima(x: &[f64]) -> Vec<usize> {
+    let mut peaks = Vec::new();
+    let mut i = 1;
+    while i + 1 < x.len() {
+        if x[i - 1] < x[i] {
+            let mut ahead = i + 1;
+            while ahead + 1 < x.len() && x[ahead] == x[i] {
+                ahead += 1;
+            }
+            if x[ahead] < x[i] {
+                peaks.push((i + ahead - 1) / 2);
+                i = ahead;
+            }
+        }
+        i += 1;
+    }
+    peaks
+}
+
+/// Savitzky-Golay smoothing; the ends use the polynomial fitted to the first/last window (scipy's "interp")
+fn savgol(series: &[f64], window: usize, order: usize) -> Vec<f64> {
+    let n = series.len();
+    let half = window / 2;
+    (0..n)
+        .map(|i| {
+            let start = i.saturating_sub(half).min(n - window);
+            polyfit_eval(&series[start..start + window], order, (i - start) as f64)
+        })
+        .collect()
+}
+
+/// Least-squares polynomial fit of `y` at x = 0, 1, ... evaluated at `x`
+fn polyfit_eval(y: &[f64], order: usize, x: f64) -> f64 {
+    let terms = order + 1;
+    let scale = y.len() as f64; // Keeps the normal equations well conditioned
+    let mut a = vec![vec![0.0; terms + 1]; terms];
+    for (j, &yj) in y.iter().enumerate() {
+        let t = j as f64 / scale;
+        let powers: Vec<f64> = (0..terms).map(|p| t.powi(p as i32)).collect();
+        for r in 0..terms {
+            for c in 0..terms {
+                a[r][c] += powers[r] * powers[c];
+            }
+            a[r][terms] += powers[r] * yj;
+        }
+    }
+    // Gaussian elimination with partial pivoting
+    for col in 0..terms {
+        let pivot = (col..terms).max_by(|&p, &q| a[p][col].abs().total_cmp(&a[q][col].abs())).unwrap_or(col);
+        a.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in a.iter_mut().skip(col + 1) {
+            let factor = row[col] / pivot_row[col];
+            for (value, p) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * p;
+            }
+        }
+    }
+    let mut coef = vec![0.0; terms];
+    for row in (0..terms).rev() {
+        let known: f64 = (row + 1..terms).map(|c| a[row][c] * coef[c]).sum();
+        coef[row] = (a[row][terms] - known) / a[row][row];
+    }
+    let t = x / scale;
+    coef.iter().enumerate().map(|(p, c)| c * t.powi(p as i32)).sum()
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0.0, 0usize), |(s, c), v| (s + v, c + 1));
+    if count == 0 { 0.0 } else { sum / count as f64 }
+}
+
+/// "Delta", "Theta", ... for a beat frequency
+fn wave_type(beat_hz: f64) -> &'static str {
+    brainwave_state_name(beat_hz as f32).split(' ').next().unwrap_or("")
+}
+
+/// A point of the section-detection timeline
+struct TimelinePoint {
+    time: f64,
+    left_freq: f64,
+    binaural_beat: f64,
+    amplitude: f64, // Left channel RMS
+}
+
+/// A detected section before its chunk analysis
+struct SectionBounds {
+    section_type: String,
+    start_time: f64,
+    end_time: f64,
+}
+
+fn detect_sections(audio: &WavAudio, spectrum: &mut Spectrum) -> Vec<SectionBounds> {
+    let rate = audio.sample_rate as f64;
+    let window = (rate * ANALYZE_TIMELINE_WINDOW_SECS) as usize;
+    let hop = (rate * ANALYZE_TIMELINE_HOP_SECS) as usize;
+    let timeline: Vec<TimelinePoint> = (0..audio.left.len().saturating_sub(window)).step_by(hop)
+        .map(|i| {
+            let (left, right) = spectrum.analyze(audio, i, i + window);
+            TimelinePoint {
+                time: i as f64 / rate,
+                left_freq: left.dominant,
+                binaural_beat: (left.dominant - right.dominant).abs(),
+                amplitude: left.rms,
+            }
+        })
+        .collect();
+
+    if timeline.is_empty() {
+        return vec![SectionBounds { section_type: "full".to_string(), start_time: 0.0, end_time: audio.duration_secs() }];
+    }
+
+    let n = timeline.len();
+    let beat: Vec<f64> = timeline.iter().map(|p| p.binaural_beat).collect();
+    let carrier: Vec<f64> = timeline.iter().map(|p| p.left_freq).collect();
+    let (beat, carrier) = if n > 5 {
+        let window = ANALYZE_SMOOTH_WINDOW.min(if n % 2 == 1 { n } else { n - 1 });
+        (savgol(&beat, window, ANALYZE_SMOOTH_ORDER), savgol(&carrier, window, ANALYZE_SMOOTH_ORDER))
+    } else {
+        (beat, carrier)
+    };
+
+    // A jump between neighbours counts when the averages of three points either side differ too
+    let shifted = |series: &[f64], i: usize, threshold: f64| {
+        i + 2 < n && (mean(series[i..(i + 3).min(n)].iter().copied()) - mean(series[i.saturating_sub(3)..i].iter().copied())).abs() > threshold
+    };
+    let mut change_points = vec![0];
+    for i in 1..n {
+        if (beat[i] - beat[i - 1]).abs() > ANALYZE_BEAT_JUMP_HZ {
+            if shifted(&beat, i, ANALYZE_BEAT_SHIFT_HZ) {
+                change_points.push(i);
+            }
+        } else if (carrier[i] - carrier[i - 1]).abs() > ANALYZE_CARRIER_JUMP_HZ && shifted(&carrier, i, ANALYZE_CARRIER_SHIFT_HZ) {
+            change_points.push(i);
+        }
+    }
+    change_points.push(n - 1);
+
+    let mut kept = vec![change_points[0]];
+    for &point in &change_points[1..] {
+        if timeline[point].time - timeline[kept[kept.len() - 1]].time > ANALYZE_MIN_SECTION_SECS {
+            kept.push(point);
+        }
+    }
+
+    // As in the Python tool, the last section ends at the start of the last timeline window
+    let mean_amplitude = mean(timeline.iter().map(|p| p.amplitude));
+    let total = kept.len() - 1;
+    kept.windows(2).enumerate()
+        .map(|(i, pair)| {
+            let points = &timeline[pair[0]..=pair[1]];
+            let amplitude = mean(points.iter().map(|p| p.amplitude));
+            let quiet = amplitude < mean_amplitude * ANALYZE_QUIET_RATIO;
+            let number = i + 1;
+            let section_type = if number == 1 && quiet {
+                "intro".to_string()
+            } else if number == total && quiet {
+                "outro".to_string()
+            } else {
+                let beat = mean(points.iter().map(|p| p.binaural_beat));
+                format!("section_{}_{}", number, wave_type(beat).to_lowercase())
+            };
+            SectionBounds { section_type, start_time: timeline[pair[0]].time, end_time: timeline[pair[1]].time }
+        })
+        .collect()
+}
+
+fn analyze_section(audio: &WavAudio, spectrum: &mut Spectrum, bounds: SectionBounds, settings: AnalysisSettings) -> AnalysisSection {
+    let rate = audio.sample_rate as f64;
+    let start = (bounds.start_time * rate) as usize;
+    let end = (bounds.end_time * rate) as usize;
+    let chunk = (settings.chunk_secs * rate) as usize;
+    let hop = (((settings.chunk_secs - settings.overlap_secs) * rate) as usize).max(1);
+
+    let chunks: Vec<AnalysisChunk> = (start..end.saturating_sub(chunk)).step_by(hop)
+        .map(|chunk_start| {
+            let (left, right) = spectrum.analyze(audio, chunk_start, chunk_start + chunk);
+            let beat = (left.dominant - right.dominant).abs();
+            AnalysisChunk {
+                time: chunk_start as f64 / rate,
+                left_freq: left.dominant,
+                right_freq: right.dominant,
+                binaural_beat: beat,
+                wave_type: wave_type(beat).to_string(),
+                left_top_freqs: left.top,
+                right_top_freqs: right.top,
+                amplitude: (left.rms + right.rms) / 2.0,
+            }
+        })
+        .collect();
+
+    AnalysisSection {
+        section_type: bounds.section_type,
+        start_time: bounds.start_time,
+        end_time: bounds.end_time,
+        average: SectionAverage {
+            left_freq: mean(chunks.iter().map(|c| c.left_freq)),
+            right_freq: mean(chunks.iter().map(|c| c.right_freq)),
+            binaural_beat: mean(chunks.iter().map(|c| c.binaural_beat)),
+        },
+        chunks,
+    }
+}
+
+/// Detect sections, then analyze each in overlapping chunks
+pub fn analyze(audio: &WavAudio, file: &str, settings: AnalysisSettings) -> Analysis {
+    let mut spectrum = Spectrum::new(audio.sample_rate);
+    let sections = detect_sections(audio, &mut spectrum).into_iter()
+        .map(|bounds| analyze_section(audio, &mut spectrum, bounds, settings))
+        .collect();
+    Analysis { file: file.to_string(), duration: audio.duration_secs(), sample_rate: audio.sample_rate, sections }
+}
+
+/// Turn an analysis into a preset: carriers and beat from the dominant section, phases from the sections at that beat, harmonics from the dominant section's peaks
+pub fn preset_from_analysis(analysis: &Analysis) -> Result<AudioParams, String> {
+    let entrains = |s: &AnalysisSection| {
+        (BEAT_MIN_HZ as f64..=BEAT_MAX_HZ as f64).contains(&s.average.binaural_beat)
+            && s.average.left_freq.min(s.average.right_freq) >= ANALYSIS_PRESET_MIN_CARRIER_HZ
+    };
+    let length = |s: &AnalysisSection| s.end_time - s.start_time;
+    let candidates: Vec<&AnalysisSection> = analysis.sections.iter().filter(|s| entrains(s)).collect();
+    let near = |a: &AnalysisSection, b: &AnalysisSection| (a.average.binaural_beat - b.average.binaural_beat).abs() <= ANALYSIS_PRESET_BEAT_TOL_HZ;
+    // Programs often break their main beat into short sections, so pick the beat played the longest in total,
+    // then the longest section at it
+    let time_near = |anchor: &AnalysisSection| candidates.iter().filter(|s| near(s, anchor)).map(|s| length(s)).sum::<f64>();
+    let anchor = candidates.iter()
+        .max_by(|a, b| time_near(a).total_cmp(&time_near(b)).then(length(a).total_cmp(&length(b))))
+        .ok_or("No section of the analysis has a binaural beat")?;
+    let dominant = candidates.iter()
+        .filter(|s| near(s, anchor))
+        .max_by(|a, b| length(a).total_cmp(&length(b)))
+        .unwrap_or(anchor);
+    let beat = dominant.average.binaural_beat;
+
+    // Startup until the beat first plays, return after it last plays; induction ends with the dominant section
+    let at_beat: Vec<&AnalysisSection> = analysis.sections.iter()
+        .filter(|s| entrains(s) && (s.average.binaural_beat - beat).abs() <= ANALYSIS_PRESET_BEAT_TOL_HZ)
+        .collect();
+    let onset = at_beat.first().map_or(dominant.start_time, |s| s.start_time);
+    let last = at_beat.last().map_or(dominant.end_time, |s| s.end_time);
+    let end = analysis.duration.max(last);
+
+    let carriers = [dominant.average.left_freq, dominant.average.right_freq];
+    let harmonic = |hz: f64| {
+        if carriers.iter().any(|c| (c - hz).abs() <= ANALYSIS_PRESET_HARMONIC_TOL_HZ) {
+            return false; // That is the carrier itself
+        }
+        let near = |peaks: &[(f64, f64)]| peaks.iter().any(|(f, _)| (f - hz).abs() <= ANALYSIS_PRESET_HARMONIC_TOL_HZ);
+        let with = dominant.chunks.iter().filter(|c| near(&c.left_top_freqs) || near(&c.right_top_freqs)).count();
+        !dominant.chunks.is_empty() && with as f64 >= dominant.chunks.len() as f64 * ANALYSIS_PRESET_HARMONIC_SHARE
+    };
+
+    let round = |hz: f64| ((hz * 100.0).round() / 100.0) as f32;
+    let minutes = |secs: f64| ((secs / 60.0 * 10.0).round() / 10.0) as f32;
+    let name = Path::new(&analysis.file).file_stem().map_or(analysis.file.clone(), |s| s.to_string_lossy().to_string());
+    let wave = wave_type(beat).to_lowercase();
+
+    let coherence = CoherenceParams {
+        enabled: true,
+        being_type: BeingType::HumanCustom,
+        left_carrier: round(carriers[0]),
+        right_carrier: round(carriers[1]),
+        harmonic_220hz: harmonic(HARMONIC_220_HZ as f64),
+        harmonic_495hz: harmonic(HARMONIC_495_HZ as f64),
+        custom_binaural_hz: round(beat),
+        startup_duration_min: minutes(onset),
+        induction_duration_min: minutes(dominant.end_time - onset),
+        stabilization_duration_min: minutes(last - dominant.end_time),
+        return_duration_min: minutes(end - last),
+        ..CoherenceParams::default()
+    };
+    Ok(AudioParams {
+        preset_title: Some(format!("{} (from analysis)", name)),
+        preset_description: Some(format!(
+            "{:.2} Hz {} binaural beats on {:.2} / {:.2} Hz carriers, generated from the analysis of {} \
+             (dominant section {:.0}-{:.0} s). EXPERIMENTAL - reproduces the recording's measured frequencies, \
+             not a research protocol. REQUIRES HEADPHONES.",
+            coherence.binaural_beat_hz(), wave, coherence.left_carrier, coherence.right_carrier,
+            analysis.file, dominant.start_time, dominant.end_time)),
+        experimental: Some(true),
+        lock_signal_layer: true,
+        coherence,
+        ..AudioParams::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_carriers_beat_and_sections() {
+        // 100 s at 4 kHz: 200/204 Hz (4 Hz theta) for 50 s, then 300/301.5 Hz (1.5 Hz delta)
+        let rate = 4000;
+        let (mut left, mut right) = (Vec::new(), Vec::new());
+        for i in 0..rate * 100 {
+            let t = i as f64 / rate as f64;
+            let (l, r) = if t < 50.0 { (200.0, 204.0) } else { (300.0, 301.5) };
+            left.push((8000.0 * (2.0 * std::f64::consts::PI * l * t).sin()) as f32);
+            right.push((8000.0 * (2.0 * std::f64::consts::PI * r * t).sin()) as f32);
+        }
+        let audio = WavAudio { sample_rate: rate as u32, left, right };
+        let analysis = analyze(&audio, "test.wav", AnalysisSettings { chunk_secs: 10.0, overlap_secs: 5.0 });
+
+        assert_eq!(analysis.sections.len(), 2);
+        let (first, second) = (&analysis.sections[0], &analysis.sections[1]);
+        // Section names average the timeline up to the next change point, so they lean towards the next section
+        assert!(first.section_type.starts_with("section_1_"));
+        assert_eq!(first.chunks[0].wave_type, "Theta");
+        assert!((first.end_time - 45.0).abs() <= 5.0);
+        assert!((first.average.left_freq - 200.0).abs() < 0.2 && (first.average.binaural_beat - 4.0).abs() < 0.2);
+        assert_eq!(second.chunks[0].wave_type, "Delta");
+        assert!((second.chunks[0].right_freq - 301.5).abs() < 0.2);
+        assert_eq!(second.chunks[0].left_top_freqs[0].0, second.chunks[0].left_freq);
+        assert!((second.chunks[0].amplitude - 8000.0 / 2f64.sqrt()).abs() < 50.0);
+    }
+
+    #[test]
+    fn test_preset_from_focus_10_analysis() {
+        let analysis: Analysis = serde_json::from_str(include_str!("../data/focus_10_analysis.json")).unwrap();
+        let preset = preset_from_analysis(&analysis).unwrap();
+        let c = &preset.coherence;
+        assert_eq!((c.left_carrier, c.right_carrier), (100.12, 104.23));
+        assert!((c.binaural_beat_hz() - 4.11).abs() < 0.005);
+        assert!(!c.harmonic_220hz && !c.harmonic_495hz);
+        // Beat from 105 s, dominant section until 435 s, last at the beat until 1915 s, recording ends at 2018 s
+        assert_eq!((c.startup_duration_min, c.induction_duration_min, c.stabilization_duration_min, c.return_duration_min),
+            (1.8, 5.5, 24.7, 1.7));
+        assert!(preset.preset_description.unwrap().starts_with("4.11 Hz theta"));
+    }
+
+    #[test]
+    fn test_savgol_keeps_cubics() {
+        let series: Vec<f64> = (0..20).map(|i| { let x = i as f64; 0.5 * x * x * x - 2.0 * x + 1.0 }).collect();
+        for (smoothed, original) in savgol(&series, 11, 3).iter().zip(&series) {
+            assert!((smoothed - original).abs() < 1e-6 * original.abs().max(1.0));
+        }
+        assert_eq!(local_maxima(&[0.0, 2.0, 2.0, 2.0, 1.0, 3.0, 0.0]), vec![2, 5]);
+    }
+}
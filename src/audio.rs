@@ -2,7 +2,10 @@ use rand::prelude::*;
 use rand::rngs::SmallRng;
 
 use serde::{Deserialize, Serialize};
-use crate::coherence::CoherenceParams;
+use crate::coherence::{BeingType, CoherenceParams, SessionPhase};
+use crate::eeg::controller::ClosedLoopConfig;
+use crate::preset_browser::PresetCategory;
+use crate::session::ReportQuestion;
 use crate::constants::*;
 use crate::utils::generate_waveform;
 
@@ -38,6 +41,9 @@ pub enum SignalType {
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AudioParams {
+    /// Preset format version (older files are upgraded by preset_schema before they get here)
+    pub schema_version: u32,
+
     // Preset Metadata (optional, not shown in UI mixer)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub preset_title: Option<String>,
@@ -45,6 +51,24 @@ pub struct AudioParams {
     pub preset_description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub experimental: Option<bool>,
+    /// Parent preset this one overrides (only the changed fields are saved)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Browser group (focus, sleep, monroe, animal, uap, rf)
+    #[serde(skip_serializing_if = "Option::is_none", deserialize_with = "crate::preset_browser::deserialize_category")]
+    pub category: Option<PresetCategory>,
+    /// Search tags shown in the preset browser
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// Extra questions asked in the post-session report
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_questions: Option<Vec<ReportQuestion>>,
+    /// EEG closed-loop control settings used when closed loop is switched on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed_loop: Option<ClosedLoopConfig>,
+    /// Noise seed; the same seed and params always render the same samples (random when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
     
     pub carrier_vol: f32, 
     pub carrier_type: SignalType,
@@ -64,6 +88,8 @@ pub struct AudioParams {
     
     pub breath_vol: f32, 
     pub breath_type: SignalType,
+    #[serde(skip)]
+    pub breath_hz: f32, // Breath LFO rate, set by HRV breath pacing
     
     pub master_vol: f32,
     pub playing: bool,
@@ -91,10 +117,18 @@ pub struct AudioParams {
     pub session_timer: f32,
     #[serde(skip)]
     pub session_phase: crate::coherence::SessionPhase,
+    #[serde(skip)]
+    pub gamma_burst: bool, // Focus 10 gamma burst playing right now
 
     // Preset change tracking (for detecting when to reset synthesizer state)
     #[serde(skip)]
     pub preset_version: u32,
+    #[serde(skip)]
+    pub session_version: u32, // preset_version the session timer belongs to (set by the audio thread)
+    #[serde(skip)]
+    pub preset_name: Option<String>, // File the preset was loaded from
+    #[serde(skip)]
+    pub session_seed: u64, // Seed the synthesizer uses from the next reset (see resolve_seed)
 }
 
 #[derive(Default)]
@@ -111,9 +145,17 @@ struct SignalComponents {
 impl Default for AudioParams {
     fn default() -> Self {
         Self {
+            schema_version: PRESET_SCHEMA_VERSION,
+
             preset_title: None,
             preset_description: None,
             experimental: None,
+            extends: None,
+            category: None,
+            tags: None,
+            report_questions: None,
+            closed_loop: None,
+            seed: None,
 
             carrier_vol: 0.0,
             carrier_type: SignalType::SchumannAM,
@@ -133,6 +175,7 @@ impl Default for AudioParams {
 
             breath_vol: 0.0,
             breath_type: SignalType::LfoBreathing,
+            breath_hz: BREATH_LFO_HZ,
 
             master_vol: DEFAULT_MASTER_VOLUME,
             playing: true,
@@ -152,9 +195,55 @@ impl Default for AudioParams {
             coherence: CoherenceParams::default(),
 
             session_timer: 0.0,
-            session_phase: crate::coherence::SessionPhase::Startup,
+            session_phase: SessionPhase::Startup,
+            gamma_burst: false,
             preset_version: 0,
+            session_version: 0,
+            preset_name: None,
+            session_seed: 0,
+        }
+    }
+}
+
+impl AudioParams {
+    /// Pick the seed for the next session: the override (e.g. `--seed`), else the preset's, else a random one
+    pub fn resolve_seed(&mut self, override_seed: Option<u64>) -> u64 {
+        self.session_seed = override_seed.or(self.seed).unwrap_or_else(rand::random);
+        self.session_seed
+    }
+
+    /// Start a new session now. The synthesizer resets when it sees the new version; until then
+    /// the timer reads zero rather than the old session's time.
+    pub fn restart_session(&mut self) {
+        self.preset_version = self.preset_version.wrapping_add(1);
+        self.session_timer = 0.0;
+        self.session_phase = SessionPhase::Startup;
+    }
+
+    /// True once the audio thread has reset for the latest restart, so `session_timer` is this session's
+    pub fn session_current(&self) -> bool {
+        self.session_version == self.preset_version
+    }
+
+    /// Begin the wake-up climb from silence (the alarm going off)
+    pub fn start_wake_program(&mut self) {
+        // The climb is binaural/isochronic, so make sure the coherence path is audible
+        if !self.coherence.enabled || matches!(self.coherence.being_type, BeingType::Unknown) {
+            self.coherence.enabled = true;
+            self.coherence.being_type = BeingType::HumanCustom;
         }
+        if self.coherence.volume <= 0.0 {
+            self.coherence.volume = DEFAULT_COHERENCE_VOLUME;
+        }
+        self.coherence.sleep.duration_min = 0.0;
+        self.coherence.wake.active = true;
+        self.playing = true;
+        self.restart_session();
+    }
+
+    /// Whether a running wake-up program is through its climb and beta hold
+    pub fn wake_program_finished(&self) -> bool {
+        self.coherence.wake.active && self.session_current() && self.coherence.wake.is_finished_at(self.session_timer / 60.0)
     }
 }
 
@@ -171,6 +260,7 @@ pub struct Synthesizer {
     phase_100hz: f32,
     phase_783hz: f32,
     phase_7_83hz: f32,
+    phase_fm: f32,
     phase_528hz: f32,
     phase_17khz: f32,
     phase_432hz: f32,
@@ -190,19 +280,21 @@ pub struct Synthesizer {
 }
 
 impl Synthesizer {
-    pub fn new(sample_rate: f32) -> Self {
+    /// `seed` seeds the noise, so a fresh synthesizer renders the same samples for the same seed
+    pub fn new(sample_rate: f32, seed: u64) -> Self {
         Self {
             sample_rate,
             phase_100hz: 0.0,
             phase_783hz: 0.0,
             phase_7_83hz: 0.0,
+            phase_fm: 0.0,
             phase_528hz: 0.0,
             phase_17khz: 0.0,
             phase_432hz: 0.0,
             phase_2_5khz: 0.0,
             breath_phase: 0.0,
             chirp_timer: 0.0,
-            rng: SmallRng::from_os_rng(),
+            rng: SmallRng::seed_from_u64(seed),
             coherence: crate::coherence::CoherenceSynth::new(sample_rate),
         }
     }
@@ -221,12 +313,12 @@ impl Synthesizer {
         let signal_mixed = if params.lock_signal_layer {
             0.0
         } else {
-            components.carrier * params.carrier_vol +
+            (components.carrier * params.carrier_vol +
             components.harmonic * params.harmonic_vol +
             components.ping * params.ping_vol +
             components.chirp * params.chirp_vol +
             components.pad * params.pad_vol +
-            components.breath * params.breath_vol
+            components.breath * params.breath_vol) * self.coherence.sleep_fade(&params.coherence)
         };
 
         // Always update session timer (regardless of being type)
@@ -239,9 +331,17 @@ impl Synthesizer {
             (0.0, 0.0)
         };
         
+        // Wake-up pink noise bed (independent of the signal layer lock)
+        let wake_noise_level = self.coherence.wake_noise_level(&params.coherence);
+        let wake_noise = if wake_noise_level > 0.0 {
+            (self.rng.random::<f32>() * 2.0 - 1.0) * PINK_NOISE_FACTOR * wake_noise_level
+        } else {
+            0.0
+        };
+
         // Final stereo mix
-        let left_out = (signal_mixed + coherence_left) * params.master_vol;
-        let right_out = (signal_mixed + coherence_right) * params.master_vol;
+        let left_out = (signal_mixed + coherence_left + wake_noise) * params.master_vol;
+        let right_out = (signal_mixed + coherence_right + wake_noise) * params.master_vol;
 
         (left_out, right_out)
     }
@@ -259,8 +359,10 @@ impl Synthesizer {
         components.carrier = match params.carrier_type {
             SignalType::SchumannAM => self.phase_100hz.sin() * (AM_MODULATION_MIN + AM_MODULATION_MIN * self.phase_7_83hz.sin()),
             SignalType::SchumannFM => {
-                let _mod_freq = CARRIER_BASE_HZ + FM_MODULATION_RANGE_HZ * self.phase_7_83hz.sin();
-                self.phase_100hz.sin()
+                // The carrier sweeps ±FM_MODULATION_RANGE_HZ around 100Hz at the Schumann rate
+                let mod_freq = CARRIER_BASE_HZ + FM_MODULATION_RANGE_HZ * self.phase_7_83hz.sin();
+                self.phase_fm = (self.phase_fm + mod_freq * dt * pi2) % pi2;
+                self.phase_fm.sin()
             },
             SignalType::Schumann783AM => self.phase_783hz.sin() * (AM_MODULATION_MIN + AM_MODULATION_MIN * self.phase_7_83hz.sin()),
             SignalType::Sine100Hz => self.phase_100hz.sin(),
@@ -323,7 +425,7 @@ impl Synthesizer {
         components.pad = generate_waveform(self.phase_432hz, params.pad_type);
 
         // 6. Breath Layer: White noise shaped
-        self.breath_phase = (self.breath_phase + BREATH_LFO_HZ * dt * pi2) % pi2;
+        self.breath_phase = (self.breath_phase + params.breath_hz * dt * pi2) % pi2;
         let noise: f32 = self.rng.random::<f32>() * 2.0 - 1.0;
 
         components.breath = match params.breath_type {
@@ -372,11 +474,15 @@ impl Synthesizer {
     }
 
     /// Reset all synthesizer state (called when preset changes to avoid glitches)
-    pub fn reset(&mut self) {
+    /// Return to the state of `Synthesizer::new(sample_rate, seed)`
+    pub fn reset(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+
         // Reset all phase accumulators
         self.phase_100hz = 0.0;
         self.phase_783hz = 0.0;
         self.phase_7_83hz = 0.0;
+        self.phase_fm = 0.0;
         self.phase_528hz = 0.0;
         self.phase_17khz = 0.0;
         self.phase_432hz = 0.0;
@@ -388,3 +494,27 @@ impl Synthesizer {
         self.coherence.reset();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wake_alarm_after_long_session_runs_its_course() {
+        // A 45-minute bedtime session, already longer than the 30-minute climb and hold
+        let mut params = AudioParams { session_timer: 45.0 * 60.0, ..Default::default() };
+        params.start_wake_program();
+        assert!(params.coherence.wake.active && params.playing);
+        assert!(!params.wake_program_finished());
+
+        // A stale time written back before the audio thread reset is not this session's
+        params.session_timer = 45.0 * 60.0;
+        assert!(!params.wake_program_finished());
+
+        params.session_version = params.preset_version;
+        params.session_timer = 10.0 * 60.0;
+        assert!(!params.wake_program_finished());
+        params.session_timer = 30.0 * 60.0;
+        assert!(params.wake_program_finished());
+    }
+}
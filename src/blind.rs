@@ -0,0 +1,283 @@
+// Blinded active/sham studies
+// A study key assigns every session of every participant to the real preset (active) or a sham
+// version of it, counterbalanced and reproducible from a seed. The key lives in its own file that
+// only whoever unblinds should open; the TUI, markers and session records only see neutral codes
+
+use std::collections::HashSet;
+use std::path::Path;
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use serde::{Deserialize, Serialize};
+use crate::audio::AudioParams;
+use crate::coherence::{BeingType, ShamMode};
+use crate::constants::*;
+use crate::session::SessionRecord;
+
+/// What a session really plays
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Active,
+    Sham,
+}
+
+impl Condition {
+    fn other(self) -> Self {
+        match self {
+            Condition::Active => Condition::Sham,
+            Condition::Sham => Condition::Active,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BlindSession {
+    pub code: String,
+    pub condition: Condition,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BlindParticipant {
+    pub id: String,
+    pub sessions: Vec<BlindSession>,
+}
+
+/// What a new study needs
+#[derive(Clone, Debug)]
+pub struct StudySpec {
+    pub study: String,
+    pub preset: String,
+    pub sham: ShamMode,
+    pub seed: u64,
+    pub participants: Vec<String>,
+    pub sessions: usize, // Per participant, even so each gets as many active as sham sessions
+}
+
+/// The sealed assignment of sessions to conditions
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct StudyKey {
+    pub study: String,
+    pub preset: String,
+    pub sham: ShamMode,
+    pub seed: u64,
+    pub created_at_unix: u64,
+    pub participants: Vec<BlindParticipant>,
+}
+
+impl StudyKey {
+    /// Assign conditions: each participant's sessions come in pairs (active/sham in random order),
+    /// and every second participant gets the mirror image of the one before, so every session
+    /// number is balanced across participants as well as within them
+    pub fn generate(spec: &StudySpec) -> Result<Self, String> {
+        if spec.sessions == 0 || !spec.sessions.is_multiple_of(2) {
+            return Err(format!("Sessions per participant must be even (got {})", spec.sessions));
+        }
+        if spec.participants.is_empty() {
+            return Err("A study needs at least one participant".to_string());
+        }
+        let unique: HashSet<&String> = spec.participants.iter().collect();
+        if unique.len() != spec.participants.len() {
+            return Err("Participant ids must be unique".to_string());
+        }
+
+        let mut rng = SmallRng::seed_from_u64(spec.seed);
+        let mut previous: Option<Vec<Condition>> = None;
+        let participants = spec.participants.iter().map(|id| {
+            let conditions = match previous.take() {
+                Some(sequence) => sequence.iter().map(|c| c.other()).collect(),
+                None => {
+                    let sequence: Vec<Condition> = (0..spec.sessions / 2)
+                        .flat_map(|_| {
+                            let first = if rng.random_bool(0.5) { Condition::Active } else { Condition::Sham };
+                            [first, first.other()]
+                        })
+                        .collect();
+                    previous = Some(sequence.clone());
+                    sequence
+                }
+            };
+            BlindParticipant {
+                id: id.clone(),
+                sessions: conditions.into_iter().enumerate()
+                    .map(|(i, condition)| BlindSession { code: session_code(id, i + 1), condition })
+                    .collect(),
+            }
+        }).collect();
+
+        Ok(Self {
+            study: spec.study.clone(),
+            preset: spec.preset.clone(),
+            sham: spec.sham,
+            seed: spec.seed,
+            created_at_unix: crate::session::unix_now(),
+            participants,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read study key {}: {}", path.display(), e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid study key {}: {}", path.display(), e))
+    }
+
+    /// Write the key to a new file (never overwrites; owner-only on Unix)
+    pub fn save_sealed(&self, path: &Path) -> std::io::Result<()> {
+        use std::io::Write;
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(path)?.write_all(json.as_bytes())
+    }
+
+    pub fn participant(&self, id: &str) -> Option<&BlindParticipant> {
+        self.participants.iter().find(|p| p.id == id)
+    }
+
+    /// The sham a blinded session played (None for active sessions), e.g. to regenerate its audio
+    pub fn sham_for(&self, run: &BlindRun) -> Result<Option<ShamMode>, String> {
+        let session = self.participant(&run.participant)
+            .and_then(|p| p.sessions.iter().find(|s| s.code == run.code))
+            .filter(|_| self.study == run.study)
+            .ok_or_else(|| format!("Session {} is not in study key '{}'", run.code, self.study))?;
+        Ok(match session.condition {
+            Condition::Active => None,
+            Condition::Sham => Some(self.sham),
+        })
+    }
+}
+
+/// Neutral code for a participant's nth session (1-based)
+pub fn session_code(participant: &str, number: usize) -> String {
+    format!("{}-{}", participant, number)
+}
+
+/// The blinded session being run, as saved with the session record (no condition)
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BlindRun {
+    pub study: String,
+    pub participant: String,
+    pub session: usize,
+    pub sessions: usize,
+    pub code: String,
+}
+
+impl BlindRun {
+    /// Label shown in place of the preset title
+    pub fn label(&self) -> String {
+        format!("Blinded session {} ({}/{})", self.code, self.session, self.sessions)
+    }
+}
+
+/// Session numbers of a participant that already have a saved session record
+pub fn sessions_run(study: &str, participant: &str) -> HashSet<usize> {
+    let Ok(entries) = std::fs::read_dir(get_sessions_dir()) else {
+        return HashSet::new();
+    };
+    entries.flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("session_"))
+        .filter_map(|e| std::fs::read_to_string(e.path()).ok())
+        .filter_map(|json| serde_json::from_str::<SessionRecord>(&json).ok())
+        .filter_map(|record| record.blind)
+        .filter(|run| run.study == study && run.participant == participant)
+        .map(|run| run.session)
+        .collect()
+}
+
+/// Load the active condition's preset; it needs a binaural beat for the sham to remove
+pub fn load_active_preset(name: &str) -> Result<AudioParams, String> {
+    let params = crate::headless::load_preset_params(Some(name))?;
+    if !params.coherence.enabled || params.coherence.being_type == BeingType::Unknown {
+        return Err(format!("Preset '{}' has no binaural beat to blind", name));
+    }
+    Ok(params)
+}
+
+/// Prepare the params for a participant's session (the next one not yet run unless `number` is given)
+/// Sham sessions play the same preset with the beat removed; titles become the neutral label
+pub fn prepare_session(key: &StudyKey, participant: &str, number: Option<usize>) -> Result<(BlindRun, AudioParams), String> {
+    let entry = key.participant(participant)
+        .ok_or_else(|| format!("Participant '{}' is not in study '{}'", participant, key.study))?;
+    let number = match number {
+        Some(n) => n,
+        None => {
+            let done = sessions_run(&key.study, participant);
+            (1..=entry.sessions.len()).find(|n| !done.contains(n))
+                .ok_or_else(|| format!("Participant '{}' has completed all {} sessions", participant, entry.sessions.len()))?
+        }
+    };
+    let session = entry.sessions.get(number.wrapping_sub(1))
+        .ok_or_else(|| format!("Participant '{}' has sessions 1-{}", participant, entry.sessions.len()))?;
+
+    let mut params = load_active_preset(&key.preset)?;
+    let run = BlindRun {
+        study: key.study.clone(),
+        participant: participant.to_string(),
+        session: number,
+        sessions: entry.sessions.len(),
+        code: session.code.clone(),
+    };
+    params.coherence.sham = match session.condition {
+        Condition::Active => None,
+        Condition::Sham => Some(key.sham),
+    };
+    params.preset_title = Some(run.label());
+    params.preset_description = None;
+    params.preset_name = Some(run.code.clone());
+    Ok((run, params))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coherence::{CoherenceParams, CoherenceSynth};
+
+    #[test]
+    fn test_assignment_is_reproducible_and_balanced() {
+        let spec = StudySpec {
+            study: "pilot".to_string(),
+            preset: "DEFAULT_deep_focus_calm.json".to_string(),
+            sham: ShamMode::ZeroBeat,
+            seed: 42,
+            participants: ["P01", "P02", "P03", "P04"].iter().map(|p| p.to_string()).collect(),
+            sessions: 4,
+        };
+        let key = StudyKey::generate(&spec).unwrap();
+        let conditions = |key: &StudyKey| -> Vec<Vec<Condition>> {
+            key.participants.iter().map(|p| p.sessions.iter().map(|s| s.condition).collect()).collect()
+        };
+        assert_eq!(conditions(&key), conditions(&StudyKey::generate(&spec).unwrap()));
+        assert_eq!(key.participants[1].sessions[2].code, "P02-3");
+
+        // Balanced within each participant, per pair of sessions and per session number
+        let table = conditions(&key);
+        for sequence in &table {
+            for pair in sequence.chunks(2) {
+                assert_ne!(pair[0], pair[1]);
+            }
+        }
+        for n in 0..4 {
+            assert_eq!(table.iter().filter(|s| s[n] == Condition::Active).count(), 2);
+        }
+        assert!(StudyKey::generate(&StudySpec { sessions: 3, ..spec }).is_err());
+    }
+
+    #[test]
+    fn test_sham_removes_interaural_difference() {
+        let mut params = CoherenceParams { enabled: true, being_type: BeingType::HumanCustom, ..Default::default() };
+        params.apply_custom_binaural(6.0);
+        for sham in [ShamMode::ZeroBeat, ShamMode::Monaural] {
+            params.sham = Some(sham);
+            let mut synth = CoherenceSynth::new(1000.0);
+            for _ in 0..3000 {
+                synth.update_timer(&params);
+                let (left, right) = synth.next_sample(&params);
+                assert!((left - right).abs() < 1e-4);
+            }
+        }
+    }
+}
@@ -0,0 +1,671 @@
+// Command-line argument parsing
+// With no arguments the TUI starts as before; options adjust the session it starts with
+
+use crate::analysis::AnalysisSettings;
+use crate::blind::StudySpec;
+use crate::coherence::ShamMode;
+use crate::constants::{MUSE_DEFAULT_OSC_PORT, OFFLINE_SAMPLE_RATE_HZ};
+use crate::eeg::edf::EdfFormat;
+use crate::eeg::openbci::Board;
+use crate::eeg::replay::ReplaySettings;
+use crate::markers::{MarkerFormat, MarkerSettings};
+use crate::preset_bundle::OnConflict;
+use crate::utils::parse_clock_time;
+
+pub const USAGE: &str = "\
+Usage: soulwhistle [OPTIONS]
+       soulwhistle replay <FILE> [REPLAY OPTIONS]
+       soulwhistle render <MANIFEST> --out <FILE.wav> [RENDER OPTIONS]
+       soulwhistle analyze <FILE.wav> [ANALYZE OPTIONS]
+       soulwhistle verify [PRESET...]
+       soulwhistle lint <FILE.json>...
+       soulwhistle export <PRESET>... [--out <FILE.tar.gz>] [--qr]
+       soulwhistle import <FILE.tar.gz|CODE> [--skip|--overwrite]
+       soulwhistle from-analysis <ANALYSIS.json> [--out <FILE.json>]
+       soulwhistle blind new <KEY FILE> [STUDY OPTIONS]
+       soulwhistle blind reveal <KEY FILE>
+
+Options:
+  --sleep <MINUTES|HH:MM>   Start in sleep mode for a duration or until a local wake time
+  --wake <HH:MM>            Schedule a wake-up program at a local time
+  --openbci <PORT>          Read EEG from an OpenBCI dongle (e.g. /dev/ttyUSB0)
+  --board <cyton|ganglion>  OpenBCI board type (default: cyton)
+  --ganglion-handle <N>     Ganglion command characteristic handle (for start/stop via the dongle)
+  --muse <PORT|ADDR>        Listen for Muse OSC (Mind Monitor / muse-io) on a UDP port (Mind Monitor default: 5000)
+  --replay <FILE>           Replay recorded EEG (OpenBCI GUI CSV, EDF/BDF or plain CSV) as the EEG source
+  --replay-rate <HZ>        Sample rate of a plain CSV without a time column
+  --replay-channels <LIST>  Channels to replay, 1-based and in order (e.g. 1,2,5)
+  --replay-speed <X>        Playback speed relative to real time (default: 1)
+  --replay-loop             Start the recording again when it ends
+  --mains <50|60>           Mains frequency removed from EEG by the notch filter (default: 50)
+  --record-eeg <edf|bdf>    Record the EEG with stimulus annotations from startup (EDF+ or 24-bit BDF+)
+  --markers-tcp <PORT|ADDR> Serve stimulus event markers to TCP clients (line-delimited JSON)
+  --markers-udp <HOST:PORT> Send stimulus event markers as UDP datagrams
+  --markers-format <json|lsl> Marker layout: full JSON events, or LSL string-marker samples (default: json)
+  --heart-serial <PORT>     Read RR intervals / heart rate lines from a serial bridge
+  --heart-baud <N>          Baud rate for --heart-serial (default: 115200)
+  --heart-udp <PORT|ADDR>   Listen for RR intervals / heart rate as UDP lines or JSON
+  --breath-pacing           Start HRV breath pacing at launch (needs a heart source)
+  --protocol <FILE>         Run an experiment protocol (silence, preset and questionnaire steps)
+  --questions <FILE>        Add an experiment's questions to every session report (JSON list)
+  --blind <KEY FILE>        Run a blinded study session from a study key (needs --participant)
+  --participant <ID>        Participant to run (their next session unless --blind-session is given)
+  --blind-session <N>       Run this session number of the participant instead (1-based)
+  --seed <N>                Noise seed for every session (default: the preset's seed, else random)
+  -h, --help                Show this help
+
+Replay subcommand (offline, no audio device or terminal; runs as fast as possible):
+  --rate <HZ>               Sample rate of a plain CSV without a time column
+  --channels <LIST>         Channels to use, 1-based and in order
+  --preset <NAME>           Preset to run (default: the deep focus preset)
+  --closed-loop             Run the closed-loop controller on the recording
+  --log <FILE>              Closed-loop log path (default: the sessions directory)
+  --out <FILE.wav>          Render the audio the listener would have heard
+  --sample-rate <HZ>        Audio sample rate for --out (default: 48000)
+  --mains <50|60>           Mains notch frequency (default: 50)
+  --seed <N>                Noise seed (default: the preset's seed, else random)
+
+Render subcommand (regenerates a session's audio from its manifest):
+  --out <FILE.wav>          WAV file to write
+  --minutes <N>             Length (default: the recorded session length, else the preset's)
+  --blind <KEY FILE>        Study key, needed to render a blinded session with its condition
+
+Analyze subcommand (carriers, binaural beat and sections of a stereo WAV recording):
+  -o, --output <FILE.json>  Write the analysis JSON (same layout as tools/coherence_analyzer.py)
+  --chunk-duration <SECS>   Analysis chunk length (default: 30)
+  --overlap <SECS>          Overlap between chunks, less than the chunk length (default: 15)
+
+Verify subcommand (renders presets offline and checks what they play against what they declare):
+  [PRESET...]               Presets to check, by name or file path (default: every embedded preset)
+
+Lint subcommand (checks preset files for unknown keys, type errors and out-of-range values):
+  <FILE.json>...            Preset files to check; exits with 1 if any has errors or warnings
+
+Export subcommand (shares presets, with the user presets they extend; prints a text code without --out):
+  <PRESET>...               Presets to export, by name (user presets first, then built-in)
+  --out <FILE.tar.gz>       Write an archive instead of a code
+  --qr                      Also print the code as a QR code
+
+Import subcommand (adds the presets of an archive or text code to the presets directory):
+  --skip                    Keep existing presets whose names are taken (default: import as <name>_imported)
+  --overwrite               Replace them (the old files are moved to the trash folder)
+
+From-analysis subcommand (makes a preset from an analysis JSON, e.g. data/focus_10_analysis.json):
+  --out <FILE.json>         Where to write it (default: the presets directory, named after the analysis)
+
+Blind subcommand (new writes a study key once; reveal prints the conditions for unblinding):
+  --preset <NAME>           Preset for the active condition (needs a binaural beat)
+  --participants <LIST>     Participant ids (e.g. P01,P02,P03,P04)
+  --sessions <N>            Sessions per participant, even (default: 2)
+  --sham <zero-beat|monaural> Sham condition: mean carrier in both ears, or both carriers in both ears (default: zero-beat)
+  --seed <N>                Randomization seed (default: random, stored in the key)
+  --study <NAME>            Study name (default: the key file name)
+";
+
+/// How long a sleep session should run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SleepTarget {
+    Minutes(f32),
+    WakeAt(u32), // Local time, minutes since midnight
+}
+
+/// Options for the interactive session
+#[derive(Default, Debug)]
+pub struct RunOptions {
+    pub sleep: Option<SleepTarget>,
+    pub wake_at: Option<u32>, // Local time, minutes since midnight
+    pub openbci_port: Option<String>,
+    pub board: Option<Board>,
+    pub ganglion_write_handle: Option<u16>,
+    pub mains_hz: Option<f32>,
+    pub muse_addr: Option<String>, // UDP address to listen on for Muse OSC
+    pub replay: Option<ReplaySettings>,
+    pub record_eeg: Option<EdfFormat>,
+    pub markers: MarkerSettings,
+    pub heart_serial: Option<String>,
+    pub heart_baud: Option<u32>,
+    pub heart_udp: Option<String>, // UDP address to listen on for heart-rate lines
+    pub breath_pacing: bool,
+    pub protocol: Option<String>,
+    pub questions: Option<String>, // Experiment report questions file
+    pub blind_key: Option<String>,
+    pub participant: Option<String>,
+    pub blind_session: Option<usize>,
+    pub seed: Option<u64>,
+}
+
+/// Options for an offline replay run
+#[derive(Debug)]
+pub struct ReplayOptions {
+    pub replay: ReplaySettings,
+    pub preset: Option<String>,
+    pub closed_loop: bool,
+    pub log: Option<String>,
+    pub out: Option<String>,
+    pub sample_rate: u32,
+    pub mains_hz: Option<f32>,
+    pub seed: Option<u64>,
+}
+
+/// Options for the `render` subcommand
+pub struct RenderOptions {
+    pub manifest: String,
+    pub out: String,
+    pub minutes: Option<f32>,
+    pub blind_key: Option<String>,
+}
+
+/// Options for the `analyze` subcommand
+pub struct AnalyzeOptions {
+    pub file: String,
+    pub output: Option<String>,
+    pub settings: AnalysisSettings,
+}
+
+/// `blind` subcommands
+#[derive(Debug)]
+pub enum BlindCommand {
+    New { key_path: String, spec: StudySpec },
+    Reveal { key_path: String },
+}
+
+pub enum Command {
+    Run(Box<RunOptions>),
+    Replay(ReplayOptions),
+    Blind(BlindCommand),
+    Render(RenderOptions),
+    Analyze(AnalyzeOptions),
+    Verify(Vec<String>), // Presets to check (empty: the embedded ones)
+    Lint(Vec<String>),   // Preset files to check
+    Export { presets: Vec<String>, out: Option<String>, qr: bool },
+    Import { source: String, on_conflict: OnConflict }, // Archive path or text code
+    FromAnalysis { analysis: String, out: Option<String> },
+    Help,
+}
+
+/// Parse command-line arguments (without the program name)
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    match args.first().map(String::as_str) {
+        Some("replay") => return parse_replay_args(&args[1..]),
+        Some("blind") => return parse_blind_args(&args[1..]),
+        Some("render") => return parse_render_args(&args[1..]),
+        Some("analyze") => return parse_analyze_args(&args[1..]),
+        Some("from-analysis") => return parse_from_analysis_args(&args[1..]),
+        Some("export") => return parse_export_args(&args[1..]),
+        Some("import") => return parse_import_args(&args[1..]),
+        Some("verify") => {
+            if args[1..].iter().any(|a| a == "-h" || a == "--help") {
+                return Ok(Command::Help);
+            }
+            if let Some(flag) = args[1..].iter().find(|a| a.starts_with("--")) {
+                return Err(format!("Unknown verify argument: {}", flag));
+            }
+            return Ok(Command::Verify(args[1..].to_vec()));
+        }
+        Some("lint") => {
+            if args[1..].iter().any(|a| a == "-h" || a == "--help") {
+                return Ok(Command::Help);
+            }
+            if args.len() < 2 {
+                return Err("lint needs at least one preset file".to_string());
+            }
+            return Ok(Command::Lint(args[1..].to_vec()));
+        }
+        _ => {}
+    }
+
+    let mut options = RunOptions::default();
+    let mut replay_file = None;
+    let mut replay = ReplaySettings::new("");
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--sleep" => {
+                let value = iter.next().ok_or("--sleep needs a duration in minutes or a wake time (HH:MM)")?;
+                options.sleep = Some(parse_sleep_target(value)?);
+            },
+            "--wake" => {
+                let value = iter.next().ok_or("--wake needs a local time (HH:MM)")?;
+                let wake_at = parse_clock_time(value)
+                    .ok_or_else(|| format!("Invalid wake time '{}' (expected HH:MM)", value))?;
+                options.wake_at = Some(wake_at);
+            },
+            "--openbci" => {
+                let value = iter.next().ok_or("--openbci needs a serial port")?;
+                options.openbci_port = Some(value.clone());
+            },
+            "--board" => {
+                let value = iter.next().ok_or("--board needs cyton or ganglion")?;
+                options.board = Some(match value.as_str() {
+                    "cyton" => Board::Cyton,
+                    "ganglion" => Board::Ganglion,
+                    other => return Err(format!("Unknown board '{}' (expected cyton or ganglion)", other)),
+                });
+            },
+            "--ganglion-handle" => {
+                let value = iter.next().ok_or("--ganglion-handle needs a handle number")?;
+                let handle = parse_u16(value)
+                    .ok_or_else(|| format!("Invalid handle '{}'", value))?;
+                options.ganglion_write_handle = Some(handle);
+            },
+            "--muse" => {
+                let value = iter.next().ok_or_else(|| format!("--muse needs a UDP port (Mind Monitor default: {})", MUSE_DEFAULT_OSC_PORT))?;
+                options.muse_addr = Some(parse_listen_addr(value)?);
+            },
+            "--replay" => {
+                replay_file = Some(iter.next().ok_or("--replay needs a recording file")?.clone());
+            },
+            "--replay-rate" => replay.sample_rate = Some(parse_rate(iter.next(), "--replay-rate")?),
+            "--replay-channels" => replay.channels = Some(parse_channel_list(iter.next(), "--replay-channels")?),
+            "--replay-speed" => {
+                let value = iter.next().ok_or("--replay-speed needs a factor")?;
+                replay.speed = value.parse::<f32>().ok().filter(|s| *s > 0.0)
+                    .ok_or_else(|| format!("Invalid replay speed '{}'", value))?;
+            },
+            "--replay-loop" => replay.looped = true,
+            "--mains" => options.mains_hz = Some(parse_mains(iter.next())?),
+            "--record-eeg" => {
+                let value = iter.next().ok_or("--record-eeg needs edf or bdf")?;
+                options.record_eeg = Some(match value.as_str() {
+                    "edf" => EdfFormat::Edf,
+                    "bdf" => EdfFormat::Bdf,
+                    other => return Err(format!("Unknown recording format '{}' (expected edf or bdf)", other)),
+                });
+            },
+            "--markers-tcp" => {
+                let value = iter.next().ok_or("--markers-tcp needs a port")?;
+                options.markers.tcp_addr = Some(parse_listen_addr(value)?);
+            },
+            "--markers-udp" => {
+                let value = iter.next().ok_or("--markers-udp needs a HOST:PORT target")?;
+                if !value.contains(':') {
+                    return Err(format!("Invalid marker target '{}' (expected HOST:PORT)", value));
+                }
+                options.markers.udp_target = Some(value.clone());
+            },
+            "--markers-format" => {
+                let value = iter.next().ok_or("--markers-format needs json or lsl")?;
+                options.markers.format = match value.as_str() {
+                    "json" => MarkerFormat::Json,
+                    "lsl" => MarkerFormat::Lsl,
+                    other => return Err(format!("Unknown marker format '{}' (expected json or lsl)", other)),
+                };
+            },
+            "--heart-serial" => {
+                let value = iter.next().ok_or("--heart-serial needs a serial port")?;
+                options.heart_serial = Some(value.clone());
+            },
+            "--heart-baud" => {
+                let value = iter.next().ok_or("--heart-baud needs a baud rate")?;
+                options.heart_baud = Some(value.parse::<u32>().ok().filter(|b| *b > 0)
+                    .ok_or_else(|| format!("Invalid baud rate '{}'", value))?);
+            },
+            "--heart-udp" => {
+                let value = iter.next().ok_or("--heart-udp needs a UDP port")?;
+                options.heart_udp = Some(parse_listen_addr(value)?);
+            },
+            "--breath-pacing" => options.breath_pacing = true,
+            "--seed" => options.seed = Some(parse_seed(iter.next())?),
+            "--protocol" => {
+                let value = iter.next().ok_or("--protocol needs a protocol file")?;
+                options.protocol = Some(value.clone());
+            },
+            "--questions" => {
+                let value = iter.next().ok_or("--questions needs a questions file")?;
+                options.questions = Some(value.clone());
+            },
+            "--blind" => {
+                let value = iter.next().ok_or("--blind needs a study key file")?;
+                options.blind_key = Some(value.clone());
+            },
+            "--participant" => {
+                let value = iter.next().ok_or("--participant needs a participant id")?;
+                options.participant = Some(value.clone());
+            },
+            "--blind-session" => {
+                let value = iter.next().ok_or("--blind-session needs a session number")?;
+                options.blind_session = Some(value.parse::<usize>().ok().filter(|n| *n > 0)
+                    .ok_or_else(|| format!("Invalid session number '{}'", value))?);
+            },
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+
+    match replay_file {
+        Some(path) => {
+            replay.path = path;
+            options.replay = Some(replay);
+        }
+        None => {
+            if replay.sample_rate.is_some() || replay.channels.is_some() || replay.speed != 1.0 || replay.looped {
+                return Err("--replay-* options need --replay <FILE>".to_string());
+            }
+        }
+    }
+
+    let sources = [options.openbci_port.is_some(), options.muse_addr.is_some(), options.replay.is_some()];
+    if sources.iter().filter(|&&s| s).count() > 1 {
+        return Err("Use only one EEG source (--openbci, --muse or --replay)".to_string());
+    }
+    if options.markers.format != MarkerFormat::Json && !options.markers.enabled() {
+        return Err("--markers-format needs --markers-tcp or --markers-udp".to_string());
+    }
+    if options.heart_serial.is_some() && options.heart_udp.is_some() {
+        return Err("Use only one heart source (--heart-serial or --heart-udp)".to_string());
+    }
+    if options.heart_baud.is_some() && options.heart_serial.is_none() {
+        return Err("--heart-baud needs --heart-serial <PORT>".to_string());
+    }
+    if options.breath_pacing && options.heart_serial.is_none() && options.heart_udp.is_none() {
+        return Err("--breath-pacing needs a heart source (--heart-serial or --heart-udp)".to_string());
+    }
+    if options.blind_key.is_some() != options.participant.is_some() {
+        return Err("--blind and --participant go together".to_string());
+    }
+    if options.blind_session.is_some() && options.blind_key.is_none() {
+        return Err("--blind-session needs --blind <KEY FILE>".to_string());
+    }
+    if options.protocol.is_some() && (options.blind_key.is_some() || options.sleep.is_some()) {
+        return Err("--protocol cannot be combined with --blind or --sleep".to_string());
+    }
+    if options.record_eeg.is_some() && !sources.contains(&true) {
+        return Err("--record-eeg needs an EEG source (--openbci, --muse or --replay)".to_string());
+    }
+
+    Ok(Command::Run(Box::new(options)))
+}
+
+/// Arguments after `replay`: the recording, then offline options
+fn parse_replay_args(args: &[String]) -> Result<Command, String> {
+    let mut iter = args.iter();
+    let path = match iter.next() {
+        Some(arg) if arg == "-h" || arg == "--help" => return Ok(Command::Help),
+        Some(arg) if !arg.starts_with("--") => arg,
+        _ => return Err("replay needs a recording file".to_string()),
+    };
+    let mut options = ReplayOptions {
+        replay: ReplaySettings::new(path),
+        preset: None,
+        closed_loop: false,
+        log: None,
+        out: None,
+        sample_rate: OFFLINE_SAMPLE_RATE_HZ,
+        mains_hz: None,
+        seed: None,
+    };
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--rate" => options.replay.sample_rate = Some(parse_rate(iter.next(), "--rate")?),
+            "--channels" => options.replay.channels = Some(parse_channel_list(iter.next(), "--channels")?),
+            "--preset" => options.preset = Some(iter.next().ok_or("--preset needs a preset name")?.clone()),
+            "--closed-loop" => options.closed_loop = true,
+            "--log" => options.log = Some(iter.next().ok_or("--log needs a file path")?.clone()),
+            "--out" => options.out = Some(iter.next().ok_or("--out needs a WAV file path")?.clone()),
+            "--sample-rate" => {
+                let value = iter.next().ok_or("--sample-rate needs a rate in Hz")?;
+                options.sample_rate = value.parse::<u32>().ok().filter(|r| *r > 0)
+                    .ok_or_else(|| format!("Invalid sample rate '{}'", value))?;
+            },
+            "--mains" => options.mains_hz = Some(parse_mains(iter.next())?),
+            "--seed" => options.seed = Some(parse_seed(iter.next())?),
+            other => return Err(format!("Unknown replay argument: {}", other)),
+        }
+    }
+
+    Ok(Command::Replay(options))
+}
+
+/// Arguments after `blind`: `new <KEY FILE> [STUDY OPTIONS]` or `reveal <KEY FILE>`
+fn parse_blind_args(args: &[String]) -> Result<Command, String> {
+    let action = args.first().map(String::as_str);
+    let key_path = match args.get(1) {
+        Some(arg) if !arg.starts_with("--") => arg.clone(),
+        _ if matches!(action, Some("-h") | Some("--help")) => return Ok(Command::Help),
+        _ => return Err("blind needs new <KEY FILE> or reveal <KEY FILE>".to_string()),
+    };
+    match action {
+        Some("reveal") if args.len() == 2 => return Ok(Command::Blind(BlindCommand::Reveal { key_path })),
+        Some("reveal") => return Err("blind reveal takes only the key file".to_string()),
+        Some("new") => {}
+        _ => return Err("blind needs new <KEY FILE> or reveal <KEY FILE>".to_string()),
+    }
+
+    let default_study = std::path::Path::new(&key_path).file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "study".to_string());
+    let mut spec = StudySpec {
+        study: default_study,
+        preset: String::new(),
+        sham: ShamMode::ZeroBeat,
+        seed: random_seed(),
+        participants: Vec::new(),
+        sessions: 2,
+    };
+    let mut iter = args[2..].iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--preset" => spec.preset = iter.next().ok_or("--preset needs a preset name")?.clone(),
+            "--participants" => {
+                let value = iter.next().ok_or("--participants needs a list of ids (e.g. P01,P02)")?;
+                spec.participants = value.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect();
+            },
+            "--sessions" => {
+                let value = iter.next().ok_or("--sessions needs a number")?;
+                spec.sessions = value.parse().map_err(|_| format!("Invalid session count '{}'", value))?;
+            },
+            "--sham" => {
+                let value = iter.next().ok_or("--sham needs zero-beat or monaural")?;
+                spec.sham = match value.as_str() {
+                    "zero-beat" => ShamMode::ZeroBeat,
+                    "monaural" => ShamMode::Monaural,
+                    other => return Err(format!("Unknown sham '{}' (expected zero-beat or monaural)", other)),
+                };
+            },
+            "--seed" => spec.seed = parse_seed(iter.next())?,
+            "--study" => spec.study = iter.next().ok_or("--study needs a name")?.clone(),
+            other => return Err(format!("Unknown blind argument: {}", other)),
+        }
+    }
+    if spec.preset.is_empty() {
+        return Err("blind new needs --preset <NAME>".to_string());
+    }
+    if spec.participants.is_empty() {
+        return Err("blind new needs --participants <LIST>".to_string());
+    }
+
+    Ok(Command::Blind(BlindCommand::New { key_path, spec }))
+}
+
+/// Seed from the clock when none is given (it is stored in the key, so the assignment can be re-derived)
+fn random_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)
+}
+
+/// Arguments after `render`: `<MANIFEST> --out <FILE.wav> [--minutes N] [--blind <KEY FILE>]`
+fn parse_render_args(args: &[String]) -> Result<Command, String> {
+    let mut iter = args.iter();
+    let manifest = match iter.next() {
+        Some(arg) if arg == "-h" || arg == "--help" => return Ok(Command::Help),
+        Some(arg) if !arg.starts_with("--") => arg.clone(),
+        _ => return Err("render needs a session manifest".to_string()),
+    };
+    let mut out = None;
+    let mut minutes = None;
+    let mut blind_key = None;
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--out" => out = Some(iter.next().ok_or("--out needs a WAV file path")?.clone()),
+            "--minutes" => {
+                let value = iter.next().ok_or("--minutes needs a length in minutes")?;
+                minutes = Some(value.parse::<f32>().ok().filter(|m| m.is_finite() && *m > 0.0)
+                    .ok_or_else(|| format!("Invalid length '{}'", value))?);
+            },
+            "--blind" => blind_key = Some(iter.next().ok_or("--blind needs a study key file")?.clone()),
+            other => return Err(format!("Unknown render argument: {}", other)),
+        }
+    }
+    let out = out.ok_or("render needs --out <FILE.wav>")?;
+
+    Ok(Command::Render(RenderOptions { manifest, out, minutes, blind_key }))
+}
+
+/// Arguments after `analyze`: `<FILE.wav> [-o FILE.json] [--chunk-duration SECS] [--overlap SECS]`
+fn parse_analyze_args(args: &[String]) -> Result<Command, String> {
+    let mut iter = args.iter();
+    let file = match iter.next() {
+        Some(arg) if arg == "-h" || arg == "--help" => return Ok(Command::Help),
+        Some(arg) if !arg.starts_with('-') => arg.clone(),
+        _ => return Err("analyze needs a WAV file".to_string()),
+    };
+    let mut output = None;
+    let mut settings = AnalysisSettings::default();
+    let parse_secs = |value: Option<&String>, flag: &str| -> Result<f64, String> {
+        let value = value.ok_or_else(|| format!("{} needs a length in seconds", flag))?;
+        value.parse::<f64>().ok().filter(|s| s.is_finite() && *s >= 0.0)
+            .ok_or_else(|| format!("Invalid length '{}'", value))
+    };
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => output = Some(iter.next().ok_or("--output needs a JSON file path")?.clone()),
+            "--chunk-duration" => settings.chunk_secs = parse_secs(iter.next(), "--chunk-duration")?,
+            "--overlap" => settings.overlap_secs = parse_secs(iter.next(), "--overlap")?,
+            other => return Err(format!("Unknown analyze argument: {}", other)),
+        }
+    }
+    if settings.chunk_secs <= 0.0 || settings.overlap_secs >= settings.chunk_secs {
+        return Err("--overlap must be shorter than --chunk-duration".to_string());
+    }
+
+    Ok(Command::Analyze(AnalyzeOptions { file, output, settings }))
+}
+
+/// Arguments after `from-analysis`: `<ANALYSIS.json> [--out <FILE.json>]`
+fn parse_from_analysis_args(args: &[String]) -> Result<Command, String> {
+    let mut iter = args.iter();
+    let analysis = match iter.next() {
+        Some(arg) if arg == "-h" || arg == "--help" => return Ok(Command::Help),
+        Some(arg) if !arg.starts_with("--") => arg.clone(),
+        _ => return Err("from-analysis needs an analysis JSON file".to_string()),
+    };
+    let mut out = None;
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--out" => out = Some(iter.next().ok_or("--out needs a preset file path")?.clone()),
+            other => return Err(format!("Unknown from-analysis argument: {}", other)),
+        }
+    }
+    Ok(Command::FromAnalysis { analysis, out })
+}
+
+/// Arguments after `export`: `<PRESET>... [--out <FILE.tar.gz>] [--qr]`
+fn parse_export_args(args: &[String]) -> Result<Command, String> {
+    let mut presets = Vec::new();
+    let mut out = None;
+    let mut qr = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--out" => out = Some(iter.next().ok_or("--out needs an archive path")?.clone()),
+            "--qr" => qr = true,
+            other if other.starts_with("--") => return Err(format!("Unknown export argument: {}", other)),
+            preset => presets.push(preset.to_string()),
+        }
+    }
+    if presets.is_empty() {
+        return Err("export needs at least one preset".to_string());
+    }
+    if qr && out.is_some() {
+        return Err("--qr shows a text code, which --out replaces with an archive".to_string());
+    }
+    Ok(Command::Export { presets, out, qr })
+}
+
+/// Arguments after `import`: `<FILE.tar.gz|CODE> [--skip|--overwrite]`
+fn parse_import_args(args: &[String]) -> Result<Command, String> {
+    let mut source = None;
+    let mut on_conflict = OnConflict::Rename;
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--skip" => on_conflict = OnConflict::Skip,
+            "--overwrite" => on_conflict = OnConflict::Overwrite,
+            other if other.starts_with("--") => return Err(format!("Unknown import argument: {}", other)),
+            other if source.is_none() => source = Some(other.to_string()),
+            other => return Err(format!("import takes one archive or code, got another: {}", other)),
+        }
+    }
+    let source = source.ok_or("import needs an archive file or a preset code")?;
+    Ok(Command::Import { source, on_conflict })
+}
+
+fn parse_seed(value: Option<&String>) -> Result<u64, String> {
+    let value = value.ok_or("--seed needs a number")?;
+    value.parse().map_err(|_| format!("Invalid seed '{}'", value))
+}
+
+fn parse_mains(value: Option<&String>) -> Result<f32, String> {
+    match value.map(String::as_str) {
+        Some("50") => Ok(50.0),
+        Some("60") => Ok(60.0),
+        Some(other) => Err(format!("Invalid mains frequency '{}' (expected 50 or 60)", other)),
+        None => Err("--mains needs 50 or 60".to_string()),
+    }
+}
+
+fn parse_rate(value: Option<&String>, flag: &str) -> Result<f32, String> {
+    let value = value.ok_or_else(|| format!("{} needs a sample rate in Hz", flag))?;
+    value.parse::<f32>().ok().filter(|r| *r > 0.0)
+        .ok_or_else(|| format!("Invalid sample rate '{}'", value))
+}
+
+/// Parse a 1-based channel list such as "1,2,5"
+fn parse_channel_list(value: Option<&String>, flag: &str) -> Result<Vec<usize>, String> {
+    let value = value.ok_or_else(|| format!("{} needs a channel list (e.g. 1,2,5)", flag))?;
+    value.split(',')
+        .map(|c| c.trim().parse::<usize>().ok().filter(|c| *c > 0)
+            .ok_or_else(|| format!("Invalid channel '{}' in '{}'", c, value)))
+        .collect()
+}
+
+/// Accept a bare port (listen on all interfaces) or a full address
+fn parse_listen_addr(value: &str) -> Result<String, String> {
+    if let Ok(port) = value.parse::<u16>() {
+        return Ok(format!("0.0.0.0:{}", port));
+    }
+    value.parse::<std::net::SocketAddr>()
+        .map(|addr| addr.to_string())
+        .map_err(|_| format!("Invalid UDP address '{}' (expected a port or IP:PORT)", value))
+}
+
+/// Parse a decimal or 0x-prefixed hexadecimal u16
+fn parse_u16(value: &str) -> Option<u16> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_sleep_target(value: &str) -> Result<SleepTarget, String> {
+    if value.contains(':') {
+        parse_clock_time(value)
+            .map(SleepTarget::WakeAt)
+            .ok_or_else(|| format!("Invalid wake time '{}' (expected HH:MM)", value))
+    } else {
+        match value.parse::<f32>() {
+            Ok(minutes) if minutes > 0.0 => Ok(SleepTarget::Minutes(minutes)),
+            _ => Err(format!("Invalid sleep duration '{}' (expected minutes)", value)),
+        }
+    }
+}
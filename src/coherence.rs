@@ -3,6 +3,7 @@
 
 use serde::{Deserialize, Serialize};
 use crate::constants::*;
+use crate::stimulus::StimulusEvent;
 
 /// Session phase for progressive entrainment (Monroe-style multi-phase structure)
 #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
@@ -38,6 +39,174 @@ impl BeingType {
     }
 }
 
+/// Sleep descent program: the beat glides alpha -> theta -> delta, holds, then fades out
+/// Stage durations are per preset; the overall length is set at runtime (timer or wake time)
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SleepParams {
+    pub alpha_beat_hz: f32,     // Starting beat (relaxed alpha)
+    pub theta_beat_hz: f32,     // Beat reached at the end of the alpha stage
+    pub delta_beat_hz: f32,     // Beat reached at the end of the theta stage, then held
+    pub alpha_stage_min: f32,   // Glide from alpha to theta (minutes)
+    pub theta_stage_min: f32,   // Glide from theta to delta (minutes)
+    pub fade_min: f32,          // Final fade to silence (minutes)
+
+    // Total sleep session length in minutes (0 = sleep mode off), set from the TUI or CLI
+    #[serde(skip)]
+    pub duration_min: f32,
+}
+
+impl Default for SleepParams {
+    fn default() -> Self {
+        Self {
+            alpha_beat_hz: SLEEP_ALPHA_BEAT_HZ,
+            theta_beat_hz: SLEEP_THETA_BEAT_HZ,
+            delta_beat_hz: SLEEP_DELTA_BEAT_HZ,
+            alpha_stage_min: SLEEP_ALPHA_STAGE_MIN,
+            theta_stage_min: SLEEP_THETA_STAGE_MIN,
+            fade_min: SLEEP_FADE_MIN,
+            duration_min: 0.0,
+        }
+    }
+}
+
+impl SleepParams {
+    /// Whether a sleep session is running
+    pub fn is_active(&self) -> bool {
+        self.duration_min > 0.0
+    }
+
+    /// Minutes left of a sleep session that started at `started_unix`, on the wall clock
+    /// (so a pause doesn't push back a requested wake time)
+    pub fn remaining_min(&self, started_unix: u64, now_unix: u64) -> f32 {
+        self.duration_min - now_unix.saturating_sub(started_unix) as f32 / 60.0
+    }
+
+    /// Whether a running sleep session has reached its end on the wall clock
+    pub fn is_over(&self, started_unix: u64, now_unix: u64) -> bool {
+        self.is_active() && self.remaining_min(started_unix, now_unix) <= 0.0
+    }
+
+    /// Factor that shrinks the stages proportionally when they don't fit in the session
+    fn stage_scale(&self) -> f32 {
+        let planned = self.alpha_stage_min + self.theta_stage_min + self.fade_min;
+        if planned > self.duration_min && planned > 0.0 {
+            self.duration_min / planned
+        } else {
+            1.0
+        }
+    }
+
+    /// End of the descent (alpha and theta stages) in minutes
+    pub fn descent_end_min(&self) -> f32 {
+        (self.alpha_stage_min + self.theta_stage_min) * self.stage_scale()
+    }
+
+    /// Start of the final fade in minutes
+    pub fn fade_start_min(&self) -> f32 {
+        self.duration_min - self.fade_min * self.stage_scale()
+    }
+
+    /// Target binaural beat at the given session time (minutes)
+    pub fn beat_hz_at(&self, minutes: f32) -> f32 {
+        let scale = self.stage_scale();
+        let alpha_end = self.alpha_stage_min * scale;
+        let theta_end = self.descent_end_min();
+
+        if minutes < alpha_end {
+            let progress = minutes / alpha_end;
+            self.alpha_beat_hz + (self.theta_beat_hz - self.alpha_beat_hz) * progress
+        } else if minutes < theta_end {
+            let progress = (minutes - alpha_end) / (theta_end - alpha_end);
+            self.theta_beat_hz + (self.delta_beat_hz - self.theta_beat_hz) * progress
+        } else {
+            self.delta_beat_hz
+        }
+    }
+
+    /// Volume multiplier for the final fade (1.0 before the fade, 0.0 at the end)
+    pub fn fade_multiplier_at(&self, minutes: f32) -> f32 {
+        let fade_start = self.fade_start_min();
+        let fade_len = self.duration_min - fade_start;
+        if minutes < fade_start {
+            1.0
+        } else if fade_len > 0.0 {
+            (1.0 - (minutes - fade_start) / fade_len).max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Wake-up program: from silence the beat climbs delta -> alpha -> beta while the volume rises
+/// Triggered at a scheduled local time; the ramp itself is the session's startup phase
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WakeParams {
+    pub delta_beat_hz: f32,   // Beat at the start of the ramp
+    pub alpha_beat_hz: f32,   // Beat at the middle of the ramp
+    pub beta_beat_hz: f32,    // Beat at the end of the ramp, then held
+    pub ramp_min: f32,        // Duration of the climb (minutes)
+    pub hold_min: f32,        // Time at beta before the session counts as complete (minutes)
+    pub isochronic: bool,     // Pulse one carrier in both ears (works on speakers) instead of binaural
+    pub pink_noise_vol: f32,  // Pink noise level reached at the end of the ramp (0 = off)
+
+    // Set when the alarm fires
+    #[serde(skip)]
+    pub active: bool,
+}
+
+impl Default for WakeParams {
+    fn default() -> Self {
+        Self {
+            delta_beat_hz: WAKE_DELTA_BEAT_HZ,
+            alpha_beat_hz: WAKE_ALPHA_BEAT_HZ,
+            beta_beat_hz: WAKE_BETA_BEAT_HZ,
+            ramp_min: WAKE_RAMP_MIN,
+            hold_min: WAKE_HOLD_MIN,
+            isochronic: false,
+            pink_noise_vol: 0.0,
+            active: false,
+        }
+    }
+}
+
+impl WakeParams {
+    /// Target beat at the given session time (minutes)
+    pub fn beat_hz_at(&self, minutes: f32) -> f32 {
+        let half = self.ramp_min / 2.0;
+        if half <= 0.0 || minutes >= self.ramp_min {
+            self.beta_beat_hz
+        } else if minutes < half {
+            self.delta_beat_hz + (self.alpha_beat_hz - self.delta_beat_hz) * (minutes / half)
+        } else {
+            self.alpha_beat_hz + (self.beta_beat_hz - self.alpha_beat_hz) * ((minutes - half) / half)
+        }
+    }
+
+    /// Whether the climb and the beta hold are both over at the given session time (minutes)
+    pub fn is_finished_at(&self, minutes: f32) -> bool {
+        minutes >= self.ramp_min + self.hold_min
+    }
+
+    /// Ramp progress from silence (0.0) to full level (1.0)
+    pub fn ramp_progress_at(&self, minutes: f32) -> f32 {
+        if self.ramp_min > 0.0 {
+            (minutes / self.ramp_min).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Sham versions of a preset for blinded studies: the carriers stay, the binaural beat doesn't
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ShamMode {
+    ZeroBeat, // Both ears get the mean of the two carriers (0 Hz beat)
+    Monaural, // Both carriers are mixed into both ears, so there is no interaural difference
+}
+
 /// Neural Coherence binaural beat parameters (based on decoded frequency maps)
 #[derive(Clone, Copy, Serialize, Deserialize)]
 #[serde(default)]
@@ -66,6 +235,14 @@ pub struct CoherenceParams {
     pub induction_duration_min: f32,      // Duration of induction phase (default: 13 min, ends at 15)
     pub stabilization_duration_min: f32,  // Duration of stabilization phase (default: 10 min, ends at 25)
     pub return_duration_min: f32,         // Duration of return phase (default: 5 min, ends at 30)
+
+    // Sleep and wake-up modes (replace the phase timings above while active)
+    pub sleep: SleepParams,
+    pub wake: WakeParams,
+
+    // Set by the blinded study runner for sham sessions (never saved in presets)
+    #[serde(skip)]
+    pub sham: Option<ShamMode>,
 }
 
 impl Default for CoherenceParams {
@@ -84,6 +261,9 @@ impl Default for CoherenceParams {
             induction_duration_min: DEFAULT_INDUCTION_DURATION_MIN,
             stabilization_duration_min: DEFAULT_STABILIZATION_DURATION_MIN,
             return_duration_min: DEFAULT_RETURN_DURATION_MIN,
+            sleep: SleepParams::default(),
+            wake: WakeParams::default(),
+            sham: None,
         }
     }
 }
@@ -107,44 +287,83 @@ impl CoherenceParams {
         (self.left_carrier - self.right_carrier).abs()
     }
 
-    /// Get brainwave state name
-    pub fn brainwave_state(&self) -> &str {
-        let hz = self.binaural_beat_hz();
-        if hz < DELTA_MAX_HZ {
-            "Delta (deep)"
-        } else if hz < THETA_MAX_HZ {
-            "Theta (meditation)"
-        } else if hz < ALPHA_MAX_HZ {
-            "Alpha (relaxed)"
-        } else if hz < BETA_MAX_HZ {
-            "Beta (alert)"
+    /// Get the beat being played at the given session time (follows the sleep descent when active)
+    pub fn current_beat_hz(&self, minutes: f32) -> f32 {
+        if self.wake.active {
+            self.wake.beat_hz_at(minutes)
+        } else if self.sleep.is_active() {
+            self.sleep.beat_hz_at(minutes)
         } else {
-            "Gamma (focus)"
+            self.binaural_beat_hz()
         }
     }
 
+    /// Get brainwave state name
+    pub fn brainwave_state(&self) -> &str {
+        brainwave_state_name(self.binaural_beat_hz())
+    }
+
     /// Get the end time for startup phase (in minutes)
+    /// In wake-up mode startup is the climb from silence
     pub fn startup_end_min(&self) -> f32 {
+        if self.wake.active {
+            return self.wake.ramp_min;
+        }
         self.startup_duration_min
     }
 
     /// Get the end time for induction phase (in minutes)
+    /// In sleep mode induction covers the alpha -> theta -> delta descent
     pub fn induction_end_min(&self) -> f32 {
+        if self.wake.active {
+            return self.wake.ramp_min + self.wake.hold_min;
+        }
+        if self.sleep.is_active() {
+            return self.sleep.descent_end_min().max(self.startup_end_min());
+        }
         self.startup_duration_min + self.induction_duration_min
     }
 
     /// Get the end time for stabilization phase (in minutes)
+    /// In sleep mode stabilization is the delta hold before the final fade
     pub fn stabilization_end_min(&self) -> f32 {
+        if self.wake.active {
+            return self.induction_end_min();
+        }
+        if self.sleep.is_active() {
+            return self.sleep.fade_start_min().max(self.induction_end_min());
+        }
         self.startup_duration_min + self.induction_duration_min + self.stabilization_duration_min
     }
 
     /// Get the total session duration (in minutes)
     pub fn total_session_min(&self) -> f32 {
+        if self.wake.active {
+            return self.induction_end_min();
+        }
+        if self.sleep.is_active() {
+            return self.sleep.duration_min;
+        }
         self.startup_duration_min + self.induction_duration_min +
         self.stabilization_duration_min + self.return_duration_min
     }
 }
 
+/// Brainwave state name for a beat frequency
+pub fn brainwave_state_name(hz: f32) -> &'static str {
+    if hz < DELTA_MAX_HZ {
+        "Delta (deep)"
+    } else if hz < THETA_MAX_HZ {
+        "Theta (meditation)"
+    } else if hz < ALPHA_MAX_HZ {
+        "Alpha (relaxed)"
+    } else if hz < BETA_MAX_HZ {
+        "Beta (alert)"
+    } else {
+        "Gamma (focus)"
+    }
+}
+
 /// Neural Coherence binaural beat synthesizer
 pub struct CoherenceSynth {
     sample_rate: f32,
@@ -154,16 +373,22 @@ pub struct CoherenceSynth {
     phase_right: f32,
     phase_harmonic_220: f32,
     phase_harmonic_495: f32,
+    phase_isochronic: f32, // Pulse envelope for isochronic wake-up tones
 
     // Gamma burst interruption (Focus 10 technique)
-    gamma_timer: f32,  // Tracks time since last gamma burst
+    // Counted in samples like the session timer (summing f32 seconds drifts by tenths of a second per burst)
+    gamma_timer: u64,  // Tracks time since last gamma burst
     gamma_active: bool, // Whether we're currently in a gamma burst
-    gamma_duration: f32, // How long the current gamma burst has been active
+    gamma_duration: u64, // How long the current gamma burst has been active
 
     // Multi-phase progression (Monroe-style session structure)
     // Use u64 sample counter to avoid f32 precision loss at high sample counts
     session_samples: u64,    // Total samples since session start
     current_phase: SessionPhase, // Current phase of the session
+
+    // Phase and gamma burst changes for marker output, stamped with session_samples
+    capture_events: bool,
+    events: Vec<(u64, StimulusEvent)>,
 }
 
 impl CoherenceSynth {
@@ -174,11 +399,30 @@ impl CoherenceSynth {
             phase_right: 0.0,
             phase_harmonic_220: 0.0,
             phase_harmonic_495: 0.0,
-            gamma_timer: 0.0,
+            phase_isochronic: 0.0,
+            gamma_timer: 0,
             gamma_active: false,
-            gamma_duration: 0.0,
+            gamma_duration: 0,
             session_samples: 0,
             current_phase: SessionPhase::Startup,
+            capture_events: false,
+            events: Vec::new(),
+        }
+    }
+
+    /// Collect phase and gamma burst changes with the session sample they happened at
+    pub fn set_event_capture(&mut self, enabled: bool) {
+        self.capture_events = enabled;
+    }
+
+    /// Changes collected since the last call (oldest first)
+    pub fn take_events(&mut self) -> Vec<(u64, StimulusEvent)> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn push_event(&mut self, event: StimulusEvent) {
+        if self.capture_events {
+            self.events.push((self.session_samples, event));
         }
     }
 
@@ -187,6 +431,10 @@ impl CoherenceSynth {
         self.session_samples as f32 / self.sample_rate
     }
     
+    fn secs_to_samples(&self, secs: f32) -> u64 {
+        (secs * self.sample_rate).round() as u64
+    }
+
     /// Update session timer (called every sample regardless of being type)
     pub fn update_timer(&mut self, params: &CoherenceParams) {
         self.session_samples += 1;
@@ -203,24 +451,38 @@ impl CoherenceSynth {
         let pi2 = 2.0 * std::f32::consts::PI;
         let phase_volume = self.get_phase_volume_multiplier(params);
 
-        // Gamma burst interruption for Focus 10 (Monroe technique)
-        let (left_carrier, right_carrier) = if params.being_type == BeingType::HumanFocus10 {
-            self.gamma_timer += dt;
+        // Wake-up and sleep programs override the preset beat; gamma bursts would work against them
+        let (left_carrier, right_carrier) = if params.wake.active {
+            let beat = params.wake.beat_hz_at(self.session_timer_secs() / 60.0);
+            if params.wake.isochronic {
+                self.phase_isochronic = (self.phase_isochronic + beat * dt * pi2) % pi2;
+                (params.left_carrier, params.left_carrier)
+            } else {
+                (params.left_carrier, params.left_carrier + beat)
+            }
+        } else if params.sleep.is_active() {
+            let beat = params.sleep.beat_hz_at(self.session_timer_secs() / 60.0);
+            (params.left_carrier, params.left_carrier + beat)
+        } else if params.being_type == BeingType::HumanFocus10 {
+            // Gamma burst interruption for Focus 10 (Monroe technique)
+            self.gamma_timer += 1;
 
             // Check if it's time for a gamma burst
-            if self.gamma_timer >= GAMMA_BURST_INTERVAL_SECS && !self.gamma_active {
+            if self.gamma_timer >= self.secs_to_samples(GAMMA_BURST_INTERVAL_SECS) && !self.gamma_active {
                 self.gamma_active = true;
-                self.gamma_duration = 0.0;
+                self.gamma_duration = 0;
+                self.push_event(StimulusEvent::GammaBurstStart);
             }
 
             // If in gamma burst
             if self.gamma_active {
-                self.gamma_duration += dt;
+                self.gamma_duration += 1;
 
                 // End gamma burst after duration
-                if self.gamma_duration >= GAMMA_BURST_DURATION_SECS {
+                if self.gamma_duration >= self.secs_to_samples(GAMMA_BURST_DURATION_SECS) {
                     self.gamma_active = false;
-                    self.gamma_timer = 0.0; // Reset for next cycle
+                    self.gamma_timer = 0; // Reset for next cycle
+                    self.push_event(StimulusEvent::GammaBurstEnd);
                 }
 
                 // Generate gamma burst binaural beat
@@ -234,6 +496,15 @@ impl CoherenceSynth {
             (params.left_carrier, params.right_carrier)
         };
 
+        // A zero-beat sham plays the mean carrier in both ears
+        let (left_carrier, right_carrier) = match params.sham {
+            Some(ShamMode::ZeroBeat) => {
+                let mean = (left_carrier + right_carrier) / 2.0;
+                (mean, mean)
+            },
+            _ => (left_carrier, right_carrier),
+        };
+
         // Update carrier oscillators
         self.phase_left = (self.phase_left + left_carrier * dt * pi2) % pi2;
         self.phase_right = (self.phase_right + right_carrier * dt * pi2) % pi2;
@@ -241,6 +512,20 @@ impl CoherenceSynth {
         // Generate carrier tones (pure sine waves for best binaural effect)
         let mut left = self.phase_left.sin();
         let mut right = self.phase_right.sin();
+
+        // A monaural sham sends the same mix to both ears (scaled to keep the loudness)
+        if params.sham == Some(ShamMode::Monaural) {
+            let mixed = (left + right) * std::f32::consts::FRAC_1_SQRT_2;
+            left = mixed;
+            right = mixed;
+        }
+
+        // Isochronic wake-up: pulse the (identical) carriers at the beat rate
+        if params.wake.active && params.wake.isochronic {
+            let pulse = AM_MODULATION_MIN + AM_MODULATION_MIN * self.phase_isochronic.sin();
+            left *= pulse;
+            right *= pulse;
+        }
         
         // Add harmonics if enabled (like in real tapes)
         if params.harmonic_220hz {
@@ -268,6 +553,7 @@ impl CoherenceSynth {
     /// Update session phase based on elapsed time (using configurable timings from params)
     fn update_session_phase(&mut self, params: &CoherenceParams) {
         let minutes = self.session_timer_secs() / 60.0;
+        let previous = self.current_phase;
 
         self.current_phase = if minutes < params.startup_end_min() {
             SessionPhase::Startup
@@ -278,21 +564,25 @@ impl CoherenceSynth {
         } else {
             SessionPhase::Return
         };
+        if self.current_phase != previous {
+            self.push_event(StimulusEvent::Phase { phase: self.current_phase });
+        }
     }
 
     /// Calculate volume multiplier based on current phase (using configurable timings)
     /// Startup: 0.0 -> 1.0 (ramp up)
     /// Induction: 1.0 (full intensity)
     /// Stabilization: 1.0 (sustained)
-    /// Return: 1.0 -> 0.3 (gentle ramp down)
+    /// Return: 1.0 -> 0.3 (gentle ramp down), or 1.0 -> 0.0 in sleep mode
     fn get_phase_volume_multiplier(&self, params: &CoherenceParams) -> f32 {
         let minutes = self.session_timer_secs() / 60.0;
 
         match self.current_phase {
             SessionPhase::Startup => {
-                // Linear ramp from 0.0 to 1.0 over startup duration
-                if params.startup_duration_min > 0.0 {
-                    (minutes / params.startup_duration_min).min(1.0)
+                // Linear ramp from 0.0 to 1.0 over startup duration (the wake-up climb in wake mode)
+                let startup_min = params.startup_end_min();
+                if startup_min > 0.0 {
+                    (minutes / startup_min).min(1.0)
                 } else {
                     1.0
                 }
@@ -301,6 +591,8 @@ impl CoherenceSynth {
                 // Full intensity
                 1.0
             },
+            SessionPhase::Return if params.wake.active => 1.0, // Keep ringing until stopped
+            SessionPhase::Return if params.sleep.is_active() => self.sleep_fade(params),
             SessionPhase::Return => {
                 // Linear ramp from 1.0 to 0.3 over return duration
                 let return_start = params.stabilization_end_min();
@@ -314,21 +606,107 @@ impl CoherenceSynth {
         }
     }
 
+    /// Volume multiplier for the sleep fade-out (1.0 unless a sleep session is fading)
+    pub fn sleep_fade(&self, params: &CoherenceParams) -> f32 {
+        if params.sleep.is_active() {
+            params.sleep.fade_multiplier_at(self.session_timer_secs() / 60.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Pink noise level for the wake-up program (0.0 unless waking with pink noise)
+    pub fn wake_noise_level(&self, params: &CoherenceParams) -> f32 {
+        if params.wake.active {
+            params.wake.pink_noise_vol * params.wake.ramp_progress_at(self.session_timer_secs() / 60.0)
+        } else {
+            0.0
+        }
+    }
+
     /// Reset all phases (useful when changing presets)
     pub fn reset(&mut self) {
         self.phase_left = 0.0;
         self.phase_right = 0.0;
         self.phase_harmonic_220 = 0.0;
         self.phase_harmonic_495 = 0.0;
-        self.gamma_timer = 0.0;
-        self.gamma_active = false;
-        self.gamma_duration = 0.0;
+        self.phase_isochronic = 0.0;
+        self.gamma_timer = 0;
+        self.gamma_duration = 0;
         self.session_samples = 0;
-        self.current_phase = SessionPhase::Startup;
+        if self.gamma_active {
+            self.gamma_active = false;
+            self.push_event(StimulusEvent::GammaBurstEnd);
+        }
+        if self.current_phase != SessionPhase::Startup {
+            self.current_phase = SessionPhase::Startup;
+            self.push_event(StimulusEvent::Phase { phase: SessionPhase::Startup });
+        }
     }
 
     /// Get current session information (for UI display)
     pub fn get_session_info(&self) -> (f32, SessionPhase) {
         (self.session_timer_secs(), self.current_phase)
     }
+
+    /// Samples since the session started (the clock stimulus markers are stamped with)
+    pub fn session_samples(&self) -> u64 {
+        self.session_samples
+    }
+
+    /// Whether a Focus 10 gamma burst is playing
+    pub fn gamma_burst_active(&self) -> bool {
+        self.gamma_active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sleep_descent_and_fade() {
+        // Long enough for the planned 10 + 20 minute descent and 20 minute fade
+        let sleep = SleepParams { duration_min: 90.0, ..Default::default() };
+        let beats: Vec<f32> = [0.0, 5.0, 10.0, 20.0, 30.0, 60.0].iter().map(|&m| sleep.beat_hz_at(m)).collect();
+        assert_eq!(beats, vec![10.0, 8.0, 6.0, 4.0, 2.0, 2.0]);
+        let fade: Vec<f32> = [60.0, 70.0, 80.0, 90.0, 95.0].iter().map(|&m| sleep.fade_multiplier_at(m)).collect();
+        assert_eq!(fade, vec![1.0, 1.0, 0.5, 0.0, 0.0]);
+
+        // A 25 minute session squeezes every stage to half
+        let short = SleepParams { duration_min: 25.0, ..Default::default() };
+        assert_eq!((short.descent_end_min(), short.fade_start_min()), (15.0, 15.0));
+        assert_eq!((short.beat_hz_at(2.5), short.beat_hz_at(10.0), short.beat_hz_at(15.0)), (8.0, 4.0, 2.0));
+        assert_eq!(short.fade_multiplier_at(20.0), 0.5);
+        assert!(!SleepParams::default().is_active());
+    }
+
+    #[test]
+    fn test_sleep_ends_on_the_wall_clock() {
+        let sleep = SleepParams { duration_min: 15.0, ..Default::default() };
+        let start = 1_760_000_000;
+        assert_eq!(sleep.remaining_min(start, start + 600), 5.0);
+        assert!(!sleep.is_over(start, start + 899) && sleep.is_over(start, start + 900));
+        assert!(!SleepParams::default().is_over(start, start + 900));
+    }
+
+    #[test]
+    fn test_wake_ramp() {
+        // Delta to alpha over the first half of the climb, alpha to beta over the second, then beta
+        let wake = WakeParams { active: true, ..Default::default() };
+        let beats: Vec<f32> = [0.0, 5.0, 10.0, 15.0, 20.0, 25.0].iter().map(|&m| wake.beat_hz_at(m)).collect();
+        assert_eq!(beats, vec![2.0, 6.0, 10.0, 14.0, 18.0, 18.0]);
+        let levels: Vec<f32> = [-1.0, 0.0, 5.0, 20.0, 40.0].iter().map(|&m| wake.ramp_progress_at(m)).collect();
+        assert_eq!(levels, vec![0.0, 0.0, 0.25, 1.0, 1.0]);
+        assert!(!wake.is_finished_at(29.9) && wake.is_finished_at(30.0));
+
+        // While waking, the climb is the startup phase and the hold ends the session
+        let coherence = CoherenceParams { wake, ..Default::default() };
+        assert_eq!((coherence.startup_end_min(), coherence.total_session_min()), (20.0, 30.0));
+        assert_eq!(coherence.current_beat_hz(10.0), 10.0);
+
+        // No ramp: straight to beta at full level
+        let instant = WakeParams { ramp_min: 0.0, ..wake };
+        assert_eq!((instant.beat_hz_at(0.0), instant.ramp_progress_at(0.0)), (18.0, 1.0));
+    }
 }
@@ -122,6 +122,14 @@ pub const STATUS_WARNING_TIMEOUT_SECS: u64 = 8;
 /// Header offset for mixer display (number of header lines)
 pub const MIXER_HEADER_OFFSET: usize = 9;
 
+// === Post-Session Report ===
+/// Rating scale range for report questions
+pub const REPORT_SCALE_MIN: u8 = 1;
+pub const REPORT_SCALE_MAX: u8 = 10;
+
+/// Initial rating shown for scale questions
+pub const REPORT_SCALE_DEFAULT: u8 = 5;
+
 // === Session Timing Defaults (Monroe-style 30-minute session) ===
 /// Default startup phase duration (minutes)
 pub const DEFAULT_STARTUP_DURATION_MIN: f32 = 2.0;
@@ -135,6 +143,322 @@ pub const DEFAULT_STABILIZATION_DURATION_MIN: f32 = 10.0;
 /// Default return phase duration (minutes)
 pub const DEFAULT_RETURN_DURATION_MIN: f32 = 5.0;
 
+// === Sleep Mode Defaults ===
+/// Beat at the start of the descent (alpha)
+pub const SLEEP_ALPHA_BEAT_HZ: f32 = 10.0;
+
+/// Beat at the end of the alpha stage (theta)
+pub const SLEEP_THETA_BEAT_HZ: f32 = 6.0;
+
+/// Beat held for the rest of the night (delta)
+pub const SLEEP_DELTA_BEAT_HZ: f32 = 2.0;
+
+/// Default alpha -> theta glide duration (minutes)
+pub const SLEEP_ALPHA_STAGE_MIN: f32 = 10.0;
+
+/// Default theta -> delta glide duration (minutes)
+pub const SLEEP_THETA_STAGE_MIN: f32 = 20.0;
+
+/// Default final fade duration (minutes)
+pub const SLEEP_FADE_MIN: f32 = 20.0;
+
+/// Sleep timer adjustment step (minutes)
+pub const SLEEP_TIMER_STEP_MIN: f32 = 15.0;
+
+/// Longest sleep timer (minutes)
+pub const SLEEP_TIMER_MAX_MIN: f32 = 720.0;
+
+// === Wake-Up Mode Defaults ===
+/// Beat at the start of the wake-up climb (delta)
+pub const WAKE_DELTA_BEAT_HZ: f32 = 2.0;
+
+/// Beat halfway through the climb (alpha)
+pub const WAKE_ALPHA_BEAT_HZ: f32 = 10.0;
+
+/// Beat at the end of the climb (beta)
+pub const WAKE_BETA_BEAT_HZ: f32 = 18.0;
+
+/// Default wake-up climb duration (minutes)
+pub const WAKE_RAMP_MIN: f32 = 20.0;
+
+/// Default time held at beta after the climb (minutes)
+pub const WAKE_HOLD_MIN: f32 = 10.0;
+
+/// Wake alarm adjustment step (minutes)
+pub const WAKE_ALARM_STEP_MIN: u32 = 15;
+
+/// Alarm time used when the alarm is first switched on (07:00)
+pub const WAKE_ALARM_DEFAULT_MIN: u32 = 7 * 60;
+
+// === EEG Input ===
+/// Seconds of EEG kept in the shared stream buffer
+pub const EEG_BUFFER_SECS: f32 = 60.0;
+
+/// Seconds without data before a source is reported as silent
+pub const EEG_IDLE_TIMEOUT_SECS: f32 = 3.0;
+
+/// Serial read timeout (tenths of a second) so reader threads can notice stop requests
+pub const SERIAL_READ_TIMEOUT_DS: u8 = 5;
+
+/// OpenBCI dongle baud rate
+pub const OPENBCI_BAUD: u32 = 115_200;
+
+/// OpenBCI start/stop streaming commands
+pub const OPENBCI_CMD_START: u8 = b'b';
+pub const OPENBCI_CMD_STOP: u8 = b's';
+
+/// Bytes read from the serial port per call
+pub const OPENBCI_READ_CHUNK_SIZE: usize = 1024;
+
+/// Cyton board: 8 channels at 250 Hz in 33-byte packets (0xA0 header, 0xCn footer)
+pub const CYTON_SAMPLE_RATE_HZ: f32 = 250.0;
+pub const CYTON_CHANNELS: usize = 8;
+pub const CYTON_PACKET_SIZE: usize = 33;
+pub const CYTON_HEADER: u8 = 0xA0;
+pub const CYTON_FOOTER_ACCEL: u8 = 0xC0;
+
+/// Cyton ADS1299 scale: 4.5 V reference, gain 24, 24-bit (microvolts per count)
+pub const CYTON_UV_PER_COUNT: f32 = 4.5 / 24.0 / 8_388_607.0 * 1_000_000.0;
+
+/// Cyton LIS3DH accelerometer scale (g per count)
+pub const CYTON_G_PER_COUNT: f32 = 0.002 / 16.0;
+
+/// Ganglion board: 4 channels at 200 Hz in 20-byte BLE notifications
+pub const GANGLION_SAMPLE_RATE_HZ: f32 = 200.0;
+pub const GANGLION_CHANNELS: usize = 4;
+pub const GANGLION_PAYLOAD_SIZE: usize = 20;
+
+/// Ganglion MCP3912 scale: 1.2 V reference, gain 51, 1.5 V/V (microvolts per count)
+pub const GANGLION_UV_PER_COUNT: f32 = 1.2 * 1_000_000.0 / (8_388_607.0 * 1.5 * 51.0);
+
+/// BGAPI framing used by the BLED112 dongle
+pub const BGAPI_HEADER_SIZE: usize = 4;
+pub const BGAPI_CLASS_ATTCLIENT: u8 = 0x04;
+pub const BGAPI_EVENT_ATTRIBUTE_VALUE: u8 = 0x05;
+pub const BGAPI_COMMAND_ATTRIBUTE_WRITE: u8 = 0x05;
+
+/// Muse headbands over OSC (Mind Monitor / muse-io): 4 channels (TP9, AF7, AF8, TP10) at 256 Hz
+pub const MUSE_SAMPLE_RATE_HZ: f32 = 256.0;
+pub const MUSE_CHANNELS: usize = 4;
+
+/// Default UDP port Mind Monitor sends OSC to
+pub const MUSE_DEFAULT_OSC_PORT: u16 = 5000;
+
+/// Largest OSC datagram accepted
+pub const OSC_MAX_PACKET_SIZE: usize = 65_536;
+
+/// UDP read timeout (milliseconds) so the listener can notice stop requests
+pub const UDP_READ_TIMEOUT_MS: u64 = 500;
+
+/// Replay: how often due samples are pushed (milliseconds) and the slowest allowed speed
+pub const REPLAY_TICK_MS: u64 = 20;
+pub const REPLAY_MIN_SPEED: f32 = 0.1;
+
+// === EEG Analysis ===
+/// Sliding analysis window (seconds of EEG per band-power estimate)
+pub const EEG_ANALYSIS_WINDOW_SECS: f32 = 4.0;
+
+/// How often band power is recomputed (seconds)
+pub const EEG_ANALYSIS_INTERVAL_SECS: f32 = 0.25;
+
+/// Frequency range covered by the bands (delta starts / gamma ends here)
+pub const EEG_BAND_MIN_HZ: f32 = 0.5;
+pub const EEG_BAND_MAX_HZ: f32 = 45.0;
+
+/// High-pass corner removing electrode drift before analysis
+pub const EEG_HIGHPASS_HZ: f32 = 0.5;
+
+/// Mains notch: default frequency and quality factor
+pub const EEG_MAINS_DEFAULT_HZ: f32 = 50.0;
+pub const EEG_NOTCH_Q: f32 = 30.0;
+
+/// Butterworth Q for the second-order high-pass
+pub const EEG_HIGHPASS_Q: f32 = 0.707;
+
+/// Artifact thresholds (microvolts)
+/// Flat: RMS below this means a disconnected or shorted electrode
+pub const EEG_FLAT_RMS_UV: f32 = 0.5;
+/// Railed: raw DC offset beyond this means the amplifier input is saturated
+pub const EEG_RAILED_UV: f32 = 100_000.0;
+/// High amplitude: filtered peak-to-peak beyond this means blinks, jaw clench or movement
+pub const EEG_ARTIFACT_PTP_UV: f32 = 200.0;
+
+/// Width of each band bar in the TUI (characters)
+pub const EEG_BAND_BAR_WIDTH: usize = 6;
+
+// === Closed-Loop Control ===
+/// Minimum time between controller adjustments (seconds)
+pub const CLOSED_LOOP_INTERVAL_SECS: f32 = 10.0;
+
+/// Rate limits: largest beat, volume and carrier change per adjustment
+pub const CLOSED_LOOP_MAX_STEP_HZ: f32 = 0.5;
+pub const CLOSED_LOOP_MAX_VOLUME_STEP: f32 = 0.05;
+pub const CLOSED_LOOP_MAX_CARRIER_STEP_HZ: f32 = 10.0;
+
+/// Safety bounds for the beat and volume the controller may set
+pub const CLOSED_LOOP_MIN_BEAT_HZ: f32 = 1.0;
+pub const CLOSED_LOOP_MAX_BEAT_HZ: f32 = 20.0;
+pub const CLOSED_LOOP_MAX_VOLUME: f32 = 0.8;
+
+/// Allowed carrier range for the controller (a preset's starting carrier and the band → carrier law)
+pub const CLOSED_LOOP_CARRIER_MIN_HZ: f32 = 100.0;
+pub const CLOSED_LOOP_CARRIER_MAX_HZ: f32 = 1000.0;
+
+/// Changes smaller than this are not applied or logged
+pub const CLOSED_LOOP_MIN_CHANGE: f32 = 0.001;
+
+/// Hold-theta law defaults
+pub const CLOSED_LOOP_THETA_HOLD_HZ: f32 = 6.0;
+pub const CLOSED_LOOP_THETA_RATIO: f32 = 1.0;
+pub const CLOSED_LOOP_THETA_SUSTAIN_SECS: f32 = 60.0;
+pub const CLOSED_LOOP_THETA_RELEASE_HZ: f32 = 4.0;
+
+// === EEG Recording (EDF+/BDF+) ===
+/// Length of one EDF data record (seconds)
+pub const EDF_RECORD_SECS: f64 = 1.0;
+
+/// Bytes reserved per data record for annotations; events that don't fit move to the next record
+pub const EDF_ANNOTATION_BYTES_PER_RECORD: usize = 480;
+
+// === Stimulus Events ===
+/// Smallest beat change reported as a stimulus event (sleep/wake ramps change continuously)
+pub const STIMULUS_BEAT_CHANGE_HZ: f32 = 0.1;
+
+/// How often the marker server checks for new TCP clients while idle
+pub const MARKER_ACCEPT_INTERVAL_MS: u64 = 100;
+
+/// A TCP client that stops reading for this long is dropped
+pub const MARKER_WRITE_TIMEOUT_MS: u64 = 200;
+
+/// Stream name and source id announced in the LSL marker layout
+pub const LSL_MARKER_STREAM_NAME: &str = "soulwhistle-markers";
+
+// === Experiment Protocols ===
+/// Longest timed protocol step
+pub const PROTOCOL_STEP_MAX_MIN: f32 = 600.0;
+
+/// Files written to each run folder
+pub const PROTOCOL_COPY_FILENAME: &str = "protocol.json";
+pub const PROTOCOL_RUN_LOG_FILENAME: &str = "run.json";
+
+/// Width of the current step's progress bar
+pub const PROTOCOL_PROGRESS_WIDTH: usize = 20;
+
+// === Heart Rate / HRV ===
+/// Default baud rate for serial heart-rate bridges
+pub const HEART_DEFAULT_BAUD: u32 = 115_200;
+
+/// Plausible RR interval range (milliseconds); plain numbers below the minimum are read as bpm
+pub const HEART_RR_MIN_MS: f32 = 300.0;
+pub const HEART_RR_MAX_MS: f32 = 2000.0;
+
+/// An RR interval differing from the previous one by more than this fraction is an ectopic or missed beat
+pub const HEART_RR_MAX_CHANGE: f32 = 0.2;
+
+/// Beats kept in the shared buffer (about 10 minutes at rest)
+pub const HEART_BUFFER_BEATS: usize = 600;
+
+/// Seconds without a reading before a heart source is reported as silent (bridges send about once per beat)
+pub const HEART_IDLE_TIMEOUT_SECS: f32 = 5.0;
+
+/// Longest accepted line from a serial or UDP bridge (bytes)
+pub const HEART_MAX_LINE_LEN: usize = 4096;
+
+/// Window of beats analysed for RMSSD and coherence (seconds), and the fewest beats needed
+pub const HRV_WINDOW_SECS: f32 = 64.0;
+pub const HRV_MIN_BEATS: usize = 20;
+
+/// Even resampling rate of the heart-rate tachogram before the spectrum (Hz)
+pub const HRV_RESAMPLE_HZ: f32 = 4.0;
+
+/// Coherence: peak search range, width of the peak window, and the total power range (Hz)
+pub const HRV_PEAK_MIN_HZ: f32 = 0.04;
+pub const HRV_PEAK_MAX_HZ: f32 = 0.26;
+pub const HRV_PEAK_HALF_WIDTH_HZ: f32 = 0.015;
+pub const HRV_TOTAL_MIN_HZ: f32 = 0.0033;
+pub const HRV_TOTAL_MAX_HZ: f32 = 0.4;
+
+/// How often HRV is added to the session log (seconds)
+pub const HRV_LOG_INTERVAL_SECS: f32 = 10.0;
+
+/// Breath pacing: starting rate and the resonance search range (Hz; 0.1 Hz = 6 breaths/min)
+pub const BREATH_PACE_START_HZ: f32 = 0.1;
+pub const BREATH_PACE_MIN_HZ: f32 = 0.075;
+pub const BREATH_PACE_MAX_HZ: f32 = 0.125;
+
+/// Breath pacing: rate change per trial, time spent at each rate, and the settling time ignored
+/// at the start of each trial (seconds)
+pub const BREATH_PACE_STEP_HZ: f32 = 0.005;
+pub const BREATH_PACE_DWELL_SECS: f32 = 60.0;
+pub const BREATH_PACE_SETTLE_SECS: f32 = 15.0;
+
+// === Recording Analysis (same settings as tools/coherence_analyzer.py) ===
+/// Section detection: window length and hop of the frequency timeline
+pub const ANALYZE_TIMELINE_WINDOW_SECS: f64 = 10.0;
+pub const ANALYZE_TIMELINE_HOP_SECS: f64 = 5.0;
+
+/// Per-section analysis chunks (defaults for --chunk-duration and --overlap)
+pub const ANALYZE_CHUNK_SECS: f64 = 30.0;
+pub const ANALYZE_OVERLAP_SECS: f64 = 15.0;
+
+/// Carrier search range
+pub const ANALYZE_MIN_FREQ_HZ: f64 = 50.0;
+pub const ANALYZE_MAX_FREQ_HZ: f64 = 1000.0;
+
+/// Secondary peaks must reach this fraction of the dominant one; this many are kept
+pub const ANALYZE_PEAK_MIN_RATIO: f64 = 0.3;
+pub const ANALYZE_TOP_PEAKS: usize = 5;
+
+/// Savitzky-Golay smoothing of the timeline before change detection
+pub const ANALYZE_SMOOTH_WINDOW: usize = 11;
+pub const ANALYZE_SMOOTH_ORDER: usize = 3;
+
+/// A section starts where the beat jumps by more than the first value and stays moved by the
+/// second (carriers: same with the carrier thresholds); sections are at least ANALYZE_MIN_SECTION_SECS long
+pub const ANALYZE_BEAT_JUMP_HZ: f64 = 2.0;
+pub const ANALYZE_BEAT_SHIFT_HZ: f64 = 1.5;
+pub const ANALYZE_CARRIER_JUMP_HZ: f64 = 20.0;
+pub const ANALYZE_CARRIER_SHIFT_HZ: f64 = 15.0;
+pub const ANALYZE_MIN_SECTION_SECS: f64 = 30.0;
+
+/// First/last sections quieter than this fraction of the mean level are the intro/outro
+pub const ANALYZE_QUIET_RATIO: f64 = 0.7;
+
+// === Presets From Analysis ===
+/// Dominant peaks below this are mains hum or the bottom edge of the analysis band, not carriers
+pub const ANALYSIS_PRESET_MIN_CARRIER_HZ: f64 = 60.0;
+/// Sections whose beat is this close to the dominant one count as the same entrainment
+pub const ANALYSIS_PRESET_BEAT_TOL_HZ: f64 = 0.5;
+/// A spectral peak this close to 220/495 Hz counts as that harmonic
+pub const ANALYSIS_PRESET_HARMONIC_TOL_HZ: f64 = 3.0;
+/// Share of the dominant section's chunks that must show a harmonic for it to be switched on
+pub const ANALYSIS_PRESET_HARMONIC_SHARE: f64 = 0.5;
+
+// === Offline Rendering ===
+/// Audio sample rate for headless runs when none is given
+pub const OFFLINE_SAMPLE_RATE_HZ: u32 = 48_000;
+
+// === Preset Verification ===
+/// Sample rate the binaural layer is rendered at (covers the carriers, harmonics and gamma burst tones)
+pub const VERIFY_SAMPLE_RATE_HZ: f32 = 8000.0;
+/// Length of the steady full-volume window the spectra are measured on (0.125 Hz bins)
+pub const VERIFY_WINDOW_SECS: f32 = 8.0;
+/// Block length for gamma burst detection
+pub const VERIFY_BLOCK_SECS: f32 = 0.05;
+/// Block length of the measured volume envelope
+pub const VERIFY_ENVELOPE_SECS: f32 = 1.0;
+/// A tone's level is the energy within this many Hz of it
+pub const VERIFY_BAND_HZ: f64 = 2.0;
+/// Sidebands on each side summed for the signal carrier modulation check
+pub const VERIFY_SIDEBANDS: usize = 3;
+pub const VERIFY_FREQ_TOL_HZ: f64 = 0.02;
+pub const VERIFY_LEVEL_TOL: f64 = 0.1;       // Relative error allowed on harmonic and sideband levels
+pub const VERIFY_ABSENT_LEVEL: f64 = 0.01;   // Level (relative to the carrier) of a tone that should be off
+pub const VERIFY_ENVELOPE_TOL: f32 = 0.03;   // Error allowed on the phase volume multiplier
+pub const VERIFY_GAMMA_TOL_SECS: f32 = 0.1;
+pub const VERIFY_GAMMA_ON_RATIO: f64 = 0.5;  // Gamma tone share of a block counted as a burst
+pub const VERIFY_GAMMA_CLEAN_RATIO: f64 = 0.1; // Blocks above this are kept out of the measurement window
+
 // === Audio Processing ===
 /// Default master volume (start low for safety)
 pub const DEFAULT_MASTER_VOLUME: f32 = 0.1;
@@ -172,7 +496,7 @@ pub const CHIRP_SWEEP_START_HZ: f32 = 2000.0;
 pub const CHIRP_SWEEP_END_HZ: f32 = 3000.0;
 
 // === Breath Layer ===
-/// Breath LFO frequency (Hz)
+/// Breath LFO frequency (Hz) unless breath pacing sets another rate
 pub const BREATH_LFO_HZ: f32 = 0.2;
 
 // === Noise Reduction ===
@@ -221,6 +545,54 @@ pub fn get_presets_dir() -> std::path::PathBuf {
     }
 }
 
+/// Get user data directory for session records
+/// Returns ~/.local/share/soulwhistle/sessions on Linux
+/// Returns ~/Library/Application Support/soulwhistle/sessions on macOS
+/// Returns %APPDATA%\soulwhistle\data\sessions on Windows
+pub fn get_sessions_dir() -> std::path::PathBuf {
+    if let Some(proj_dirs) = directories::ProjectDirs::from("", "", "soulwhistle") {
+        proj_dirs.data_dir().join("sessions")
+    } else {
+        // Fallback to current directory if we can't determine data dir
+        std::path::PathBuf::from("sessions")
+    }
+}
+
+/// Get user data directory for protocol runs (one folder per run)
+/// Returns ~/.local/share/soulwhistle/runs on Linux
+pub fn get_runs_dir() -> std::path::PathBuf {
+    if let Some(proj_dirs) = directories::ProjectDirs::from("", "", "soulwhistle") {
+        proj_dirs.data_dir().join("runs")
+    } else {
+        std::path::PathBuf::from("runs")
+    }
+}
+
+// === Preset Schema ===
+/// Schema version written into presets; older files are migrated step by step on load
+pub const PRESET_SCHEMA_VERSION: u32 = 1;
+
+/// Subdirectory of the presets folder that keeps the originals of upgraded presets
+pub const PRESET_BACKUP_DIRNAME: &str = "backup";
+
+/// Subdirectory of the presets folder that deleted and reverted presets are moved to
+pub const PRESET_TRASH_DIRNAME: &str = "trash";
+
+/// File in the presets folder recording which built-in version each DEFAULT_ copy was installed from
+pub const PRESET_DEFAULTS_MANIFEST: &str = ".installed_defaults";
+
+/// Start of a shareable preset code (what follows is deflated JSON in URL-safe base64)
+pub const PRESET_CODE_PREFIX: &str = "SW1:";
+
+/// Largest preset file read from a bundle archive or code, in bytes
+pub const PRESET_BUNDLE_MAX_BYTES: u64 = 1024 * 1024;
+
+/// Characters of a pasted preset code shown in the import prompt (its end)
+pub const PRESET_IMPORT_PROMPT_TAIL: usize = 32;
+
+/// Custom beats further than this from the carriers' difference are reported as inconsistent
+pub const PRESET_LINT_BEAT_TOL_HZ: f32 = 0.01;
+
 // === Amplitude Thresholds ===
 /// Threshold for AM modulation depth
 pub const AM_MODULATION_MIN: f32 = 0.5;
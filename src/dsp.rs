@@ -0,0 +1,326 @@
+// Shared signal-processing helpers: FFT, windows, spectra and IIR filters
+
+use num_complex::{Complex32, Complex64};
+use std::f32::consts::PI;
+
+/// In-place iterative radix-2 FFT (length must be a power of two)
+pub fn fft(buffer: &mut [Complex32]) {
+    let n = buffer.len();
+    assert!(n.is_power_of_two(), "FFT length must be a power of two");
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buffer.swap(i, j);
+        }
+    }
+
+    // Butterflies
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        let w_len = Complex32::new(angle.cos(), angle.sin());
+        for start in (0..n).step_by(len) {
+            let mut w = Complex32::new(1.0, 0.0);
+            for k in 0..len / 2 {
+                let u = buffer[start + k];
+                let v = buffer[start + k + len / 2] * w;
+                buffer[start + k] = u + v;
+                buffer[start + k + len / 2] = u - v;
+                w *= w_len;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Discrete Fourier transform of any length in f64, for long audio segments (Bluestein's algorithm
+/// over a power-of-two FFT); the plan caches the chirp so repeated transforms of one length are cheap
+pub struct DftPlan {
+    n: usize,
+    twiddles: Vec<Complex64>,  // exp(-2πik/m) for the power-of-two length m
+    chirp: Vec<Complex64>,     // exp(-iπk²/n)
+    chirp_fft: Vec<Complex64>, // FFT of the conjugate chirp, wrapped to length m
+}
+
+impl DftPlan {
+    pub fn new(n: usize) -> Self {
+        let m = (2 * n.max(1) - 1).next_power_of_two();
+        let twiddles = (0..m / 2)
+            .map(|k| Complex64::from_polar(1.0, -2.0 * std::f64::consts::PI * k as f64 / m as f64))
+            .collect();
+        // k² mod 2n keeps the phase exact for long transforms
+        let chirp: Vec<Complex64> = (0..n as u64)
+            .map(|k| Complex64::from_polar(1.0, -std::f64::consts::PI * ((k * k) % (2 * n as u64)) as f64 / n as f64))
+            .collect();
+
+        let mut chirp_fft = vec![Complex64::new(0.0, 0.0); m];
+        for (k, c) in chirp.iter().enumerate() {
+            chirp_fft[k] = c.conj();
+            if k > 0 {
+                chirp_fft[m - k] = c.conj();
+            }
+        }
+        let mut plan = Self { n, twiddles, chirp, chirp_fft: Vec::new() };
+        plan.fft(&mut chirp_fft);
+        plan.chirp_fft = chirp_fft;
+        plan
+    }
+
+    /// Forward DFT of `input` (which must have the plan's length)
+    pub fn transform(&self, input: &[Complex64]) -> Vec<Complex64> {
+        assert_eq!(input.len(), self.n, "DFT input length must match the plan");
+        let m = self.chirp_fft.len();
+        let mut buffer = vec![Complex64::new(0.0, 0.0); m];
+        for ((b, x), c) in buffer.iter_mut().zip(input).zip(&self.chirp) {
+            *b = x * c;
+        }
+        self.fft(&mut buffer);
+        // Convolve with the chirp, then inverse FFT (as the conjugate of a forward one)
+        for (b, c) in buffer.iter_mut().zip(&self.chirp_fft) {
+            *b = (*b * c).conj();
+        }
+        self.fft(&mut buffer);
+        let scale = 1.0 / m as f64;
+        buffer.iter().zip(&self.chirp).map(|(b, c)| b.conj() * scale * c).collect()
+    }
+
+    /// Spectra (bins 0..=n/2) of two real signals, computed together as one complex transform
+    pub fn real_pair(&self, left: &[f64], right: &[f64]) -> (Vec<Complex64>, Vec<Complex64>) {
+        let input: Vec<Complex64> = left.iter().zip(right).map(|(&l, &r)| Complex64::new(l, r)).collect();
+        let z = self.transform(&input);
+        // L = (Z[k] + Z*[n-k]) / 2, R = (Z[k] - Z*[n-k]) / 2i
+        (0..=self.n / 2)
+            .map(|k| {
+                let mirror = z[(self.n - k) % self.n].conj();
+                ((z[k] + mirror) * 0.5, (z[k] - mirror) * Complex64::new(0.0, -0.5))
+            })
+            .unzip()
+    }
+
+    /// In-place radix-2 FFT of the plan's power-of-two length
+    fn fft(&self, buffer: &mut [Complex64]) {
+        let m = buffer.len();
+        let mut j = 0;
+        for i in 1..m {
+            let mut bit = m >> 1;
+            while j & bit != 0 {
+                j ^= bit;
+                bit >>= 1;
+            }
+            j |= bit;
+            if i < j {
+                buffer.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        while len <= m {
+            let stride = m / len;
+            for start in (0..m).step_by(len) {
+                for k in 0..len / 2 {
+                    let u = buffer[start + k];
+                    let v = buffer[start + k + len / 2] * self.twiddles[k * stride];
+                    buffer[start + k] = u + v;
+                    buffer[start + k + len / 2] = u - v;
+                }
+            }
+            len <<= 1;
+        }
+    }
+}
+
+/// Hann window coefficients
+pub fn hann_window(len: usize) -> Vec<f32> {
+    if len < 2 {
+        return vec![1.0; len];
+    }
+    (0..len)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (len - 1) as f32).cos())
+        .collect()
+}
+
+/// One-sided power spectral density of a windowed segment (units²/Hz)
+/// `segment.len()` must equal `window.len()` and be a power of two
+pub fn power_spectrum(segment: &[f32], window: &[f32], sample_rate: f32) -> Vec<f32> {
+    let n = segment.len();
+    let mean = segment.iter().sum::<f32>() / n as f32;
+    let mut buffer: Vec<Complex32> = segment.iter().zip(window)
+        .map(|(&x, &w)| Complex32::new((x - mean) * w, 0.0))
+        .collect();
+    fft(&mut buffer);
+
+    let window_power: f32 = window.iter().map(|w| w * w).sum();
+    let scale = 1.0 / (sample_rate * window_power);
+    (0..=n / 2)
+        .map(|k| {
+            let p = buffer[k].norm_sqr() * scale;
+            // Double everything except DC and Nyquist for the one-sided spectrum
+            if k == 0 || k == n / 2 { p } else { 2.0 * p }
+        })
+        .collect()
+}
+
+/// Welch PSD estimate: average of Hann-windowed segments with 50% overlap
+/// Returns an empty spectrum if the signal is shorter than one segment
+pub fn welch_psd(signal: &[f32], segment_len: usize, sample_rate: f32) -> Vec<f32> {
+    if signal.len() < segment_len {
+        return Vec::new();
+    }
+    let window = hann_window(segment_len);
+    let hop = (segment_len / 2).max(1);
+
+    let mut psd = vec![0.0; segment_len / 2 + 1];
+    let mut segments = 0;
+    let mut start = 0;
+    while start + segment_len <= signal.len() {
+        let spectrum = power_spectrum(&signal[start..start + segment_len], &window, sample_rate);
+        for (acc, p) in psd.iter_mut().zip(spectrum) {
+            *acc += p;
+        }
+        segments += 1;
+        start += hop;
+    }
+    for p in psd.iter_mut() {
+        *p /= segments as f32;
+    }
+    psd
+}
+
+/// Integrate a PSD over [low_hz, high_hz) (units²)
+pub fn band_power(psd: &[f32], bin_hz: f32, low_hz: f32, high_hz: f32) -> f32 {
+    psd.iter().enumerate()
+        .filter(|(k, _)| {
+            let f = *k as f32 * bin_hz;
+            f >= low_hz && f < high_hz
+        })
+        .map(|(_, p)| p * bin_hz)
+        .sum()
+}
+
+/// Frequency of the largest PSD bin in [low_hz, high_hz), refined by parabolic interpolation
+/// None if the range holds no bins
+pub fn spectral_peak_hz(psd: &[f32], bin_hz: f32, low_hz: f32, high_hz: f32) -> Option<f32> {
+    let (k, _) = psd.iter().enumerate()
+        .filter(|(k, _)| {
+            let f = *k as f32 * bin_hz;
+            f >= low_hz && f < high_hz
+        })
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+
+    // Fit a parabola through the peak and its neighbours for sub-bin resolution
+    if k == 0 || k + 1 >= psd.len() {
+        return Some(k as f32 * bin_hz);
+    }
+    let (left, centre, right) = (psd[k - 1], psd[k], psd[k + 1]);
+    let denom = left - 2.0 * centre + right;
+    let offset = if denom.abs() > f32::EPSILON { 0.5 * (left - right) / denom } else { 0.0 };
+    Some((k as f32 + offset.clamp(-0.5, 0.5)) * bin_hz)
+}
+
+/// Second-order IIR section (RBJ cookbook), direct form I
+#[derive(Clone)]
+pub struct Biquad {
+    b0: f32, b1: f32, b2: f32,
+    a1: f32, a2: f32,
+    x1: f32, x2: f32,
+    y1: f32, y2: f32,
+}
+
+impl Biquad {
+    /// Notch filter removing `freq_hz` (e.g. 50/60 Hz mains) with quality factor `q`
+    pub fn notch(freq_hz: f32, q: f32, sample_rate: f32) -> Self {
+        let w0 = 2.0 * PI * freq_hz / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: 1.0 / a0,
+            b1: -2.0 * w0.cos() / a0,
+            b2: 1.0 / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha) / a0,
+            x1: 0.0, x2: 0.0,
+            y1: 0.0, y2: 0.0,
+        }
+    }
+
+    /// High-pass filter with corner `freq_hz` (q = 0.707 for Butterworth)
+    pub fn highpass(freq_hz: f32, q: f32, sample_rate: f32) -> Self {
+        let w0 = 2.0 * PI * freq_hz / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos_w0 = w0.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: (1.0 + cos_w0) / 2.0 / a0,
+            b1: -(1.0 + cos_w0) / a0,
+            b2: (1.0 + cos_w0) / 2.0 / a0,
+            a1: -2.0 * cos_w0 / a0,
+            a2: (1.0 - alpha) / a0,
+            x1: 0.0, x2: 0.0,
+            y1: 0.0, y2: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_welch_finds_sine_peak() {
+        let sample_rate = 256.0;
+        let signal: Vec<f32> = (0..1024)
+            .map(|i| (2.0 * PI * 10.0 * i as f32 / sample_rate).sin())
+            .collect();
+        let psd = welch_psd(&signal, 256, sample_rate);
+        let peak = psd.iter().enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .map(|(k, _)| k as f32 * sample_rate / 256.0)
+            .unwrap();
+        assert!((peak - 10.0).abs() < 1.01);
+
+        // Total power of a unit sine is 0.5
+        let total = band_power(&psd, sample_rate / 256.0, 0.0, 128.0);
+        assert!((total - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_dft_plan_matches_direct_dft() {
+        let n = 45; // Not a power of two
+        let input: Vec<Complex64> = (0..n).map(|i| Complex64::new((i as f64 * 0.7).sin(), (i as f64 * 0.3).cos())).collect();
+        let output = DftPlan::new(n).transform(&input);
+        for (k, value) in output.iter().enumerate() {
+            let direct: Complex64 = input.iter().enumerate()
+                .map(|(i, x)| x * Complex64::from_polar(1.0, -2.0 * std::f64::consts::PI * (i * k) as f64 / n as f64))
+                .sum();
+            assert!((value - direct).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_notch_removes_mains() {
+        let sample_rate = 250.0;
+        let mut notch = Biquad::notch(50.0, 30.0, sample_rate);
+        let output: Vec<f32> = (0..2500)
+            .map(|i| notch.process((2.0 * PI * 50.0 * i as f32 / sample_rate).sin()))
+            .collect();
+        let tail_peak = output[2000..].iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        assert!(tail_peak < 0.05);
+    }
+}
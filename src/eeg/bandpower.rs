@@ -0,0 +1,329 @@
+// Real-time EEG band-power analysis
+// Samples are high-passed and mains-notched as they arrive; every analysis interval a Welch PSD
+// over the sliding window gives per-channel band power, dominant frequency and artifact flags
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use crate::constants::*;
+use crate::dsp::{band_power, spectral_peak_hz, welch_psd, Biquad};
+use super::EegStream;
+
+/// Absolute power per band (µV²), using the same edges as the binaural beat states
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct BandPowers {
+    pub delta: f32,
+    pub theta: f32,
+    pub alpha: f32,
+    pub beta: f32,
+    pub gamma: f32,
+}
+
+impl BandPowers {
+    /// Band labels in display order
+    pub const NAMES: [&'static str; 5] = ["δ", "θ", "α", "β", "γ"];
+
+    fn from_psd(psd: &[f32], bin_hz: f32) -> Self {
+        Self {
+            delta: band_power(psd, bin_hz, EEG_BAND_MIN_HZ, DELTA_MAX_HZ),
+            theta: band_power(psd, bin_hz, DELTA_MAX_HZ, THETA_MAX_HZ),
+            alpha: band_power(psd, bin_hz, THETA_MAX_HZ, ALPHA_MAX_HZ),
+            beta: band_power(psd, bin_hz, ALPHA_MAX_HZ, BETA_MAX_HZ),
+            gamma: band_power(psd, bin_hz, BETA_MAX_HZ, EEG_BAND_MAX_HZ),
+        }
+    }
+
+    pub fn as_array(&self) -> [f32; 5] {
+        [self.delta, self.theta, self.alpha, self.beta, self.gamma]
+    }
+
+    pub fn total(&self) -> f32 {
+        self.as_array().iter().sum()
+    }
+
+    /// Fraction of total power in each band (all zero when there is no power)
+    pub fn relative(&self) -> Self {
+        let total = self.total();
+        if total <= 0.0 {
+            return Self::default();
+        }
+        Self {
+            delta: self.delta / total,
+            theta: self.theta / total,
+            alpha: self.alpha / total,
+            beta: self.beta / total,
+            gamma: self.gamma / total,
+        }
+    }
+}
+
+/// Signal quality problems detected on a channel
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ArtifactFlags {
+    pub flat: bool,           // Electrode disconnected or shorted
+    pub railed: bool,         // Amplifier saturated by a large DC offset
+    pub high_amplitude: bool, // Blink, jaw clench or movement
+}
+
+impl ArtifactFlags {
+    pub fn any(&self) -> bool {
+        self.flat || self.railed || self.high_amplitude
+    }
+
+    /// Short label for the first flag set
+    pub fn label(&self) -> Option<&'static str> {
+        if self.railed {
+            Some("railed")
+        } else if self.flat {
+            Some("flat")
+        } else if self.high_amplitude {
+            Some("artifact")
+        } else {
+            None
+        }
+    }
+}
+
+/// Analysis result for one channel
+#[derive(Clone, Debug)]
+pub struct ChannelBandPower {
+    pub bands: BandPowers,
+    pub dominant_hz: f32,
+    /// Individual alpha peak frequency (strongest bin in the alpha band)
+    pub alpha_peak_hz: f32,
+    pub artifacts: ArtifactFlags,
+}
+
+/// Analysis result for all channels over the current window
+#[derive(Clone, Debug)]
+pub struct BandPowerSnapshot {
+    pub channels: Vec<ChannelBandPower>,
+}
+
+impl BandPowerSnapshot {
+    /// Relative band power averaged over channels without artifacts
+    /// None when every channel is flagged
+    pub fn mean_relative(&self) -> Option<BandPowers> {
+        let clean: Vec<BandPowers> = self.channels.iter()
+            .filter(|c| !c.artifacts.any())
+            .map(|c| c.bands.relative())
+            .collect();
+        if clean.is_empty() {
+            return None;
+        }
+        let n = clean.len() as f32;
+        let mut mean = BandPowers::default();
+        for bands in &clean {
+            mean.delta += bands.delta / n;
+            mean.theta += bands.theta / n;
+            mean.alpha += bands.alpha / n;
+            mean.beta += bands.beta / n;
+            mean.gamma += bands.gamma / n;
+        }
+        Some(mean)
+    }
+
+    /// Alpha peak frequency averaged over channels without artifacts
+    pub fn mean_alpha_peak_hz(&self) -> Option<f32> {
+        let peaks: Vec<f32> = self.channels.iter()
+            .filter(|c| !c.artifacts.any())
+            .map(|c| c.alpha_peak_hz)
+            .collect();
+        if peaks.is_empty() {
+            None
+        } else {
+            Some(peaks.iter().sum::<f32>() / peaks.len() as f32)
+        }
+    }
+}
+
+struct ChannelState {
+    highpass: Biquad,
+    notch: Option<Biquad>, // None when mains is above Nyquist
+    raw: VecDeque<f32>,      // Unfiltered, for DC offset (railing) checks
+    filtered: VecDeque<f32>, // High-passed and notched, for spectra
+}
+
+/// Sliding-window band-power analyzer fed from an EegStream
+pub struct BandPowerAnalyzer {
+    sample_rate: f32,
+    segment_len: usize,    // Welch segment (power of two, about one second)
+    window_len: usize,     // Samples kept per channel
+    interval_len: usize,   // Samples between estimates
+    mains_hz: f32,
+    channels: Vec<ChannelState>,
+    cursor: u64,
+    samples_since_update: usize,
+    latest: Option<BandPowerSnapshot>,
+}
+
+impl BandPowerAnalyzer {
+    pub fn new(sample_rate: f32, channel_count: usize, mains_hz: f32) -> Self {
+        let segment_len = (sample_rate.max(2.0) as usize).next_power_of_two();
+        let window_len = ((sample_rate * EEG_ANALYSIS_WINDOW_SECS) as usize).max(segment_len);
+        let mut analyzer = Self {
+            sample_rate,
+            segment_len,
+            window_len,
+            interval_len: ((sample_rate * EEG_ANALYSIS_INTERVAL_SECS) as usize).max(1),
+            mains_hz,
+            channels: Vec::new(),
+            cursor: 0,
+            samples_since_update: 0,
+            latest: None,
+        };
+        analyzer.reset_channels(channel_count);
+        analyzer
+    }
+
+    fn reset_channels(&mut self, channel_count: usize) {
+        let notch = (self.mains_hz < self.sample_rate / 2.0)
+            .then(|| Biquad::notch(self.mains_hz, EEG_NOTCH_Q, self.sample_rate));
+        self.channels = (0..channel_count)
+            .map(|_| ChannelState {
+                highpass: Biquad::highpass(EEG_HIGHPASS_HZ, EEG_HIGHPASS_Q, self.sample_rate),
+                notch: notch.clone(),
+                raw: VecDeque::with_capacity(self.window_len),
+                filtered: VecDeque::with_capacity(self.window_len),
+            })
+            .collect();
+    }
+
+    /// Pull new samples from the stream; returns true when a new snapshot was computed
+    pub fn update(&mut self, stream: &EegStream) -> bool {
+        let samples = stream.read_since(&mut self.cursor);
+        for sample in &samples {
+            if sample.channels.len() != self.channels.len() {
+                self.reset_channels(sample.channels.len());
+            }
+            self.push(&sample.channels);
+        }
+
+        // Analyse once per interval, however many samples arrived since the last call
+        self.samples_since_update += samples.len();
+        if self.samples_since_update < self.interval_len {
+            return false;
+        }
+        self.samples_since_update = 0;
+        match self.analyze() {
+            Some(snapshot) => {
+                self.latest = Some(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Most recent estimate, if enough data has arrived
+    pub fn latest(&self) -> Option<&BandPowerSnapshot> {
+        self.latest.as_ref()
+    }
+
+    fn push(&mut self, values: &[f32]) {
+        for (state, &x) in self.channels.iter_mut().zip(values) {
+            let mut y = state.highpass.process(x);
+            if let Some(notch) = state.notch.as_mut() {
+                y = notch.process(y);
+            }
+            if state.raw.len() >= self.window_len {
+                state.raw.pop_front();
+                state.filtered.pop_front();
+            }
+            state.raw.push_back(x);
+            state.filtered.push_back(y);
+        }
+    }
+
+    fn analyze(&self) -> Option<BandPowerSnapshot> {
+        let bin_hz = self.sample_rate / self.segment_len as f32;
+        let mut channels = Vec::with_capacity(self.channels.len());
+
+        for state in &self.channels {
+            if state.filtered.len() < self.segment_len {
+                return None;
+            }
+            let signal: Vec<f32> = state.filtered.iter().copied().collect();
+            let psd = welch_psd(&signal, self.segment_len, self.sample_rate);
+
+            let dominant_hz = spectral_peak_hz(&psd, bin_hz, EEG_BAND_MIN_HZ, EEG_BAND_MAX_HZ).unwrap_or(0.0);
+            let alpha_peak_hz = spectral_peak_hz(&psd, bin_hz, THETA_MAX_HZ, ALPHA_MAX_HZ).unwrap_or(0.0);
+
+            channels.push(ChannelBandPower {
+                bands: BandPowers::from_psd(&psd, bin_hz),
+                dominant_hz,
+                alpha_peak_hz,
+                artifacts: detect_artifacts(&state.raw, &signal),
+            });
+        }
+
+        Some(BandPowerSnapshot { channels })
+    }
+}
+
+fn detect_artifacts(raw: &VecDeque<f32>, filtered: &[f32]) -> ArtifactFlags {
+    let n = filtered.len().max(1) as f32;
+    let raw_mean = raw.iter().sum::<f32>() / raw.len().max(1) as f32;
+    let rms = (filtered.iter().map(|x| x * x).sum::<f32>() / n).sqrt();
+    let (min, max) = filtered.iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), &x| (lo.min(x), hi.max(x)));
+
+    ArtifactFlags {
+        flat: rms < EEG_FLAT_RMS_UV,
+        railed: raw_mean.abs() > EEG_RAILED_UV,
+        high_amplitude: max - min > EEG_ARTIFACT_PTP_UV,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eeg::EegSample;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_alpha_sine_with_mains_hum() {
+        let sample_rate = 250.0;
+        let stream = EegStream::new("test", sample_rate, 2);
+        let batch: Vec<EegSample> = (0..(sample_rate * 6.0) as usize)
+            .map(|i| {
+                let t = i as f32 / sample_rate;
+                let alpha = 20.0 * (2.0 * PI * 10.0 * t).sin();
+                let hum = 50.0 * (2.0 * PI * 50.0 * t).sin();
+                EegSample {
+                    timestamp: t as f64,
+                    // Channel 1 carries a DC offset, which the high-pass removes
+                    channels: vec![alpha + hum, alpha + hum + 1000.0],
+                    accel: None,
+                }
+            })
+            .collect();
+        stream.push_batch(&batch);
+
+        let mut analyzer = BandPowerAnalyzer::new(sample_rate, 2, 50.0);
+        assert!(analyzer.update(&stream));
+        let snapshot = analyzer.latest().unwrap();
+
+        for channel in &snapshot.channels {
+            assert!((channel.dominant_hz - 10.0).abs() < 0.3);
+            assert!((channel.alpha_peak_hz - 10.0).abs() < 0.3);
+            assert!(!channel.artifacts.any());
+        }
+        let mean = snapshot.mean_relative().unwrap();
+        assert!(mean.alpha > 0.8, "alpha fraction {}", mean.alpha);
+    }
+
+    #[test]
+    fn test_railed_channel_is_flagged() {
+        let sample_rate = 200.0;
+        let stream = EegStream::new("test", sample_rate, 1);
+        let batch: Vec<EegSample> = (0..(sample_rate * 2.0) as usize)
+            .map(|i| EegSample { timestamp: i as f64 / 200.0, channels: vec![150_000.0], accel: None })
+            .collect();
+        stream.push_batch(&batch);
+
+        let mut analyzer = BandPowerAnalyzer::new(sample_rate, 1, 60.0);
+        analyzer.update(&stream);
+        let flags = analyzer.latest().unwrap().channels[0].artifacts;
+        assert!(flags.railed);
+        assert!(analyzer.latest().unwrap().mean_relative().is_none());
+    }
+}
@@ -0,0 +1,519 @@
+// Closed-loop entrainment controller
+// Reads band-power snapshots and steers the binaural beat, carrier and volume toward a target
+// state under rate limits and safety bounds; every adjustment is appended to a JSONL log
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use crate::coherence::{BeingType, CoherenceParams};
+use crate::constants::*;
+use super::bandpower::{BandPowerSnapshot, BandPowers};
+
+/// EEG band used by band-driven control laws
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Band {
+    Delta,
+    Theta,
+    Alpha,
+    Beta,
+    Gamma,
+}
+
+impl Band {
+    fn fraction(&self, bands: &BandPowers) -> f32 {
+        match self {
+            Band::Delta => bands.delta,
+            Band::Theta => bands.theta,
+            Band::Alpha => bands.alpha,
+            Band::Beta => bands.beta,
+            Band::Gamma => bands.gamma,
+        }
+    }
+}
+
+/// How the controller chooses its target
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(tag = "law", rename_all = "snake_case")]
+pub enum ControlLaw {
+    /// Track the individual alpha peak, leading it by `offset_hz` (negative = guide downward)
+    FollowAlphaPeak {
+        #[serde(default = "default_alpha_offset")]
+        offset_hz: f32,
+    },
+    /// Hold a theta beat until theta/alpha stays above `ratio_threshold` for `sustain_secs`,
+    /// then move on to `release_hz`
+    HoldTheta {
+        #[serde(default = "default_theta_hold")]
+        hold_hz: f32,
+        #[serde(default = "default_theta_ratio")]
+        ratio_threshold: f32,
+        #[serde(default = "default_theta_sustain")]
+        sustain_secs: f32,
+        #[serde(default = "default_theta_release")]
+        release_hz: f32,
+    },
+    /// Neurofeedback: map the relative power of `band` onto the binaural volume
+    BandVolume {
+        band: Band,
+        low_fraction: f32,
+        high_fraction: f32,
+        min_volume: f32,
+        max_volume: f32,
+    },
+    /// Map the relative power of `band` onto the carrier the beat sits on
+    /// (`low_carrier_hz` at `low_fraction`, `high_carrier_hz` at `high_fraction`)
+    BandCarrier {
+        band: Band,
+        low_fraction: f32,
+        high_fraction: f32,
+        low_carrier_hz: f32,
+        high_carrier_hz: f32,
+    },
+}
+
+fn default_alpha_offset() -> f32 { -CLOSED_LOOP_MAX_STEP_HZ }
+fn default_theta_hold() -> f32 { CLOSED_LOOP_THETA_HOLD_HZ }
+fn default_theta_ratio() -> f32 { CLOSED_LOOP_THETA_RATIO }
+fn default_theta_sustain() -> f32 { CLOSED_LOOP_THETA_SUSTAIN_SECS }
+fn default_theta_release() -> f32 { CLOSED_LOOP_THETA_RELEASE_HZ }
+
+impl ControlLaw {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControlLaw::FollowAlphaPeak { .. } => "follow alpha peak",
+            ControlLaw::HoldTheta { .. } => "hold theta",
+            ControlLaw::BandVolume { .. } => "band → volume",
+            ControlLaw::BandCarrier { .. } => "band → carrier",
+        }
+    }
+
+    /// Built-in laws with default settings, in the order the TUI cycles through them
+    pub fn presets() -> Vec<ControlLaw> {
+        vec![
+            ControlLaw::FollowAlphaPeak { offset_hz: default_alpha_offset() },
+            ControlLaw::HoldTheta {
+                hold_hz: default_theta_hold(),
+                ratio_threshold: default_theta_ratio(),
+                sustain_secs: default_theta_sustain(),
+                release_hz: default_theta_release(),
+            },
+            ControlLaw::BandVolume {
+                band: Band::Theta,
+                low_fraction: 0.1,
+                high_fraction: 0.4,
+                min_volume: 0.2,
+                max_volume: 0.6,
+            },
+            ControlLaw::BandCarrier {
+                band: Band::Alpha,
+                low_fraction: 0.1,
+                high_fraction: 0.4,
+                low_carrier_hz: 400.0,
+                high_carrier_hz: 200.0,
+            },
+        ]
+    }
+}
+
+/// The law before or after `current` in the TUI cycle. `remembered` keeps the settings each law
+/// had when it was last left, so a preset's own parameters come back when its law does.
+pub fn cycle_law(current: &ControlLaw, remembered: &mut Vec<ControlLaw>, direction: i32) -> ControlLaw {
+    let mut laws = ControlLaw::presets();
+    for law in laws.iter_mut() {
+        if let Some(kept) = std::iter::once(current).chain(remembered.iter()).find(|kept| kept.name() == law.name()) {
+            *law = kept.clone();
+        }
+    }
+    let index = laws.iter().position(|law| law.name() == current.name()).unwrap_or(0);
+    let next = laws[crate::utils::cycle_index(index, laws.len(), direction)].clone();
+    *remembered = laws;
+    next
+}
+
+/// Closed-loop settings (stored in presets so experiments can be repeated)
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct ClosedLoopConfig {
+    pub law: ControlLaw,
+    /// Minimum time between adjustments (seconds)
+    pub interval_secs: f32,
+    /// Rate limits per adjustment
+    pub max_step_hz: f32,
+    pub max_volume_step: f32,
+    pub max_carrier_step_hz: f32,
+    /// Safety bounds the controller never leaves
+    pub min_beat_hz: f32,
+    pub max_beat_hz: f32,
+    pub max_volume: f32,
+    /// Carrier to start from (None keeps the one playing); the band → carrier law moves it from there
+    pub carrier_hz: Option<f32>,
+}
+
+impl Default for ClosedLoopConfig {
+    fn default() -> Self {
+        Self {
+            law: ControlLaw::presets().remove(0),
+            interval_secs: CLOSED_LOOP_INTERVAL_SECS,
+            max_step_hz: CLOSED_LOOP_MAX_STEP_HZ,
+            max_volume_step: CLOSED_LOOP_MAX_VOLUME_STEP,
+            max_carrier_step_hz: CLOSED_LOOP_MAX_CARRIER_STEP_HZ,
+            min_beat_hz: CLOSED_LOOP_MIN_BEAT_HZ,
+            max_beat_hz: CLOSED_LOOP_MAX_BEAT_HZ,
+            max_volume: CLOSED_LOOP_MAX_VOLUME,
+            carrier_hz: None,
+        }
+    }
+}
+
+/// Put the coherence path into custom-beat mode so the controller can steer it,
+/// continuing from the beat that is playing now (on the configured carrier, if any).
+/// The preset's harmonic choices are left as they are.
+pub fn prepare_coherence(coherence: &mut CoherenceParams, config: &ClosedLoopConfig) {
+    if !matches!(coherence.being_type, BeingType::HumanCustom) {
+        let beat = coherence.binaural_beat_hz().clamp(config.min_beat_hz, config.max_beat_hz);
+        coherence.being_type = BeingType::HumanCustom;
+        set_beat(coherence, beat);
+        set_carrier(coherence, OPTIMAL_CARRIER_HZ);
+    }
+    if let Some(carrier) = config.carrier_hz {
+        set_carrier(coherence, carrier.clamp(CLOSED_LOOP_CARRIER_MIN_HZ, CLOSED_LOOP_CARRIER_MAX_HZ));
+    }
+    coherence.enabled = true;
+}
+
+/// Change only the beat (apply_custom_binaural would also reset the carrier and switch harmonics)
+fn set_beat(coherence: &mut CoherenceParams, beat: f32) {
+    coherence.custom_binaural_hz = beat.clamp(BEAT_MIN_HZ, BEAT_MAX_HZ);
+    coherence.right_carrier = coherence.left_carrier + coherence.custom_binaural_hz;
+}
+
+/// Move both carriers so the left one is `carrier` and the beat is unchanged
+fn set_carrier(coherence: &mut CoherenceParams, carrier: f32) {
+    coherence.left_carrier = carrier;
+    coherence.right_carrier = carrier + coherence.custom_binaural_hz;
+}
+
+/// One line of the adjustment log
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LogEntry<'a> {
+    Start { unix_time: u64, config: &'a ClosedLoopConfig },
+    Adjust(Adjustment),
+    Stop { unix_time: u64, adjustments: u32 },
+}
+
+/// A change the controller made, with the EEG evidence behind it
+#[derive(Clone, Serialize, Debug)]
+pub struct Adjustment {
+    pub unix_time: u64,
+    pub session_secs: f32,
+    pub bands: BandPowers,
+    pub alpha_peak_hz: Option<f32>,
+    pub beat_before: f32,
+    pub beat_after: f32,
+    pub volume_before: f32,
+    pub volume_after: f32,
+    pub carrier_before: f32,
+    pub carrier_after: f32,
+    pub reason: String,
+}
+
+/// What a control law wants this step (None leaves that value alone)
+struct Targets {
+    beat: Option<f32>,
+    volume: Option<f32>,
+    carrier: Option<f32>,
+    reason: String,
+}
+
+/// Running controller with its log file
+pub struct ClosedLoopController {
+    config: ClosedLoopConfig,
+    log: Option<BufWriter<File>>,
+    log_path: Option<PathBuf>,
+    last_adjust_secs: Option<f32>,
+    theta_above_since: Option<f32>, // HoldTheta: when the ratio last crossed the threshold
+    theta_released: bool,
+    adjustments: u32,
+}
+
+impl ClosedLoopController {
+    /// Start a controller that logs to `log_path` (None disables logging, e.g. in tests)
+    pub fn new(config: ClosedLoopConfig, log_path: Option<PathBuf>) -> std::io::Result<Self> {
+        let log = match &log_path {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                Some(BufWriter::new(File::create(path)?))
+            }
+            None => None,
+        };
+        let mut controller = Self {
+            config,
+            log,
+            log_path,
+            last_adjust_secs: None,
+            theta_above_since: None,
+            theta_released: false,
+            adjustments: 0,
+        };
+        let config = controller.config.clone();
+        controller.write_log(&LogEntry::Start { unix_time: crate::session::unix_now(), config: &config });
+        Ok(controller)
+    }
+
+    pub fn config(&self) -> &ClosedLoopConfig {
+        &self.config
+    }
+
+    pub fn log_path(&self) -> Option<&PathBuf> {
+        self.log_path.as_ref()
+    }
+
+    pub fn adjustments(&self) -> u32 {
+        self.adjustments
+    }
+
+    /// Apply one control step; returns the adjustment made, if any
+    /// Holds still when every channel is flagged as artifact
+    pub fn step(&mut self, snapshot: &BandPowerSnapshot, coherence: &mut CoherenceParams, session_secs: f32) -> Option<Adjustment> {
+        // A session timer that went backwards means a new session started; don't wait for it
+        if let Some(last) = self.last_adjust_secs {
+            if session_secs >= last && session_secs - last < self.config.interval_secs {
+                return None;
+            }
+        }
+        let bands = snapshot.mean_relative()?;
+        let alpha_peak_hz = snapshot.mean_alpha_peak_hz();
+
+        let beat_before = coherence.custom_binaural_hz;
+        let volume_before = coherence.volume;
+        let carrier_before = coherence.left_carrier;
+        let Targets { beat: target_beat, volume: target_volume, carrier: target_carrier, reason } =
+            self.targets(&bands, alpha_peak_hz, session_secs);
+
+        // Rate limit, then clamp to the safety bounds
+        let beat_after = match target_beat {
+            Some(target) => step_toward(beat_before, target, self.config.max_step_hz)
+                .clamp(self.config.min_beat_hz, self.config.max_beat_hz),
+            None => beat_before,
+        };
+        let volume_after = match target_volume {
+            Some(target) => step_toward(volume_before, target, self.config.max_volume_step)
+                .clamp(0.0, self.config.max_volume),
+            None => volume_before.min(self.config.max_volume),
+        };
+        let carrier_after = match target_carrier {
+            Some(target) => step_toward(carrier_before, target, self.config.max_carrier_step_hz)
+                .clamp(CLOSED_LOOP_CARRIER_MIN_HZ, CLOSED_LOOP_CARRIER_MAX_HZ),
+            None => carrier_before,
+        };
+
+        let unchanged = |before: f32, after: f32| (after - before).abs() < CLOSED_LOOP_MIN_CHANGE;
+        if unchanged(beat_before, beat_after) && unchanged(volume_before, volume_after) && unchanged(carrier_before, carrier_after) {
+            return None;
+        }
+
+        if target_beat.is_some() {
+            set_beat(coherence, beat_after);
+        }
+        set_carrier(coherence, carrier_after);
+        coherence.volume = volume_after;
+
+        self.last_adjust_secs = Some(session_secs);
+        self.adjustments += 1;
+        let adjustment = Adjustment {
+            unix_time: crate::session::unix_now(),
+            session_secs,
+            bands,
+            alpha_peak_hz,
+            beat_before,
+            beat_after: coherence.custom_binaural_hz,
+            volume_before,
+            volume_after,
+            carrier_before,
+            carrier_after,
+            reason,
+        };
+        self.write_log(&LogEntry::Adjust(adjustment.clone()));
+        Some(adjustment)
+    }
+
+    /// Target beat and volume for the configured law, with a short explanation
+    fn targets(&mut self, bands: &BandPowers, alpha_peak_hz: Option<f32>, session_secs: f32) -> Targets {
+        let none = |reason: String| Targets { beat: None, volume: None, carrier: None, reason };
+        match self.config.law.clone() {
+            ControlLaw::FollowAlphaPeak { offset_hz } => match alpha_peak_hz {
+                Some(peak) => Targets {
+                    beat: Some(peak + offset_hz),
+                    ..none(format!("alpha peak {:.2} Hz, target {:.2} Hz", peak, peak + offset_hz))
+                },
+                None => none("no alpha peak".to_string()),
+            },
+            ControlLaw::HoldTheta { hold_hz, ratio_threshold, sustain_secs, release_hz } => {
+                let ratio = if bands.alpha > 0.0 { bands.theta / bands.alpha } else { 0.0 };
+                if !self.theta_released {
+                    if ratio >= ratio_threshold {
+                        let since = *self.theta_above_since.get_or_insert(session_secs);
+                        if session_secs - since >= sustain_secs {
+                            self.theta_released = true;
+                        }
+                    } else {
+                        self.theta_above_since = None;
+                    }
+                }
+                let target = if self.theta_released { release_hz } else { hold_hz };
+                let stage = if self.theta_released { "released" } else { "holding" };
+                Targets { beat: Some(target), ..none(format!("theta/alpha {:.2} ({} at {:.2} Hz)", ratio, stage, target)) }
+            },
+            ControlLaw::BandVolume { band, low_fraction, high_fraction, min_volume, max_volume } => {
+                let fraction = band.fraction(bands);
+                let volume = min_volume + (max_volume - min_volume) * band_level(fraction, low_fraction, high_fraction);
+                Targets { volume: Some(volume), ..none(format!("{:?} fraction {:.2}, volume {:.2}", band, fraction, volume)) }
+            },
+            ControlLaw::BandCarrier { band, low_fraction, high_fraction, low_carrier_hz, high_carrier_hz } => {
+                let fraction = band.fraction(bands);
+                let carrier = low_carrier_hz + (high_carrier_hz - low_carrier_hz) * band_level(fraction, low_fraction, high_fraction);
+                Targets { carrier: Some(carrier), ..none(format!("{:?} fraction {:.2}, carrier {:.1} Hz", band, fraction, carrier)) }
+            },
+        }
+    }
+
+    fn write_log(&mut self, entry: &LogEntry) {
+        if let Some(log) = self.log.as_mut() {
+            // Flush per line so the log survives a crash mid-session
+            if let Ok(line) = serde_json::to_string(entry) {
+                let _ = writeln!(log, "{}", line);
+                let _ = log.flush();
+            }
+        }
+    }
+}
+
+impl Drop for ClosedLoopController {
+    // Close the log however the controller ends (switched off, preset change or quit)
+    fn drop(&mut self) {
+        let adjustments = self.adjustments;
+        self.write_log(&LogEntry::Stop { unix_time: crate::session::unix_now(), adjustments });
+    }
+}
+
+/// Where `fraction` sits between `low` (0.0) and `high` (1.0), clamped
+fn band_level(fraction: f32, low: f32, high: f32) -> f32 {
+    let span = (high - low).max(f32::EPSILON);
+    ((fraction - low) / span).clamp(0.0, 1.0)
+}
+
+/// Move `current` toward `target` by at most `max_step`
+fn step_toward(current: f32, target: f32, max_step: f32) -> f32 {
+    current + (target - current).clamp(-max_step, max_step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eeg::bandpower::{ArtifactFlags, ChannelBandPower};
+
+    fn snapshot(bands: BandPowers, alpha_peak_hz: f32) -> BandPowerSnapshot {
+        BandPowerSnapshot {
+            channels: vec![ChannelBandPower {
+                bands,
+                dominant_hz: alpha_peak_hz,
+                alpha_peak_hz,
+                artifacts: ArtifactFlags::default(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_follow_alpha_is_rate_limited() {
+        let mut controller = ClosedLoopController::new(ClosedLoopConfig::default(), None).unwrap();
+        let mut coherence = CoherenceParams::default();
+        coherence.apply_custom_binaural(12.0);
+        let eeg = snapshot(BandPowers { alpha: 1.0, ..Default::default() }, 9.0);
+
+        // Target is 8.5 Hz but each step moves at most 0.5 Hz, and only once per interval
+        let adjustment = controller.step(&eeg, &mut coherence, 0.0).unwrap();
+        assert!((adjustment.beat_after - 11.5).abs() < 1e-4);
+        assert!(controller.step(&eeg, &mut coherence, 1.0).is_none());
+        for i in 1..10 {
+            controller.step(&eeg, &mut coherence, i as f32 * CLOSED_LOOP_INTERVAL_SECS);
+        }
+        assert!((coherence.custom_binaural_hz - 8.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_hold_theta_releases_after_sustained_ratio() {
+        let config = ClosedLoopConfig {
+            law: ControlLaw::HoldTheta { hold_hz: 6.0, ratio_threshold: 1.5, sustain_secs: 20.0, release_hz: 4.0 },
+            max_step_hz: 5.0,
+            ..Default::default()
+        };
+        let mut controller = ClosedLoopController::new(config, None).unwrap();
+        let mut coherence = CoherenceParams::default();
+        coherence.apply_custom_binaural(8.0);
+
+        let alpha_dominant = snapshot(BandPowers { theta: 0.3, alpha: 0.7, ..Default::default() }, 10.0);
+        controller.step(&alpha_dominant, &mut coherence, 0.0);
+        assert!((coherence.custom_binaural_hz - 6.0).abs() < 1e-4);
+
+        let theta_dominant = snapshot(BandPowers { theta: 0.7, alpha: 0.3, ..Default::default() }, 10.0);
+        controller.step(&theta_dominant, &mut coherence, 10.0);
+        assert!((coherence.custom_binaural_hz - 6.0).abs() < 1e-4);
+        controller.step(&theta_dominant, &mut coherence, 30.0);
+        assert!((coherence.custom_binaural_hz - 4.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_beat_changes_leave_harmonics_alone() {
+        let config = ClosedLoopConfig {
+            law: ControlLaw::HoldTheta { hold_hz: 2.0, ratio_threshold: 10.0, sustain_secs: 60.0, release_hz: 2.0 },
+            max_step_hz: 10.0,
+            ..Default::default()
+        };
+        let mut controller = ClosedLoopController::new(config.clone(), None).unwrap();
+        // A Focus 10 preset with the harmonics switched off, steered from its 4.1 Hz beat down past delta
+        let mut coherence = CoherenceParams { being_type: BeingType::HumanFocus10, ..Default::default() };
+        coherence.harmonic_220hz = false;
+        coherence.harmonic_495hz = false;
+        prepare_coherence(&mut coherence, &config);
+        let eeg = snapshot(BandPowers { theta: 0.3, alpha: 0.7, ..Default::default() }, 10.0);
+        controller.step(&eeg, &mut coherence, 0.0).unwrap();
+        assert!((coherence.binaural_beat_hz() - 2.0).abs() < 1e-4);
+        assert!(!coherence.harmonic_220hz && !coherence.harmonic_495hz);
+    }
+
+    #[test]
+    fn test_band_carrier_moves_carrier_and_keeps_beat() {
+        let config = ClosedLoopConfig { law: ControlLaw::presets().remove(3), carrier_hz: Some(300.0), ..Default::default() };
+        let mut controller = ClosedLoopController::new(config.clone(), None).unwrap();
+        let mut coherence = CoherenceParams { being_type: BeingType::HumanCustom, ..Default::default() };
+        coherence.apply_custom_binaural(6.0);
+        prepare_coherence(&mut coherence, &config);
+        assert_eq!((coherence.left_carrier, coherence.right_carrier), (300.0, 306.0));
+
+        // Strong alpha asks for the 200 Hz carrier, 10 Hz per adjustment
+        let alpha = snapshot(BandPowers { alpha: 0.5, ..Default::default() }, 10.0);
+        let adjustment = controller.step(&alpha, &mut coherence, 0.0).unwrap();
+        assert_eq!((adjustment.carrier_before, adjustment.carrier_after), (300.0, 290.0));
+        for i in 1..20 {
+            controller.step(&alpha, &mut coherence, i as f32 * CLOSED_LOOP_INTERVAL_SECS);
+        }
+        assert_eq!((coherence.left_carrier, coherence.binaural_beat_hz()), (200.0, 6.0));
+    }
+
+    #[test]
+    fn test_cycling_keeps_custom_law_settings() {
+        let custom = ControlLaw::HoldTheta { hold_hz: 5.5, ratio_threshold: 1.2, sustain_secs: 90.0, release_hz: 4.0 };
+        let mut remembered = Vec::new();
+        let mut law = custom.clone();
+        for _ in 0..ControlLaw::presets().len() {
+            law = cycle_law(&law, &mut remembered, 1);
+        }
+        assert_eq!(law, custom);
+        assert_eq!(cycle_law(&cycle_law(&law, &mut remembered, -1), &mut remembered, 1), custom);
+    }
+}
@@ -0,0 +1,382 @@
+// European Data Format (EDF/EDF+) and BioSemi BDF reading and writing
+// EDF stores 16-bit samples, BDF 24-bit; both use the same ASCII header and scale digital
+// values to physical units per signal. Annotation signals are skipped when reading; when
+// writing, an EDF+ annotation signal carries stimulus events (onsets on the sample clock).
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::constants::*;
+use crate::utils::LocalDateTime;
+use super::replay::Recording;
+
+const EDF_FIXED_HEADER_BYTES: usize = 256;
+const EDF_SIGNAL_HEADER_BYTES: usize = 256;
+
+/// One signal's header fields needed to decode it
+struct SignalHeader {
+    label: String,
+    physical_dim: String,
+    physical_min: f64,
+    physical_max: f64,
+    digital_min: f64,
+    digital_max: f64,
+    samples_per_record: usize,
+}
+
+impl SignalHeader {
+    fn is_annotation(&self) -> bool {
+        self.label.ends_with("Annotations")
+    }
+
+    /// Multiplier from the signal's physical unit to microvolts
+    fn microvolt_scale(&self) -> f64 {
+        match self.physical_dim.as_str() {
+            "mV" => 1_000.0,
+            "V" => 1_000_000.0,
+            "nV" => 0.001,
+            _ => 1.0, // uV, µV, or unlabelled
+        }
+    }
+}
+
+fn ascii_field(bytes: &[u8], start: usize, len: usize) -> Result<String, String> {
+    let field = bytes.get(start..start + len).ok_or("EDF header is truncated")?;
+    Ok(String::from_utf8_lossy(field).trim().to_string())
+}
+
+fn numeric_field<T: std::str::FromStr>(bytes: &[u8], start: usize, len: usize, name: &str) -> Result<T, String> {
+    let text = ascii_field(bytes, start, len)?;
+    text.parse().map_err(|_| format!("Invalid EDF {} '{}'", name, text))
+}
+
+/// Decode an EDF/EDF+/BDF file; channels with the highest sample rate are kept (in microvolts)
+pub fn read_edf(bytes: &[u8]) -> Result<Recording, String> {
+    // BDF marks its version field with 0xFF followed by "BIOSEMI"
+    let is_bdf = bytes.first() == Some(&0xFF);
+    let bytes_per_sample = if is_bdf { 3 } else { 2 };
+
+    let header_bytes: usize = numeric_field(bytes, 184, 8, "header size")?;
+    let declared_records: i64 = numeric_field(bytes, 236, 8, "record count")?;
+    let record_duration: f64 = numeric_field(bytes, 244, 8, "record duration")?;
+    let signal_count: usize = numeric_field(bytes, 252, 4, "signal count")?;
+    if header_bytes != EDF_FIXED_HEADER_BYTES + signal_count * EDF_SIGNAL_HEADER_BYTES {
+        return Err("EDF header size does not match its signal count".to_string());
+    }
+    if record_duration <= 0.0 {
+        return Err("EDF record duration must be positive".to_string());
+    }
+
+    // Signal headers are stored field by field: all labels, then all transducers, ...
+    let field = |offset: usize, len: usize, i: usize| EDF_FIXED_HEADER_BYTES + offset * signal_count + i * len;
+    let mut signals = Vec::with_capacity(signal_count);
+    for i in 0..signal_count {
+        signals.push(SignalHeader {
+            label: ascii_field(bytes, field(0, 16, i), 16)?,
+            physical_dim: ascii_field(bytes, field(96, 8, i), 8)?,
+            physical_min: numeric_field(bytes, field(104, 8, i), 8, "physical minimum")?,
+            physical_max: numeric_field(bytes, field(112, 8, i), 8, "physical maximum")?,
+            digital_min: numeric_field(bytes, field(120, 8, i), 8, "digital minimum")?,
+            digital_max: numeric_field(bytes, field(128, 8, i), 8, "digital maximum")?,
+            samples_per_record: numeric_field(bytes, field(216, 8, i), 8, "samples per record")?,
+        });
+    }
+
+    let record_bytes: usize = signals.iter().map(|s| s.samples_per_record * bytes_per_sample).sum();
+    if record_bytes == 0 {
+        return Err("EDF file has no samples".to_string());
+    }
+    // Writers that crash leave -1 records; count what is actually there
+    let available_records = (bytes.len().saturating_sub(header_bytes)) / record_bytes;
+    let records = if declared_records < 0 { available_records } else { (declared_records as usize).min(available_records) };
+
+    let samples_per_record = signals.iter()
+        .filter(|s| !s.is_annotation())
+        .map(|s| s.samples_per_record)
+        .max()
+        .ok_or("EDF file has no data signals")?;
+    let selected: Vec<usize> = (0..signal_count)
+        .filter(|&i| !signals[i].is_annotation() && signals[i].samples_per_record == samples_per_record)
+        .collect();
+
+    let mut samples = vec![vec![0.0f32; selected.len()]; records * samples_per_record];
+    let mut pos = header_bytes;
+    for record in 0..records {
+        for (i, signal) in signals.iter().enumerate() {
+            let column = selected.iter().position(|&s| s == i);
+            let gain = (signal.physical_max - signal.physical_min) / (signal.digital_max - signal.digital_min);
+            for j in 0..signal.samples_per_record {
+                if let Some(column) = column {
+                    let raw = &bytes[pos..pos + bytes_per_sample];
+                    let digital = if is_bdf {
+                        // Sign-extend 24-bit little-endian
+                        (i32::from_le_bytes([raw[0], raw[1], raw[2], 0]) << 8 >> 8) as f64
+                    } else {
+                        i16::from_le_bytes([raw[0], raw[1]]) as f64
+                    };
+                    let physical = (digital - signal.digital_min) * gain + signal.physical_min;
+                    samples[record * samples_per_record + j][column] = (physical * signal.microvolt_scale()) as f32;
+                }
+                pos += bytes_per_sample;
+            }
+        }
+    }
+
+    Ok(Recording {
+        sample_rate: (samples_per_record as f64 / record_duration) as f32,
+        channel_names: selected.iter().map(|&i| signals[i].label.clone()).collect(),
+        samples,
+    })
+}
+
+const EDF_MONTHS: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+const EDF_MAX_ANNOTATION_TEXT_BYTES: usize = 120;
+
+/// Output flavour: EDF+ stores 16-bit samples, BDF+ 24-bit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdfFormat {
+    Edf,
+    Bdf,
+}
+
+impl EdfFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdfFormat::Edf => "EDF+",
+            EdfFormat::Bdf => "BDF+",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            EdfFormat::Edf => "edf",
+            EdfFormat::Bdf => "bdf",
+        }
+    }
+
+    fn bytes_per_sample(&self) -> usize {
+        match self {
+            EdfFormat::Edf => 2,
+            EdfFormat::Bdf => 3,
+        }
+    }
+
+    fn digital_range(&self) -> (i32, i32) {
+        match self {
+            EdfFormat::Edf => (-32_768, 32_767),
+            EdfFormat::Bdf => (-8_388_608, 8_388_607),
+        }
+    }
+
+    /// Physical range in microvolts: 0.1 µV steps for EDF, ~0.03 µV for BDF (covers the Cyton's ±187.5 mV)
+    fn physical_range_uv(&self) -> (f64, f64) {
+        match self {
+            EdfFormat::Edf => (-3276.8, 3276.7),
+            EdfFormat::Bdf => (-262_144.0, 262_144.0),
+        }
+    }
+
+    fn annotation_label(&self) -> &'static str {
+        match self {
+            EdfFormat::Edf => "EDF Annotations",
+            EdfFormat::Bdf => "BDF Annotations",
+        }
+    }
+}
+
+/// A text event at a time offset from the start of the recording
+#[derive(Clone, Debug, PartialEq)]
+pub struct EdfAnnotation {
+    pub onset_secs: f64,
+    pub text: String,
+}
+
+/// Append a space-padded ASCII header field (non-printable characters become '_')
+fn push_field(header: &mut Vec<u8>, text: &str, len: usize) {
+    let mut field: Vec<u8> = text.chars()
+        .map(|c| if (' '..='~').contains(&c) { c as u8 } else { b'_' })
+        .take(len)
+        .collect();
+    field.resize(len, b' ');
+    header.extend_from_slice(&field);
+}
+
+/// Format a number to fit an EDF header field, dropping decimals as needed
+fn fit_number(value: f64, len: usize) -> String {
+    (0..=6).rev()
+        .map(|decimals| {
+            let text = format!("{:.*}", decimals, value);
+            if text.contains('.') { text.trim_end_matches('0').trim_end_matches('.').to_string() } else { text }
+        })
+        .find(|text| text.len() <= len)
+        .unwrap_or_else(|| format!("{:.0}", value))
+}
+
+/// Writes an EDF+C/BDF+C file record by record, with an annotation signal carrying events
+/// The record count is filled in when the writer is finished (or dropped)
+pub struct EdfWriter {
+    file: BufWriter<File>,
+    format: EdfFormat,
+    channel_count: usize,
+    samples_per_record: usize,
+    record_secs: f64,
+    records: u64,
+    finished: bool,
+}
+
+impl EdfWriter {
+    pub fn create(path: &Path, format: EdfFormat, sample_rate: f32, channel_names: &[String], start: LocalDateTime) -> io::Result<Self> {
+        let samples_per_record = ((sample_rate as f64 * EDF_RECORD_SECS).round() as usize).max(1);
+        // Non-integer rates keep their exact rate by stretching the record
+        let record_secs = samples_per_record as f64 / sample_rate as f64;
+        let signal_count = channel_names.len() + 1;
+        let (digital_min, digital_max) = format.digital_range();
+        let (physical_min, physical_max) = format.physical_range_uv();
+
+        let mut header = Vec::with_capacity(EDF_FIXED_HEADER_BYTES + signal_count * EDF_SIGNAL_HEADER_BYTES);
+        match format {
+            EdfFormat::Edf => push_field(&mut header, "0", 8),
+            EdfFormat::Bdf => {
+                header.push(0xFF);
+                push_field(&mut header, "BIOSEMI", 7);
+            }
+        }
+        // EDF+ subfields: patient code, sex, birthdate, name ("X" = unknown)
+        push_field(&mut header, "X X X X", 80);
+        let month = EDF_MONTHS[(start.month.clamp(1, 12) - 1) as usize];
+        push_field(&mut header, &format!("Startdate {:02}-{}-{} X X soulwhistle", start.day, month, start.year), 80);
+        push_field(&mut header, &format!("{:02}.{:02}.{:02}", start.day, start.month, start.year % 100), 8);
+        push_field(&mut header, &format!("{:02}.{:02}.{:02}", start.hour, start.minute, start.second), 8);
+        push_field(&mut header, &(EDF_FIXED_HEADER_BYTES + signal_count * EDF_SIGNAL_HEADER_BYTES).to_string(), 8);
+        push_field(&mut header, &format!("{}C", format.name()), 44); // Continuous recording
+        push_field(&mut header, "-1", 8); // Unknown until finished
+        push_field(&mut header, &fit_number(record_secs, 8), 8);
+        push_field(&mut header, &signal_count.to_string(), 4);
+
+        // Signal headers field by field; the annotation signal comes last
+        let mut signals: Vec<SignalHeader> = channel_names.iter()
+            .map(|name| SignalHeader {
+                label: name.clone(),
+                physical_dim: "uV".to_string(),
+                physical_min,
+                physical_max,
+                digital_min: digital_min as f64,
+                digital_max: digital_max as f64,
+                samples_per_record,
+            })
+            .collect();
+        signals.push(SignalHeader {
+            label: format.annotation_label().to_string(),
+            physical_dim: String::new(),
+            physical_min: -1.0,
+            physical_max: 1.0,
+            digital_min: digital_min as f64,
+            digital_max: digital_max as f64,
+            samples_per_record: EDF_ANNOTATION_BYTES_PER_RECORD.div_ceil(format.bytes_per_sample()),
+        });
+        for s in &signals { push_field(&mut header, &s.label, 16); }
+        for _ in &signals { push_field(&mut header, "", 80); } // Transducer
+        for s in &signals { push_field(&mut header, &s.physical_dim, 8); }
+        for s in &signals { push_field(&mut header, &fit_number(s.physical_min, 8), 8); }
+        for s in &signals { push_field(&mut header, &fit_number(s.physical_max, 8), 8); }
+        for s in &signals { push_field(&mut header, &fit_number(s.digital_min, 8), 8); }
+        for s in &signals { push_field(&mut header, &fit_number(s.digital_max, 8), 8); }
+        for _ in &signals { push_field(&mut header, "", 80); } // Prefiltering: none, raw samples
+        for s in &signals { push_field(&mut header, &s.samples_per_record.to_string(), 8); }
+        for _ in &signals { push_field(&mut header, "", 32); }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&header)?;
+        Ok(Self {
+            file,
+            format,
+            channel_count: channel_names.len(),
+            samples_per_record,
+            record_secs,
+            records: 0,
+            finished: false,
+        })
+    }
+
+    pub fn samples_per_record(&self) -> usize {
+        self.samples_per_record
+    }
+
+    pub fn record_secs(&self) -> f64 {
+        self.record_secs
+    }
+
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Write one data record (`samples_per_record` rows; missing rows or channels are zero) and
+    /// as many pending annotations as fit, removing the written ones from the front of `annotations`
+    pub fn write_record(&mut self, rows: &[Vec<f32>], annotations: &mut Vec<EdfAnnotation>) -> io::Result<()> {
+        let (digital_min, digital_max) = self.format.digital_range();
+        let (physical_min, physical_max) = self.format.physical_range_uv();
+        let gain = (physical_max - physical_min) / (digital_max - digital_min) as f64;
+        let bytes_per_sample = self.format.bytes_per_sample();
+
+        let mut record = Vec::with_capacity(self.channel_count * self.samples_per_record * bytes_per_sample
+            + EDF_ANNOTATION_BYTES_PER_RECORD);
+        for channel in 0..self.channel_count {
+            for j in 0..self.samples_per_record {
+                let value = rows.get(j).and_then(|row| row.get(channel)).copied().unwrap_or(0.0) as f64;
+                let digital = ((value - physical_min) / gain + digital_min as f64).round()
+                    .clamp(digital_min as f64, digital_max as f64) as i32;
+                record.extend_from_slice(&digital.to_le_bytes()[..bytes_per_sample]);
+            }
+        }
+
+        // Time-keeping TAL first (record onset), then events
+        let annotation_bytes = EDF_ANNOTATION_BYTES_PER_RECORD.div_ceil(bytes_per_sample) * bytes_per_sample;
+        let mut tals = format!("+{}\x14\x14\0", fit_number(self.records as f64 * self.record_secs, 16)).into_bytes();
+        while let Some(annotation) = annotations.first() {
+            let tal = format!("+{:.4}\x14{}\x14\0", annotation.onset_secs.max(0.0), annotation_text(&annotation.text));
+            if tals.len() + tal.len() > annotation_bytes {
+                break;
+            }
+            tals.extend_from_slice(tal.as_bytes());
+            annotations.remove(0);
+        }
+        tals.resize(annotation_bytes, 0);
+        record.extend_from_slice(&tals);
+
+        self.file.write_all(&record)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Fill in the record count and flush
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        let mut count = Vec::with_capacity(8);
+        push_field(&mut count, &self.records.to_string(), 8);
+        self.file.seek(SeekFrom::Start(236))?;
+        self.file.write_all(&count)?;
+        self.file.flush()
+    }
+}
+
+impl Drop for EdfWriter {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Annotation text without the TAL separators, capped so one event always fits in a record
+fn annotation_text(text: &str) -> String {
+    let mut clean: String = text.chars().filter(|&c| c != '\x14' && c != '\0').collect();
+    if clean.len() > EDF_MAX_ANNOTATION_TEXT_BYTES {
+        let mut end = EDF_MAX_ANNOTATION_TEXT_BYTES;
+        while !clean.is_char_boundary(end) {
+            end -= 1;
+        }
+        clean.truncate(end);
+    }
+    clean
+}
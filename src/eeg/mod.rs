@@ -0,0 +1,159 @@
+// EEG input subsystem
+// Device readers run on their own threads and push timestamped samples into a shared stream;
+// consumers (band power, recorders, UI) read from it with their own cursor
+
+pub mod bandpower;
+pub mod controller;
+pub mod edf;
+pub mod muse;
+pub mod openbci;
+pub mod recorder;
+pub mod replay;
+
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use crate::constants::*;
+use bandpower::BandPowers;
+
+/// One multi-channel EEG sample
+#[derive(Clone, Debug)]
+pub struct EegSample {
+    /// Seconds since the stream started, on the device sample clock (lost samples included)
+    pub timestamp: f64,
+    /// Channel values in microvolts
+    pub channels: Vec<f32>,
+    /// Accelerometer reading in g, when the packet carries one
+    pub accel: Option<[f32; 3]>,
+}
+
+/// Electrode contact quality reported by the device (e.g. the Muse horseshoe indicator)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContactQuality {
+    Good,
+    Fair,
+    Bad,
+}
+
+/// Connection state of an EEG source
+#[derive(Clone, Debug, PartialEq)]
+pub enum EegStatus {
+    Disconnected,
+    Connecting,
+    Streaming,
+    Error(String),
+}
+
+struct EegStreamState {
+    samples: VecDeque<EegSample>,
+    total_pushed: u64, // Samples ever pushed (cursor space for readers)
+    status: EegStatus,
+    source_name: String,
+    sample_rate: f32,
+    channel_count: usize,
+    lost_samples: u64,
+    contact: Option<Vec<ContactQuality>>, // Per channel, when the device reports it
+    device_bands: Option<BandPowers>,     // Band power computed on the device, when it sends it
+}
+
+/// Shared, bounded buffer between an EEG reader thread and its consumers
+pub struct EegStream {
+    state: Mutex<EegStreamState>,
+    capacity: usize,
+}
+
+impl EegStream {
+    pub fn new(source_name: &str, sample_rate: f32, channel_count: usize) -> Self {
+        let capacity = (sample_rate * EEG_BUFFER_SECS) as usize;
+        Self {
+            state: Mutex::new(EegStreamState {
+                samples: VecDeque::with_capacity(capacity),
+                total_pushed: 0,
+                status: EegStatus::Disconnected,
+                source_name: source_name.to_string(),
+                sample_rate,
+                channel_count,
+                lost_samples: 0,
+                contact: None,
+                device_bands: None,
+            }),
+            capacity,
+        }
+    }
+
+    /// Push a batch of samples (single lock acquisition)
+    pub fn push_batch(&self, batch: &[EegSample]) {
+        let mut state = self.state.lock();
+        for sample in batch {
+            if state.samples.len() >= self.capacity {
+                state.samples.pop_front();
+            }
+            state.samples.push_back(sample.clone());
+        }
+        state.total_pushed += batch.len() as u64;
+    }
+
+    /// Copy of the most recent `count` samples (oldest first)
+    pub fn recent(&self, count: usize) -> Vec<EegSample> {
+        let state = self.state.lock();
+        let skip = state.samples.len().saturating_sub(count);
+        state.samples.iter().skip(skip).cloned().collect()
+    }
+
+    /// Samples pushed since `cursor` (oldest first), advancing the cursor
+    /// Samples already dropped from the buffer are skipped
+    pub fn read_since(&self, cursor: &mut u64) -> Vec<EegSample> {
+        let state = self.state.lock();
+        let oldest = state.total_pushed - state.samples.len() as u64;
+        let start = (*cursor).max(oldest);
+        *cursor = state.total_pushed;
+        state.samples.iter().skip((start - oldest) as usize).cloned().collect()
+    }
+
+    pub fn set_status(&self, status: EegStatus) {
+        self.state.lock().status = status;
+    }
+
+    pub fn status(&self) -> EegStatus {
+        self.state.lock().status.clone()
+    }
+
+    pub fn add_lost_samples(&self, count: u64) {
+        self.state.lock().lost_samples += count;
+    }
+
+    pub fn lost_samples(&self) -> u64 {
+        self.state.lock().lost_samples
+    }
+
+    pub fn set_contact_quality(&self, contact: Vec<ContactQuality>) {
+        self.state.lock().contact = Some(contact);
+    }
+
+    pub fn contact_quality(&self) -> Option<Vec<ContactQuality>> {
+        self.state.lock().contact.clone()
+    }
+
+    pub fn set_device_bands(&self, bands: BandPowers) {
+        self.state.lock().device_bands = Some(bands);
+    }
+
+    pub fn device_bands(&self) -> Option<BandPowers> {
+        self.state.lock().device_bands
+    }
+
+    pub fn source_name(&self) -> String {
+        self.state.lock().source_name.clone()
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.state.lock().sample_rate
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.state.lock().channel_count
+    }
+
+    pub fn total_samples(&self) -> u64 {
+        self.state.lock().total_pushed
+    }
+}
@@ -0,0 +1,385 @@
+// Muse headband input over OSC/UDP, as sent by Mind Monitor or muse-io
+// Raw EEG (/muse/eeg) feeds the shared stream like any other source; horseshoe contact quality
+// and the headband's own band powers (/muse/elements/*_absolute) are passed through for display
+
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use crate::constants::*;
+use super::bandpower::BandPowers;
+use super::{ContactQuality, EegSample, EegStatus, EegStream};
+
+/// A single OSC argument
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Str(String),
+    Blob(Vec<u8>),
+}
+
+impl OscArg {
+    /// Numeric value of the argument (None for strings and blobs)
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            OscArg::Int(v) => Some(*v as f32),
+            OscArg::Long(v) => Some(*v as f32),
+            OscArg::Float(v) => Some(*v),
+            OscArg::Double(v) => Some(*v as f32),
+            OscArg::Str(_) | OscArg::Blob(_) => None,
+        }
+    }
+}
+
+/// A decoded OSC message
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+    /// Numeric arguments, skipping non-numeric ones
+    pub fn floats(&self) -> Vec<f32> {
+        self.args.iter().filter_map(|a| a.as_f32()).collect()
+    }
+}
+
+/// Decode an OSC packet (a message or a possibly nested bundle) into its messages
+pub fn parse_osc_packet(data: &[u8], out: &mut Vec<OscMessage>) -> Result<(), String> {
+    if data.starts_with(b"#bundle\0") {
+        // 8-byte tag, 8-byte time tag, then size-prefixed elements
+        let mut pos = 16;
+        while pos + 4 <= data.len() {
+            let size = read_size(data, pos)?;
+            pos += 4;
+            let element = read_bytes(data, pos, size).ok_or("Truncated bundle element")?;
+            parse_osc_packet(element, out)?;
+            pos += size;
+        }
+        Ok(())
+    } else {
+        out.push(parse_osc_message(data)?);
+        Ok(())
+    }
+}
+
+fn parse_osc_message(data: &[u8]) -> Result<OscMessage, String> {
+    let (address, mut pos) = read_string(data, 0)?;
+    if !address.starts_with('/') {
+        return Err(format!("Invalid OSC address '{}'", address));
+    }
+
+    // Very old senders omit the type tag string entirely
+    if pos >= data.len() || data[pos] != b',' {
+        return Ok(OscMessage { address, args: Vec::new() });
+    }
+    let (tags, next) = read_string(data, pos)?;
+    pos = next;
+
+    let mut args = Vec::new();
+    for tag in tags.chars().skip(1) {
+        let arg = match tag {
+            'i' => { let v = read_i32(data, pos)?; pos += 4; OscArg::Int(v) },
+            'f' => { let v = f32::from_bits(read_i32(data, pos)? as u32); pos += 4; OscArg::Float(v) },
+            'h' => { let v = read_i64(data, pos)?; pos += 8; OscArg::Long(v) },
+            'd' => { let v = f64::from_bits(read_i64(data, pos)? as u64); pos += 8; OscArg::Double(v) },
+            's' | 'S' => { let (v, next) = read_string(data, pos)?; pos = next; OscArg::Str(v) },
+            'b' => {
+                let size = read_size(data, pos)?;
+                let blob = read_bytes(data, pos + 4, size).ok_or("Truncated OSC blob")?.to_vec();
+                pos += 4 + align4(size);
+                OscArg::Blob(blob)
+            },
+            'T' => OscArg::Int(1),
+            'F' => OscArg::Int(0),
+            'N' | 'I' => continue, // Nil / infinitum carry no data
+            other => return Err(format!("Unsupported OSC type tag '{}'", other)),
+        };
+        args.push(arg);
+    }
+
+    Ok(OscMessage { address, args })
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+/// Read a NUL-terminated, 4-byte padded string; returns it with the position after the padding
+fn read_string(data: &[u8], pos: usize) -> Result<(String, usize), String> {
+    let rest = data.get(pos..).ok_or("Truncated OSC string")?;
+    let len = rest.iter().position(|&b| b == 0).ok_or("Unterminated OSC string")?;
+    let text = String::from_utf8_lossy(&rest[..len]).into_owned();
+    Ok((text, pos + align4(len + 1)))
+}
+
+fn read_i32(data: &[u8], pos: usize) -> Result<i32, String> {
+    let bytes = data.get(pos..pos + 4).ok_or("Truncated OSC packet")?;
+    Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// A blob or bundle element size. Packets come from anyone on the network, so a negative
+/// size is an error rather than a huge usize.
+fn read_size(data: &[u8], pos: usize) -> Result<usize, String> {
+    let size = read_i32(data, pos)?;
+    usize::try_from(size).map_err(|_| format!("Negative OSC size {}", size))
+}
+
+/// `size` bytes from `pos`, if the packet is that long
+fn read_bytes(data: &[u8], pos: usize, size: usize) -> Option<&[u8]> {
+    data.get(pos..pos.checked_add(size)?)
+}
+
+fn read_i64(data: &[u8], pos: usize) -> Result<i64, String> {
+    let bytes = data.get(pos..pos + 8).ok_or("Truncated OSC packet")?;
+    let mut array = [0u8; 8];
+    array.copy_from_slice(bytes);
+    Ok(i64::from_be_bytes(array))
+}
+
+/// Band names as used in the Muse element paths, in BandPowers order
+const MUSE_BANDS: [&str; 5] = ["delta", "theta", "alpha", "beta", "gamma"];
+
+/// UDP listener turning Muse OSC messages into EEG samples
+pub struct MuseReader {
+    socket: UdpSocket,
+    stream: Arc<EegStream>,
+    stop: Arc<AtomicBool>,
+    sample_index: u64,          // Samples received (the OSC stream carries no sample counter)
+    last_accel: Option<[f32; 3]>,
+    pending_bands: [Option<f32>; 5], // Headband band powers collected until all five have arrived
+}
+
+impl MuseReader {
+    pub fn new(socket: UdpSocket, stream: Arc<EegStream>, stop: Arc<AtomicBool>) -> std::io::Result<Self> {
+        socket.set_read_timeout(Some(std::time::Duration::from_millis(UDP_READ_TIMEOUT_MS)))?;
+        Ok(Self {
+            socket,
+            stream,
+            stop,
+            sample_index: 0,
+            last_accel: None,
+            pending_bands: [None; 5],
+        })
+    }
+
+    /// Decode one UDP datagram and push any EEG samples it holds to the stream
+    pub fn handle_packet(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut messages = Vec::new();
+        parse_osc_packet(data, &mut messages)?;
+
+        let mut samples = Vec::new();
+        for message in &messages {
+            self.handle_message(message, &mut samples);
+        }
+        if !samples.is_empty() {
+            self.stream.push_batch(&samples);
+        }
+        Ok(())
+    }
+
+    fn handle_message(&mut self, message: &OscMessage, samples: &mut Vec<EegSample>) {
+        // Mind Monitor sends /muse/...; muse-io can be configured with other prefixes
+        let path = message.address.strip_prefix("/muse").unwrap_or(&message.address);
+        let values = message.floats();
+
+        match path {
+            "/eeg" => {
+                if values.len() < MUSE_CHANNELS {
+                    return;
+                }
+                // Dropped samples arrive as NaN
+                let channels = values[..MUSE_CHANNELS].iter()
+                    .map(|v| if v.is_finite() { *v } else { 0.0 })
+                    .collect();
+                samples.push(EegSample {
+                    timestamp: self.sample_index as f64 / MUSE_SAMPLE_RATE_HZ as f64,
+                    channels,
+                    accel: self.last_accel.take(),
+                });
+                self.sample_index += 1;
+            },
+            "/acc" if values.len() >= 3 => {
+                // muse-io reports milli-g, Mind Monitor reports g
+                let scale = if values[..3].iter().any(|v| v.abs() > 100.0) { 0.001 } else { 1.0 };
+                self.last_accel = Some([values[0] * scale, values[1] * scale, values[2] * scale]);
+            },
+            "/elements/horseshoe" => {
+                let contact = values.iter()
+                    .take(MUSE_CHANNELS)
+                    .map(|&v| match v.round() as i32 {
+                        1 => ContactQuality::Good,
+                        2 => ContactQuality::Fair,
+                        _ => ContactQuality::Bad,
+                    })
+                    .collect();
+                self.stream.set_contact_quality(contact);
+            },
+            _ => {
+                if let Some(band) = path.strip_prefix("/elements/").and_then(|p| p.strip_suffix("_absolute")) {
+                    self.handle_band(band, &values);
+                }
+            }
+        }
+    }
+
+    /// Collect a headband band power (log10 units, per channel or averaged) and publish
+    /// the set once every band has arrived
+    fn handle_band(&mut self, band: &str, values: &[f32]) {
+        let Some(index) = MUSE_BANDS.iter().position(|b| *b == band) else {
+            return;
+        };
+        let finite: Vec<f32> = values.iter().copied().filter(|v| v.is_finite()).collect();
+        if finite.is_empty() {
+            return;
+        }
+        let mean_log = finite.iter().sum::<f32>() / finite.len() as f32;
+        self.pending_bands[index] = Some(10f32.powf(mean_log));
+
+        if let [Some(delta), Some(theta), Some(alpha), Some(beta), Some(gamma)] = self.pending_bands {
+            self.stream.set_device_bands(BandPowers { delta, theta, alpha, beta, gamma });
+            self.pending_bands = [None; 5];
+        }
+    }
+
+    /// Listen until the stop flag is set (blocking - run in a separate thread)
+    pub fn run(&mut self) {
+        self.stream.set_status(EegStatus::Connecting);
+        let mut buf = vec![0u8; OSC_MAX_PACKET_SIZE];
+        let mut last_data: Option<std::time::Instant> = None;
+
+        while !self.stop.load(Ordering::Relaxed) {
+            match self.socket.recv_from(&mut buf) {
+                Ok((n, _)) => {
+                    last_data = Some(std::time::Instant::now());
+                    if let Err(e) = self.handle_packet(&buf[..n]) {
+                        self.stream.set_status(EegStatus::Error(format!("Bad OSC packet: {}", e)));
+                        continue;
+                    }
+                    if self.stream.status() != EegStatus::Streaming {
+                        self.stream.set_status(EegStatus::Streaming);
+                    }
+                },
+                Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+                    // Keep waiting, but report a headband that went quiet
+                    if last_data.is_some_and(|t| t.elapsed().as_secs_f32() > EEG_IDLE_TIMEOUT_SECS) {
+                        self.stream.set_status(EegStatus::Error("No data from headband".to_string()));
+                    }
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+                Err(e) => {
+                    self.stream.set_status(EegStatus::Error(format!("Receive error: {}", e)));
+                    return;
+                }
+            }
+        }
+
+        self.stream.set_status(EegStatus::Disconnected);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn osc_string(out: &mut Vec<u8>, text: &str) {
+        out.extend_from_slice(text.as_bytes());
+        out.push(0);
+        while !out.len().is_multiple_of(4) {
+            out.push(0);
+        }
+    }
+
+    fn osc_floats(address: &str, values: &[f32]) -> Vec<u8> {
+        let mut out = Vec::new();
+        osc_string(&mut out, address);
+        osc_string(&mut out, &format!(",{}", "f".repeat(values.len())));
+        for v in values {
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        out
+    }
+
+    fn osc_bundle(messages: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = b"#bundle\0".to_vec();
+        out.extend_from_slice(&1u64.to_be_bytes()); // "immediately"
+        for message in messages {
+            out.extend_from_slice(&(message.len() as i32).to_be_bytes());
+            out.extend_from_slice(message);
+        }
+        out
+    }
+
+    #[test]
+    fn test_parse_bundle() {
+        let packet = osc_bundle(&[
+            osc_floats("/muse/eeg", &[800.0, 810.5, 820.0, 830.25]),
+            osc_floats("/muse/elements/horseshoe", &[1.0, 2.0, 4.0, 1.0]),
+        ]);
+        let mut messages = Vec::new();
+        parse_osc_packet(&packet, &mut messages).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].address, "/muse/eeg");
+        assert_eq!(messages[0].floats(), vec![800.0, 810.5, 820.0, 830.25]);
+        assert!(parse_osc_packet(&packet[..packet.len() - 2], &mut messages).is_err());
+    }
+
+    #[test]
+    fn test_rejects_malformed_sizes() {
+        let mut messages = Vec::new();
+        for size in [-1i32, -4, i32::MAX, 1000] {
+            let mut bundle = osc_bundle(&[]);
+            bundle.extend_from_slice(&size.to_be_bytes());
+            bundle.extend_from_slice(&osc_floats("/muse/eeg", &[1.0]));
+            assert!(parse_osc_packet(&bundle, &mut messages).is_err(), "bundle element size {}", size);
+
+            let mut blob = Vec::new();
+            osc_string(&mut blob, "/muse/blob");
+            osc_string(&mut blob, ",b");
+            blob.extend_from_slice(&size.to_be_bytes());
+            blob.extend_from_slice(&[0; 8]);
+            assert!(parse_osc_packet(&blob, &mut messages).is_err(), "blob size {}", size);
+        }
+        assert!(messages.is_empty());
+    }
+
+    #[test]
+    fn test_reader_over_localhost_udp() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        let stream = Arc::new(EegStream::new("Muse", MUSE_SAMPLE_RATE_HZ, MUSE_CHANNELS));
+        let mut reader = MuseReader::new(socket, stream.clone(), Arc::new(AtomicBool::new(false))).unwrap();
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut packets = vec![
+            osc_floats("/muse/acc", &[0.0, 0.0, 1.0]),
+            osc_floats("/muse/eeg", &[800.0, 801.0, 802.0, 803.0, f32::NAN]),
+            osc_floats("/muse/eeg", &[f32::NAN, 811.0, 812.0, 813.0]),
+            osc_floats("/muse/elements/horseshoe", &[1.0, 2.0, 4.0, 1.0]),
+        ];
+        for (i, band) in MUSE_BANDS.iter().enumerate() {
+            packets.push(osc_floats(&format!("/muse/elements/{}_absolute", band), &[i as f32 * 0.1]));
+        }
+
+        let mut buf = vec![0u8; OSC_MAX_PACKET_SIZE];
+        for packet in &packets {
+            sender.send_to(packet, addr).unwrap();
+            let (n, _) = reader.socket.recv_from(&mut buf).unwrap();
+            reader.handle_packet(&buf[..n]).unwrap();
+        }
+
+        let samples = stream.recent(10);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].channels, vec![800.0, 801.0, 802.0, 803.0]);
+        assert_eq!(samples[0].accel, Some([0.0, 0.0, 1.0]));
+        assert_eq!(samples[1].channels[0], 0.0);
+        assert!((samples[1].timestamp - 1.0 / 256.0).abs() < 1e-9);
+        assert_eq!(stream.contact_quality().unwrap(),
+            vec![ContactQuality::Good, ContactQuality::Fair, ContactQuality::Bad, ContactQuality::Good]);
+        let bands = stream.device_bands().unwrap();
+        assert!((bands.delta - 1.0).abs() < 1e-5 && (bands.gamma - 10f32.powf(0.4)).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,531 @@
+// OpenBCI Cyton and Ganglion packet decoding
+// Cyton: 33-byte serial packets from the RFduino dongle (8 channels, 24-bit, 250 Hz)
+// Ganglion: 20-byte BLE notifications wrapped in BGAPI events by the BLED112 dongle
+// (4 channels, 18/19-bit delta compression, 200 Hz)
+
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use crate::constants::*;
+use super::{EegSample, EegStatus, EegStream};
+
+/// Supported OpenBCI boards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Board {
+    Cyton,
+    Ganglion,
+}
+
+impl Board {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Board::Cyton => "OpenBCI Cyton",
+            Board::Ganglion => "OpenBCI Ganglion",
+        }
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        match self {
+            Board::Cyton => CYTON_SAMPLE_RATE_HZ,
+            Board::Ganglion => GANGLION_SAMPLE_RATE_HZ,
+        }
+    }
+
+    pub fn channel_count(&self) -> usize {
+        match self {
+            Board::Cyton => CYTON_CHANNELS,
+            Board::Ganglion => GANGLION_CHANNELS,
+        }
+    }
+}
+
+/// A decoded sample before it is placed on the stream clock
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedSample {
+    pub channels: Vec<f32>,        // Microvolts
+    pub accel: Option<[f32; 3]>,   // g
+    pub skipped: u32,              // Samples lost immediately before this one
+}
+
+/// Turns a raw byte stream from a board into samples
+pub trait PacketDecoder: Send {
+    fn feed(&mut self, bytes: &[u8], out: &mut Vec<DecodedSample>);
+}
+
+/// Sign-extend a 24-bit big-endian two's complement value
+fn i24_be(bytes: &[u8]) -> i32 {
+    let raw = ((bytes[0] as i32) << 16) | ((bytes[1] as i32) << 8) | bytes[2] as i32;
+    (raw << 8) >> 8
+}
+
+// === Cyton ===
+
+/// Stateful Cyton packet parser with resynchronisation and packet-loss detection
+#[derive(Default)]
+pub struct CytonDecoder {
+    buffer: Vec<u8>,
+    last_sample_number: Option<u8>,
+}
+
+impl CytonDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn decode_packet(&mut self, packet: &[u8]) -> DecodedSample {
+        let sample_number = packet[1];
+        let skipped = match self.last_sample_number {
+            Some(last) => sample_number.wrapping_sub(last).wrapping_sub(1) as u32,
+            None => 0,
+        };
+        self.last_sample_number = Some(sample_number);
+
+        let channels = (0..CYTON_CHANNELS)
+            .map(|ch| i24_be(&packet[2 + ch * 3..5 + ch * 3]) as f32 * CYTON_UV_PER_COUNT)
+            .collect();
+
+        // Footer 0xC0 means the aux bytes carry accelerometer data (sent at 25 Hz, zeros otherwise)
+        let aux = &packet[26..32];
+        let accel = if packet[32] == CYTON_FOOTER_ACCEL && aux.iter().any(|&b| b != 0) {
+            let axis = |i: usize| i16::from_be_bytes([aux[i * 2], aux[i * 2 + 1]]) as f32 * CYTON_G_PER_COUNT;
+            Some([axis(0), axis(1), axis(2)])
+        } else {
+            None
+        };
+
+        DecodedSample { channels, accel, skipped }
+    }
+}
+
+impl PacketDecoder for CytonDecoder {
+    fn feed(&mut self, bytes: &[u8], out: &mut Vec<DecodedSample>) {
+        self.buffer.extend_from_slice(bytes);
+        let mut pos = 0;
+
+        while self.buffer.len() - pos >= CYTON_PACKET_SIZE {
+            let candidate = &self.buffer[pos..pos + CYTON_PACKET_SIZE];
+            let footer = candidate[CYTON_PACKET_SIZE - 1];
+            if candidate[0] == CYTON_HEADER && footer & 0xF0 == CYTON_FOOTER_ACCEL {
+                let packet = candidate.to_vec();
+                out.push(self.decode_packet(&packet));
+                pos += CYTON_PACKET_SIZE;
+            } else {
+                // Out of sync (or text output from the board): slide forward one byte
+                pos += 1;
+            }
+        }
+
+        self.buffer.drain(..pos);
+    }
+}
+
+// === Ganglion ===
+
+/// Read `bits` bits starting at `bit_offset` from a big-endian bit stream
+fn read_bits(data: &[u8], bit_offset: usize, bits: usize) -> u32 {
+    let mut value = 0u32;
+    for i in 0..bits {
+        let bit = bit_offset + i;
+        let byte = data[bit / 8];
+        value = (value << 1) | ((byte >> (7 - bit % 8)) & 1) as u32;
+    }
+    value
+}
+
+/// Ganglion compressed deltas carry their sign in the least significant bit
+fn ganglion_delta(raw: u32, bits: u32) -> i32 {
+    if raw & 1 != 0 {
+        (raw | (u32::MAX << bits)) as i32
+    } else {
+        raw as i32
+    }
+}
+
+/// Decodes 20-byte Ganglion BLE payloads (packet id + data)
+#[derive(Default)]
+pub struct GanglionPayloadDecoder {
+    last_values: [i32; GANGLION_CHANNELS],
+    last_packet_id: Option<u8>,
+}
+
+impl GanglionPayloadDecoder {
+    fn to_sample(values: &[i32; GANGLION_CHANNELS], skipped: u32) -> DecodedSample {
+        DecodedSample {
+            channels: values.iter().map(|&v| v as f32 * GANGLION_UV_PER_COUNT).collect(),
+            accel: None,
+            skipped,
+        }
+    }
+
+    /// Samples lost between the previous packet and this one (two samples per compressed packet)
+    fn skipped_samples(&self, packet_id: u8) -> u32 {
+        let Some(last) = self.last_packet_id else { return 0; };
+        // Ids 1-100 (18-bit) and 101-200 (19-bit) both count through the same 100-packet cycle, so
+        // a compression switch or a corrupted id still gives a gap in 0..100 rather than underflowing
+        let position = |id: u8| (i32::from(id) - 1).rem_euclid(100);
+        let expected = if last == 0 { 0 } else { (position(last) + 1) % 100 };
+        (position(packet_id) - expected).rem_euclid(100) as u32 * 2
+    }
+
+    pub fn decode(&mut self, payload: &[u8], out: &mut Vec<DecodedSample>) {
+        if payload.len() < GANGLION_PAYLOAD_SIZE {
+            return;
+        }
+        let packet_id = payload[0];
+        let data = &payload[1..];
+
+        match packet_id {
+            0 => {
+                // Uncompressed reference sample
+                for ch in 0..GANGLION_CHANNELS {
+                    self.last_values[ch] = i24_be(&data[ch * 3..ch * 3 + 3]);
+                }
+                self.last_packet_id = Some(0);
+                out.push(Self::to_sample(&self.last_values, 0));
+            },
+            1..=200 => {
+                let bits = if packet_id <= 100 { 18 } else { 19 };
+                let skipped = self.skipped_samples(packet_id);
+                self.last_packet_id = Some(packet_id);
+
+                for sample in 0..2 {
+                    for ch in 0..GANGLION_CHANNELS {
+                        let index = sample * GANGLION_CHANNELS + ch;
+                        let raw = read_bits(data, index * bits, bits);
+                        self.last_values[ch] -= ganglion_delta(raw, bits as u32);
+                    }
+                    out.push(Self::to_sample(&self.last_values, if sample == 0 { skipped } else { 0 }));
+                }
+            },
+            _ => {} // Impedance and ASCII message packets carry no samples
+        }
+    }
+}
+
+/// Extracts Ganglion notifications from the BLED112 dongle's BGAPI byte stream
+#[derive(Default)]
+pub struct GanglionDecoder {
+    buffer: Vec<u8>,
+    payloads: GanglionPayloadDecoder,
+}
+
+impl GanglionDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PacketDecoder for GanglionDecoder {
+    fn feed(&mut self, bytes: &[u8], out: &mut Vec<DecodedSample>) {
+        self.buffer.extend_from_slice(bytes);
+        let mut pos = 0;
+
+        while self.buffer.len() - pos >= BGAPI_HEADER_SIZE {
+            let header = &self.buffer[pos..pos + BGAPI_HEADER_SIZE];
+            // Bits 3-6 of the first byte are the technology type (0 = Bluetooth Smart)
+            if header[0] & 0x78 != 0 {
+                pos += 1;
+                continue;
+            }
+            let length = (((header[0] & 0x07) as usize) << 8) | header[1] as usize;
+            let total = BGAPI_HEADER_SIZE + length;
+            if self.buffer.len() - pos < total {
+                break;
+            }
+
+            let is_event = header[0] & 0x80 != 0;
+            let (class, command) = (header[2], header[3]);
+            let payload = &self.buffer[pos + BGAPI_HEADER_SIZE..pos + total];
+
+            // attclient_attribute_value event: connection, handle (2), type, value length, value
+            if is_event && class == BGAPI_CLASS_ATTCLIENT && command == BGAPI_EVENT_ATTRIBUTE_VALUE && payload.len() >= 5 {
+                let value_len = payload[4] as usize;
+                if payload.len() >= 5 + value_len {
+                    let value = payload[5..5 + value_len].to_vec();
+                    self.payloads.decode(&value, out);
+                }
+            }
+            pos += total;
+        }
+
+        self.buffer.drain(..pos);
+    }
+}
+
+/// Wrap a Ganglion command in a BGAPI attclient_attribute_write for the given characteristic handle
+pub fn bgapi_attribute_write(handle: u16, data: &[u8]) -> Vec<u8> {
+    let payload_len = 4 + data.len();
+    let mut packet = vec![0x00, payload_len as u8, BGAPI_CLASS_ATTCLIENT, BGAPI_COMMAND_ATTRIBUTE_WRITE];
+    packet.push(0); // Connection handle (the dongle's single connection)
+    packet.extend_from_slice(&handle.to_le_bytes());
+    packet.push(data.len() as u8);
+    packet.extend_from_slice(data);
+    packet
+}
+
+// === Reader thread ===
+
+/// Streams samples from an OpenBCI board into an EegStream
+pub struct OpenBciReader<P: Read + Write> {
+    port: P,
+    board: Board,
+    decoder: Box<dyn PacketDecoder>,
+    stream: Arc<EegStream>,
+    stop: Arc<AtomicBool>,
+    ganglion_write_handle: Option<u16>,
+    sample_index: u64, // Position on the device sample clock (lost samples included)
+}
+
+impl<P: Read + Write> OpenBciReader<P> {
+    pub fn new(port: P, board: Board, stream: Arc<EegStream>, stop: Arc<AtomicBool>) -> Self {
+        let decoder: Box<dyn PacketDecoder> = match board {
+            Board::Cyton => Box::new(CytonDecoder::new()),
+            Board::Ganglion => Box::new(GanglionDecoder::new()),
+        };
+        Self {
+            port,
+            board,
+            decoder,
+            stream,
+            stop,
+            ganglion_write_handle: None,
+            sample_index: 0,
+        }
+    }
+
+    /// Characteristic handle used to send start/stop to a Ganglion through the dongle
+    /// Without it the Ganglion is assumed to be streaming already
+    pub fn with_ganglion_write_handle(mut self, handle: Option<u16>) -> Self {
+        self.ganglion_write_handle = handle;
+        self
+    }
+
+    fn send_command(&mut self, command: u8) -> std::io::Result<()> {
+        match self.board {
+            Board::Cyton => self.port.write_all(&[command]),
+            Board::Ganglion => match self.ganglion_write_handle {
+                Some(handle) => self.port.write_all(&bgapi_attribute_write(handle, &[command])),
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Decode a chunk of raw bytes and push the resulting samples to the stream
+    pub fn handle_bytes(&mut self, bytes: &[u8]) {
+        let mut decoded = Vec::new();
+        self.decoder.feed(bytes, &mut decoded);
+        if decoded.is_empty() {
+            return;
+        }
+
+        let rate = self.board.sample_rate() as f64;
+        let mut lost = 0u64;
+        let samples: Vec<EegSample> = decoded.into_iter()
+            .map(|d| {
+                lost += d.skipped as u64;
+                self.sample_index += d.skipped as u64;
+                let sample = EegSample {
+                    timestamp: self.sample_index as f64 / rate,
+                    channels: d.channels,
+                    accel: d.accel,
+                };
+                self.sample_index += 1;
+                sample
+            })
+            .collect();
+
+        if lost > 0 {
+            self.stream.add_lost_samples(lost);
+        }
+        self.stream.push_batch(&samples);
+    }
+
+    /// Start streaming and read until the stop flag is set (blocking - run in a separate thread)
+    pub fn run(&mut self) {
+        self.stream.set_status(EegStatus::Connecting);
+        if let Err(e) = self.send_command(OPENBCI_CMD_STOP).and_then(|_| self.send_command(OPENBCI_CMD_START)) {
+            self.stream.set_status(EegStatus::Error(format!("Failed to start streaming: {}", e)));
+            return;
+        }
+
+        let mut buf = [0u8; OPENBCI_READ_CHUNK_SIZE];
+        let mut last_data = std::time::Instant::now();
+
+        while !self.stop.load(Ordering::Relaxed) {
+            match self.port.read(&mut buf) {
+                Ok(0) => {
+                    // Serial read timeout: report a silent board but keep waiting
+                    if last_data.elapsed().as_secs_f32() > EEG_IDLE_TIMEOUT_SECS {
+                        self.stream.set_status(EegStatus::Error("No data from board".to_string()));
+                    }
+                },
+                Ok(n) => {
+                    last_data = std::time::Instant::now();
+                    self.handle_bytes(&buf[..n]);
+                    if self.stream.total_samples() > 0 && self.stream.status() != EegStatus::Streaming {
+                        self.stream.set_status(EegStatus::Streaming);
+                    }
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+                Err(e) => {
+                    self.stream.set_status(EegStatus::Error(format!("Read error: {}", e)));
+                    return;
+                }
+            }
+        }
+
+        let _ = self.send_command(OPENBCI_CMD_STOP);
+        self.stream.set_status(EegStatus::Disconnected);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cyton_packet(sample_number: u8, counts: [i32; 8], accel: Option<[i16; 3]>) -> Vec<u8> {
+        let mut packet = vec![CYTON_HEADER, sample_number];
+        for count in counts {
+            let bytes = count.to_be_bytes();
+            packet.extend_from_slice(&bytes[1..4]);
+        }
+        let aux = accel.unwrap_or([0, 0, 0]);
+        for axis in aux {
+            packet.extend_from_slice(&axis.to_be_bytes());
+        }
+        packet.push(CYTON_FOOTER_ACCEL);
+        packet
+    }
+
+    #[test]
+    fn test_cyton_decodes_channels_and_accel() {
+        let mut decoder = CytonDecoder::new();
+        let mut out = Vec::new();
+        let packet = cyton_packet(7, [1000, -1000, 0, 1, -1, 8_388_607, -8_388_608, 42], Some([16, -16, 0]));
+        decoder.feed(&packet, &mut out);
+
+        assert_eq!(out.len(), 1);
+        assert!((out[0].channels[0] - 1000.0 * CYTON_UV_PER_COUNT).abs() < 1e-3);
+        assert!((out[0].channels[1] + 1000.0 * CYTON_UV_PER_COUNT).abs() < 1e-3);
+        assert!(out[0].channels[6] < 0.0);
+        let accel = out[0].accel.expect("accel present");
+        assert!((accel[0] - 16.0 * CYTON_G_PER_COUNT).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_cyton_resyncs_and_counts_lost_packets() {
+        let mut decoder = CytonDecoder::new();
+        let mut out = Vec::new();
+        let mut bytes = b"OpenBCI V3 8-16 channel$$$".to_vec(); // Board banner before streaming
+        bytes.extend(cyton_packet(254, [0; 8], None));
+        bytes.extend(cyton_packet(255, [0; 8], None));
+        bytes.extend(cyton_packet(2, [0; 8], None)); // 0 and 1 lost across the wrap
+
+        // Feed in awkward chunk sizes to exercise buffering
+        for chunk in bytes.chunks(7) {
+            decoder.feed(chunk, &mut out);
+        }
+
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[1].skipped, 0);
+        assert_eq!(out[2].skipped, 2);
+    }
+
+    fn pack_bits(values: &[u32], bits: usize) -> Vec<u8> {
+        let mut out = vec![0u8; (values.len() * bits).div_ceil(8)];
+        for (i, &value) in values.iter().enumerate() {
+            for b in 0..bits {
+                if (value >> (bits - 1 - b)) & 1 != 0 {
+                    let bit = i * bits + b;
+                    out[bit / 8] |= 1 << (7 - bit % 8);
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_ganglion_delta_decoding() {
+        let mut decoder = GanglionPayloadDecoder::default();
+        let mut out = Vec::new();
+
+        // Raw reference sample: 100 counts on every channel
+        let mut raw = vec![0u8];
+        for _ in 0..4 {
+            raw.extend_from_slice(&100i32.to_be_bytes()[1..4]);
+        }
+        raw.resize(GANGLION_PAYLOAD_SIZE, 0);
+        decoder.decode(&raw, &mut out);
+
+        // 18-bit deltas: +4 (even = positive) then -3 encoded with the sign in the LSB
+        let minus_three = ((-3i32) as u32) & 0x3FFFF;
+        let mut packet = vec![1u8];
+        packet.extend(pack_bits(&[4, 4, 4, 4, minus_three, minus_three, minus_three, minus_three], 18));
+        packet.resize(GANGLION_PAYLOAD_SIZE, 0);
+        decoder.decode(&packet, &mut out);
+
+        assert_eq!(out.len(), 3);
+        assert!((out[1].channels[0] - 96.0 * GANGLION_UV_PER_COUNT).abs() < 1e-6);
+        assert!((out[2].channels[0] - 99.0 * GANGLION_UV_PER_COUNT).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_ganglion_bgapi_unwrapping_and_loss() {
+        let mut decoder = GanglionDecoder::new();
+        let mut out = Vec::new();
+
+        let event = |packet_id: u8| {
+            let mut value = vec![packet_id];
+            value.resize(GANGLION_PAYLOAD_SIZE, 0);
+            let mut payload = vec![0, 0x25, 0x00, 1, value.len() as u8];
+            payload.extend(value);
+            let mut packet = vec![0x80, payload.len() as u8, BGAPI_CLASS_ATTCLIENT, BGAPI_EVENT_ATTRIBUTE_VALUE];
+            packet.extend(payload);
+            packet
+        };
+
+        let mut bytes = event(1);
+        bytes.extend(event(2));
+        bytes.extend(event(5)); // 3 and 4 lost
+        decoder.feed(&bytes, &mut out);
+
+        assert_eq!(out.len(), 6);
+        assert_eq!(out[4].skipped, 4);
+
+        // Back from the 19-bit ids (101-200) to the 18-bit ones (1-100)
+        let mut decoder = GanglionDecoder::new();
+        let mut out = Vec::new();
+        let mut bytes = event(150);
+        bytes.extend(event(51)); // Next in the cycle
+        bytes.extend(event(199));
+        bytes.extend(event(3)); // 200, 1 and 2 lost
+        bytes.extend(event(100));
+        bytes.extend(event(2)); // Wrapped past 101 to 1
+        decoder.feed(&bytes, &mut out);
+        let skipped: Vec<u32> = out.iter().step_by(2).map(|s| s.skipped).collect();
+        assert_eq!(skipped, vec![0, 0, 2 * 47, 2 * 3, 2 * 96, 2]);
+    }
+
+    #[test]
+    fn test_reader_timestamps_follow_sample_clock() {
+        struct Sink;
+        impl Read for Sink {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> { Ok(0) }
+        }
+        impl Write for Sink {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> { Ok(buf.len()) }
+            fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+        }
+
+        let stream = Arc::new(EegStream::new("test", CYTON_SAMPLE_RATE_HZ, CYTON_CHANNELS));
+        let mut reader = OpenBciReader::new(Sink, Board::Cyton, stream.clone(), Arc::new(AtomicBool::new(false)));
+        let mut bytes = cyton_packet(0, [0; 8], None);
+        bytes.extend(cyton_packet(3, [0; 8], None));
+        reader.handle_bytes(&bytes);
+
+        let samples = stream.recent(10);
+        assert_eq!(samples.len(), 2);
+        assert!((samples[1].timestamp - 3.0 / CYTON_SAMPLE_RATE_HZ as f64).abs() < 1e-9);
+        assert_eq!(stream.lost_samples(), 2);
+    }
+}
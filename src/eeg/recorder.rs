@@ -0,0 +1,139 @@
+// EEG recording to EDF+/BDF+ with stimulus annotations
+// Samples are pulled from the shared stream with the recorder's own cursor; annotation onsets
+// are counted in EEG samples, so they sit on the same clock as the data in the file
+
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::utils::local_date_time;
+use super::edf::{EdfAnnotation, EdfFormat, EdfWriter};
+use super::EegStream;
+
+pub struct EegRecorder {
+    writer: EdfWriter,
+    path: PathBuf,
+    sample_rate: f32,
+    cursor: u64,
+    start_sample: u64, // Stream position of the first recorded sample
+    pending: Vec<Vec<f32>>, // Samples waiting for a full data record
+    annotations: Vec<EdfAnnotation>, // Events not yet written
+    annotation_count: u32,
+}
+
+impl EegRecorder {
+    /// Start recording from the stream's current position
+    pub fn start(stream: &EegStream, path: &Path, format: EdfFormat) -> io::Result<Self> {
+        let sample_rate = stream.sample_rate();
+        let channel_names: Vec<String> = (1..=stream.channel_count()).map(|c| format!("EEG {}", c)).collect();
+        let writer = EdfWriter::create(path, format, sample_rate, &channel_names, local_date_time())?;
+        let start_sample = stream.total_samples();
+        Ok(Self {
+            writer,
+            path: path.to_path_buf(),
+            sample_rate,
+            cursor: start_sample,
+            start_sample,
+            pending: Vec::new(),
+            annotations: Vec::new(),
+            annotation_count: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn annotation_count(&self) -> u32 {
+        self.annotation_count
+    }
+
+    /// Seconds of EEG written so far
+    pub fn duration_secs(&self) -> f64 {
+        self.writer.records() as f64 * self.writer.record_secs()
+    }
+
+    /// Mark an event at the newest sample the stream has received
+    pub fn annotate(&mut self, stream: &EegStream, text: &str) {
+        let samples = stream.total_samples().saturating_sub(self.start_sample);
+        self.annotations.push(EdfAnnotation {
+            onset_secs: samples as f64 / self.sample_rate as f64,
+            text: text.to_string(),
+        });
+        self.annotation_count += 1;
+    }
+
+    /// Write every complete data record that has arrived
+    pub fn update(&mut self, stream: &EegStream) -> io::Result<()> {
+        self.pending.extend(stream.read_since(&mut self.cursor).into_iter().map(|s| s.channels));
+        let record_len = self.writer.samples_per_record();
+        while self.pending.len() >= record_len {
+            let rows: Vec<Vec<f32>> = self.pending.drain(..record_len).collect();
+            self.writer.write_record(&rows, &mut self.annotations)?;
+        }
+        Ok(())
+    }
+
+    /// Write the partial last record (zero-padded) and any remaining events, then close the file
+    pub fn finish(mut self) -> io::Result<PathBuf> {
+        self.flush_remaining()?;
+        self.writer.finish()?;
+        Ok(self.path.clone())
+    }
+
+    fn flush_remaining(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() || !self.annotations.is_empty() {
+            let count = self.pending.len().min(self.writer.samples_per_record());
+            let rows: Vec<Vec<f32>> = self.pending.drain(..count).collect();
+            self.writer.write_record(&rows, &mut self.annotations)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EegRecorder {
+    fn drop(&mut self) {
+        // Quitting with the recording still running keeps the tail of the data
+        let _ = self.flush_remaining();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eeg::edf::read_edf;
+    use crate::utils::TempDir;
+    use crate::eeg::EegSample;
+
+    #[test]
+    fn test_recording_round_trip_with_annotations() {
+        let dir = TempDir::new("recorder");
+        for format in [EdfFormat::Edf, EdfFormat::Bdf] {
+            let path = dir.join(format!("test.{}", format.extension()));
+            let stream = EegStream::new("test", 256.0, 2);
+            let mut recorder = EegRecorder::start(&stream, &path, format).unwrap();
+
+            let batch: Vec<EegSample> = (0..640)
+                .map(|n| EegSample { timestamp: n as f64 / 256.0, channels: vec![n as f32 * 0.5, -100.25], accel: None })
+                .collect();
+            stream.push_batch(&batch[..128]);
+            recorder.annotate(&stream, "Phase: Induction");
+            stream.push_batch(&batch[128..]);
+            recorder.update(&stream).unwrap();
+            recorder.annotate(&stream, "Key: Space");
+            recorder.finish().unwrap();
+
+            let bytes = std::fs::read(&path).unwrap();
+            let recording = read_edf(&bytes).unwrap();
+            assert_eq!(recording.sample_rate, 256.0);
+            assert_eq!(recording.channel_names, vec!["EEG 1", "EEG 2"]);
+            assert_eq!(recording.samples.len(), 768); // Three records, the last zero-padded
+            assert!((recording.samples[639][0] - 319.5).abs() < 0.1);
+            assert!((recording.samples[10][1] + 100.25).abs() < 0.1);
+            assert!(recording.samples[700][0].abs() < 0.1);
+
+            let text = String::from_utf8_lossy(&bytes);
+            assert!(text.contains(&format!("{}C", format.name())));
+            assert!(text.contains("+0.5000\u{14}Phase: Induction\u{14}"));
+            assert!(text.contains("+2.5000\u{14}Key: Space\u{14}"));
+        }
+    }
+}
@@ -0,0 +1,292 @@
+// EEG replay from recorded files
+// Loads OpenBCI GUI CSV, EDF/BDF or plain CSV into memory and plays it into an EegStream at
+// recorded speed (or faster), so analysis and closed-loop code run exactly as with a live device
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use crate::constants::*;
+use super::{EegSample, EegStatus, EegStream};
+
+/// A fully loaded recording (microvolts, one row per sample)
+#[derive(Clone, Debug)]
+pub struct Recording {
+    pub sample_rate: f32,
+    pub channel_names: Vec<String>,
+    pub samples: Vec<Vec<f32>>,
+}
+
+impl Recording {
+    pub fn channel_count(&self) -> usize {
+        self.channel_names.len()
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate
+    }
+
+    /// Keep only the given channels, in the given order (1-based)
+    pub fn select_channels(&mut self, mapping: &[usize]) -> Result<(), String> {
+        if let Some(&bad) = mapping.iter().find(|&&c| c == 0 || c > self.channel_count()) {
+            return Err(format!("Channel {} does not exist (recording has {})", bad, self.channel_count()));
+        }
+        self.channel_names = mapping.iter().map(|&c| self.channel_names[c - 1].clone()).collect();
+        for row in self.samples.iter_mut() {
+            *row = mapping.iter().map(|&c| row[c - 1]).collect();
+        }
+        Ok(())
+    }
+}
+
+/// Recorded file formats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayFormat {
+    OpenBciCsv, // OpenBCI GUI "%OpenBCI Raw EEG Data" export
+    Edf,        // EDF, EDF+ or BDF
+    PlainCsv,   // One row per sample, optional header and time column
+}
+
+/// What to replay and how
+#[derive(Clone, Debug)]
+pub struct ReplaySettings {
+    pub path: String,
+    /// Sample rate for plain CSV without a time column (ignored for self-describing formats)
+    pub sample_rate: Option<f32>,
+    /// Channels to use, 1-based, in order (None keeps all)
+    pub channels: Option<Vec<usize>>,
+    /// Playback speed relative to real time
+    pub speed: f32,
+    /// Start again from the beginning at the end of the file
+    pub looped: bool,
+}
+
+impl ReplaySettings {
+    pub fn new(path: &str) -> Self {
+        Self { path: path.to_string(), sample_rate: None, channels: None, speed: 1.0, looped: false }
+    }
+}
+
+/// Pick the format from the extension and, for text files, the first line
+pub fn detect_format(path: &Path, head: &[u8]) -> ReplayFormat {
+    let extension = path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if extension == "edf" || extension == "bdf" || head.first() == Some(&0xFF) {
+        ReplayFormat::Edf
+    } else if head.starts_with(b"%") {
+        ReplayFormat::OpenBciCsv
+    } else {
+        ReplayFormat::PlainCsv
+    }
+}
+
+/// Load a recording and apply the channel mapping
+pub fn load_recording(settings: &ReplaySettings) -> Result<Recording, String> {
+    let path = Path::new(&settings.path);
+    let bytes = std::fs::read(path).map_err(|e| format!("Could not read {}: {}", settings.path, e))?;
+
+    let mut recording = match detect_format(path, &bytes) {
+        ReplayFormat::Edf => super::edf::read_edf(&bytes)?,
+        ReplayFormat::OpenBciCsv => parse_openbci_csv(&String::from_utf8_lossy(&bytes))?,
+        ReplayFormat::PlainCsv => parse_plain_csv(&String::from_utf8_lossy(&bytes), settings.sample_rate)?,
+    };
+    if recording.samples.is_empty() {
+        return Err(format!("{} holds no samples", settings.path));
+    }
+    if let Some(mapping) = &settings.channels {
+        recording.select_channels(mapping)?;
+    }
+    Ok(recording)
+}
+
+fn split_fields(line: &str) -> Vec<&str> {
+    let delimiter = if line.contains('\t') { '\t' } else if line.contains(';') { ';' } else { ',' };
+    line.split(delimiter).map(|f| f.trim()).collect()
+}
+
+fn parse_row(fields: &[&str], columns: &[usize], line_number: usize) -> Result<Vec<f32>, String> {
+    columns.iter()
+        .map(|&c| {
+            fields.get(c)
+                .and_then(|f| f.parse::<f32>().ok())
+                .ok_or_else(|| format!("Line {}: missing or invalid value in column {}", line_number, c + 1))
+        })
+        .collect()
+}
+
+/// OpenBCI GUI export: '%' header lines (sample rate, channel count), an optional column header
+/// row (GUI v5), then "Sample Index, EXG Channel 0..N-1, Accel..., ..." rows
+pub fn parse_openbci_csv(text: &str) -> Result<Recording, String> {
+    let mut sample_rate = None;
+    let mut channel_count = None;
+    let mut columns: Option<Vec<usize>> = None;
+    let mut channel_names = Vec::new();
+    let mut samples = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(meta) = line.strip_prefix('%') {
+            if let Some((key, value)) = meta.split_once('=') {
+                let number = value.split_whitespace().next().unwrap_or("");
+                match key.trim() {
+                    "Sample Rate" => sample_rate = number.parse::<f32>().ok(),
+                    "Number of channels" => channel_count = number.parse::<usize>().ok(),
+                    _ => {}
+                }
+            }
+            continue;
+        }
+
+        let fields = split_fields(line);
+        if columns.is_none() {
+            if fields.first().is_some_and(|f| f.parse::<f64>().is_err()) {
+                // GUI v5 column header row
+                let exg: Vec<usize> = fields.iter().enumerate()
+                    .filter(|(_, name)| name.starts_with("EXG Channel"))
+                    .map(|(c, _)| c)
+                    .collect();
+                channel_names = exg.iter().map(|&c| fields[c].to_string()).collect();
+                columns = Some(exg);
+                continue;
+            }
+            // GUI v4: no header row, channels follow the sample index
+            let count = channel_count.ok_or("OpenBCI CSV has no channel count header")?;
+            columns = Some((1..=count).collect());
+            channel_names = (1..=count).map(|c| format!("EXG Channel {}", c - 1)).collect();
+        }
+        samples.push(parse_row(&fields, columns.as_ref().unwrap(), i + 1)?);
+    }
+
+    if channel_names.is_empty() {
+        return Err("OpenBCI CSV has no EXG channels".to_string());
+    }
+    Ok(Recording {
+        sample_rate: sample_rate.ok_or("OpenBCI CSV has no sample rate header")?,
+        channel_names,
+        samples,
+    })
+}
+
+/// Plain CSV: optional header row; a column named time/timestamp (seconds) is used to infer the
+/// sample rate when none is given and is not treated as a channel
+pub fn parse_plain_csv(text: &str, sample_rate: Option<f32>) -> Result<Recording, String> {
+    let mut lines = text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty() && !l.starts_with('#')).peekable();
+    let (_, first) = *lines.peek().ok_or("CSV file is empty")?;
+    let first_fields = split_fields(first);
+    let has_header = first_fields.iter().any(|f| f.parse::<f64>().is_err());
+
+    let names: Vec<String> = if has_header {
+        lines.next();
+        first_fields.iter().map(|f| f.to_string()).collect()
+    } else {
+        (1..=first_fields.len()).map(|c| format!("Ch{}", c)).collect()
+    };
+    let time_column = names.iter().position(|n| {
+        matches!(n.to_lowercase().as_str(), "time" | "timestamp" | "t" | "seconds" | "time_s")
+    });
+    let columns: Vec<usize> = (0..names.len()).filter(|c| Some(*c) != time_column).collect();
+
+    let mut samples = Vec::new();
+    let mut times = Vec::new();
+    for (i, line) in lines {
+        let fields = split_fields(line);
+        samples.push(parse_row(&fields, &columns, i + 1)?);
+        if let Some(t) = time_column {
+            times.push(parse_row(&fields, &[t], i + 1)?[0]);
+        }
+    }
+
+    let sample_rate = match (sample_rate, times.first(), times.last()) {
+        (Some(rate), _, _) => rate,
+        (None, Some(&start), Some(&end)) if times.len() > 1 && end > start => (times.len() - 1) as f32 / (end - start),
+        _ => return Err("Plain CSV needs a sample rate (--replay-rate) or a time column".to_string()),
+    };
+
+    Ok(Recording {
+        sample_rate,
+        channel_names: columns.iter().map(|&c| names[c].clone()).collect(),
+        samples,
+    })
+}
+
+/// Plays a recording into an EegStream in real time (scaled by the speed setting)
+pub struct ReplayReader {
+    recording: Recording,
+    stream: Arc<EegStream>,
+    stop: Arc<AtomicBool>,
+    speed: f32,
+    looped: bool,
+}
+
+impl ReplayReader {
+    pub fn new(recording: Recording, settings: &ReplaySettings, stream: Arc<EegStream>, stop: Arc<AtomicBool>) -> Self {
+        Self { recording, stream, stop, speed: settings.speed.max(REPLAY_MIN_SPEED), looped: settings.looped }
+    }
+
+    /// Push samples as their playback time comes due (blocking - run in a separate thread)
+    pub fn run(&mut self) {
+        self.stream.set_status(EegStatus::Streaming);
+        let started = std::time::Instant::now();
+        let rate = self.recording.sample_rate as f64;
+        let length = self.recording.samples.len() as u64;
+        let mut pushed: u64 = 0;
+
+        while !self.stop.load(Ordering::Relaxed) {
+            let due = (started.elapsed().as_secs_f64() * rate * self.speed as f64) as u64;
+            let end = if self.looped { due } else { due.min(length) };
+            if pushed < end {
+                // Timestamps keep increasing across loops, like a device that never stopped
+                let batch: Vec<EegSample> = (pushed..end)
+                    .map(|n| EegSample {
+                        timestamp: n as f64 / rate,
+                        channels: self.recording.samples[(n % length) as usize].clone(),
+                        accel: None,
+                    })
+                    .collect();
+                self.stream.push_batch(&batch);
+                pushed = end;
+            }
+            if !self.looped && pushed >= length {
+                self.stream.set_status(EegStatus::Error("End of recording".to_string()));
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(REPLAY_TICK_MS));
+        }
+
+        self.stream.set_status(EegStatus::Disconnected);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openbci_gui_v5_csv() {
+        let text = "%OpenBCI Raw EXG Data\n%Number of channels = 2\n%Sample Rate = 250 Hz\n%Board = OpenBCI_GUI$BoardCytonSerial\n\
+            Sample Index, EXG Channel 0, EXG Channel 1, Accel Channel 0, Timestamp\n\
+            0, 1.5, -2.0, 0.01, 1700000000.0\n\
+            1, 2.5, -3.0, 0.01, 1700000000.004\n";
+        let recording = parse_openbci_csv(text).unwrap();
+        assert_eq!(recording.sample_rate, 250.0);
+        assert_eq!(recording.channel_names, vec!["EXG Channel 0", "EXG Channel 1"]);
+        assert_eq!(recording.samples, vec![vec![1.5, -2.0], vec![2.5, -3.0]]);
+    }
+
+    #[test]
+    fn test_plain_csv_rate_from_time_column_and_mapping() {
+        let text = "time,Fp1,Fp2,O1\n0.00,1,2,3\n0.01,4,5,6\n0.02,7,8,9\n";
+        let mut recording = parse_plain_csv(text, None).unwrap();
+        assert!((recording.sample_rate - 100.0).abs() < 0.01);
+        recording.select_channels(&[3, 1]).unwrap();
+        assert_eq!(recording.channel_names, vec!["O1", "Fp1"]);
+        assert_eq!(recording.samples[2], vec![9.0, 7.0]);
+        assert!(recording.select_channels(&[4]).is_err());
+
+        assert!(parse_plain_csv("1,2\n3,4\n", None).is_err());
+        assert_eq!(parse_plain_csv("1,2\n3,4\n", Some(128.0)).unwrap().samples.len(), 2);
+    }
+}
@@ -0,0 +1,193 @@
+// Headless (offline) runs: the same synthesis, EEG analysis and closed-loop code as the TUI,
+// without an audio device or terminal, running as fast as the CPU allows
+
+use std::path::{Path, PathBuf};
+use crate::audio::{AudioParams, Synthesizer};
+use crate::cli::{RenderOptions, ReplayOptions};
+use crate::constants::*;
+use crate::eeg::bandpower::{BandPowerAnalyzer, BandPowers};
+use crate::eeg::controller::{prepare_coherence, ClosedLoopController};
+use crate::eeg::replay::load_recording;
+use crate::eeg::{EegSample, EegStream};
+use crate::manifest::SessionManifest;
+use crate::wav::WavWriter;
+
+/// What an offline replay produced
+pub struct ReplaySummary {
+    pub duration_secs: f32,
+    pub sample_rate: f32,
+    pub channel_names: Vec<String>,
+    pub mean_bands: Option<BandPowers>, // Relative band power averaged over the whole recording
+    pub adjustments: u32,
+    pub final_beat_hz: f32,
+    pub log_path: Option<PathBuf>,
+    pub manifest_path: Option<PathBuf>, // Written next to the rendered audio
+}
+
+/// Load a preset by name (user directory first, then embedded) or from a file path
+pub fn load_preset_params(name: Option<&str>) -> Result<AudioParams, String> {
+    let name = name.unwrap_or(DEFAULT_PRESET_FILENAME);
+    let linted = if std::path::Path::new(name).is_file() {
+        // A preset file outside the presets folder finds its parents next to it
+        crate::preset_lint::lint_preset_file(std::path::Path::new(name))
+    } else {
+        let json = if name.ends_with(".json") {
+            crate::load_preset_hybrid(name)
+        } else {
+            crate::load_preset_hybrid(&format!("{}.json", name))
+        };
+        let json = json.ok_or_else(|| format!("Preset '{}' not found", name))?;
+        crate::preset_lint::lint_preset(&json)
+    };
+    for diagnostic in linted.diagnostics.iter().filter(|d| d.severity == crate::preset_lint::Severity::Warning) {
+        eprintln!("Preset '{}': {}", name, diagnostic);
+    }
+    let mut params = linted.into_params()
+        .map_err(|e| format!("Preset '{}' is invalid: {}", name, e))?;
+    params.rf_enabled = false; // Safety
+    params.stream_enabled = false;
+    params.playing = true;
+    Ok(params)
+}
+
+/// Replay a recording through band-power analysis (and optionally the closed loop), rendering
+/// the audio to a WAV file when asked
+pub fn run_replay(options: &ReplayOptions) -> Result<ReplaySummary, String> {
+    let recording = load_recording(&options.replay)?;
+    let mut params = load_preset_params(options.preset.as_deref())?;
+
+    let name = format!("Replay of {}", options.replay.path);
+    let stream = EegStream::new(&name, recording.sample_rate, recording.channel_count());
+    let mut analyzer = BandPowerAnalyzer::new(recording.sample_rate, recording.channel_count(),
+        options.mains_hz.unwrap_or(EEG_MAINS_DEFAULT_HZ));
+
+    let mut controller = if options.closed_loop {
+        let config = params.closed_loop.clone().unwrap_or_default();
+        prepare_coherence(&mut params.coherence, &config);
+        let log_path = match &options.log {
+            Some(path) => PathBuf::from(path),
+            None => get_sessions_dir().join(format!("closed_loop_replay_{}.jsonl", crate::session::unix_now())),
+        };
+        Some(ClosedLoopController::new(config, Some(log_path))
+            .map_err(|e| format!("Could not create closed-loop log: {}", e))?)
+    } else {
+        None
+    };
+
+    params.resolve_seed(options.seed);
+    let mut synth = Synthesizer::new(options.sample_rate as f32, params.session_seed);
+    let mut wav = match &options.out {
+        Some(path) => Some(WavWriter::create(path, options.sample_rate)
+            .map_err(|e| format!("Could not create {}: {}", path, e))?),
+        None => None,
+    };
+    params.preset_name = Some(options.preset.clone().unwrap_or_else(|| DEFAULT_PRESET_FILENAME.to_string()));
+    let mut manifest = options.out.as_ref().map(|_| SessionManifest::new(&params, options.sample_rate as f32));
+
+    // Feed the recording in analysis-interval chunks; the session clock is the EEG clock
+    let rate = recording.sample_rate;
+    let chunk = ((rate * EEG_ANALYSIS_INTERVAL_SECS) as usize).max(1);
+    let mut band_sum = BandPowers::default();
+    let mut band_count = 0u32;
+    let mut frames_rendered: u64 = 0;
+
+    for (chunk_index, rows) in recording.samples.chunks(chunk).enumerate() {
+        let first = chunk_index * chunk;
+        let batch: Vec<EegSample> = rows.iter().enumerate()
+            .map(|(i, row)| EegSample { timestamp: (first + i) as f64 / rate as f64, channels: row.clone(), accel: None })
+            .collect();
+        stream.push_batch(&batch);
+
+        if analyzer.update(&stream) {
+            if let Some(snapshot) = analyzer.latest() {
+                if let Some(bands) = snapshot.mean_relative() {
+                    band_sum.delta += bands.delta;
+                    band_sum.theta += bands.theta;
+                    band_sum.alpha += bands.alpha;
+                    band_sum.beta += bands.beta;
+                    band_sum.gamma += bands.gamma;
+                    band_count += 1;
+                }
+                if let Some(controller) = controller.as_mut() {
+                    let session_secs = (first + rows.len()) as f32 / rate;
+                    controller.step(snapshot, &mut params.coherence, session_secs);
+                }
+            }
+        }
+
+        // Render the audio that plays during this chunk
+        if let Some(wav) = wav.as_mut() {
+            let end_secs = (first + rows.len()) as f64 / rate as f64;
+            let target_frames = (end_secs * options.sample_rate as f64) as u64;
+            while frames_rendered < target_frames {
+                let (left, right) = synth.next_sample(&params);
+                wav.write_frame(left, right).map_err(|e| format!("Could not write audio: {}", e))?;
+                frames_rendered += 1;
+            }
+        }
+    }
+
+    if let Some(wav) = wav {
+        wav.finish(options.sample_rate).map_err(|e| format!("Could not finish audio file: {}", e))?;
+    }
+    // The closed loop moves the beat during the replay, so such a render is not reproducible from the manifest alone
+    let manifest_path = match (manifest.as_mut(), &options.out) {
+        (Some(manifest), Some(out)) => {
+            manifest.duration_secs = Some(frames_rendered as f32 / options.sample_rate as f32);
+            manifest.params_changed = controller.as_ref().is_some_and(|c| c.adjustments() > 0);
+            let path = Path::new(out).with_extension("manifest.json");
+            manifest.save(&path).map_err(|e| format!("Could not write manifest: {}", e))?;
+            Some(path)
+        }
+        _ => None,
+    };
+
+    let mean_bands = (band_count > 0).then(|| {
+        let n = band_count as f32;
+        BandPowers {
+            delta: band_sum.delta / n,
+            theta: band_sum.theta / n,
+            alpha: band_sum.alpha / n,
+            beta: band_sum.beta / n,
+            gamma: band_sum.gamma / n,
+        }
+    });
+
+    Ok(ReplaySummary {
+        duration_secs: recording.duration_secs(),
+        sample_rate: rate,
+        channel_names: recording.channel_names.clone(),
+        mean_bands,
+        adjustments: controller.as_ref().map(|c| c.adjustments()).unwrap_or(0),
+        final_beat_hz: params.coherence.binaural_beat_hz(),
+        log_path: controller.as_ref().and_then(|c| c.log_path().cloned()),
+        manifest_path,
+    })
+}
+
+/// Regenerate a session's audio from its manifest; returns the seconds rendered
+pub fn render_manifest(options: &RenderOptions) -> Result<f32, String> {
+    let manifest = SessionManifest::load(Path::new(&options.manifest))?;
+    let mut params = manifest.render_params();
+    if let Some(run) = &manifest.blind {
+        let key_path = options.blind_key.as_ref()
+            .ok_or_else(|| format!("{} is a blinded session - give its study key with --blind", run.code))?;
+        params.coherence.sham = crate::blind::StudyKey::load(Path::new(key_path))?.sham_for(run)?;
+    }
+
+    let session_secs = params.coherence.enabled && params.coherence.being_type != crate::coherence::BeingType::Unknown;
+    let secs = options.minutes.map(|m| m * 60.0)
+        .or(manifest.duration_secs)
+        .or(session_secs.then(|| params.coherence.total_session_min() * 60.0))
+        .ok_or("The manifest has no session length - give --minutes")?;
+
+    let rate = manifest.sample_rate.round() as u32;
+    let mut synth = Synthesizer::new(manifest.sample_rate, manifest.seed);
+    let mut wav = WavWriter::create(&options.out, rate).map_err(|e| format!("Could not create {}: {}", options.out, e))?;
+    for _ in 0..(secs as f64 * manifest.sample_rate as f64) as u64 {
+        let (left, right) = synth.next_sample(&params);
+        wav.write_frame(left, right).map_err(|e| format!("Could not write audio: {}", e))?;
+    }
+    wav.finish(rate).map_err(|e| format!("Could not finish audio file: {}", e))?;
+    Ok(secs)
+}
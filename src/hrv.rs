@@ -0,0 +1,547 @@
+// Heart-rate / HRV input and resonance breathing
+// A BLE bridge (or any other device) sends RR intervals or heart rate as text lines over a serial
+// port or UDP; beats go into a shared buffer on their own clock (the running sum of RR intervals),
+// from which RMSSD, coherence and the breath pacer's resonance search are computed
+
+use std::io::Read;
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::collections::VecDeque;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::constants::*;
+use crate::dsp::{band_power, hann_window, power_spectrum, spectral_peak_hz};
+use crate::eeg::EegStatus;
+
+/// One line from a heart-rate bridge
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeartReading {
+    pub rr_ms: Vec<f32>,
+    pub hr_bpm: Option<f32>,
+}
+
+/// Parse a bridge line: a JSON object with `rr` (ms, or seconds if ≤ 10; a number or an array)
+/// and/or `hr`, or plain numbers (RR in ms, or bpm below HEART_RR_MIN_MS), optionally prefixed
+/// with `RR` or `HR`. None if the line holds no reading (banners, blank lines)
+pub fn parse_heart_line(line: &str) -> Option<HeartReading> {
+    let line = line.trim();
+    let mut reading = HeartReading::default();
+
+    if line.starts_with('{') {
+        let value: serde_json::Value = serde_json::from_str(line).ok()?;
+        let object = value.as_object()?;
+        for (key, value) in object {
+            let numbers: Vec<f32> = match value {
+                serde_json::Value::Array(items) => items.iter().filter_map(|v| v.as_f64()).map(|v| v as f32).collect(),
+                other => other.as_f64().map(|v| v as f32).into_iter().collect(),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "rr" | "rr_ms" | "rri" | "rr_intervals" => {
+                    reading.rr_ms.extend(numbers.iter().map(|&v| if v <= 10.0 { v * 1000.0 } else { v }));
+                },
+                "hr" | "bpm" | "heart_rate" => reading.hr_bpm = numbers.first().copied(),
+                _ => {}
+            }
+        }
+    } else {
+        let lower = line.to_ascii_lowercase();
+        let (kind, rest) = match lower.get(..2) {
+            Some("rr") => (Some(true), &lower[2..]),
+            Some("hr") => (Some(false), &lower[2..]),
+            _ => (None, lower.as_str()),
+        };
+        let numbers: Vec<f32> = rest.split(|c: char| c.is_whitespace() || ",;:=".contains(c))
+            .filter(|t| !t.is_empty())
+            .map(|t| t.parse::<f32>().ok())
+            .collect::<Option<Vec<f32>>>()?;
+        for value in numbers {
+            match kind.unwrap_or(value >= HEART_RR_MIN_MS) {
+                true => reading.rr_ms.push(value),
+                false => reading.hr_bpm = Some(value),
+            }
+        }
+    }
+
+    if reading.rr_ms.is_empty() && reading.hr_bpm.is_none() {
+        None
+    } else {
+        Some(reading)
+    }
+}
+
+/// An accepted heartbeat
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Beat {
+    pub time_secs: f64, // Beat clock: sum of the plausible RR intervals received (ectopic ones included)
+    pub rr_ms: f32,
+}
+
+impl Beat {
+    pub fn hr_bpm(&self) -> f32 {
+        60_000.0 / self.rr_ms
+    }
+}
+
+struct HeartStreamState {
+    beats: VecDeque<Beat>,
+    clock_secs: f64,
+    last_rr_ms: Option<f32>,
+    total_beats: u64,
+    rejected_beats: u64,
+    reported_hr: Option<f32>, // Heart rate as sent by the device
+    status: EegStatus,
+}
+
+/// Shared, bounded beat buffer between a heart-rate reader thread and the UI
+pub struct HeartStream {
+    state: Mutex<HeartStreamState>,
+    source_name: String,
+}
+
+impl HeartStream {
+    pub fn new(source_name: &str) -> Self {
+        Self {
+            state: Mutex::new(HeartStreamState {
+                beats: VecDeque::with_capacity(HEART_BUFFER_BEATS),
+                clock_secs: 0.0,
+                last_rr_ms: None,
+                total_beats: 0,
+                rejected_beats: 0,
+                reported_hr: None,
+                status: EegStatus::Disconnected,
+            }),
+            source_name: source_name.to_string(),
+        }
+    }
+
+    /// Add a reading, dropping implausible intervals and ectopic or missed beats
+    pub fn push_reading(&self, reading: &HeartReading) {
+        let mut state = self.state.lock();
+        if reading.hr_bpm.is_some() {
+            state.reported_hr = reading.hr_bpm;
+        }
+        for &rr_ms in &reading.rr_ms {
+            if !(HEART_RR_MIN_MS..=HEART_RR_MAX_MS).contains(&rr_ms) {
+                state.rejected_beats += 1;
+                continue;
+            }
+            state.clock_secs += rr_ms as f64 / 1000.0;
+            let previous = state.last_rr_ms.replace(rr_ms);
+            if previous.is_some_and(|last| (rr_ms - last).abs() / last > HEART_RR_MAX_CHANGE) {
+                state.rejected_beats += 1;
+                continue;
+            }
+            if state.beats.len() >= HEART_BUFFER_BEATS {
+                state.beats.pop_front();
+            }
+            let time_secs = state.clock_secs;
+            state.beats.push_back(Beat { time_secs, rr_ms });
+            state.total_beats += 1;
+        }
+    }
+
+    /// Copy of the buffered beats (oldest first)
+    pub fn beats(&self) -> Vec<Beat> {
+        self.state.lock().beats.iter().copied().collect()
+    }
+
+    pub fn set_status(&self, status: EegStatus) {
+        self.state.lock().status = status;
+    }
+
+    pub fn status(&self) -> EegStatus {
+        self.state.lock().status.clone()
+    }
+
+    pub fn reported_hr(&self) -> Option<f32> {
+        self.state.lock().reported_hr
+    }
+
+    pub fn total_beats(&self) -> u64 {
+        self.state.lock().total_beats
+    }
+
+    pub fn rejected_beats(&self) -> u64 {
+        self.state.lock().rejected_beats
+    }
+
+    pub fn source_name(&self) -> &str {
+        &self.source_name
+    }
+}
+
+/// Where readings come from
+pub enum HeartSource {
+    Serial(Box<dyn Read + Send>),
+    Udp(UdpSocket),
+}
+
+/// Reads lines from a heart-rate bridge into a HeartStream
+pub struct HeartReader {
+    source: HeartSource,
+    stream: Arc<HeartStream>,
+    stop: Arc<AtomicBool>,
+    line: Vec<u8>,
+}
+
+impl HeartReader {
+    pub fn new(source: HeartSource, stream: Arc<HeartStream>, stop: Arc<AtomicBool>) -> std::io::Result<Self> {
+        if let HeartSource::Udp(socket) = &source {
+            socket.set_read_timeout(Some(std::time::Duration::from_millis(UDP_READ_TIMEOUT_MS)))?;
+        }
+        Ok(Self { source, stream, stop, line: Vec::new() })
+    }
+
+    /// Split received bytes into lines and push every reading; `end_of_message` ends a line
+    /// without a newline (a UDP datagram is always complete)
+    pub fn handle_bytes(&mut self, bytes: &[u8], end_of_message: bool) -> bool {
+        let mut got_reading = false;
+        for &byte in bytes {
+            if byte == b'\n' {
+                got_reading |= self.handle_line();
+            } else if self.line.len() < HEART_MAX_LINE_LEN {
+                self.line.push(byte);
+            }
+        }
+        if end_of_message {
+            got_reading |= self.handle_line();
+        }
+        got_reading
+    }
+
+    fn handle_line(&mut self) -> bool {
+        let line = String::from_utf8_lossy(&self.line).to_string();
+        self.line.clear();
+        match parse_heart_line(&line) {
+            Some(reading) => {
+                self.stream.push_reading(&reading);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Read until the stop flag is set (blocking - run in a separate thread)
+    pub fn run(&mut self) {
+        self.stream.set_status(EegStatus::Connecting);
+        let mut buf = vec![0u8; HEART_MAX_LINE_LEN];
+        let mut last_data: Option<std::time::Instant> = None;
+
+        while !self.stop.load(Ordering::Relaxed) {
+            let received = match &mut self.source {
+                HeartSource::Serial(port) => port.read(&mut buf).map(|n| (n, false)),
+                HeartSource::Udp(socket) => socket.recv_from(&mut buf).map(|(n, _)| (n, true)),
+            };
+            match received {
+                Ok((0, false)) => {},
+                Ok((n, end_of_message)) => {
+                    if self.handle_bytes(&buf[..n], end_of_message) {
+                        last_data = Some(std::time::Instant::now());
+                        if self.stream.status() != EegStatus::Streaming {
+                            self.stream.set_status(EegStatus::Streaming);
+                        }
+                    }
+                    continue;
+                },
+                Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::Interrupted) => {},
+                Err(e) => {
+                    self.stream.set_status(EegStatus::Error(format!("Read error: {}", e)));
+                    return;
+                }
+            }
+            // Keep waiting, but report a sensor that went quiet
+            if last_data.is_some_and(|t| t.elapsed().as_secs_f32() > HEART_IDLE_TIMEOUT_SECS) {
+                self.stream.set_status(EegStatus::Error("No data from heart sensor".to_string()));
+            }
+        }
+
+        self.stream.set_status(EegStatus::Disconnected);
+    }
+}
+
+/// Heart rate variability over the last HRV_WINDOW_SECS of beats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HrvMetrics {
+    pub hr_bpm: f32,
+    pub rmssd_ms: f32,
+    /// Peak power / remaining power of the heart-rate spectrum (None until 32 s of beats)
+    pub coherence: Option<f32>,
+    pub peak_hz: Option<f32>,
+}
+
+/// RMSSD, mean heart rate and coherence of the most recent beats
+pub fn analyze(beats: &[Beat]) -> Option<HrvMetrics> {
+    let end = beats.last()?.time_secs;
+    let window: Vec<Beat> = beats.iter().copied()
+        .filter(|b| b.time_secs >= end - HRV_WINDOW_SECS as f64)
+        .collect();
+    if window.len() < HRV_MIN_BEATS {
+        return None;
+    }
+
+    let mean_rr = window.iter().map(|b| b.rr_ms).sum::<f32>() / window.len() as f32;
+    let diffs: Vec<f32> = window.windows(2).map(|w| w[1].rr_ms - w[0].rr_ms).collect();
+    let rmssd_ms = (diffs.iter().map(|d| d * d).sum::<f32>() / diffs.len() as f32).sqrt();
+
+    let (coherence, peak_hz) = match coherence(&window) {
+        Some((ratio, peak)) => (Some(ratio), Some(peak)),
+        None => (None, None),
+    };
+    Some(HrvMetrics { hr_bpm: 60_000.0 / mean_rr, rmssd_ms, coherence, peak_hz })
+}
+
+/// Coherence ratio and peak frequency: the heart-rate tachogram is resampled evenly, and the power
+/// around the largest peak in 0.04-0.26 Hz is compared with the rest of the 0.0033-0.4 Hz power
+fn coherence(window: &[Beat]) -> Option<(f32, f32)> {
+    let start = window.first()?.time_secs;
+    let span = window.last()?.time_secs - start;
+    let available = (span * HRV_RESAMPLE_HZ as f64) as usize;
+    let full = (HRV_WINDOW_SECS * HRV_RESAMPLE_HZ) as usize;
+    if available < full / 2 {
+        return None;
+    }
+    // Largest power of two that fits, taken from the newest end
+    let n = 1usize << available.min(full).ilog2();
+    let from = window.last()?.time_secs - (n - 1) as f64 / HRV_RESAMPLE_HZ as f64;
+
+    // Linear interpolation of instantaneous heart rate at each grid point
+    let mut series = Vec::with_capacity(n);
+    let mut k = 0;
+    for i in 0..n {
+        let t = from + i as f64 / HRV_RESAMPLE_HZ as f64;
+        while k + 2 < window.len() && window[k + 1].time_secs < t {
+            k += 1;
+        }
+        let (a, b) = (window[k], window[k + 1]);
+        let frac = ((t - a.time_secs) / (b.time_secs - a.time_secs)).clamp(0.0, 1.0) as f32;
+        series.push(a.hr_bpm() + (b.hr_bpm() - a.hr_bpm()) * frac);
+    }
+
+    let psd = power_spectrum(&series, &hann_window(n), HRV_RESAMPLE_HZ);
+    let bin_hz = HRV_RESAMPLE_HZ / n as f32;
+    let peak_hz = spectral_peak_hz(&psd, bin_hz, HRV_PEAK_MIN_HZ, HRV_PEAK_MAX_HZ)?;
+    let peak_power = band_power(&psd, bin_hz, peak_hz - HRV_PEAK_HALF_WIDTH_HZ, peak_hz + HRV_PEAK_HALF_WIDTH_HZ);
+    let rest = band_power(&psd, bin_hz, HRV_TOTAL_MIN_HZ, HRV_TOTAL_MAX_HZ) - peak_power;
+    if rest <= 0.0 {
+        return None;
+    }
+    Some((peak_power / rest, peak_hz))
+}
+
+/// Mean peak-to-trough heart-rate swing per breath over [from, to] on the beat clock
+/// None if no breath cycle in the range holds two beats
+pub fn breath_amplitude(beats: &[Beat], from: f64, to: f64, breath_hz: f32) -> Option<f32> {
+    let period = 1.0 / breath_hz as f64;
+    let mut swings = Vec::new();
+    let mut cycle_start = from;
+    while cycle_start + period <= to {
+        let rates: Vec<f32> = beats.iter()
+            .filter(|b| b.time_secs >= cycle_start && b.time_secs < cycle_start + period)
+            .map(|b| b.hr_bpm())
+            .collect();
+        if rates.len() >= 2 {
+            let max = rates.iter().copied().fold(f32::MIN, f32::max);
+            let min = rates.iter().copied().fold(f32::MAX, f32::min);
+            swings.push(max - min);
+        }
+        cycle_start += period;
+    }
+    if swings.is_empty() {
+        None
+    } else {
+        Some(swings.iter().sum::<f32>() / swings.len() as f32)
+    }
+}
+
+/// Result of one breath pacing trial
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PaceTrial {
+    pub breath_hz: f32,
+    pub amplitude_bpm: f32, // Mean heart-rate swing per breath at that rate
+}
+
+/// Searches for the resonance breathing rate: each rate is held for BREATH_PACE_DWELL_SECS and
+/// scored by the heart-rate swing it produces; the rate keeps moving in the same direction while
+/// the swing grows and turns around when it shrinks, so it settles around the best rate
+pub struct BreathPacer {
+    breath_hz: f32,
+    direction: f32,
+    trial_start: Option<f64>, // Beat clock time the current rate was set
+    last_amplitude: Option<f32>,
+    best: Option<PaceTrial>,
+    trials: Vec<PaceTrial>,
+}
+
+impl Default for BreathPacer {
+    fn default() -> Self {
+        Self {
+            breath_hz: BREATH_PACE_START_HZ,
+            direction: -1.0, // Most adults resonate slightly below 6 breaths/min
+            trial_start: None,
+            last_amplitude: None,
+            best: None,
+            trials: Vec::new(),
+        }
+    }
+}
+
+impl BreathPacer {
+    pub fn breath_hz(&self) -> f32 {
+        self.breath_hz
+    }
+
+    pub fn best(&self) -> Option<PaceTrial> {
+        self.best
+    }
+
+    pub fn trials(&self) -> &[PaceTrial] {
+        &self.trials
+    }
+
+    /// Score the current rate once it has been held long enough and pick the next one
+    pub fn update(&mut self, beats: &[Beat]) -> Option<PaceTrial> {
+        let now = beats.last()?.time_secs;
+        let start = *self.trial_start.get_or_insert(now);
+        if now - start < BREATH_PACE_DWELL_SECS as f64 {
+            return None;
+        }
+        self.trial_start = Some(now);
+        // Heart rate takes a few breaths to follow a new pace
+        let amplitude = breath_amplitude(beats, start + BREATH_PACE_SETTLE_SECS as f64, now, self.breath_hz)?;
+        let trial = PaceTrial { breath_hz: self.breath_hz, amplitude_bpm: amplitude };
+        self.trials.push(trial);
+
+        if self.last_amplitude.is_some_and(|last| amplitude < last) {
+            self.direction = -self.direction;
+        }
+        self.last_amplitude = Some(amplitude);
+        if self.best.is_none_or(|best| amplitude > best.amplitude_bpm) {
+            self.best = Some(trial);
+        }
+
+        let next = self.breath_hz + self.direction * BREATH_PACE_STEP_HZ;
+        if !(BREATH_PACE_MIN_HZ..=BREATH_PACE_MAX_HZ).contains(&next) {
+            self.direction = -self.direction;
+        }
+        self.breath_hz = next.clamp(BREATH_PACE_MIN_HZ, BREATH_PACE_MAX_HZ);
+        Some(trial)
+    }
+}
+
+/// HRV at one point in the session
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct HrvPoint {
+    pub session_secs: f32,
+    pub hr_bpm: f32,
+    pub rmssd_ms: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coherence: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breath_hz: Option<f32>, // Paced breathing rate, when pacing was on
+}
+
+/// HRV saved with the session record
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HrvSummary {
+    pub source: String,
+    pub mean_hr_bpm: f32,
+    pub mean_rmssd_ms: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mean_coherence: Option<f32>,
+    /// Breath pacing trials, and the rate with the largest heart-rate swing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pace_trials: Vec<PaceTrial>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resonance_breath_hz: Option<f32>,
+    pub points: Vec<HrvPoint>,
+}
+
+/// Collects HRV every HRV_LOG_INTERVAL_SECS of session time
+#[derive(Default)]
+pub struct HrvLog {
+    points: Vec<HrvPoint>,
+}
+
+impl HrvLog {
+    pub fn record(&mut self, session_secs: f32, metrics: &HrvMetrics, breath_hz: Option<f32>) {
+        if self.points.last().is_some_and(|p| session_secs - p.session_secs < HRV_LOG_INTERVAL_SECS) {
+            return;
+        }
+        self.points.push(HrvPoint {
+            session_secs,
+            hr_bpm: metrics.hr_bpm,
+            rmssd_ms: metrics.rmssd_ms,
+            coherence: metrics.coherence,
+            breath_hz,
+        });
+    }
+
+    /// Session summary (None if no HRV was measured)
+    pub fn summary(&self, source: &str, pacer: Option<&BreathPacer>) -> Option<HrvSummary> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let mean = |values: Vec<f32>| {
+            if values.is_empty() { None } else { Some(values.iter().sum::<f32>() / values.len() as f32) }
+        };
+        Some(HrvSummary {
+            source: source.to_string(),
+            mean_hr_bpm: mean(self.points.iter().map(|p| p.hr_bpm).collect())?,
+            mean_rmssd_ms: mean(self.points.iter().map(|p| p.rmssd_ms).collect())?,
+            mean_coherence: mean(self.points.iter().filter_map(|p| p.coherence).collect()),
+            pace_trials: pacer.map(|p| p.trials().to_vec()).unwrap_or_default(),
+            resonance_breath_hz: pacer.and_then(|p| p.best()).map(|t| t.breath_hz),
+            points: self.points.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bridge_lines() {
+        assert_eq!(parse_heart_line(r#"{"hr": 64, "rr": [812, 0.798]}"#),
+            Some(HeartReading { rr_ms: vec![812.0, 798.0], hr_bpm: Some(64.0) }));
+        assert_eq!(parse_heart_line("RR: 845, 870"), Some(HeartReading { rr_ms: vec![845.0, 870.0], hr_bpm: None }));
+        assert_eq!(parse_heart_line("72"), Some(HeartReading { rr_ms: vec![], hr_bpm: Some(72.0) }));
+        assert_eq!(parse_heart_line("Polar H10 bridge ready"), None);
+
+        // Out-of-range intervals and both intervals around an ectopic beat are dropped
+        let stream = HeartStream::new("test");
+        stream.push_reading(&HeartReading { rr_ms: vec![1000.0, 1010.0, 600.0, 1000.0, 990.0, 5000.0], hr_bpm: None });
+        assert_eq!(stream.total_beats(), 3);
+        assert_eq!(stream.rejected_beats(), 3);
+        assert!((stream.beats()[2].time_secs - 4.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pacer_finds_resonance() {
+        // Heart rate follows the breath with a swing that peaks at 0.09 Hz (5.4 breaths/min)
+        let swing = |hz: f32| 12.0 * (-((hz - 0.09) / 0.02).powi(2)).exp();
+        let mut pacer = BreathPacer::default();
+        let stream = HeartStream::new("test");
+        let mut t = 0.0f64;
+        let mut phase = 0.0f64;
+        while t < 40.0 * 60.0 {
+            let hz = pacer.breath_hz();
+            let hr = 60.0 + swing(hz) * (phase.sin() as f32);
+            let rr = 60_000.0 / hr;
+            stream.push_reading(&HeartReading { rr_ms: vec![rr], hr_bpm: None });
+            t += rr as f64 / 1000.0;
+            phase += 2.0 * std::f64::consts::PI * hz as f64 * rr as f64 / 1000.0;
+            pacer.update(&stream.beats());
+        }
+
+        assert!((pacer.best().unwrap().breath_hz - 0.09).abs() < 0.006);
+        assert!((pacer.breath_hz() - 0.09).abs() <= 0.011);
+
+        let metrics = analyze(&stream.beats()).unwrap();
+        assert!(metrics.rmssd_ms > 10.0);
+        assert!((metrics.peak_hz.unwrap() - pacer.breath_hz()).abs() < 0.03);
+        assert!(metrics.coherence.unwrap() > 1.0);
+    }
+}
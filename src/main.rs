@@ -1,10 +1,11 @@
+use std::collections::HashSet;
 use std::error::Error;
 use std::fs::File;
-use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use parking_lot::Mutex;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -18,24 +19,68 @@ use ratatui::{
 };
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 
+mod analysis;
 mod audio;
+mod blind;
+mod cli;
 mod coherence;
 mod rf;
 mod streaming;
 mod constants;
+mod dsp;
+mod eeg;
+mod serial;
 mod utils;
+mod verify;
+mod wav;
 mod embedded_presets;
+mod headless;
+mod hrv;
+mod manifest;
+mod preset_browser;
+mod preset_bundle;
+mod preset_editor;
+mod preset_files;
+mod preset_inherit;
+mod preset_lint;
+mod preset_schema;
+mod preset_updates;
+mod markers;
+mod protocol;
+mod session;
+mod stimulus;
 
 use audio::{AudioParams, Synthesizer, SignalType};
 use coherence::BeingType;
+use eeg::{ContactQuality, EegStatus, EegStream};
+use eeg::bandpower::{BandPowerAnalyzer, BandPowers};
+use eeg::controller::{ClosedLoopConfig, ClosedLoopController, ControlLaw};
+use eeg::edf::EdfFormat;
+use eeg::recorder::EegRecorder;
 use rf::RfWorker;
 use streaming::{AudioRingBuffer, StreamingServer};
 use constants::*;
-use utils::{wrap_text, cycle_index};
+use utils::{wrap_text, cycle_index, format_clock_time, format_duration_min, local_minutes_of_day, minutes_until};
+use session::{ReportForm, ReportQuestion, ReportValue, SessionRecord, QuestionKind};
+use stimulus::{StimulusEvent, StimulusTracker};
+use markers::{MarkerServer, StimulusMarker};
+use hrv::{BreathPacer, HeartStream, HrvLog, HrvMetrics};
+use blind::{BlindRun, StudyKey};
+use protocol::{ProtocolRun, ProtocolStep, RunStatus};
+use manifest::SessionManifest;
+use preset_editor::{EditorField, PresetEditor, EDITOR_FIELDS};
+use preset_files::Trashed;
+use preset_browser::{PresetCategory, PresetFilter, PresetInfo, PresetRow, UNCATEGORIZED_LABEL};
+use preset_lint::Severity;
+use preset_updates::PresetMerge;
 
 enum AppMode {
     Mixer,
     PresetSelect,
+    Report,
+    Protocol, // Running an experiment protocol step by step
+    PresetEditor, // Naming and saving the current settings as a preset
+    PresetMerge, // Choosing fields of an updated built-in preset to take over
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -45,11 +90,20 @@ enum PresetDescMode {
     Full,       // Show full description
 }
 
-struct PresetInfo {
+/// Operation on the selected preset waiting for a new name or a confirmation
+enum PresetAction {
+    Rename(String),
+    Delete,
+    Revert,
+    Import(String), // Preset code or archive path being typed or pasted
+    ImportConflict { source: String, taken: Vec<String> }, // Waiting for rename, skip or overwrite
+}
+
+/// Share code of a preset, shown in the browser's details pane
+struct SharedCode {
     filename: String,
-    title: Option<String>,
-    description: Option<String>,
-    experimental: Option<bool>,
+    code: String,
+    qr: Option<String>, // Rendered QR code, once asked for
 }
 
 struct App {
@@ -61,16 +115,52 @@ struct App {
     // Preset state
     status_msg: Option<(String, std::time::Instant)>,
     preset_list: Vec<PresetInfo>,
-    preset_state: ListState,
+    preset_state: ListState, // Selected row of preset_rows
+    preset_rows: Vec<PresetRow>,
+    preset_filter: PresetFilter,
+    preset_searching: bool, // Typing a search after '/'
+    collapsed_categories: HashSet<Option<PresetCategory>>,
     current_preset: Option<String>,
 
     // Network streaming
     stream_client_count: Arc<Mutex<usize>>,
 
+    // EEG input (None when no device was configured)
+    eeg: Option<Arc<EegStream>>,
+    eeg_analyzer: Option<BandPowerAnalyzer>,
+    closed_loop: Option<ClosedLoopController>,
+    closed_loop_laws: (u32, Vec<ControlLaw>), // Law settings left while cycling, for this preset version
+    eeg_recorder: Option<EegRecorder>,
+    record_format: EdfFormat,
+
+    // Stimulus changes, annotated into the EEG recording
+    stimulus: StimulusTracker,
+
+    // Heart-rate input and HRV breath pacing (None when no sensor was configured)
+    heart: Option<Arc<HeartStream>>,
+    hrv: Option<HrvMetrics>,
+    hrv_beats_seen: u64, // Beats (accepted + rejected) already analysed
+    breath_pacer: Option<BreathPacer>,
+    hrv_log: HrvLog,
+
+    // Blinded study session (presets locked; the record carries only the neutral code)
+    blind: Option<BlindRun>,
+
+    // Experiment protocol being run (AppMode::Protocol)
+    protocol: Option<ProtocolRun>,
+
+    // Reproducibility: every session's resolved params and seed go to a manifest
+    sample_rate: f32,
+    seed_override: Option<u64>, // --seed
+    experiment_questions: Vec<ReportQuestion>, // --questions, asked after the preset's own
+    manifest: Option<(PathBuf, SessionManifest)>,
+
     // Collapsible sections
     signal_layer_collapsed: bool,
     hackrf_collapsed: bool,
     streaming_collapsed: bool,
+    eeg_collapsed: bool,
+    heart_collapsed: bool,
     preset_desc_mode: PresetDescMode,
 
     // Mapping from UI index to original channel index (for collapse toggle)
@@ -78,6 +168,28 @@ struct App {
 
     // RF safety
     rf_disclaimer_shown: bool,
+
+    // Session record and post-session report
+    session_started_at: u64,
+    session_report_prompted: bool,
+    report_form: Option<ReportForm>,
+
+    // Preset being saved (AppMode::PresetEditor)
+    preset_editor: Option<PresetEditor>,
+
+    // Preset browser operations (pending rename/confirmation, and the last preset moved to the trash)
+    preset_action: Option<PresetAction>,
+    last_trashed: Option<Trashed>,
+
+    // Share code shown for the selected preset
+    shared_code: Option<SharedCode>,
+
+    // Edited DEFAULT_ preset being merged with its newer built-in version (AppMode::PresetMerge)
+    preset_merge: Option<PresetMerge>,
+
+    // Wake-up alarm (local time in minutes since midnight, and when it next fires)
+    wake_at: Option<u32>,
+    wake_deadline_unix: u64,
 }
 
 struct ChannelInfo {
@@ -95,6 +207,9 @@ enum ChannelId {
     CoherenceVol,
     BinauralAdjust,
     SessionTimer,
+    EegBands,
+    SleepTimer,
+    WakeAlarm,
     // UAP/Unknown Frequencies
     Carrier,
     Harmonic,
@@ -112,12 +227,22 @@ enum ChannelId {
     // Network Streaming
     StreamEnable,
     StreamPort,
+    // EEG Input
+    EegSource,
+    EegSignal,
+    EegDominant,
+    ClosedLoop,
+    EegRecord,
+    // Heart Input
+    HeartSource,
+    HeartRate,
+    BreathPacing,
     // UI Spacer
     Spacer,
 }
 
 impl App {
-    fn new(params: Arc<Mutex<AudioParams>>, stream_client_count: Arc<Mutex<usize>>) -> Self {
+    fn new(params: Arc<Mutex<AudioParams>>, stream_client_count: Arc<Mutex<usize>>, eeg: Option<Arc<EegStream>>) -> Self {
         let mut state = ListState::default();
         state.select(Some(0));
 
@@ -130,6 +255,8 @@ impl App {
             signal_layer_collapsed: false,  // Start expanded for visibility
             hackrf_collapsed: true,         // Start collapsed (advanced)
             streaming_collapsed: true,      // Start collapsed (experimental)
+            eeg_collapsed: eeg.is_none(),   // Expanded only when a device is connected
+            heart_collapsed: true,          // Expanded in main() when a heart sensor is connected
             preset_desc_mode: PresetDescMode::Hidden,  // Start hidden to save space
             visible_channel_indices: Vec::new(),
             rf_disclaimer_shown: false,
@@ -143,6 +270,9 @@ impl App {
                 ChannelInfo { name: "  Volume".to_string(), id: ChannelId::CoherenceVol },
                 ChannelInfo { name: "  Beat Adjust".to_string(), id: ChannelId::BinauralAdjust },
                 ChannelInfo { name: "  Session Progress".to_string(), id: ChannelId::SessionTimer },
+                ChannelInfo { name: "  EEG Band Power".to_string(), id: ChannelId::EegBands },
+                ChannelInfo { name: "  Sleep Timer".to_string(), id: ChannelId::SleepTimer },
+                ChannelInfo { name: "  Wake Alarm".to_string(), id: ChannelId::WakeAlarm },
                 ChannelInfo { name: "".to_string(), id: ChannelId::Spacer }, // Spacer
                 ChannelInfo { name: "SIGNAL LAYER".to_string(), id: ChannelId::Carrier },
                 ChannelInfo { name: "  Carrier (7.83Hz)".to_string(), id: ChannelId::Carrier },
@@ -163,18 +293,69 @@ impl App {
                 ChannelInfo { name: "NETWORK STREAMING [EXPERIMENTAL]".to_string(), id: ChannelId::StreamEnable },
                 ChannelInfo { name: "  Stream Enable".to_string(), id: ChannelId::StreamEnable },
                 ChannelInfo { name: "  Stream Port".to_string(), id: ChannelId::StreamPort },
+                ChannelInfo { name: "".to_string(), id: ChannelId::Spacer }, // Spacer
+                ChannelInfo { name: "EEG INPUT".to_string(), id: ChannelId::EegSource },
+                ChannelInfo { name: "  Source".to_string(), id: ChannelId::EegSource },
+                ChannelInfo { name: "  Signal (µV RMS)".to_string(), id: ChannelId::EegSignal },
+                ChannelInfo { name: "  Dominant (Hz)".to_string(), id: ChannelId::EegDominant },
+                ChannelInfo { name: "  Closed Loop".to_string(), id: ChannelId::ClosedLoop },
+                ChannelInfo { name: "  Record EEG".to_string(), id: ChannelId::EegRecord },
+                ChannelInfo { name: "".to_string(), id: ChannelId::Spacer }, // Spacer
+                ChannelInfo { name: "HEART INPUT".to_string(), id: ChannelId::HeartSource },
+                ChannelInfo { name: "  Source".to_string(), id: ChannelId::HeartSource },
+                ChannelInfo { name: "  Heart Rate / HRV".to_string(), id: ChannelId::HeartRate },
+                ChannelInfo { name: "  Breath Pacing".to_string(), id: ChannelId::BreathPacing },
             ],
             state,
             status_msg: None,
             preset_list: Vec::new(),
             preset_state,
+            preset_rows: Vec::new(),
+            preset_filter: PresetFilter::default(),
+            preset_searching: false,
+            collapsed_categories: HashSet::new(),
             current_preset: None,
             stream_client_count,
+            eeg,
+            eeg_analyzer: None,
+            closed_loop: None,
+            closed_loop_laws: (0, Vec::new()),
+            eeg_recorder: None,
+            record_format: EdfFormat::Edf,
+            stimulus: StimulusTracker::default(),
+            heart: None,
+            hrv: None,
+            hrv_beats_seen: 0,
+            breath_pacer: None,
+            hrv_log: HrvLog::default(),
+            blind: None,
+            protocol: None,
+            sample_rate: 0.0,
+            seed_override: None,
+            experiment_questions: Vec::new(),
+            manifest: None,
+            session_started_at: session::unix_now(),
+            session_report_prompted: false,
+            report_form: None,
+            preset_editor: None,
+            preset_action: None,
+            last_trashed: None,
+            preset_merge: None,
+            shared_code: None,
+            wake_at: None,
+            wake_deadline_unix: 0,
         }
     }
 
     // --- Helper Methods ---
 
+    /// True while keys go into typed text (report answers, preset fields, a new name, a search or an
+    /// import code). That text may be private, so those keys are not annotated in the EEG recording.
+    fn typing_text(&self) -> bool {
+        self.report_form.is_some() || self.preset_editor.is_some() || self.preset_searching
+            || matches!(self.preset_action, Some(PresetAction::Rename(_) | PresetAction::Import(_)))
+    }
+
     /// Check if a channel is locked (signal layer locked when lock_signal_layer is true)
     fn is_channel_locked(&self, id: ChannelId, params: &AudioParams) -> bool {
         let is_signal_channel = matches!(id,
@@ -195,9 +376,24 @@ impl App {
                 self.state.select(Some(next_visual_idx));
             },
             AppMode::PresetSelect => {
-                if self.preset_list.is_empty() { return; }
-                let i = cycle_index(self.preset_state.selected().unwrap_or(0), self.preset_list.len(), 1);
+                if self.preset_rows.is_empty() { return; }
+                let i = cycle_index(self.preset_state.selected().unwrap_or(0), self.preset_rows.len(), 1);
                 self.preset_state.select(Some(i));
+            },
+            AppMode::Report | AppMode::Protocol => {
+                if let Some(form) = self.report_form.as_mut() {
+                    form.select(1);
+                }
+            }
+            AppMode::PresetEditor => {
+                if let Some(editor) = self.preset_editor.as_mut() {
+                    editor.selected = cycle_index(editor.selected, EDITOR_FIELDS.len(), 1);
+                }
+            }
+            AppMode::PresetMerge => {
+                if let Some(merge) = self.preset_merge.as_mut().filter(|m| !m.diffs.is_empty()) {
+                    merge.selected = cycle_index(merge.selected, merge.diffs.len(), 1);
+                }
             }
         }
     }
@@ -212,9 +408,24 @@ impl App {
                 self.state.select(Some(prev_visual_idx));
             },
             AppMode::PresetSelect => {
-                if self.preset_list.is_empty() { return; }
-                let i = cycle_index(self.preset_state.selected().unwrap_or(0), self.preset_list.len(), -1);
+                if self.preset_rows.is_empty() { return; }
+                let i = cycle_index(self.preset_state.selected().unwrap_or(0), self.preset_rows.len(), -1);
                 self.preset_state.select(Some(i));
+            },
+            AppMode::Report | AppMode::Protocol => {
+                if let Some(form) = self.report_form.as_mut() {
+                    form.select(-1);
+                }
+            }
+            AppMode::PresetEditor => {
+                if let Some(editor) = self.preset_editor.as_mut() {
+                    editor.selected = cycle_index(editor.selected, EDITOR_FIELDS.len(), -1);
+                }
+            }
+            AppMode::PresetMerge => {
+                if let Some(merge) = self.preset_merge.as_mut().filter(|m| !m.diffs.is_empty()) {
+                    merge.selected = cycle_index(merge.selected, merge.diffs.len(), -1);
+                }
             }
         }
     }
@@ -271,9 +482,12 @@ impl App {
 
                     drop(params); // Release lock before file I/O
 
-                    // Use hybrid loading (user dir first, then embedded fallback)
-                    if let Some(json) = load_preset_hybrid(&filename) {
-                        if let Ok(mut loaded) = serde_json::from_str::<AudioParams>(&json) {
+                    // Use hybrid loading (user dir first, then embedded fallback); presets with lint errors are refused
+                    let checked = load_preset_hybrid(&filename)
+                        .ok_or_else(|| "not found".to_string())
+                        .and_then(|json| preset_lint::lint_preset(&json).into_params());
+                    match checked {
+                        Ok(mut loaded) => {
                             loaded.rf_enabled = false; // Safety
 
                             // Preserve streaming settings when loading preset
@@ -285,17 +499,20 @@ impl App {
                             loaded.stream_enabled = stream_enabled;
                             loaded.stream_port = stream_port;
                             loaded.preset_version = current_version.wrapping_add(1);
+                            loaded.preset_name = Some(filename.clone());
+                            loaded.resolve_seed(self.seed_override);
 
                             *params = loaded;
                             drop(params);
 
                             self.current_preset = Some(filename.clone());
-                            return;
+                            self.note_stimulus(StimulusEvent::PresetLoaded { preset: filename });
+                            self.begin_session();
+                        }
+                        Err(e) => {
+                            self.status_msg = Some((format!("Failed to load {}: {}", filename, e), std::time::Instant::now()));
                         }
                     }
-
-                    // If load fails
-                    self.status_msg = Some((format!("Failed to load {}", filename), std::time::Instant::now()));
                 },
                 ChannelId::CoherenceVol => {
                     // Only allow adjustment when preset selected (binaural beats for human listening need headphones!)
@@ -315,6 +532,47 @@ impl App {
                     // Read-only display, no adjustment
                 },
 
+                ChannelId::WakeAlarm => {
+                    // Move the alarm time in 15-minute steps (Space switches it on/off)
+                    let day = 24 * 60;
+                    let current = self.wake_at.unwrap_or(WAKE_ALARM_DEFAULT_MIN);
+                    let new_time = if delta > 0.0 {
+                        (current + WAKE_ALARM_STEP_MIN) % day
+                    } else {
+                        (current + day - WAKE_ALARM_STEP_MIN) % day
+                    };
+                    drop(params);
+                    self.schedule_wake(Some(new_time));
+                },
+
+                ChannelId::SleepTimer => {
+                    // Sleep descent is a binaural program, so it needs a human preset
+                    if !params.coherence.enabled || matches!(params.coherence.being_type, BeingType::Unknown) {
+                        self.status_msg = Some(("Sleep mode needs a binaural preset".to_string(), std::time::Instant::now()));
+                        return;
+                    }
+
+                    let was_active = params.coherence.sleep.is_active();
+                    let elapsed_min = if was_active {
+                        session::unix_now().saturating_sub(self.session_started_at) as f32 / 60.0
+                    } else {
+                        0.0
+                    };
+                    let mut new_min = (params.coherence.sleep.duration_min + delta.signum() * SLEEP_TIMER_STEP_MIN)
+                        .clamp(0.0, SLEEP_TIMER_MAX_MIN);
+                    if new_min <= elapsed_min {
+                        new_min = 0.0; // Shortened past the current time: switch off
+                    }
+                    params.coherence.sleep.duration_min = new_min;
+
+                    if !was_active && new_min > 0.0 {
+                        // Restart the session so the descent begins now
+                        params.restart_session();
+                        drop(params);
+                        self.begin_session();
+                    }
+                },
+
                 ChannelId::PingFreq => {
                     let step = if delta.abs() > 0.05 { PING_FREQ_COARSE_STEP } else { PING_FREQ_FINE_STEP };
                     let new_hz = (params.ping_freq_hz + delta.signum() * step).clamp(PING_FREQ_MIN_HZ, PING_FREQ_MAX_HZ);
@@ -374,6 +632,38 @@ impl App {
                 ChannelId::StreamEnable => {
                     // Toggle with space, not arrows
                 }
+
+                ChannelId::EegSource | ChannelId::EegSignal | ChannelId::EegDominant | ChannelId::EegBands => {
+                    // Read-only display, no adjustment
+                }
+
+                ChannelId::HeartSource | ChannelId::HeartRate | ChannelId::BreathPacing => {
+                    // Read-only display (Space toggles breath pacing)
+                }
+
+                ChannelId::ClosedLoop => {
+                    // Cycle the control law while the loop is off (Space starts it)
+                    if self.closed_loop.is_none() {
+                        // Laws cycled away from keep their settings until another preset loads
+                        if self.closed_loop_laws.0 != params.preset_version {
+                            self.closed_loop_laws = (params.preset_version, Vec::new());
+                        }
+                        let mut config = params.closed_loop.clone().unwrap_or_default();
+                        let step = if delta > 0.0 { 1 } else { -1 };
+                        config.law = eeg::controller::cycle_law(&config.law, &mut self.closed_loop_laws.1, step);
+                        params.closed_loop = Some(config);
+                    }
+                }
+
+                ChannelId::EegRecord => {
+                    // Switch between EDF+ and BDF+ while not recording (Space starts it)
+                    if self.eeg_recorder.is_none() {
+                        self.record_format = match self.record_format {
+                            EdfFormat::Edf => EdfFormat::Bdf,
+                            EdfFormat::Bdf => EdfFormat::Edf,
+                        };
+                    }
+                }
                 
                 ChannelId::StreamPort => {
                     let step = if delta.abs() > 0.05 { PORT_COARSE_STEP } else { PORT_FINE_STEP };
@@ -415,6 +705,27 @@ impl App {
                          params.stream_enabled = !params.stream_enabled;
                          return;
                      }
+                     ChannelId::ClosedLoop => {
+                         drop(params);
+                         self.toggle_closed_loop();
+                         return;
+                     }
+                     ChannelId::EegRecord => {
+                         drop(params);
+                         self.toggle_eeg_recording();
+                         return;
+                     }
+                     ChannelId::BreathPacing => {
+                         drop(params);
+                         self.toggle_breath_pacing();
+                         return;
+                     }
+                     ChannelId::WakeAlarm => {
+                         drop(params);
+                         let new_time = if self.wake_at.is_some() { None } else { Some(WAKE_ALARM_DEFAULT_MIN) };
+                         self.schedule_wake(new_time);
+                         return;
+                     }
                      _ => {}
                  }
              }
@@ -546,7 +857,6 @@ impl App {
         let presets_dir = get_presets_dir();
         let _ = std::fs::create_dir_all(&presets_dir);
 
-        use std::collections::HashSet;
         let mut loaded_files = HashSet::new();
 
         // Load from user directory first (user presets and modified defaults)
@@ -556,54 +866,25 @@ impl App {
                     if name.ends_with(".json") {
                         loaded_files.insert(name.clone());
 
-                        // Try to load metadata
-                        let path = presets_dir.join(&name);
-                        let mut title = None;
-                        let mut description = None;
-                        let mut experimental = None;
-
-                        if let Ok(mut file) = File::open(&path) {
-                            use std::io::Read;
-                            let mut json = String::new();
-                            if file.read_to_string(&mut json).is_ok() {
-                                if let Ok(params) = serde_json::from_str::<AudioParams>(&json) {
-                                    title = params.preset_title;
-                                    description = params.preset_description;
-                                    experimental = params.experimental;
-                                }
-                            }
-                        }
-
-                        self.preset_list.push(PresetInfo {
-                            filename: name,
-                            title,
-                            description,
-                            experimental,
-                        });
+                        // Load metadata (invalid presets stay listed with their errors)
+                        let linted = preset_lint::lint_preset_file(&presets_dir.join(&name));
+                        self.preset_list.push(PresetInfo::new(name, linted));
                     }
                 }
             }
         }
 
+        // Edited built-in presets with a newer version waiting to be merged
+        for filename in preset_updates::pending_merges(&presets_dir) {
+            if let Some(info) = self.preset_list.iter_mut().find(|info| info.filename == filename) {
+                info.update_available = true;
+            }
+        }
+
         // Add embedded presets that aren't already loaded from user directory
         for preset in embedded_presets::EMBEDDED_PRESETS {
             if !loaded_files.contains(preset.filename) {
-                let mut title = None;
-                let mut description = None;
-                let mut experimental = None;
-
-                if let Ok(params) = serde_json::from_str::<AudioParams>(preset.content) {
-                    title = params.preset_title;
-                    description = params.preset_description;
-                    experimental = params.experimental;
-                }
-
-                self.preset_list.push(PresetInfo {
-                    filename: preset.filename.to_string(),
-                    title,
-                    description,
-                    experimental,
-                });
+                self.preset_list.push(PresetInfo::new(preset.filename.to_string(), preset_lint::lint_preset(preset.content)));
             }
         }
         // Sort with DEFAULT_ files first
@@ -616,31 +897,280 @@ impl App {
                 _ => a.filename.cmp(&b.filename),
             }
         });
-        if !self.preset_list.is_empty() {
-             self.preset_state.select(Some(0));
-        } else {
-             self.preset_state.select(None);
+        self.preset_state.select(None);
+        self.rebuild_preset_rows();
+    }
+
+    /// Regroup and filter the list, keeping the selected preset when it is still shown
+    fn rebuild_preset_rows(&mut self) {
+        let selected = self.selected_preset_index();
+        let header = self.preset_state.selected().and_then(|i| self.preset_rows.get(i)).and_then(|row| match row {
+            PresetRow::Header { category, .. } => Some(*category),
+            PresetRow::Preset(_) => None,
+        });
+        self.preset_rows = preset_browser::build_rows(&self.preset_list, &self.preset_filter, &self.collapsed_categories);
+
+        let row = self.preset_rows.iter().position(|row| match (row, selected, header) {
+            (PresetRow::Preset(i), Some(selected), _) => *i == selected,
+            (PresetRow::Header { category, .. }, None, Some(header)) => *category == header,
+            _ => false,
+        });
+        // Otherwise the first preset (not a header)
+        let row = row.or_else(|| self.preset_rows.iter().position(|row| matches!(row, PresetRow::Preset(_))));
+        self.preset_state.select(row.or(if self.preset_rows.is_empty() { None } else { Some(0) }));
+    }
+
+    /// Index into preset_list of the selected row (None on a category header)
+    fn selected_preset_index(&self) -> Option<usize> {
+        match self.preset_state.selected().and_then(|i| self.preset_rows.get(i)) {
+            Some(PresetRow::Preset(i)) => Some(*i),
+            _ => None,
         }
     }
+
+    /// Enter on a category header folds or unfolds it
+    fn toggle_selected_category(&mut self) -> bool {
+        let Some(PresetRow::Header { category, .. }) = self.preset_state.selected().and_then(|i| self.preset_rows.get(i)).copied() else {
+            return false;
+        };
+        if !self.collapsed_categories.remove(&category) {
+            self.collapsed_categories.insert(category);
+        }
+        self.rebuild_preset_rows();
+        true
+    }
     
     fn enter_preset_mode(&mut self) {
+        if self.blind.is_some() {
+            self.status_msg = Some(("Presets are locked during a blinded session".to_string(), std::time::Instant::now()));
+            return;
+        }
         self.mode = AppMode::PresetSelect;
         self.refresh_presets();
     }
     
     fn exit_preset_mode(&mut self) {
+        self.preset_action = None;
         self.mode = AppMode::Mixer;
     }
+
+    fn selected_preset_filename(&self) -> Option<String> {
+        self.selected_preset_index()
+            .and_then(|i| self.preset_list.get(i))
+            .map(|info| info.filename.clone())
+    }
+
+    /// Reload the list and keep the cursor on the given preset
+    fn refresh_presets_selecting(&mut self, filename: &str) {
+        self.refresh_presets();
+        let index = self.preset_list.iter().position(|info| info.filename == filename);
+        if let Some(row) = self.preset_rows.iter().position(|row| matches!(row, PresetRow::Preset(i) if Some(*i) == index)) {
+            self.preset_state.select(Some(row));
+        }
+    }
+
+    /// Ask for a new name or a confirmation before changing the selected preset
+    fn start_preset_action(&mut self, action: PresetAction) {
+        let Some(filename) = self.selected_preset_filename() else { return; };
+        let refusal = match action {
+            PresetAction::Rename(_) if preset_files::is_default(&filename) =>
+                Some("DEFAULT_ presets can't be renamed - duplicate it instead".to_string()),
+            PresetAction::Delete if preset_files::is_default(&filename) =>
+                Some("DEFAULT_ presets can't be deleted - revert it to the built-in version instead".to_string()),
+            PresetAction::Delete => preset_files::delete_blocker(&get_presets_dir(), &filename),
+            PresetAction::Revert if !preset_files::is_default(&filename) =>
+                Some("Only DEFAULT_ presets have a built-in version to revert to".to_string()),
+            _ => None,
+        };
+        if let Some(refusal) = refusal {
+            self.status_msg = Some((format!("⚠️  {}", refusal), std::time::Instant::now()));
+            return;
+        }
+        self.preset_action = Some(action);
+    }
+
+    /// Carry out the pending rename, delete or revert
+    fn confirm_preset_action(&mut self) {
+        let (Some(action), Some(filename)) = (self.preset_action.take(), self.selected_preset_filename()) else { return; };
+        let dir = get_presets_dir();
+        let result = match action {
+            PresetAction::Rename(name) => preset_files::rename_preset(&dir, &filename, &name).map(|(renamed, children)| {
+                // Keep following the playing preset under its new name
+                if self.current_preset.as_deref() == Some(filename.as_str()) {
+                    self.current_preset = Some(renamed.clone());
+                    self.params.lock().preset_name = Some(renamed.clone());
+                }
+                let msg = match children.len() {
+                    0 => format!("Renamed {} to {}", filename, renamed),
+                    n => format!("Renamed {} to {} ({} preset{} extending it updated)", filename, renamed, n, if n == 1 { "" } else { "s" }),
+                };
+                (msg, renamed)
+            }),
+            PresetAction::Delete => preset_files::delete_preset(&dir, &filename).map(|trashed| {
+                self.last_trashed = Some(trashed);
+                (format!("Deleted {} (u: undo)", filename), filename.clone())
+            }),
+            PresetAction::Revert => preset_files::revert_preset(&dir, &filename).map(|trashed| match trashed {
+                Some(trashed) => {
+                    self.last_trashed = Some(trashed);
+                    (format!("Reverted {} to the built-in version (u: undo)", filename), filename.clone())
+                }
+                None => (format!("{} already matches the built-in version", filename), filename.clone()),
+            }),
+            PresetAction::Import(source) => {
+                self.preset_action = Some(PresetAction::Import(source));
+                return self.confirm_import();
+            }
+            PresetAction::ImportConflict { source, .. } => return self.finish_import(&source, preset_bundle::OnConflict::Rename),
+        };
+        match result {
+            Ok((msg, select)) => {
+                self.status_msg = Some((msg, std::time::Instant::now()));
+                self.refresh_presets_selecting(&select);
+            }
+            Err(e) => self.status_msg = Some((format!("⚠️  {}", e), std::time::Instant::now())),
+        }
+    }
+
+    fn duplicate_selected_preset(&mut self) {
+        let Some(filename) = self.selected_preset_filename() else { return; };
+        match preset_files::duplicate_preset(&get_presets_dir(), &filename) {
+            Ok(copy) => {
+                self.status_msg = Some((format!("Duplicated {} as {}", filename, copy), std::time::Instant::now()));
+                self.refresh_presets_selecting(&copy);
+            }
+            Err(e) => self.status_msg = Some((format!("⚠️  {}", e), std::time::Instant::now())),
+        }
+    }
+
+    /// Compare the selected edited DEFAULT_ preset with its newer built-in version
+    fn open_preset_merge(&mut self) {
+        let Some(filename) = self.selected_preset_filename() else { return; };
+        if !self.preset_list.iter().any(|info| info.filename == filename && info.update_available) {
+            self.status_msg = Some((format!("{} has no built-in update to merge", filename), std::time::Instant::now()));
+            return;
+        }
+        match PresetMerge::open(&get_presets_dir(), &filename) {
+            Ok(merge) => {
+                self.preset_merge = Some(merge);
+                self.mode = AppMode::PresetMerge;
+            }
+            Err(e) => self.status_msg = Some((format!("⚠️  {}", e), std::time::Instant::now())),
+        }
+    }
+
+    /// Write the merged preset (the previous copy goes to the trash, u undoes it)
+    fn apply_preset_merge(&mut self) {
+        let Some(merge) = self.preset_merge.take() else { return; };
+        self.mode = AppMode::PresetSelect;
+        match merge.apply(&get_presets_dir()) {
+            Ok(trashed) => {
+                let taken = merge.take_theirs.iter().filter(|t| **t).count();
+                self.last_trashed = Some(trashed);
+                self.status_msg = Some((format!("Merged {}: took {} of {} updated fields (u: undo)",
+                    merge.filename, taken, merge.diffs.len()), std::time::Instant::now()));
+                self.refresh_presets_selecting(&merge.filename);
+            }
+            Err(e) => self.status_msg = Some((format!("⚠️  {}", e), std::time::Instant::now())),
+        }
+    }
+
+    fn close_preset_merge(&mut self) {
+        self.preset_merge = None;
+        self.mode = AppMode::PresetSelect;
+    }
+
+    /// Show the selected preset's share code; pressed again, its QR code; a third time hides it
+    fn share_selected_preset(&mut self) {
+        let Some(filename) = self.selected_preset_filename() else { return; };
+        match self.shared_code.as_mut() {
+            Some(shared) if shared.filename == filename && shared.qr.is_none() => {
+                shared.qr = Some(preset_bundle::qr_code(&shared.code).unwrap_or_else(|e| format!("⚠️  {}", e)));
+            }
+            Some(shared) if shared.filename == filename => self.shared_code = None,
+            _ => {
+                let code = preset_bundle::collect(&get_presets_dir(), std::slice::from_ref(&filename))
+                    .and_then(|entries| preset_bundle::encode_code(&entries));
+                match code {
+                    Ok(code) => self.shared_code = Some(SharedCode { filename, code, qr: None }),
+                    Err(e) => self.status_msg = Some((format!("⚠️  {}", e), std::time::Instant::now())),
+                }
+            }
+        }
+    }
+
+    /// Import the pasted preset code or archive path (taken names are imported as <name>_imported)
+    fn confirm_import(&mut self) {
+        let Some(PresetAction::Import(source)) = self.preset_action.take() else { return; };
+        // Ask what to do when names are taken; a bundle without conflicts imports straight away
+        match read_bundle(&source).and_then(|entries| preset_bundle::conflicts(&get_presets_dir(), &entries)) {
+            Ok(taken) if !taken.is_empty() => self.preset_action = Some(PresetAction::ImportConflict { source, taken }),
+            Ok(_) => self.finish_import(&source, preset_bundle::OnConflict::Rename),
+            Err(e) => self.status_msg = Some((format!("⚠️  {}", e), std::time::Instant::now())),
+        }
+    }
+
+    /// Import with the chosen way of handling taken names
+    fn finish_import(&mut self, source: &str, on_conflict: preset_bundle::OnConflict) {
+        self.preset_action = None;
+        match import_presets(source, on_conflict) {
+            Ok(report) => {
+                self.status_msg = Some((report.summary(), std::time::Instant::now()));
+                let select = report.imported.first().cloned().unwrap_or_default();
+                self.refresh_presets_selecting(&select);
+            }
+            Err(e) => self.status_msg = Some((format!("⚠️  {}", e), std::time::Instant::now())),
+        }
+    }
+
+    /// Pasted text goes to whatever is being typed; in the preset list it starts an import
+    fn paste(&mut self, text: &str) {
+        match (&self.mode, self.preset_action.as_mut()) {
+            (AppMode::PresetSelect, Some(PresetAction::Import(source))) => source.push_str(text),
+            (AppMode::PresetSelect, Some(PresetAction::Rename(name))) => name.push_str(text.trim()),
+            (AppMode::PresetSelect, None) if self.preset_searching => {
+                self.preset_filter.query.push_str(text.trim());
+                self.rebuild_preset_rows();
+            }
+            (AppMode::PresetSelect, None) => self.preset_action = Some(PresetAction::Import(text.to_string())),
+            (AppMode::PresetEditor, _) => {
+                if let Some(editor) = self.preset_editor.as_mut() {
+                    text.trim().chars().for_each(|c| editor.push_char(c));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Bring back the preset last deleted or reverted in this session
+    fn undo_preset_trash(&mut self) {
+        let Some(trashed) = self.last_trashed.take() else {
+            self.status_msg = Some(("Nothing to undo".to_string(), std::time::Instant::now()));
+            return;
+        };
+        match preset_files::restore_preset(&get_presets_dir(), &trashed) {
+            Ok(()) => {
+                self.status_msg = Some((format!("Restored {}", trashed.name), std::time::Instant::now()));
+                self.refresh_presets_selecting(&trashed.name);
+            }
+            Err(e) => self.status_msg = Some((format!("⚠️  {}", e), std::time::Instant::now())),
+        }
+    }
     
     fn load_selected_preset(&mut self) {
-        if let Some(i) = self.preset_state.selected() {
+        if self.toggle_selected_category() {
+            return;
+        }
+        if let Some(i) = self.selected_preset_index() {
             if i < self.preset_list.len() {
-                let preset_info = &self.preset_list[i];
-                let filename = &preset_info.filename;
-
-                // Use hybrid loading (user dir first, then embedded fallback)
-                if let Some(json) = load_preset_hybrid(filename) {
-                    if let Ok(mut loaded) = serde_json::from_str::<AudioParams>(&json) {
+                let filename = self.preset_list[i].filename.clone();
+
+                // Use hybrid loading (user dir first, then embedded fallback); presets with lint errors are refused
+                let checked = load_preset_hybrid(&filename)
+                    .ok_or_else(|| "not found".to_string())
+                    .and_then(|json| preset_lint::lint_preset(&json).into_params());
+                match checked {
+                    Ok(mut loaded) => {
                         // Safety
                         loaded.rf_enabled = false;
 
@@ -653,63 +1183,549 @@ impl App {
                         loaded.stream_enabled = stream_enabled;
                         loaded.stream_port = stream_port;
                         loaded.preset_version = current_version.wrapping_add(1);
+                        loaded.preset_name = Some(filename.clone());
+                        loaded.resolve_seed(self.seed_override);
 
                         *params = loaded;
                         drop(params);
 
                         // Store preset name
                         self.current_preset = Some(filename.clone());
+                        self.note_stimulus(StimulusEvent::PresetLoaded { preset: filename.clone() });
 
                         self.status_msg = Some((format!("Loaded {}", filename), std::time::Instant::now()));
                         self.exit_preset_mode();
-                        return;
+                        self.begin_session();
+                    }
+                    Err(e) => {
+                        self.status_msg = Some((format!("Failed to load {}: {}", filename, e), std::time::Instant::now()));
                     }
                 }
-                self.status_msg = Some(("Failed to load preset".to_string(), std::time::Instant::now()));
             }
         }
     }
 
+    /// Open the preset editor with the current settings (saving is done from there)
+    fn open_preset_editor(&mut self) {
+        if self.blind.is_some() {
+            self.status_msg = Some(("Saving is locked during a blinded session".to_string(), std::time::Instant::now()));
+            return;
+        }
+        let params = self.params.lock();
+        self.preset_editor = Some(PresetEditor::new(&params, self.current_preset.as_deref()));
+        drop(params);
+        self.mode = AppMode::PresetEditor;
+    }
+
+    fn close_preset_editor(&mut self) {
+        self.preset_editor = None;
+        self.mode = AppMode::Mixer;
+    }
+
+    /// Save the edited preset (the first Enter on an existing file only asks to confirm)
     fn save_preset(&mut self) {
+        let Some(editor) = self.preset_editor.as_mut() else { return; };
+        let snapshot = self.params.lock().clone();
+        match editor.save(&get_presets_dir(), &snapshot) {
+            Ok(Some(path)) => {
+                // The playing preset now is the saved one, with its new title and flags
+                let filename = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let mut params = self.params.lock();
+                editor.apply(&mut params);
+                params.preset_name = Some(filename.clone());
+                drop(params);
+                self.current_preset = Some(filename);
+                self.status_msg = Some((format!("Saved to {}", path.display()), std::time::Instant::now()));
+                self.close_preset_editor();
+            }
+            Ok(None) => {
+                let name = editor.file_name().unwrap_or_default();
+                self.status_msg = Some((format!("⚠️  {} exists - press Enter again to overwrite it", name), std::time::Instant::now()));
+            }
+            Err(e) => {
+                self.status_msg = Some((format!("⚠️  {}", e), std::time::Instant::now()));
+            }
+        }
+    }
+
+    // --- Session Records ---
+
+    /// Start a new session record (the audio thread resets its session timer on preset change)
+    fn begin_session(&mut self) {
+        self.close_manifest();
+        self.session_started_at = session::unix_now();
+        self.session_report_prompted = false;
+        self.hrv_log = HrvLog::default();
+        self.write_manifest();
+    }
+
+    /// Write the manifest of the session that just started (nothing when the audio is off)
+    /// Protocol runs keep theirs in the run folder, named after the step
+    fn write_manifest(&mut self) {
         let params = self.params.lock();
-        let json = match serde_json::to_string_pretty(&*params) {
-            Ok(j) => j,
+        if !params.playing {
+            self.manifest = None;
+            return;
+        }
+        let mut manifest = SessionManifest::new(&params, self.sample_rate);
+        drop(params);
+        manifest.blind = self.blind.clone();
+
+        let path = match self.protocol.as_ref() {
+            Some(run) => run.dir.join(format!("manifest_step{}.json", run.current + 1)),
+            None => get_sessions_dir().join(format!("manifest_{}.json", self.session_started_at)),
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        match std::fs::create_dir_all(dir).and_then(|_| manifest.save(&path)) {
+            Ok(()) => self.manifest = Some((path, manifest)),
             Err(e) => {
-                self.status_msg = Some((format!("Error serializing: {}", e), std::time::Instant::now()));
-                return;
+                self.manifest = None;
+                self.status_msg = Some((format!("⚠️  Error writing session manifest: {}", e), std::time::Instant::now()));
+            }
+        }
+    }
+
+    /// Keep the manifest's session length current and notice changes made during the session
+    fn update_manifest(&mut self) {
+        let Some((_, manifest)) = self.manifest.as_mut() else { return; };
+        let params = self.params.lock();
+        manifest.duration_secs = Some(params.session_timer);
+        manifest.note_changes(&params);
+    }
+
+    /// Save the manifest with the session's length when the session ends; returns its path
+    fn close_manifest(&mut self) -> Option<PathBuf> {
+        let (path, manifest) = self.manifest.take()?;
+        if let Err(e) = manifest.save(&path) {
+            self.status_msg = Some((format!("⚠️  Error updating session manifest: {}", e), std::time::Instant::now()));
+        }
+        Some(path)
+    }
+
+    /// End the current session and open the report prompt
+    /// `completed` is true when the session ran its full length, false when stopped by the user
+    fn end_session(&mut self, completed: bool) {
+        let mut params = self.params.lock();
+        if !completed {
+            params.playing = false;
+        }
+        let mut questions = params.report_questions.clone().unwrap_or_default();
+        drop(params);
+        questions.extend(self.experiment_questions.iter()
+            .filter(|q| !questions.iter().any(|p| p.prompt == q.prompt)).cloned().collect::<Vec<_>>());
+
+        self.session_report_prompted = true;
+        self.report_form = Some(ReportForm::new(&questions, completed));
+        self.mode = AppMode::Report;
+    }
+
+    /// Set (or clear) the wake-up alarm; it fires at the next occurrence of the local time
+    fn schedule_wake(&mut self, wake_at: Option<u32>) {
+        self.wake_at = wake_at;
+        if let Some(time) = wake_at {
+            // Wall-clock deadline so the alarm survives suspend, unlike a monotonic timer
+            let now_unix = session::unix_now();
+            let until_min = minutes_until(local_minutes_of_day(), time) as u64;
+            self.wake_deadline_unix = now_unix - now_unix % 60 + until_min * 60;
+            self.status_msg = Some((format!("Wake alarm set for {} (in {})",
+                format_clock_time(time), format_duration_min(until_min as f32)), std::time::Instant::now()));
+        } else {
+            self.status_msg = Some(("Wake alarm off".to_string(), std::time::Instant::now()));
+        }
+    }
+
+    /// Start the wake-up program once the alarm time is reached
+    fn check_wake_alarm(&mut self) {
+        if self.wake_at.is_none() || session::unix_now() < self.wake_deadline_unix {
+            return;
+        }
+        self.wake_at = None;
+
+        // Restart the session so the climb starts from silence now
+        self.params.lock().start_wake_program();
+        self.begin_session();
+        self.status_msg = Some(("⏰ Good morning - wake-up program started".to_string(), std::time::Instant::now()));
+    }
+
+    /// End the wake-up program once the climb and the beta hold are over: the alarm stops and
+    /// the session is reported like any other that ran its full length
+    fn check_wake_program(&mut self) {
+        let mut params = self.params.lock();
+        if !params.wake_program_finished() {
+            return;
+        }
+
+        params.coherence.wake.active = false;
+        params.playing = false;
+        drop(params);
+
+        self.status_msg = Some(("Wake-up program finished - playback stopped".to_string(), std::time::Instant::now()));
+        if !self.session_report_prompted && matches!(self.mode, AppMode::Mixer) {
+            self.end_session(true);
+        }
+    }
+
+    /// Stop playback, RF and streaming when a sleep session has run its course
+    fn check_sleep_timer(&mut self) {
+        let mut params = self.params.lock();
+        if !params.coherence.sleep.is_over(self.session_started_at, session::unix_now()) {
+            return;
+        }
+
+        params.playing = false;
+        params.rf_enabled = false;
+        params.stream_enabled = false;
+        params.coherence.sleep.duration_min = 0.0;
+        drop(params);
+
+        self.status_msg = Some(("Sleep session ended - playback, RF and streaming stopped".to_string(), std::time::Instant::now()));
+        if !self.session_report_prompted && matches!(self.mode, AppMode::Mixer) {
+            self.end_session(true);
+        }
+    }
+
+    /// Prompt for a report once the session timer passes the configured session length
+    fn check_session_complete(&mut self) {
+        if self.session_report_prompted || !matches!(self.mode, AppMode::Mixer) {
+            return;
+        }
+        let params = self.params.lock();
+        let is_session = params.coherence.enabled && !matches!(params.coherence.being_type, BeingType::Unknown);
+        let finished = params.session_current() && params.session_timer / 60.0 >= params.coherence.total_session_min();
+        drop(params);
+
+        if is_session && finished {
+            self.end_session(true);
+        }
+    }
+
+    /// Feed new EEG samples into the band-power analyzer and let the closed loop react
+    fn update_eeg_analysis(&mut self) {
+        let (Some(eeg), Some(analyzer)) = (self.eeg.as_ref(), self.eeg_analyzer.as_mut()) else {
+            return;
+        };
+        if !analyzer.update(eeg) {
+            return;
+        }
+        let (Some(controller), Some(snapshot)) = (self.closed_loop.as_mut(), analyzer.latest()) else {
+            return;
+        };
+
+        let mut params = self.params.lock();
+        // Sleep and wake-up programs own the beat while they run
+        if params.coherence.sleep.is_active() || params.coherence.wake.active {
+            return;
+        }
+        let session_secs = params.session_timer;
+        controller.step(snapshot, &mut params.coherence, session_secs);
+    }
+
+    /// Start or stop closed-loop control with the preset's settings (or the defaults)
+    fn toggle_closed_loop(&mut self) {
+        if let Some(controller) = self.closed_loop.take() {
+            let log = controller.log_path().map(|p| p.display().to_string()).unwrap_or_default();
+            self.status_msg = Some((format!("Closed loop off after {} adjustments - log: {}", controller.adjustments(), log),
+                std::time::Instant::now()));
+            return;
+        }
+        if self.eeg.is_none() {
+            self.status_msg = Some(("Closed loop needs an EEG source (start with --openbci <port>)".to_string(), std::time::Instant::now()));
+            return;
+        }
+
+        let mut params = self.params.lock();
+        let config: ClosedLoopConfig = params.closed_loop.clone().unwrap_or_default();
+        eeg::controller::prepare_coherence(&mut params.coherence, &config);
+        drop(params);
+
+        let log_path = get_sessions_dir().join(format!("closed_loop_{}.jsonl", session::unix_now()));
+        match ClosedLoopController::new(config, Some(log_path)) {
+            Ok(controller) => {
+                self.status_msg = Some((format!("Closed loop on: {}", controller.config().law.name()), std::time::Instant::now()));
+                self.closed_loop = Some(controller);
             }
+            Err(e) => {
+                self.status_msg = Some((format!("Could not start closed-loop log: {}", e), std::time::Instant::now()));
+            }
+        }
+    }
+
+    /// Analyse newly arrived beats, let the breath pacer move and add HRV to the session log
+    fn update_heart(&mut self) {
+        let Some(heart) = self.heart.as_ref() else {
+            return;
         };
+        let mut params = self.params.lock();
+        // Loading a preset replaces the params, so the paced rate is applied every tick
+        if let Some(pacer) = self.breath_pacer.as_ref() {
+            params.breath_hz = pacer.breath_hz();
+        }
 
-        // Create presets directory if it doesn't exist
-        let presets_dir = get_presets_dir();
-        if let Err(e) = std::fs::create_dir_all(&presets_dir) {
-            self.status_msg = Some((format!("Error creating presets directory: {}", e), std::time::Instant::now()));
+        let seen = heart.total_beats() + heart.rejected_beats();
+        if seen == self.hrv_beats_seen {
+            return;
+        }
+        self.hrv_beats_seen = seen;
+        let beats = heart.beats();
+        self.hrv = hrv::analyze(&beats);
+        if let Some(pacer) = self.breath_pacer.as_mut() {
+            pacer.update(&beats);
+            params.breath_hz = pacer.breath_hz();
+        }
+        if let Some(metrics) = self.hrv.as_ref() {
+            self.hrv_log.record(params.session_timer, metrics, self.breath_pacer.as_ref().map(|p| p.breath_hz()));
+        }
+    }
+
+    /// Start or stop pacing the breath layer toward the breathing rate with the largest HRV
+    fn toggle_breath_pacing(&mut self) {
+        let mut params = self.params.lock();
+        if let Some(pacer) = self.breath_pacer.take() {
+            params.breath_hz = BREATH_LFO_HZ;
+            let best = pacer.best()
+                .map(|t| format!(" - best {:.1} breaths/min ({:.1} bpm swing)", t.breath_hz * 60.0, t.amplitude_bpm))
+                .unwrap_or_default();
+            self.status_msg = Some((format!("Breath pacing off{}", best), std::time::Instant::now()));
+            return;
+        }
+        if self.heart.is_none() {
+            self.status_msg = Some(("Breath pacing needs a heart sensor (start with --heart-serial or --heart-udp)".to_string(),
+                std::time::Instant::now()));
             return;
         }
 
-        // Create timestamped filename (never overwrites DEFAULT_ files)
-        use std::time::{SystemTime, UNIX_EPOCH};
-        let start = SystemTime::now();
-        let since_the_epoch = start.duration_since(UNIX_EPOCH).expect("Time went backwards");
-        let timestamp = since_the_epoch.as_secs();
+        let pacer = BreathPacer::default();
+        params.breath_hz = pacer.breath_hz();
+        // The breath layer is the pacer, so make sure it can be heard
+        if params.breath_vol == 0.0 && !params.lock_signal_layer {
+            Self::toggle_volume(&mut params.breath_vol);
+        }
+        self.status_msg = Some((format!("Breath pacing on: {:.1} breaths/min, searching for your resonance rate",
+            pacer.breath_hz() * 60.0), std::time::Instant::now()));
+        self.breath_pacer = Some(pacer);
+    }
 
-        let filename = format!("custom_{}.json", timestamp);
-        let path = presets_dir.join(filename);
+    /// Detect stimulus changes and write newly arrived EEG to the recording
+    fn update_stimulus(&mut self) {
+        let events = self.stimulus.update(&self.params.lock());
+        for event in events {
+            self.note_stimulus(event);
+        }
 
-        match File::create(&path) {
-            Ok(mut file) => {
-                if let Err(e) = file.write_all(json.as_bytes()) {
-                     self.status_msg = Some((format!("Error writing file: {}", e), std::time::Instant::now()));
-                } else {
-                     self.status_msg = Some((format!("Saved to {}", path.display()), std::time::Instant::now()));
+        let (Some(recorder), Some(eeg)) = (self.eeg_recorder.as_mut(), self.eeg.as_ref()) else {
+            return;
+        };
+        if let Err(e) = recorder.update(eeg) {
+            self.eeg_recorder = None;
+            self.status_msg = Some((format!("⚠️  EEG recording stopped: {}", e), std::time::Instant::now()));
+        }
+    }
+
+    /// Annotate a stimulus event into the EEG recording (on the EEG sample clock)
+    fn note_stimulus(&mut self, event: StimulusEvent) {
+        if let (Some(recorder), Some(eeg)) = (self.eeg_recorder.as_mut(), self.eeg.as_ref()) {
+            recorder.annotate(eeg, &event.label());
+        }
+    }
+
+    /// Start or stop recording the EEG to EDF+/BDF+ in the sessions directory
+    fn toggle_eeg_recording(&mut self) {
+        if let Some(recorder) = self.eeg_recorder.take() {
+            let (secs, events) = (recorder.duration_secs(), recorder.annotation_count());
+            self.status_msg = Some((match recorder.finish() {
+                Ok(path) => format!("EEG recording saved to {} ({:.0}s, {} events)", path.display(), secs, events),
+                Err(e) => format!("⚠️  Error finishing EEG recording: {}", e),
+            }, std::time::Instant::now()));
+            return;
+        }
+        let Some(eeg) = self.eeg.clone() else {
+            self.status_msg = Some(("Recording needs an EEG source (start with --openbci <port>)".to_string(), std::time::Instant::now()));
+            return;
+        };
+
+        let path = get_sessions_dir().join(format!("eeg_{}.{}", session::unix_now(), self.record_format.extension()));
+        self.start_eeg_recording(&eeg, &path);
+    }
+
+    /// Start recording the EEG to `path`, annotating the state the recording starts in
+    fn start_eeg_recording(&mut self, eeg: &EegStream, path: &Path) {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        match std::fs::create_dir_all(dir).and_then(|_| EegRecorder::start(eeg, path, self.record_format)) {
+            Ok(recorder) => {
+                self.status_msg = Some((format!("Recording EEG ({}) to {}", self.record_format.name(), path.display()),
+                    std::time::Instant::now()));
+                self.eeg_recorder = Some(recorder);
+                self.stimulus = StimulusTracker::default();
+                if let Some(preset) = self.current_preset.clone() {
+                    self.note_stimulus(StimulusEvent::PresetLoaded { preset });
                 }
             }
             Err(e) => {
-                self.status_msg = Some((format!("Error creating file: {}", e), std::time::Instant::now()));
+                self.status_msg = Some((format!("Could not start EEG recording: {}", e), std::time::Instant::now()));
             }
         }
     }
 
+    // --- Experiment Protocols ---
+
+    /// Run a protocol from its first step; a connected EEG is recorded into the run folder
+    fn start_protocol(&mut self, mut run: ProtocolRun) {
+        if let Some(eeg) = self.eeg.clone() {
+            if let Some(recorder) = self.eeg_recorder.take() {
+                let _ = recorder.finish();
+            }
+            let path = run.dir.join(format!("eeg.{}", self.record_format.extension()));
+            self.start_eeg_recording(&eeg, &path);
+            run.log.eeg_recording = self.eeg_recorder.as_ref().map(|r| r.path().display().to_string());
+        }
+        self.protocol = Some(run);
+        self.mode = AppMode::Protocol;
+        self.enter_protocol_step();
+    }
+
+    /// Set up the current step: the preset for preset steps, audio off otherwise
+    fn enter_protocol_step(&mut self) {
+        let Some(run) = self.protocol.as_ref() else { return; };
+        let Some(step) = run.current_step() else { return; };
+        let number = run.current + 1;
+        let (label, kind, loaded) = (step.label.clone(), step.step.clone(), step.params.clone());
+
+        self.note_stimulus(StimulusEvent::ProtocolStep { step: number, label });
+        match (kind, loaded) {
+            (ProtocolStep::Preset { preset, .. }, Some(mut loaded)) => {
+                // Preserve streaming settings, as when loading a preset by hand
+                let mut params = self.params.lock();
+                loaded.stream_enabled = params.stream_enabled;
+                loaded.stream_port = params.stream_port;
+                loaded.preset_version = params.preset_version.wrapping_add(1);
+                loaded.preset_name = Some(preset.clone());
+                loaded.resolve_seed(self.seed_override);
+                *params = loaded;
+                drop(params);
+
+                self.current_preset = Some(preset.clone());
+                self.note_stimulus(StimulusEvent::PresetLoaded { preset });
+            }
+            (ProtocolStep::Questionnaire { questions, .. }, _) => {
+                self.params.lock().playing = false;
+                self.report_form = Some(ReportForm::new(&questions, true));
+            }
+            _ => self.params.lock().playing = false,
+        }
+        self.begin_session();
+    }
+
+    /// Move on once a timed step has run its length
+    fn update_protocol(&mut self) {
+        if self.protocol.as_ref().is_some_and(|run| run.step_due(std::time::Instant::now())) {
+            self.next_protocol_step(false);
+        }
+    }
+
+    /// Log the current step and start the next one
+    fn next_protocol_step(&mut self, skipped: bool) {
+        if self.log_protocol_step(skipped) {
+            self.enter_protocol_step();
+        } else {
+            self.end_protocol(RunStatus::Completed);
+        }
+    }
+
+    /// Log the current step (questionnaire answers, or HRV and manifest for timed steps) in run.json;
+    /// returns false when there are no more steps
+    fn log_protocol_step(&mut self, skipped: bool) -> bool {
+        if self.protocol.as_ref().is_none_or(|run| run.current_step().is_none()) {
+            return false;
+        }
+        let report = self.report_form.take().filter(|_| !skipped).map(|form| form.to_report());
+        let hrv = self.heart.as_ref().and_then(|heart| self.hrv_log.summary(heart.source_name(), self.breath_pacer.as_ref()));
+        let manifest = self.close_manifest().map(|path| path.display().to_string());
+        let Some(run) = self.protocol.as_mut() else { return false; };
+        let timed = run.current_step().is_some_and(|step| step.duration_secs.is_some());
+
+        let more = run.finish_step(std::time::Instant::now(), skipped, report, hrv.filter(|_| timed));
+        if let Some(result) = run.log.steps.last_mut() {
+            result.manifest = manifest;
+        }
+        if let Err(e) = run.save_log() {
+            self.status_msg = Some((format!("⚠️  Error saving run log: {}", e), std::time::Instant::now()));
+        }
+        more
+    }
+
+    /// Pause or resume the current timed step (and its preset)
+    fn toggle_protocol_pause(&mut self) {
+        let Some(run) = self.protocol.as_mut() else { return; };
+        let Some(step) = run.current_step() else { return; };
+        if step.duration_secs.is_none() {
+            return;
+        }
+        let is_preset = matches!(step.step, ProtocolStep::Preset { .. });
+        let paused = run.toggle_pause(std::time::Instant::now());
+        if is_preset {
+            self.params.lock().playing = !paused;
+        }
+        self.status_msg = Some((if paused { "Protocol paused" } else { "Protocol resumed" }.to_string(), std::time::Instant::now()));
+    }
+
+    /// Stop the protocol (logging an unfinished step as skipped), close the run folder and return to the mixer
+    fn end_protocol(&mut self, status: RunStatus) {
+        if status == RunStatus::Aborted {
+            self.log_protocol_step(true);
+        }
+        let Some(mut run) = self.protocol.take() else { return; };
+        self.report_form = None;
+        self.params.lock().playing = false;
+        self.mode = AppMode::Mixer;
+        // The run log has every step; the last step's timer mustn't open a session report later
+        self.session_report_prompted = true;
+
+        let mut warning = None;
+        if self.eeg_recorder.as_ref().is_some_and(|r| r.path().starts_with(&run.dir)) {
+            if let Some(Err(e)) = self.eeg_recorder.take().map(|r| r.finish()) {
+                warning = Some(format!("⚠️  Error finishing EEG recording: {}", e));
+            }
+        }
+        run.end(status);
+        if let Err(e) = run.save_log() {
+            warning = Some(format!("⚠️  Error saving run log: {}", e));
+        }
+        let outcome = if status == RunStatus::Completed { "complete" } else { "aborted" };
+        self.status_msg = Some((warning.unwrap_or_else(|| format!("Protocol {} - run saved to {}", outcome, run.dir.display())),
+            std::time::Instant::now()));
+    }
+
+    /// Save the session record, with the report unless the prompt was skipped
+    fn finish_report(&mut self, include_report: bool) {
+        let Some(form) = self.report_form.take() else {
+            self.mode = AppMode::Mixer;
+            return;
+        };
+
+        let manifest = self.close_manifest().map(|path| path.display().to_string());
+        let params = self.params.lock();
+        let record = SessionRecord {
+            preset: self.current_preset.clone(),
+            preset_title: params.preset_title.clone(),
+            started_at_unix: self.session_started_at,
+            ended_at_unix: session::unix_now(),
+            duration_secs: params.session_timer,
+            completed: form.completed,
+            report: if include_report { Some(form.to_report()) } else { None },
+            eeg_recording: self.eeg_recorder.as_ref().map(|r| r.path().display().to_string()),
+            hrv: self.heart.as_ref().and_then(|heart| self.hrv_log.summary(heart.source_name(), self.breath_pacer.as_ref())),
+            blind: self.blind.clone(),
+            manifest,
+        };
+        drop(params);
+
+        self.status_msg = match record.save() {
+            Ok(path) => Some((format!("Session saved to {}", path.display()), std::time::Instant::now())),
+            Err(e) => Some((format!("⚠️  Error saving session: {}", e), std::time::Instant::now())),
+        };
+        self.mode = AppMode::Mixer;
+    }
+
     fn toggle_collapse(&mut self) {
         if let Some(ui_idx) = self.state.selected() {
             // Map UI index to original channel index
@@ -747,6 +1763,16 @@ impl App {
                             let state = if self.streaming_collapsed { "collapsed" } else { "expanded" };
                             self.status_msg = Some((format!("Network Streaming {}", state), std::time::Instant::now()));
                         },
+                        "EEG INPUT" => {
+                            self.eeg_collapsed = !self.eeg_collapsed;
+                            let state = if self.eeg_collapsed { "collapsed" } else { "expanded" };
+                            self.status_msg = Some((format!("EEG Input {}", state), std::time::Instant::now()));
+                        },
+                        "HEART INPUT" => {
+                            self.heart_collapsed = !self.heart_collapsed;
+                            let state = if self.heart_collapsed { "collapsed" } else { "expanded" };
+                            self.status_msg = Some((format!("Heart Input {}", state), std::time::Instant::now()));
+                        },
                         _ => {}
                     }
                 }
@@ -755,24 +1781,15 @@ impl App {
     }
 }
 
-/// Initialize presets directory and copy embedded presets if needed
-fn initialize_presets() -> std::io::Result<()> {
+/// Initialize presets directory, install missing built-in presets and update untouched older copies
+fn initialize_presets() -> std::io::Result<preset_updates::DefaultsSync> {
     let presets_dir = get_presets_dir();
 
     // Create presets directory if it doesn't exist
     std::fs::create_dir_all(&presets_dir)?;
 
-    // Copy embedded presets to user directory if they don't exist
-    for preset in embedded_presets::EMBEDDED_PRESETS {
-        let preset_path = presets_dir.join(preset.filename);
-
-        // Only copy if the file doesn't already exist (user may have modified it)
-        if !preset_path.exists() {
-            std::fs::write(&preset_path, preset.content)?;
-        }
-    }
-
-    Ok(())
+    // Copies the user modified are left alone (they can be merged from the preset list)
+    preset_updates::sync_defaults(&presets_dir)
 }
 
 /// Load preset from user directory or embedded fallback
@@ -783,21 +1800,322 @@ fn load_preset_hybrid(filename: &str) -> Option<String> {
         if let Ok(content) = std::fs::read_to_string(&user_path) {
             return Some(content);
         }
-    }
-
-    // Fallback to embedded preset
-    for preset in embedded_presets::EMBEDDED_PRESETS {
-        if preset.filename == filename {
-            return Some(preset.content.to_string());
+    }
+
+    // Fallback to embedded preset
+    for preset in embedded_presets::EMBEDDED_PRESETS {
+        if preset.filename == filename {
+            return Some(preset.content.to_string());
+        }
+    }
+
+    None
+}
+
+/// `soulwhistle replay <FILE>`: run a recording through analysis offline and print a summary
+fn run_offline_replay(options: &cli::ReplayOptions) -> Result<(), Box<dyn Error>> {
+    let _ = initialize_presets();
+    let summary = headless::run_replay(options)?;
+
+    println!("Replayed {} ({:.1}s at {:.0} Hz, channels: {})", options.replay.path,
+        summary.duration_secs, summary.sample_rate, summary.channel_names.join(", "));
+    match summary.mean_bands {
+        Some(bands) => println!("Mean relative band power: delta {:.1}%  theta {:.1}%  alpha {:.1}%  beta {:.1}%  gamma {:.1}%",
+            bands.delta * 100.0, bands.theta * 100.0, bands.alpha * 100.0, bands.beta * 100.0, bands.gamma * 100.0),
+        None => println!("No artifact-free analysis windows"),
+    }
+    if let Some(log) = &summary.log_path {
+        println!("Closed loop: {} adjustments, final beat {:.2} Hz, log: {}", summary.adjustments, summary.final_beat_hz, log.display());
+    }
+    if let Some(out) = &options.out {
+        println!("Audio written to {}", out);
+    }
+    if let Some(manifest) = &summary.manifest_path {
+        println!("Manifest written to {}", manifest.display());
+    }
+    Ok(())
+}
+
+/// `blind new` writes a sealed study key; `blind reveal` prints the conditions for unblinding
+fn run_blind_command(command: cli::BlindCommand) -> Result<(), String> {
+    match command {
+        cli::BlindCommand::New { key_path, spec } => {
+            blind::load_active_preset(&spec.preset)?;
+            let key = StudyKey::generate(&spec)?;
+            key.save_sealed(Path::new(&key_path))
+                .map_err(|e| format!("Cannot write study key {}: {}", key_path, e))?;
+            println!("Study '{}' key written to {} (keep it away from participants and session staff)", key.study, key_path);
+            for participant in &key.participants {
+                let codes: Vec<&str> = participant.sessions.iter().map(|s| s.code.as_str()).collect();
+                println!("  {}: {}", participant.id, codes.join(", "));
+            }
+        }
+        cli::BlindCommand::Reveal { key_path } => {
+            let key = StudyKey::load(Path::new(&key_path))?;
+            println!("Study '{}' - preset {}, sham {:?}, seed {}", key.study, key.preset, key.sham, key.seed);
+            for participant in &key.participants {
+                for session in &participant.sessions {
+                    println!("  {}\t{:?}", session.code, session.condition);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `analyze`: print the section report of a recording and optionally save the analysis JSON
+fn run_analysis(options: &cli::AnalyzeOptions) -> Result<(), String> {
+    println!("Loading {}...", options.file);
+    let audio = wav::read_stereo_wav(&options.file)?;
+    println!("Analyzing {:.1} minutes at {} Hz...", audio.duration_secs() / 60.0, audio.sample_rate);
+    let results = analysis::analyze(&audio, &options.file, options.settings);
+
+    let rule = "=".repeat(80);
+    println!("\n{}\nFREQUENCY ANALYSIS REPORT\n{}", rule, rule);
+    println!("File: {}", results.file);
+    println!("Duration: {:.1} seconds ({:.1} minutes)", results.duration, results.duration / 60.0);
+    println!("Sample Rate: {} Hz\n{}", results.sample_rate, rule);
+    for section in &results.sections {
+        println!("\n{} SECTION\n{}", section.section_type.to_uppercase(), "-".repeat(80));
+        println!("Time Range: {:.1}s - {:.1}s", section.start_time, section.end_time);
+        println!("Duration: {:.1}s", section.end_time - section.start_time);
+        println!("\nAverage Frequencies:");
+        println!("  Left Channel:    {:8.2} Hz", section.average.left_freq);
+        println!("  Right Channel:   {:8.2} Hz", section.average.right_freq);
+        println!("  Binaural Beat:   {:8.2} Hz", section.average.binaural_beat);
+        if !section.chunks.is_empty() {
+            println!("\nTemporal Analysis ({} chunks):", section.chunks.len());
+            println!("  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}", "Time", "Left Hz", "Right Hz", "Beat Hz", "Type");
+            println!("  {}", "-".repeat(52));
+            for chunk in section.chunks.iter().take(10) {
+                println!("  {:8.1}  {:8.2}  {:8.2}  {:8.2}  {:>8}",
+                    chunk.time, chunk.left_freq, chunk.right_freq, chunk.binaural_beat, chunk.wave_type);
+            }
+            if section.chunks.len() > 10 {
+                println!("  ... ({} more chunks)", section.chunks.len() - 10);
+            }
+        }
+    }
+
+    if let Some(output) = &options.output {
+        let json = serde_json::to_string_pretty(&results).map_err(|e| e.to_string())?;
+        std::fs::write(output, json).map_err(|e| format!("Cannot write {}: {}", output, e))?;
+        println!("\nResults saved to: {}", output);
+    }
+    Ok(())
+}
+
+/// `from-analysis`: write a preset made from an analysis JSON; returns where it was written
+fn run_from_analysis(analysis_path: &str, out: Option<&str>) -> Result<PathBuf, String> {
+    let path = Path::new(analysis_path);
+    let results = analysis::Analysis::load(path)?;
+    let params = analysis::preset_from_analysis(&results)?;
+
+    let out = match out {
+        Some(out) => PathBuf::from(out),
+        None => {
+            let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let name = stem.strip_suffix("_analysis").unwrap_or(&stem);
+            let dir = get_presets_dir();
+            std::fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+            dir.join(format!("{}.json", name))
+        }
+    };
+    if out.exists() {
+        return Err(format!("{} already exists (choose another path with --out)", out.display()));
+    }
+    let json = serde_json::to_string_pretty(&params).map_err(|e| e.to_string())?;
+    std::fs::write(&out, json).map_err(|e| format!("Cannot write {}: {}", out.display(), e))?;
+
+    let c = &params.coherence;
+    println!("{}", params.preset_title.as_deref().unwrap_or_default());
+    println!("  Carriers: {:.2} / {:.2} Hz (beat {:.2} Hz)", c.left_carrier, c.right_carrier, c.binaural_beat_hz());
+    println!("  Phases:   startup {:.1}, induction {:.1}, stabilization {:.1}, return {:.1} min",
+        c.startup_duration_min, c.induction_duration_min, c.stabilization_duration_min, c.return_duration_min);
+    println!("  Harmonics: 220 Hz {}, 495 Hz {}", if c.harmonic_220hz { "on" } else { "off" }, if c.harmonic_495hz { "on" } else { "off" });
+    Ok(out)
+}
+
+/// `lint`: print every problem in the given preset files; returns false if any has one
+fn run_lint(files: &[String]) -> bool {
+    let mut clean = true;
+    for file in files {
+        let linted = preset_lint::lint_preset_file(Path::new(file));
+        if linted.diagnostics.is_empty() {
+            println!("{}: ok", file);
+        }
+        for diagnostic in &linted.diagnostics {
+            println!("{}: {}", file, diagnostic);
+            clean = false;
+        }
+    }
+    clean
+}
+
+/// `export`: write the presets to an archive, or print them as a text code (and QR code)
+fn run_export(presets: &[String], out: Option<&str>, qr: bool) -> Result<(), String> {
+    let entries = preset_bundle::collect(&get_presets_dir(), presets)?;
+    let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+    match out {
+        Some(out) => {
+            preset_bundle::write_archive(&entries, Path::new(out))?;
+            println!("Exported {} to {}", names.join(", "), out);
+        }
+        None => {
+            let code = preset_bundle::encode_code(&entries)?;
+            if qr {
+                println!("{}", preset_bundle::qr_code(&code)?);
+            }
+            eprintln!("Preset code for {} (import it with `soulwhistle import <CODE>` or i in the preset list):", names.join(", "));
+            println!("{}", code);
+        }
+    }
+    Ok(())
+}
+
+/// The presets in an archive file or a text code
+fn read_bundle(source: &str) -> Result<Vec<preset_bundle::BundleEntry>, String> {
+    let source = source.trim();
+    if Path::new(source).is_file() {
+        preset_bundle::read_archive(Path::new(source))
+    } else if source.starts_with(PRESET_CODE_PREFIX) {
+        preset_bundle::decode_code(source)
+    } else {
+        Err(format!("'{}' is neither an archive file nor a preset code (codes start with {})", source, PRESET_CODE_PREFIX))
+    }
+}
+
+/// Import an archive file or a text code into the presets directory
+fn import_presets(source: &str, on_conflict: preset_bundle::OnConflict) -> Result<preset_bundle::ImportReport, String> {
+    preset_bundle::import(&get_presets_dir(), read_bundle(source)?, on_conflict)
+}
+
+/// `verify`: check presets against their declared parameters; returns false if any fails
+fn run_verify(presets: &[String]) -> bool {
+    let loaded: Vec<(String, Result<AudioParams, String>)> = if presets.is_empty() {
+        embedded_presets::EMBEDDED_PRESETS.iter()
+            .map(|p| (p.filename.to_string(), preset_lint::lint_preset_in(p.content, None).into_params().map_err(|e| format!("Invalid preset: {}", e))))
+            .collect()
+    } else {
+        presets.iter().map(|name| (name.clone(), headless::load_preset_params(Some(name)))).collect()
+    };
+
+    println!("Verifying {} presets (rendered at {} Hz)...", loaded.len(), VERIFY_SAMPLE_RATE_HZ);
+    let mut failed = 0;
+    for (name, params) in loaded {
+        let report = match params {
+            Ok(params) => verify::verify_preset(&name, &params),
+            Err(e) => {
+                println!("\nFAIL  {}\n      {}", name, e);
+                failed += 1;
+                continue;
+            }
+        };
+        let status = if report.checks.is_empty() { "-" } else if report.passed() { "PASS" } else { "FAIL" };
+        println!("\n{:<5} {}", status, report.preset);
+        if report.checks.is_empty() {
+            println!("      nothing to check (no binaural layer or modulated signal carrier)");
+        }
+        for check in &report.checks {
+            println!("      {:<4} {:<16} expected {}\n{:28}measured {}",
+                if check.pass { "ok" } else { "FAIL" }, check.name, check.expected, "", check.measured);
+        }
+        if !report.passed() {
+            failed += 1;
+        }
+    }
+    println!("\n{} failed", failed);
+    failed == 0
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match cli::parse_args(&args) {
+        Ok(cli::Command::Run(options)) => *options,
+        Ok(cli::Command::Replay(options)) => {
+            return run_offline_replay(&options);
+        }
+        Ok(cli::Command::Blind(command)) => {
+            if let Err(e) = run_blind_command(command) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(cli::Command::Render(options)) => {
+            let _ = initialize_presets();
+            match headless::render_manifest(&options) {
+                Ok(secs) => println!("Rendered {:.1}s of {} to {}", secs, options.manifest, options.out),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Ok(cli::Command::Analyze(options)) => {
+            if let Err(e) = run_analysis(&options) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(cli::Command::FromAnalysis { analysis, out }) => {
+            match run_from_analysis(&analysis, out.as_deref()) {
+                Ok(path) => println!("Preset written to {}", path.display()),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Ok(cli::Command::Lint(files)) => {
+            if !run_lint(&files) {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(cli::Command::Export { presets, out, qr }) => {
+            let _ = initialize_presets();
+            if let Err(e) = run_export(&presets, out.as_deref(), qr) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(cli::Command::Import { source, on_conflict }) => {
+            let _ = initialize_presets();
+            match import_presets(&source, on_conflict) {
+                Ok(report) => println!("{}", report.summary()),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Ok(cli::Command::Verify(presets)) => {
+            let _ = initialize_presets();
+            if !run_verify(&presets) {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(cli::Command::Help) => {
+            print!("{}", cli::USAGE);
+            return Ok(());
         }
-    }
-
-    None
-}
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
 
-fn main() -> Result<(), Box<dyn Error>> {
     // Initialize presets directory and copy embedded presets on first run
-    let _ = initialize_presets();
+    let defaults_sync = initialize_presets();
+    // Bring older user presets up to the current schema (originals are kept in the backup folder)
+    let preset_upgrade = preset_schema::upgrade_preset_dir(&get_presets_dir());
 
     // 1. Audio Setup
     let host = cpal::default_host();
@@ -813,7 +2131,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         use std::io::Read;
         let mut json = String::new();
         if file.read_to_string(&mut json).is_ok() {
-             if let Ok(loaded) = serde_json::from_str::<AudioParams>(&json) {
+             if let Ok(loaded) = preset_lint::lint_preset(&json).into_params() {
                  initial_params = loaded;
                  initial_params.rf_enabled = false; // Safety
                  loaded_preset_name = Some(PRESET_FILENAME.to_string());
@@ -831,7 +2149,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     // If no preset.json, load default deep focus preset using hybrid loading
     if !preset_loaded {
         if let Some(json) = load_preset_hybrid(DEFAULT_PRESET_FILENAME) {
-            if let Ok(loaded) = serde_json::from_str::<AudioParams>(&json) {
+            if let Ok(loaded) = preset_lint::lint_preset(&json).into_params() {
                 initial_params = loaded;
                 initial_params.rf_enabled = false; // Safety
                 loaded_preset_name = Some(DEFAULT_PRESET_FILENAME.to_string());
@@ -839,6 +2157,75 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    initial_params.preset_name = loaded_preset_name.clone();
+
+    // A blinded session replaces the startup preset with the study's active or sham version
+    let mut blind_run = None;
+    if let (Some(key_path), Some(participant)) = (&options.blind_key, &options.participant) {
+        let prepared = StudyKey::load(Path::new(key_path))
+            .and_then(|key| blind::prepare_session(&key, participant, options.blind_session));
+        match prepared {
+            Ok((run, params)) => {
+                loaded_preset_name = Some(run.code.clone());
+                initial_params = params;
+                blind_run = Some(run);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // Sleep and wake-up modes from the command line (a wake time is resolved against the local clock now)
+    let defaults_msg = match defaults_sync {
+        Ok(sync) => sync.status_message(),
+        Err(e) => Some(format!("⚠️  Could not install built-in presets: {}", e)),
+    };
+    let mut startup_msg = [defaults_msg, preset_upgrade.status_message()].into_iter().flatten()
+        .reduce(|a, b| format!("{} | {}", a, b));
+    if let Some(target) = options.sleep {
+        let now = local_minutes_of_day();
+        let minutes = match target {
+            cli::SleepTarget::Minutes(m) => m,
+            cli::SleepTarget::WakeAt(wake) => minutes_until(now, wake) as f32,
+        }.min(SLEEP_TIMER_MAX_MIN);
+        if initial_params.coherence.enabled && !matches!(initial_params.coherence.being_type, BeingType::Unknown) {
+            initial_params.coherence.sleep.duration_min = minutes;
+            startup_msg = Some(format!("Sleep mode: {} until {}", format_duration_min(minutes),
+                format_clock_time(now + minutes.round() as u32)));
+        } else {
+            startup_msg = Some("⚠️  Sleep mode needs a binaural preset - ignoring --sleep".to_string());
+        }
+    }
+
+    initial_params.resolve_seed(options.seed);
+
+    // A protocol is checked (every preset loaded) before the terminal is taken over
+    let mut protocol_run = None;
+    if let Some(path) = &options.protocol {
+        let path = Path::new(path);
+        let started = protocol::load_protocol(path)
+            .and_then(|(protocol, steps)| ProtocolRun::start(path, &protocol, steps)
+                .map_err(|e| format!("Cannot create run folder: {}", e)));
+        match started {
+            Ok(run) => protocol_run = Some(run),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let experiment_questions = match options.questions.as_deref().map(|path| session::load_questions(Path::new(path))) {
+        Some(Ok(questions)) => questions,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+        None => Vec::new(),
+    };
+
     let params = Arc::new(Mutex::new(initial_params));
     let audio_params = params.clone();
     let rf_params = params.clone();
@@ -885,27 +2272,152 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     });
 
+    // Start the stimulus marker thread; it stops when the audio stream (the sender) is dropped
+    let mut marker_tx = None;
+    if options.markers.enabled() {
+        let (tx, rx) = std::sync::mpsc::channel::<StimulusMarker>();
+        match MarkerServer::new(&options.markers, rx) {
+            Ok(mut server) => {
+                if let Some(addr) = server.local_tcp_addr() {
+                    startup_msg.get_or_insert_with(|| format!("Stimulus markers on tcp://{}", addr));
+                }
+                std::thread::spawn(move || server.run());
+                marker_tx = Some(tx);
+            }
+            Err(e) => {
+                startup_msg = Some(format!("⚠️  Could not start marker output: {}", e));
+            }
+        }
+    }
+
     // Run audio in a separate thread (handled by cpal stream)
 
     let stream = match config.sample_format() {
-        cpal::SampleFormat::F32 => run::<f32>(&device, &config.into(), audio_params, stream_buffer_for_audio, sample_rate),
-        cpal::SampleFormat::I16 => run::<i16>(&device, &config.into(), audio_params, stream_buffer_for_audio, sample_rate),
-        cpal::SampleFormat::U16 => run::<u16>(&device, &config.into(), audio_params, stream_buffer_for_audio, sample_rate),
+        cpal::SampleFormat::F32 => run::<f32>(&device, &config.into(), audio_params, stream_buffer_for_audio, sample_rate, marker_tx),
+        cpal::SampleFormat::I16 => run::<i16>(&device, &config.into(), audio_params, stream_buffer_for_audio, sample_rate, marker_tx),
+        cpal::SampleFormat::U16 => run::<u16>(&device, &config.into(), audio_params, stream_buffer_for_audio, sample_rate, marker_tx),
         _ => panic!("Unsupported sample format"),
     }?;
 
     stream.play()?;
 
+    // Start EEG reader thread if a device was given
+    let eeg_stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let mut eeg_thread = None;
+    let mut eeg_stream = None;
+    if let Some(port_path) = options.openbci_port.as_deref() {
+        let board = options.board.unwrap_or(eeg::openbci::Board::Cyton);
+        match serial::open_serial(port_path, OPENBCI_BAUD) {
+            Ok(port) => {
+                let stream = Arc::new(EegStream::new(&format!("{} on {}", board.name(), port_path),
+                    board.sample_rate(), board.channel_count()));
+                let mut reader = eeg::openbci::OpenBciReader::new(port, board, stream.clone(), eeg_stop.clone())
+                    .with_ganglion_write_handle(options.ganglion_write_handle);
+                eeg_thread = Some(std::thread::spawn(move || reader.run()));
+                eeg_stream = Some(stream);
+            }
+            Err(e) => {
+                startup_msg = Some(format!("⚠️  Could not open OpenBCI port {}: {}", port_path, e));
+            }
+        }
+    } else if let Some(addr) = options.muse_addr.as_deref() {
+        let listener = std::net::UdpSocket::bind(addr).and_then(|socket| {
+            let stream = Arc::new(EegStream::new(&format!("Muse via OSC on udp {}", addr),
+                MUSE_SAMPLE_RATE_HZ, MUSE_CHANNELS));
+            eeg::muse::MuseReader::new(socket, stream.clone(), eeg_stop.clone()).map(|reader| (reader, stream))
+        });
+        match listener {
+            Ok((mut reader, stream)) => {
+                eeg_thread = Some(std::thread::spawn(move || reader.run()));
+                eeg_stream = Some(stream);
+            }
+            Err(e) => {
+                startup_msg = Some(format!("⚠️  Could not listen for Muse OSC on {}: {}", addr, e));
+            }
+        }
+    } else if let Some(settings) = options.replay.as_ref() {
+        match eeg::replay::load_recording(settings) {
+            Ok(recording) => {
+                let stream = Arc::new(EegStream::new(&format!("Replay of {}", settings.path),
+                    recording.sample_rate, recording.channel_count()));
+                let mut reader = eeg::replay::ReplayReader::new(recording, settings, stream.clone(), eeg_stop.clone());
+                eeg_thread = Some(std::thread::spawn(move || reader.run()));
+                eeg_stream = Some(stream);
+            }
+            Err(e) => {
+                startup_msg = Some(format!("⚠️  Could not load EEG recording: {}", e));
+            }
+        }
+    }
+
+    // Start the heart-rate reader thread if a sensor bridge was given
+    let heart_stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let mut heart_thread = None;
+    let mut heart_stream = None;
+    let heart_source = match (options.heart_serial.as_deref(), options.heart_udp.as_deref()) {
+        (Some(port_path), _) => Some((format!("Heart bridge on {}", port_path),
+            serial::open_serial(port_path, options.heart_baud.unwrap_or(HEART_DEFAULT_BAUD))
+                .map(|port| hrv::HeartSource::Serial(Box::new(port))))),
+        (None, Some(addr)) => Some((format!("Heart data on udp {}", addr),
+            std::net::UdpSocket::bind(addr).map(hrv::HeartSource::Udp))),
+        (None, None) => None,
+    };
+    if let Some((name, source)) = heart_source {
+        let stream = Arc::new(HeartStream::new(&name));
+        match source.and_then(|source| hrv::HeartReader::new(source, stream.clone(), heart_stop.clone())) {
+            Ok(mut reader) => {
+                heart_thread = Some(std::thread::spawn(move || reader.run()));
+                heart_stream = Some(stream);
+            }
+            Err(e) => {
+                startup_msg = Some(format!("⚠️  Could not open heart source ({}): {}", name, e));
+            }
+        }
+    }
+
     // 2. TUI Setup
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(params, stream_client_count_for_app);
+    let eeg_analyzer = eeg_stream.as_ref().map(|stream| {
+        BandPowerAnalyzer::new(stream.sample_rate(), stream.channel_count(),
+            options.mains_hz.unwrap_or(EEG_MAINS_DEFAULT_HZ))
+    });
+    let mut app = App::new(params, stream_client_count_for_app, eeg_stream);
+    app.eeg_analyzer = eeg_analyzer;
     app.current_preset = loaded_preset_name;
+    app.blind = blind_run;
+    if let Some(format) = options.record_eeg {
+        if app.eeg.is_some() {
+            app.record_format = format;
+            // A protocol records into its run folder instead
+            if protocol_run.is_none() {
+                app.toggle_eeg_recording();
+            }
+        }
+    }
+    app.heart_collapsed = heart_stream.is_none();
+    app.heart = heart_stream;
+    if options.breath_pacing && app.heart.is_some() {
+        app.toggle_breath_pacing();
+    }
+    if options.wake_at.is_some() {
+        app.schedule_wake(options.wake_at);
+    }
+    if let Some(msg) = startup_msg {
+        app.status_msg = Some((msg, std::time::Instant::now()));
+    }
     app.refresh_presets();
+    app.sample_rate = sample_rate;
+    app.seed_override = options.seed;
+    app.experiment_questions = experiment_questions;
+    match protocol_run {
+        Some(run) => app.start_protocol(run),
+        None => app.begin_session(),
+    }
     
     let res = run_app(&mut terminal, app, error_rx);
 
@@ -914,10 +2426,21 @@ fn main() -> Result<(), Box<dyn Error>> {
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
 
+    // Stop the EEG reader so the board is told to stop streaming
+    eeg_stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    if let Some(handle) = eeg_thread {
+        let _ = handle.join();
+    }
+    heart_stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    if let Some(handle) = heart_thread {
+        let _ = handle.join();
+    }
+
     if let Err(err) = res {
         println!("{:?}", err)
     }
@@ -931,11 +2454,12 @@ fn run<T>(
     params: Arc<Mutex<AudioParams>>,
     stream_buffer: Arc<AudioRingBuffer>,
     sample_rate: f32,
+    markers: Option<std::sync::mpsc::Sender<StimulusMarker>>,
 ) -> Result<cpal::Stream, anyhow::Error>
 where
     T: cpal::Sample + cpal::FromSample<f32> + cpal::SizedSample,
 {
-    let mut synth = Synthesizer::new(sample_rate);
+    let mut synth = Synthesizer::new(sample_rate, params.lock().session_seed);
     let channels = config.channels as usize;
 
     // Preset change tracking
@@ -946,6 +2470,11 @@ where
     // Cache last params to use when lock contention occurs (realtime audio best practice)
     let mut cached_params = AudioParams::default();
 
+    // Stimulus markers are stamped with frames rendered since the stream started
+    let mut frames_rendered: u64 = 0;
+    let mut marker_tracker = StimulusTracker::default();
+    synth.coherence.set_event_capture(markers.is_some());
+
     let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
 
     let stream = device.build_output_stream(
@@ -965,9 +2494,10 @@ where
             };
 
             // Detect preset change
+            let preset_changed = p.preset_version != last_preset_version || frames_rendered == 0;
             if p.preset_version != last_preset_version {
                 // Preset changed - reset synthesizer state and flush streaming buffer
-                synth.reset();
+                synth.reset(p.session_seed);
                 stream_buffer.flush();
 
                 // Start crossfade
@@ -975,6 +2505,22 @@ where
                 last_preset_version = p.preset_version;
             }
 
+            // Parameter changes take effect at the start of this buffer, so stamp them there
+            let buffer_start_frame = frames_rendered;
+            let buffer_start_session = synth.coherence.session_samples();
+            let marker_at = |event, frame, session_sample| StimulusMarker {
+                event, stream_frame: frame, session_sample, sample_rate,
+            };
+            if let Some(tx) = markers.as_ref() {
+                if preset_changed {
+                    let preset = p.preset_name.clone().or_else(|| p.preset_title.clone()).unwrap_or_else(|| "custom".to_string());
+                    let _ = tx.send(marker_at(StimulusEvent::PresetLoaded { preset }, buffer_start_frame, buffer_start_session));
+                }
+                for event in marker_tracker.param_events(&p, buffer_start_session as f32 / sample_rate) {
+                    let _ = tx.send(marker_at(event, buffer_start_frame, buffer_start_session));
+                }
+            }
+
             // Collect samples for batch streaming buffer write
             let mut stream_samples: Vec<(f32, f32)> = if p.stream_enabled {
                 Vec::with_capacity(data.len() / channels)
@@ -1015,11 +2561,22 @@ where
                 stream_buffer.push_samples_batch(&stream_samples);
             }
 
+            // Phase and gamma burst changes carry the exact session sample they happened at
+            frames_rendered += (data.len() / channels) as u64;
+            if let Some(tx) = markers.as_ref() {
+                for (session_sample, event) in synth.coherence.take_events() {
+                    let frame = buffer_start_frame + session_sample.saturating_sub(buffer_start_session);
+                    let _ = tx.send(marker_at(event, frame, session_sample));
+                }
+            }
+
             // Update session info using try_lock to avoid blocking audio
             let (session_timer, session_phase) = synth.coherence.get_session_info();
             if let Some(mut params_write) = params.try_lock() {
                 params_write.session_timer = session_timer;
                 params_write.session_phase = session_phase;
+                params_write.session_version = last_preset_version;
+                params_write.gamma_burst = synth.coherence.gamma_burst_active();
             }
             // If lock fails, skip update this callback - UI will get updated next time
         },
@@ -1039,19 +2596,40 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, error_rx: std::
             app.status_msg = Some((err_msg, std::time::Instant::now()));
         }
 
+        app.check_sleep_timer();
+        app.check_wake_alarm();
+        app.check_wake_program();
+        app.check_session_complete();
+        app.update_protocol();
+        app.update_eeg_analysis();
+        app.update_heart();
+        app.update_stimulus();
+        app.update_manifest();
+
         if event::poll(std::time::Duration::from_millis(EVENT_POLL_INTERVAL_MS))? {
-            if let Event::Key(key) = event::read()? {
+            let event = event::read()?;
+            if let Event::Paste(text) = &event {
+                app.paste(text);
+            }
+            if let Event::Key(key) = event {
                 if key.kind == KeyEventKind::Press {
+                    if !app.typing_text() {
+                        app.note_stimulus(StimulusEvent::Key { key: key_name(key.code) });
+                    }
                     match app.mode {
                         AppMode::Mixer => {
                             match key.code {
-                                KeyCode::Char('q') => return Ok(()),
-                                KeyCode::Char('s') => app.save_preset(),
+                                KeyCode::Char('q') => {
+                                    app.close_manifest();
+                                    return Ok(());
+                                },
+                                KeyCode::Char('s') => app.open_preset_editor(),
                                 KeyCode::Char('l') => app.enter_preset_mode(),
                                 KeyCode::Char('o') => app.cycle_modulation(),
                                 KeyCode::Char('m') => app.toggle_mute(),
                                 KeyCode::Char(' ') => app.toggle_playback(),
                                 KeyCode::Char('x') => app.toggle_collapse(),
+                                KeyCode::Char('e') => app.end_session(false),
                                 KeyCode::Down | KeyCode::Char('j') => app.next(),
                                 KeyCode::Up | KeyCode::Char('k') => app.previous(),
                                 KeyCode::Left | KeyCode::Char('h') => app.adjust_volume(-0.01),
@@ -1060,11 +2638,136 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, error_rx: std::
                             }
                         },
                         AppMode::PresetSelect => {
+                            match (app.preset_action.as_mut(), key.code) {
+                                // Typing a new name
+                                (Some(PresetAction::Rename(_)), KeyCode::Enter) => app.confirm_preset_action(),
+                                (Some(PresetAction::Rename(_)), KeyCode::Esc) => app.preset_action = None,
+                                (Some(PresetAction::Rename(name)), KeyCode::Backspace) => { name.pop(); },
+                                (Some(PresetAction::Rename(name)), KeyCode::Char(c)) => name.push(c),
+                                (Some(PresetAction::Rename(_)), _) => {}
+                                // Typing or pasting a code to import
+                                (Some(PresetAction::Import(_)), KeyCode::Enter) => app.confirm_import(),
+                                (Some(PresetAction::Import(_)), KeyCode::Esc) => app.preset_action = None,
+                                (Some(PresetAction::Import(source)), KeyCode::Backspace) => { source.pop(); },
+                                (Some(PresetAction::Import(source)), KeyCode::Char(c)) => source.push(c),
+                                (Some(PresetAction::Import(_)), _) => {}
+                                // Some names are taken: rename, skip or overwrite
+                                (Some(PresetAction::ImportConflict { source, .. }), KeyCode::Char(c @ ('r' | 's' | 'o'))) => {
+                                    let source = source.clone();
+                                    let on_conflict = match c {
+                                        'r' => preset_bundle::OnConflict::Rename,
+                                        's' => preset_bundle::OnConflict::Skip,
+                                        _ => preset_bundle::OnConflict::Overwrite,
+                                    };
+                                    app.finish_import(&source, on_conflict);
+                                }
+                                (Some(PresetAction::ImportConflict { .. }), KeyCode::Esc) => app.preset_action = None,
+                                (Some(PresetAction::ImportConflict { .. }), _) => {}
+                                // Delete/revert confirmation: Enter or y, anything else cancels
+                                (Some(_), KeyCode::Enter | KeyCode::Char('y')) => app.confirm_preset_action(),
+                                (Some(_), _) => app.preset_action = None,
+                                // Typing a search: the list narrows with every key
+                                (None, code) if app.preset_searching => {
+                                    match code {
+                                        KeyCode::Enter | KeyCode::Down | KeyCode::Up => app.preset_searching = false,
+                                        KeyCode::Esc => {
+                                            app.preset_searching = false;
+                                            app.preset_filter.query.clear();
+                                        },
+                                        KeyCode::Backspace => { app.preset_filter.query.pop(); },
+                                        KeyCode::Char(c) => app.preset_filter.query.push(c),
+                                        _ => {}
+                                    }
+                                    app.rebuild_preset_rows();
+                                },
+                                (None, KeyCode::Char('/')) => app.preset_searching = true,
+                                (None, KeyCode::Char('e')) => {
+                                    app.preset_filter.experimental = !app.preset_filter.experimental;
+                                    app.rebuild_preset_rows();
+                                },
+                                (None, KeyCode::Char('h')) => {
+                                    app.preset_filter.headphones = !app.preset_filter.headphones;
+                                    app.rebuild_preset_rows();
+                                },
+                                (None, KeyCode::Char('f')) => {
+                                    app.preset_filter.rf = !app.preset_filter.rf;
+                                    app.rebuild_preset_rows();
+                                },
+                                // Esc clears the search and filters first, then leaves the browser
+                                (None, KeyCode::Esc) if app.preset_filter.is_active() => {
+                                    app.preset_filter = PresetFilter::default();
+                                    app.rebuild_preset_rows();
+                                },
+                                (None, KeyCode::Esc | KeyCode::Char('q')) => app.exit_preset_mode(),
+                                (None, KeyCode::Enter) => app.load_selected_preset(),
+                                (None, KeyCode::Down | KeyCode::Char('j')) => app.next(),
+                                (None, KeyCode::Up | KeyCode::Char('k')) => app.previous(),
+                                (None, KeyCode::Char('r')) => {
+                                    let stem = app.selected_preset_filename().unwrap_or_default().trim_end_matches(".json").to_string();
+                                    app.start_preset_action(PresetAction::Rename(stem));
+                                },
+                                (None, KeyCode::Char('c')) => app.duplicate_selected_preset(),
+                                (None, KeyCode::Char('d')) => app.start_preset_action(PresetAction::Delete),
+                                (None, KeyCode::Char('v')) => app.start_preset_action(PresetAction::Revert),
+                                (None, KeyCode::Char('u')) => app.undo_preset_trash(),
+                                (None, KeyCode::Char('m')) => app.open_preset_merge(),
+                                (None, KeyCode::Char('x')) => app.share_selected_preset(),
+                                (None, KeyCode::Char('i')) => app.preset_action = Some(PresetAction::Import(String::new())),
+                                _ => {}
+                            }
+                        },
+                        AppMode::Report => {
+                            // Letters are typed into text answers, so navigation is arrows/Tab only
+                            match key.code {
+                                KeyCode::Enter => app.finish_report(true),
+                                KeyCode::Esc => app.finish_report(false),
+                                KeyCode::Down | KeyCode::Tab => app.next(),
+                                KeyCode::Up | KeyCode::BackTab => app.previous(),
+                                code => if let Some(form) = app.report_form.as_mut() { edit_report_form(form, code) },
+                            }
+                        },
+                        AppMode::Protocol if app.report_form.is_some() => {
+                            // Questionnaire step: Enter saves the answers, Esc skips it
+                            match key.code {
+                                KeyCode::Enter => app.next_protocol_step(false),
+                                KeyCode::Esc => app.next_protocol_step(true),
+                                KeyCode::Down | KeyCode::Tab => app.next(),
+                                KeyCode::Up | KeyCode::BackTab => app.previous(),
+                                code => if let Some(form) = app.report_form.as_mut() { edit_report_form(form, code) },
+                            }
+                        },
+                        AppMode::PresetEditor => {
+                            // Letters are typed into the text fields, so navigation is arrows/Tab only
                             match key.code {
-                                KeyCode::Esc | KeyCode::Char('q') => app.exit_preset_mode(),
-                                KeyCode::Enter => app.load_selected_preset(),
+                                KeyCode::Enter => app.save_preset(),
+                                KeyCode::Esc => app.close_preset_editor(),
+                                KeyCode::Down | KeyCode::Tab => app.next(),
+                                KeyCode::Up | KeyCode::BackTab => app.previous(),
+                                code => if let Some(editor) = app.preset_editor.as_mut() { edit_preset_editor(editor, code) },
+                            }
+                        },
+                        AppMode::PresetMerge => {
+                            match key.code {
+                                KeyCode::Enter => app.apply_preset_merge(),
+                                KeyCode::Esc | KeyCode::Char('q') => app.close_preset_merge(),
                                 KeyCode::Down | KeyCode::Char('j') => app.next(),
                                 KeyCode::Up | KeyCode::Char('k') => app.previous(),
+                                KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right => {
+                                    if let Some(merge) = app.preset_merge.as_mut() { merge.toggle(); }
+                                },
+                                KeyCode::Char('a') => if let Some(merge) = app.preset_merge.as_mut() { merge.toggle_all(); },
+                                _ => {}
+                            }
+                        },
+                        AppMode::Protocol => {
+                            match key.code {
+                                KeyCode::Char('q') => {
+                                    app.end_protocol(RunStatus::Aborted);
+                                    return Ok(());
+                                },
+                                KeyCode::Esc => app.end_protocol(RunStatus::Aborted),
+                                KeyCode::Char(' ') => app.toggle_protocol_pause(),
+                                KeyCode::Char('n') => app.next_protocol_step(true),
                                 _ => {}
                             }
                         }
@@ -1075,6 +2778,28 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, error_rx: std::
     }
 }
 
+/// Rate, toggle or type into the selected report answer
+fn edit_report_form(form: &mut ReportForm, code: KeyCode) {
+    match code {
+        KeyCode::Left => form.adjust(-1),
+        KeyCode::Right => form.adjust(1),
+        KeyCode::Backspace => form.pop_char(),
+        KeyCode::Char(c) if form.editing_text() => form.push_char(c),
+        KeyCode::Char(' ') => form.adjust(1),
+        _ => {}
+    }
+}
+
+/// Type into the selected editor field, or flip the selected flag
+fn edit_preset_editor(editor: &mut PresetEditor, code: KeyCode) {
+    match code {
+        KeyCode::Backspace => editor.pop_char(),
+        KeyCode::Char(c) if editor.editing_text() => editor.push_char(c),
+        KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right => editor.toggle(),
+        _ => {}
+    }
+}
+
 fn ui(f: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -1084,6 +2809,10 @@ fn ui(f: &mut Frame, app: &mut App) {
     match app.mode {
         AppMode::Mixer => draw_mixer(f, app, chunks[0]),
         AppMode::PresetSelect => draw_preset_list(f, app, chunks[0]),
+        AppMode::Report => draw_report(f, app, chunks[0]),
+        AppMode::Protocol => draw_protocol(f, app, chunks[0]),
+        AppMode::PresetEditor => draw_preset_editor(f, app, chunks[0]),
+        AppMode::PresetMerge => draw_preset_merge(f, app, chunks[0]),
     }
 
     // Build compact 2-line status display
@@ -1134,6 +2863,18 @@ fn ui(f: &mut Frame, app: &mut App) {
             },
         }
     };
+    let sleep_status = if params.coherence.wake.active {
+        " | ⏰ Waking".to_string()
+    } else if params.coherence.sleep.is_active() {
+        let remaining = params.coherence.sleep.remaining_min(app.session_started_at, session::unix_now());
+        format!(" | 🌙 Sleep: {} left", format_duration_min(remaining))
+    } else {
+        String::new()
+    };
+    let wake_status = match app.wake_at {
+        Some(time) => format!(" | ⏰ {}", format_clock_time(time)),
+        None => String::new(),
+    };
     let rf_status = if params.rf_enabled {
         format!("RF: ON {:.1}MHz", params.rf_freq_hz as f64 / 1_000_000.0)
     } else {
@@ -1141,23 +2882,30 @@ fn ui(f: &mut Frame, app: &mut App) {
     };
     
     let line1 = format!(
-        "{} {} | Master: {:.0}% | {} {} | {}",
+        "{} {} | Master: {:.0}% | {} {} | {}{}{}",
         playback_icon,
         if params.playing { "Playing" } else { "Paused" },
         params.master_vol * 100.0,
         being_icon,
         being_short,
-        rf_status
+        rf_status,
+        sleep_status,
+        wake_status
     );
     
     // Line 2: Keybindings | Status message
-    let mut line2 = "[m]ute [o]scillator [Space]pause [q]uit".to_string();
+    let keys = if matches!(app.mode, AppMode::Protocol) {
+        "[Space]pause [n]ext step [Esc]abort protocol [q]uit"
+    } else {
+        "[m]ute [o]scillator [Space]pause [e]nd session [q]uit"
+    };
+    let mut line2 = keys.to_string();
 
     if let Some((text, time)) = &app.status_msg {
         // Show warnings (⚠️) for longer
         let timeout = if text.starts_with("⚠️") { STATUS_WARNING_TIMEOUT_SECS } else { STATUS_TIMEOUT_SECS };
         if time.elapsed() < std::time::Duration::from_secs(timeout) {
-            line2 = format!("[m]ute [o]scillator [Space]pause [q]uit | STATUS: {}", text);
+            line2 = format!("{} | STATUS: {}", keys, text);
         }
     }
     
@@ -1176,43 +2924,106 @@ fn draw_preset_list(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
         .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
         .split(area);
 
-    let items: Vec<ListItem> = app.preset_list.iter()
-        .map(|preset_info| {
+    let items: Vec<ListItem> = app.preset_rows.iter()
+        .map(|row| {
+            let preset_info = match row {
+                PresetRow::Header { category, count, collapsed } => {
+                    let label = category.map_or(UNCATEGORIZED_LABEL, |c| c.label());
+                    let arrow = if *collapsed { "▶" } else { "▼" };
+                    return ListItem::new(Line::from(format!("{} {} ({})", arrow, label, count)))
+                        .style(Style::default().add_modifier(Modifier::BOLD));
+                }
+                PresetRow::Preset(i) => &app.preset_list[*i],
+            };
             let experimental_tag = if preset_info.experimental == Some(true) {
                 "[EXPERIMENTAL] "
             } else {
                 ""
             };
+            // Presets the linter flags keep their place in the list, with a badge
+            let (lint_tag, lint_style) = if preset_info.diagnostics.iter().any(|d| d.severity == Severity::Error) {
+                ("[INVALID] ", Style::default().fg(Color::Red))
+            } else if !preset_info.diagnostics.is_empty() {
+                ("[WARNINGS] ", Style::default().fg(Color::Yellow))
+            } else {
+                ("", Style::default())
+            };
+            let update_tag = if preset_info.update_available { "[UPDATE] " } else { "" };
+            let experimental_tag = format!("{}{}{}", update_tag, lint_tag, experimental_tag);
             let display_name = if preset_info.filename.starts_with("DEFAULT_") {
                 if let Some(ref title) = preset_info.title {
-                    format!("🔒 {}{}", experimental_tag, title)
+                    format!("  🔒 {}{}", experimental_tag, title)
                 } else {
-                    format!("🔒 {}{}", experimental_tag, preset_info.filename)
+                    format!("  🔒 {}{}", experimental_tag, preset_info.filename)
                 }
             } else {
                 if let Some(ref title) = preset_info.title {
-                    format!("   {}{}", experimental_tag, title)
+                    format!("     {}{}", experimental_tag, title)
                 } else {
-                    format!("   {}{}", experimental_tag, preset_info.filename)
+                    format!("     {}{}", experimental_tag, preset_info.filename)
                 }
             };
-            ListItem::new(Line::from(display_name))
+            ListItem::new(Line::from(display_name)).style(lint_style)
         })
         .collect();
 
+    // The search being typed, or the filters narrowing the list
+    let title = if let Some(PresetAction::Import(source)) = &app.preset_action {
+        // Codes are long; the end of what was pasted is enough to see it arrived
+        let tail: String = source.chars().rev().take(PRESET_IMPORT_PROMPT_TAIL).collect::<Vec<_>>().into_iter().rev().collect();
+        let more = if source.chars().count() > PRESET_IMPORT_PROMPT_TAIL { "…" } else { "" };
+        format!("Import code or archive path: {}{}_ (Enter: Import, Esc: Cancel)", more, tail)
+    } else if let Some(PresetAction::ImportConflict { taken, .. }) = &app.preset_action {
+        format!("Already in your presets: {} - r: Import as _imported, s: Skip them, o: Overwrite (old copies to {}/), Esc: Cancel",
+            taken.join(", "), PRESET_TRASH_DIRNAME)
+    } else if app.preset_searching {
+        format!("Search: {}_ (Enter: Done, Esc: Clear)", app.preset_filter.query)
+    } else if app.preset_filter.is_active() {
+        format!("Presets matching {} (Esc: Clear filters)", app.preset_filter.summary())
+    } else {
+        "Available Presets (↑↓: Navigate, Enter: Load, /: Search, Esc: Cancel)".to_string()
+    };
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("Available Presets (↑↓: Navigate, Enter: Load, Esc: Cancel)"))
+        .block(Block::default().borders(Borders::ALL).title(title))
         .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan))
         .highlight_symbol(">> ");
 
     f.render_stateful_widget(list, chunks[0], &mut app.preset_state);
 
     // Show details for selected preset
-    if let Some(i) = app.preset_state.selected() {
+    if let Some(i) = app.selected_preset_index() {
         if i < app.preset_list.len() {
             let preset_info = &app.preset_list[i];
             let mut detail_text = String::new();
 
+            // Pending operation on this preset
+            match &app.preset_action {
+                Some(PresetAction::Rename(name)) => {
+                    detail_text.push_str(&format!("Rename to: {}_.json   (Enter: Rename, Esc: Cancel)\n\n", name));
+                }
+                Some(PresetAction::Delete) => {
+                    detail_text.push_str(&format!("Delete {}? It is moved to the {} folder (u: undo).   (Enter/y: Delete, any other key: Cancel)\n\n",
+                        preset_info.filename, PRESET_TRASH_DIRNAME));
+                }
+                Some(PresetAction::Revert) => {
+                    detail_text.push_str(&format!("Revert {} to the built-in version? Your changes are moved to the {} folder (u: undo).   (Enter/y: Revert, any other key: Cancel)\n\n",
+                        preset_info.filename, PRESET_TRASH_DIRNAME));
+                }
+                Some(PresetAction::Import(_) | PresetAction::ImportConflict { .. }) | None => {}
+            }
+
+            // Share code (wrapped; whitespace is ignored when it is imported)
+            if let Some(shared) = app.shared_code.as_ref().filter(|s| s.filename == preset_info.filename) {
+                let max_width = chunks[1].width.saturating_sub(4) as usize;
+                detail_text.push_str("Share code - copy it into chat; others paste it with i in their preset list (x: QR code):\n");
+                detail_text.push_str(&textwrap::fill(&shared.code, max_width));
+                detail_text.push_str("\n\n");
+                if let Some(qr) = &shared.qr {
+                    detail_text.push_str(qr);
+                    detail_text.push_str("\n\n");
+                }
+            }
+
             // Title section
             if let Some(ref title) = preset_info.title {
                 detail_text.push_str(&format!("━━ {} ━━\n\n", title));
@@ -1228,17 +3039,197 @@ fn draw_preset_list(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
                 detail_text.push_str("\n\n");
             }
 
+            // Linter findings
+            if !preset_info.diagnostics.is_empty() {
+                let max_width = chunks[1].width.saturating_sub(4) as usize;
+                for diagnostic in &preset_info.diagnostics {
+                    detail_text.push_str(&textwrap::fill(&format!("• {}", diagnostic), max_width));
+                    detail_text.push('\n');
+                }
+                detail_text.push('\n');
+            }
+
+            if preset_info.update_available {
+                detail_text.push_str("A newer built-in version of this preset is available - press m to merge it field by field.\n\n");
+            }
+
+            // Parent this preset overrides
+            if let Some(ref parent) = preset_info.extends {
+                let parent_title = app.preset_list.iter().find(|p| &p.filename == parent).and_then(|p| p.title.as_deref());
+                match parent_title {
+                    Some(title) => detail_text.push_str(&format!("Extends: {} ({})\n", title, parent)),
+                    None => detail_text.push_str(&format!("Extends: {}\n", parent)),
+                }
+            }
+
+            // Category and tags
+            if !preset_info.tags.is_empty() || preset_info.category.is_some() {
+                let category = preset_info.category.map_or(UNCATEGORIZED_LABEL, |c| c.label());
+                detail_text.push_str(&format!("Category: {}   Tags: {}\n", category, preset_info.tags.join(", ")));
+            }
+
             // File info
             detail_text.push_str(&format!("File: {}", preset_info.filename));
 
             let detail = Paragraph::new(detail_text)
-                .block(Block::default().borders(Borders::ALL).title("Preset Information"))
+                .block(Block::default().borders(Borders::ALL)
+                    .title("Preset Information (r: Rename, c: Duplicate, d: Delete, v: Revert, m: Merge update, x: Share, i: Import, u: Undo, e/h/f: Experimental/Headphones/RF filter)"))
                 .style(Style::default().fg(Color::White));
             f.render_widget(detail, chunks[1]);
         }
     }
 }
 
+fn draw_report(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let Some(form) = app.report_form.as_ref() else { return; };
+    let title = if form.completed {
+        "Session Complete - How was it? (↑↓/Tab: Navigate, ←→: Rate, Enter: Save, Esc: Skip)"
+    } else {
+        "Session Stopped - How was it? (↑↓/Tab: Navigate, ←→: Rate, Enter: Save, Esc: Skip)"
+    };
+    draw_report_form(f, form, title, area);
+}
+
+fn draw_report_form(f: &mut Frame, form: &ReportForm, title: &str, area: ratatui::layout::Rect) {
+    let items: Vec<ListItem> = form.fields.iter()
+        .map(|field| {
+            let answer = match (&field.value, field.question.kind) {
+                (ReportValue::Scale(v), _) => {
+                    let filled = *v as usize;
+                    let empty = (REPORT_SCALE_MAX as usize).saturating_sub(filled);
+                    format!("[{}{}] {}/{}", "■".repeat(filled), "□".repeat(empty), v, REPORT_SCALE_MAX)
+                },
+                (ReportValue::YesNo(b), _) => if *b { "[Yes]".to_string() } else { "[No]".to_string() },
+                (ReportValue::Text(text), QuestionKind::Text) => format!("{}_", text),
+                (ReportValue::Text(text), _) => text.clone(),
+            };
+            ListItem::new(Line::from(format!("{:<40} {}", field.question.prompt, answer)))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan))
+        .highlight_symbol(">> ");
+
+    let mut state = ListState::default();
+    state.select(Some(form.selected));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_preset_editor(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let Some(editor) = app.preset_editor.as_ref() else { return; };
+    let width = area.width.saturating_sub(8) as usize;
+    let checkbox = |on: bool| if on { "[x]" } else { "[ ]" };
+
+    let items: Vec<ListItem> = EDITOR_FIELDS.iter().enumerate()
+        .map(|(i, field)| {
+            // Show a cursor on the text field being edited
+            let cursor = if i == editor.selected { "_" } else { "" };
+            let text = match field {
+                EditorField::Title => format!("Title:       {}{}", editor.title, cursor),
+                EditorField::Description => format!("Description: {}{}", editor.description, cursor),
+                EditorField::Extends if editor.extends.is_empty() && i != editor.selected => "Extends:     (none - every field is saved)".to_string(),
+                EditorField::Extends => format!("Extends:     {}{}", editor.extends, cursor),
+                EditorField::Experimental => format!("Experimental:        {}", checkbox(editor.experimental)),
+                EditorField::Locked => format!("Lock signal layer:   {}", checkbox(editor.locked)),
+                EditorField::Filename => format!("Filename:    {}{}.json", editor.filename.trim_end_matches(".json"), cursor),
+            };
+            ListItem::new(textwrap::fill(&text, width.max(20)))
+        })
+        .collect();
+
+    // Where Enter will write, so an overwrite is never a surprise
+    let target = match editor.file_name() {
+        Ok(name) if get_presets_dir().join(&name).exists() => format!("Overwrites {}", name),
+        Ok(name) => format!("New preset {}", name),
+        Err(e) => e,
+    };
+    let title = format!("Save Preset - {} (↑↓/Tab: Navigate, Space: Toggle, Enter: Save, Esc: Cancel)", target);
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan))
+        .highlight_symbol(">> ");
+
+    let mut state = ListState::default();
+    state.select(Some(editor.selected));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_preset_merge(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let Some(merge) = app.preset_merge.as_ref() else { return; };
+    let value = |v: &Option<serde_json::Value>| v.as_ref().map_or("(none)".to_string(), |v| v.to_string());
+
+    let items: Vec<ListItem> = merge.diffs.iter().zip(&merge.take_theirs)
+        .map(|(diff, take)| {
+            // The side that will be written is highlighted
+            let (mine, theirs) = if *take { ("   ", ">> ") } else { (">> ", "   ") };
+            ListItem::new(vec![
+                Line::from(diff.path.clone()).style(Style::default().add_modifier(Modifier::BOLD)),
+                Line::from(format!("  {}yours:    {}", mine, value(&diff.mine))),
+                Line::from(format!("  {}built-in: {}", theirs, value(&diff.theirs))),
+            ])
+        })
+        .collect();
+
+    let title = format!("Merge {} - {} fields differ (↑↓: Navigate, Space/←→: Switch, a: All built-in, Enter: Save, Esc: Cancel)",
+        merge.filename, merge.diffs.len());
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().fg(Color::Cyan));
+
+    let mut state = ListState::default();
+    state.select(Some(merge.selected).filter(|_| !merge.diffs.is_empty()));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_protocol(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let Some(run) = app.protocol.as_ref() else { return; };
+    let now = std::time::Instant::now();
+    let mmss = |secs: f32| format!("{:02}:{:02}", secs as u32 / 60, secs as u32 % 60);
+
+    let items: Vec<ListItem> = run.steps.iter().enumerate()
+        .map(|(i, step)| {
+            let (icon, timing) = if let Some(result) = run.log.steps.get(i) {
+                (if result.skipped { "⏭" } else { "✓" }, mmss(result.elapsed_secs))
+            } else if i == run.current {
+                let icon = if run.is_paused() { "⏸" } else { "▶" };
+                match step.duration_secs {
+                    Some(duration) => {
+                        let elapsed = run.elapsed_secs(now).min(duration);
+                        let filled = (elapsed / duration * PROTOCOL_PROGRESS_WIDTH as f32) as usize;
+                        (icon, format!("[{}{}] {} / {}", "■".repeat(filled), "□".repeat(PROTOCOL_PROGRESS_WIDTH - filled),
+                            mmss(elapsed), mmss(duration)))
+                    }
+                    None => (icon, "answering".to_string()),
+                }
+            } else {
+                (" ", step.duration_secs.map(mmss).unwrap_or_default())
+            };
+            ListItem::new(Line::from(format!("{} {:>2}. {:<40} {}", icon, i + 1, step.label, timing)))
+        })
+        .collect();
+
+    let name = run.log.title.clone().unwrap_or_else(|| run.log.protocol.clone());
+    let title = format!("Protocol: {} - step {}/{}", name, (run.current + 1).min(run.steps.len()), run.steps.len());
+    let mut state = ListState::default();
+    state.select(Some(run.current));
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan));
+
+    let Some(form) = app.report_form.as_ref() else {
+        f.render_stateful_widget(list, area, &mut state);
+        return;
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(run.steps.len() as u16 + 2), Constraint::Min(6)].as_ref())
+        .split(area);
+    f.render_stateful_widget(list, chunks[0], &mut state);
+    draw_report_form(f, form, "Questionnaire (↑↓/Tab: Navigate, ←→: Rate, Enter: Next step, Esc: Skip)", chunks[1]);
+}
+
 fn draw_mixer(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
     // Get current params to display
     let params = app.params.lock(); // This lock is quick, just for reading
@@ -1284,6 +3275,12 @@ fn draw_mixer(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
                         if parent.name.starts_with("NETWORK STREAMING") && app.streaming_collapsed {
                             return None;
                         }
+                        if parent.name == "EEG INPUT" && app.eeg_collapsed {
+                            return None;
+                        }
+                        if parent.name == "HEART INPUT" && app.heart_collapsed {
+                            return None;
+                        }
                         break;
                     }
                 }
@@ -1387,6 +3384,33 @@ fn draw_mixer(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
                 return Some((idx, ListItem::new(Line::from(format!("{} {} ({})            Press [x] to toggle", chan.name, indicator, status)))));
             }
 
+            if chan.name == "EEG INPUT" {
+                let indicator = if app.eeg_collapsed { "[+]" } else { "[-]" };
+                let item_count = 5; // source, signal, dominant, closed loop, record
+                let status = if app.eeg_collapsed {
+                    format!("{} items hidden", item_count)
+                } else {
+                    "expanded".to_string()
+                };
+                return Some((idx, ListItem::new(Line::from(format!("{} {} ({})            Press [x] to toggle", chan.name, indicator, status)))));
+            }
+
+            if chan.name == "HEART INPUT" {
+                let indicator = if app.heart_collapsed { "[+]" } else { "[-]" };
+                let item_count = 3; // source, heart rate, breath pacing
+                let status = if app.heart_collapsed {
+                    format!("{} items hidden", item_count)
+                } else {
+                    "expanded".to_string()
+                };
+                return Some((idx, ListItem::new(Line::from(format!("{} {} ({})            Press [x] to toggle", chan.name, indicator, status)))));
+            }
+
+            // Band power only means something with a device attached
+            if matches!(chan.id, ChannelId::EegBands) && app.eeg.is_none() {
+                return None;
+            }
+
             let content = match chan.id {
                 ChannelId::BeingType => {
                     // This is now handled in the header section above
@@ -1399,8 +3423,12 @@ fn draw_mixer(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
                         let filled = (params.coherence.volume * 20.0) as usize;
                         let bar: String = std::iter::repeat("█").take(filled).collect();
                         let empty: String = std::iter::repeat("░").take(20 - filled).collect();
-                        let beat_hz = params.coherence.binaural_beat_hz();
-                        let state = params.coherence.brainwave_state();
+                        let beat_hz = params.coherence.current_beat_hz(params.session_timer / 60.0);
+                        let state = if params.coherence.sleep.is_active() || params.coherence.wake.active {
+                            crate::coherence::brainwave_state_name(beat_hz)
+                        } else {
+                            params.coherence.brainwave_state()
+                        };
                         format!("{:<40} [{}{}] {:.0}% {:.1}Hz {}", 
                             chan.name, bar, empty, params.coherence.volume * 100.0, beat_hz, state)
                     }
@@ -1420,11 +3448,18 @@ fn draw_mixer(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
                 ChannelId::SessionTimer => {
                     let minutes = (params.session_timer / 60.0) as u32;
                     let seconds = (params.session_timer % 60.0) as u32;
-                    let phase_name = match params.session_phase {
-                        crate::coherence::SessionPhase::Startup => "Startup",
-                        crate::coherence::SessionPhase::Induction => "Induction",
-                        crate::coherence::SessionPhase::Stabilization => "Stabilization",
-                        crate::coherence::SessionPhase::Return => "Return",
+                    let phase_name = match (params.session_phase, params.coherence.sleep.is_active()) {
+                        _ if params.coherence.wake.active => {
+                            if params.session_phase == crate::coherence::SessionPhase::Startup { "Waking" } else { "Beta hold" }
+                        },
+                        (crate::coherence::SessionPhase::Startup, false) => "Startup",
+                        (crate::coherence::SessionPhase::Induction, false) => "Induction",
+                        (crate::coherence::SessionPhase::Stabilization, false) => "Stabilization",
+                        (crate::coherence::SessionPhase::Return, false) => "Return",
+                        (crate::coherence::SessionPhase::Startup, true) => "Settling",
+                        (crate::coherence::SessionPhase::Induction, true) => "Sleep descent",
+                        (crate::coherence::SessionPhase::Stabilization, true) => "Delta hold",
+                        (crate::coherence::SessionPhase::Return, true) => "Fade out",
                     };
                     let total_min = params.coherence.total_session_min() as u32;
                     let recommendation = if minutes < 15 {
@@ -1437,6 +3472,40 @@ fn draw_mixer(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
                     format!("{:<40} {:02}:{:02} - {}{}",
                         chan.name, minutes, seconds, phase_name, recommendation)
                 },
+                ChannelId::EegBands => {
+                    let snapshot = app.eeg_analyzer.as_ref().and_then(|a| a.latest());
+                    let device_bands = app.eeg.as_ref().and_then(|eeg| eeg.device_bands());
+                    match (snapshot, device_bands) {
+                        (Some(snapshot), _) => match snapshot.mean_relative() {
+                            Some(bands) => {
+                                let flagged = snapshot.channels.iter().filter(|c| c.artifacts.any()).count();
+                                let warning = if flagged > 0 { format!("  ⚠ {} ch flagged", flagged) } else { String::new() };
+                                format!("{:<40} {}{}", chan.name, format_band_bars(&bands), warning)
+                            },
+                            None => format!("{:<40} ⚠ all channels flagged - check electrodes", chan.name),
+                        },
+                        // No raw EEG (yet), but the headband sends its own band powers
+                        (None, Some(bands)) => format!("{:<40} {}  (device)", chan.name, format_band_bars(&bands.relative())),
+                        (None, None) => format!("{:<40} waiting for data...", chan.name),
+                    }
+                },
+                ChannelId::SleepTimer => {
+                    let sleep = &params.coherence.sleep;
+                    if sleep.is_active() {
+                        let remaining = sleep.remaining_min(app.session_started_at, session::unix_now());
+                        let wake = local_minutes_of_day() + remaining.max(0.0).round() as u32;
+                        format!("{:<40} {} left (until {}) (arrows to adjust)",
+                            chan.name, format_duration_min(remaining), format_clock_time(wake))
+                    } else {
+                        format!("{:<40} Off (arrows to set)", chan.name)
+                    }
+                },
+                ChannelId::WakeAlarm => {
+                    match app.wake_at {
+                        Some(time) => format!("{:<40} {} (arrows to adjust, Space off)", chan.name, format_clock_time(time)),
+                        None => format!("{:<40} Off (Space to set)", chan.name),
+                    }
+                },
                 ChannelId::RfEnable => {
                     let detection = if params.rf_detected { "✓" } else { "✗" };
                     let state = if params.rf_enabled { "ON " } else { "OFF" };
@@ -1474,6 +3543,130 @@ fn draw_mixer(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
                     format!("{:<40} {} (http://<ip>:{}/stream.wav)", 
                         chan.name, params.stream_port, params.stream_port)
                 },
+                ChannelId::EegSource => {
+                    match &app.eeg {
+                        Some(eeg) => {
+                            let state = match eeg.status() {
+                                EegStatus::Disconnected => "disconnected".to_string(),
+                                EegStatus::Connecting => "connecting...".to_string(),
+                                EegStatus::Streaming => "✓ streaming".to_string(),
+                                EegStatus::Error(e) => format!("✗ {}", e),
+                            };
+                            let stream_time = eeg.recent(1).first().map(|s| s.timestamp).unwrap_or(0.0);
+                            format!("{:<40} {} {} ({:.0}Hz, {}ch, {} samples lost, {:.0}s)", chan.name, eeg.source_name(), state,
+                                eeg.sample_rate(), eeg.channel_count(), eeg.lost_samples(), stream_time)
+                        },
+                        None => format!("{:<40} Not connected (start with --openbci, --muse or --replay)", chan.name),
+                    }
+                },
+                ChannelId::EegSignal => {
+                    // Per-channel RMS over the last second - a quick electrode contact check
+                    let window = app.eeg.as_ref()
+                        .map(|eeg| eeg.recent(eeg.sample_rate() as usize))
+                        .unwrap_or_default();
+                    if window.is_empty() {
+                        format!("{:<40} -", chan.name)
+                    } else {
+                        let channel_count = window[0].channels.len();
+                        let rms: Vec<String> = (0..channel_count)
+                            .map(|ch| {
+                                let sum_sq: f32 = window.iter().map(|s| s.channels[ch] * s.channels[ch]).sum();
+                                format!("{:.0}", (sum_sq / window.len() as f32).sqrt())
+                            })
+                            .collect();
+                        let accel = window.iter().rev().find_map(|s| s.accel)
+                            .map(|a| format!("  acc {:.2}/{:.2}/{:.2}g", a[0], a[1], a[2]))
+                            .unwrap_or_default();
+                        let contact = app.eeg.as_ref().and_then(|eeg| eeg.contact_quality())
+                            .map(|quality| {
+                                let dots: String = quality.iter().map(|q| match q {
+                                    ContactQuality::Good => '●',
+                                    ContactQuality::Fair => '◐',
+                                    ContactQuality::Bad => '○',
+                                }).collect();
+                                format!("  contact {}", dots)
+                            })
+                            .unwrap_or_default();
+                        format!("{:<40} {}{}{}", chan.name, rms.join(" "), accel, contact)
+                    }
+                },
+                ChannelId::EegDominant => {
+                    match app.eeg_analyzer.as_ref().and_then(|a| a.latest()) {
+                        Some(snapshot) => {
+                            let peaks: Vec<String> = snapshot.channels.iter()
+                                .map(|c| match c.artifacts.label() {
+                                    Some(label) => format!("{:.1}({})", c.dominant_hz, label),
+                                    None => format!("{:.1}", c.dominant_hz),
+                                })
+                                .collect();
+                            format!("{:<40} {}", chan.name, peaks.join(" "))
+                        },
+                        None => format!("{:<40} -", chan.name),
+                    }
+                },
+                ChannelId::ClosedLoop => {
+                    match &app.closed_loop {
+                        Some(controller) => format!("{:<40} ON {} → {:.2}Hz, {} adjustments (Space off)",
+                            chan.name, controller.config().law.name(), params.coherence.custom_binaural_hz, controller.adjustments()),
+                        None => {
+                            let law = params.closed_loop.as_ref().map(|c| c.law.name())
+                                .unwrap_or_else(|| ClosedLoopConfig::default().law.name());
+                            format!("{:<40} Off - {} (arrows to change, Space on)", chan.name, law)
+                        }
+                    }
+                },
+                ChannelId::EegRecord => {
+                    match &app.eeg_recorder {
+                        Some(recorder) => {
+                            let secs = recorder.duration_secs() as u64;
+                            let file = recorder.path().file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+                            format!("{:<40} ● REC {:02}:{:02} {}, {} events (Space stop)",
+                                chan.name, secs / 60, secs % 60, file, recorder.annotation_count())
+                        },
+                        None => format!("{:<40} Off - {} (arrows to change, Space to record)", chan.name, app.record_format.name()),
+                    }
+                },
+                ChannelId::HeartSource => {
+                    match &app.heart {
+                        Some(heart) => {
+                            let state = match heart.status() {
+                                EegStatus::Disconnected => "disconnected".to_string(),
+                                EegStatus::Connecting => "waiting for data...".to_string(),
+                                EegStatus::Streaming => "✓ receiving".to_string(),
+                                EegStatus::Error(e) => format!("✗ {}", e),
+                            };
+                            format!("{:<40} {} {} ({} beats, {} rejected)", chan.name, heart.source_name(), state,
+                                heart.total_beats(), heart.rejected_beats())
+                        },
+                        None => format!("{:<40} Not connected (start with --heart-serial or --heart-udp)", chan.name),
+                    }
+                },
+                ChannelId::HeartRate => {
+                    match (app.hrv.as_ref(), app.heart.as_ref().and_then(|h| h.reported_hr())) {
+                        (Some(hrv), _) => {
+                            let coherence = match (hrv.coherence, hrv.peak_hz) {
+                                (Some(c), Some(peak)) => format!("  coherence {:.1} at {:.1}/min", c, peak * 60.0),
+                                _ => String::new(),
+                            };
+                            format!("{:<40} {:.0} bpm  RMSSD {:.0} ms{}", chan.name, hrv.hr_bpm, hrv.rmssd_ms, coherence)
+                        },
+                        (None, Some(hr)) => format!("{:<40} {:.0} bpm (HRV needs RR intervals)", chan.name, hr),
+                        (None, None) => format!("{:<40} -", chan.name),
+                    }
+                },
+                ChannelId::BreathPacing => {
+                    match &app.breath_pacer {
+                        Some(pacer) => {
+                            let best = pacer.best()
+                                .map(|t| format!(", best {:.1}/min ({:.1} bpm swing)", t.breath_hz * 60.0, t.amplitude_bpm))
+                                .unwrap_or_default();
+                            format!("{:<40} ON {:.1} breaths/min, {} trials{} (Space off)",
+                                chan.name, pacer.breath_hz() * 60.0, pacer.trials().len(), best)
+                        },
+                        None => format!("{:<40} Off - breath layer at {:.1}/min (Space to pace from HRV)",
+                            chan.name, params.breath_hz * 60.0),
+                    }
+                },
                 _ => {
                     let (vol, mod_type) = match chan.id {
                         ChannelId::Master => (params.master_vol, None),
@@ -1534,3 +3727,48 @@ fn draw_mixer(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
 
     f.render_stateful_widget(list, area, &mut adjusted_state);
 }
+
+/// Key name for stimulus annotations ("Space", "a", "Left", ...)
+fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Relative band power as compact bars (δ██░░░░ 30% θ...)
+fn format_band_bars(bands: &BandPowers) -> String {
+    BandPowers::NAMES.iter().zip(bands.as_array())
+        .map(|(name, fraction)| {
+            let filled = ((fraction * EEG_BAND_BAR_WIDTH as f32).round() as usize).min(EEG_BAND_BAR_WIDTH);
+            format!("{}{}{} {:>2.0}%", name, "█".repeat(filled), "░".repeat(EEG_BAND_BAR_WIDTH - filled), fraction * 100.0)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_text_is_not_annotated() {
+        let mut app = App::new(Arc::new(Mutex::new(AudioParams::default())), Arc::new(Mutex::new(0)), None);
+        assert!(!app.typing_text());
+
+        app.preset_searching = true;
+        assert!(app.typing_text());
+        app.preset_searching = false;
+        for action in [PresetAction::Rename(String::new()), PresetAction::Import(String::new())] {
+            app.preset_action = Some(action);
+            assert!(app.typing_text());
+        }
+        // A yes/no confirmation is only a keypress
+        app.preset_action = Some(PresetAction::Delete);
+        assert!(!app.typing_text());
+        app.preset_action = None;
+        app.report_form = Some(ReportForm::new(&[], true));
+        assert!(app.typing_text());
+    }
+}
@@ -0,0 +1,159 @@
+// Session manifests: what is needed to regenerate the exact audio of a session
+// (software version and build, the resolved parameters, sample rate and noise seed)
+
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::audio::AudioParams;
+use crate::blind::BlindRun;
+
+/// Commit the binary was built from (set by build.rs when built from a git checkout)
+pub const GIT_HASH: Option<&str> = option_env!("SOULWHISTLE_GIT_HASH");
+
+/// State that shapes the audio but is set at run time rather than by the preset, so `params` doesn't serialize it
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeState {
+    pub sleep_duration_min: f32, // Sleep timer length (0 = off)
+    pub wake_active: bool,       // Wake alarm ramp running
+    pub breath_hz: f32,          // Breath LFO rate (moved by HRV breath pacing)
+}
+
+impl Default for RuntimeState {
+    fn default() -> Self {
+        Self::of(&AudioParams::default())
+    }
+}
+
+impl RuntimeState {
+    pub fn of(params: &AudioParams) -> Self {
+        Self {
+            sleep_duration_min: params.coherence.sleep.duration_min,
+            wake_active: params.coherence.wake.active,
+            breath_hz: params.breath_hz,
+        }
+    }
+
+    fn apply(&self, params: &mut AudioParams) {
+        params.coherence.sleep.duration_min = self.sleep_duration_min;
+        params.coherence.wake.active = self.wake_active;
+        params.breath_hz = self.breath_hz;
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionManifest {
+    pub software_version: String,
+    pub git_hash: Option<String>,
+    pub created_at_unix: u64,
+    pub preset: Option<String>,
+    pub sample_rate: f32,
+    pub seed: u64,
+    /// How long the session ran (filled in when it ends)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f32>,
+    /// True if the parameters were changed by hand after the session started (the manifest holds the starting ones)
+    #[serde(default)]
+    pub params_changed: bool,
+    /// Set for blinded sessions; the condition (and so the sham) is only in the study key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blind: Option<BlindRun>,
+    pub params: AudioParams,
+    /// Run-time state at the start (older manifests without it get the defaults)
+    #[serde(default)]
+    pub runtime: RuntimeState,
+}
+
+impl SessionManifest {
+    /// Snapshot the parameters a session starts with (`session_seed` must already be resolved)
+    pub fn new(params: &AudioParams, sample_rate: f32) -> Self {
+        Self {
+            software_version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: GIT_HASH.map(str::to_string),
+            created_at_unix: crate::session::unix_now(),
+            preset: params.preset_name.clone(),
+            sample_rate,
+            seed: params.session_seed,
+            duration_secs: None,
+            params_changed: false,
+            blind: None,
+            params: params.clone(),
+            runtime: RuntimeState::of(params),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read manifest {}: {}", path.display(), e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid manifest {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, json)
+    }
+
+    /// Flag the manifest if `params` no longer match the ones it was taken from; returns true on the first change
+    pub fn note_changes(&mut self, params: &AudioParams) -> bool {
+        if self.params_changed {
+            return false;
+        }
+        self.params_changed = serde_json::to_value(params).ok() != serde_json::to_value(&self.params).ok()
+            || RuntimeState::of(params) != self.runtime;
+        self.params_changed
+    }
+
+    /// Parameters to render the session from (audio on, RF and streaming off, the recorded seed)
+    pub fn render_params(&self) -> AudioParams {
+        let mut params = self.params.clone();
+        self.runtime.apply(&mut params);
+        params.session_seed = self.seed;
+        params.playing = true;
+        params.rf_enabled = false;
+        params.stream_enabled = false;
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::Synthesizer;
+
+    #[test]
+    fn test_manifest_regenerates_identical_audio() {
+        let mut params = AudioParams { breath_vol: 0.5, ..Default::default() }; // The breath layer is noise
+        params.resolve_seed(Some(1234));
+        let render = |params: &AudioParams, seed: u64| -> Vec<(f32, f32)> {
+            let mut synth = Synthesizer::new(48000.0, seed);
+            (0..4800).map(|_| synth.next_sample(params)).collect()
+        };
+        let heard = render(&params, params.session_seed);
+
+        let json = serde_json::to_string(&SessionManifest::new(&params, 48000.0)).unwrap();
+        let manifest: SessionManifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(manifest.seed, 1234);
+        assert_eq!(render(&manifest.render_params(), manifest.seed), heard);
+        assert_ne!(render(&params, 1235), heard);
+
+        let mut changed = manifest.clone();
+        assert!(!changed.note_changes(&manifest.params));
+        assert!(changed.note_changes(&AudioParams { master_vol: 0.9, ..manifest.params.clone() }));
+    }
+
+    #[test]
+    fn test_manifest_keeps_runtime_state() {
+        let mut params = AudioParams { breath_vol: 0.5, breath_hz: 0.09, ..Default::default() };
+        params.coherence.sleep.duration_min = 45.0;
+        params.coherence.wake.active = true;
+
+        let json = serde_json::to_string(&SessionManifest::new(&params, 48000.0)).unwrap();
+        let manifest: SessionManifest = serde_json::from_str(&json).unwrap();
+        let rendered = manifest.render_params();
+        assert_eq!((rendered.coherence.sleep.duration_min, rendered.coherence.wake.active, rendered.breath_hz), (45.0, true, 0.09));
+
+        // The breath pacer moving the rate is a change, though no serialized field differs
+        let mut changed = manifest.clone();
+        assert!(!changed.note_changes(&params));
+        assert!(changed.note_changes(&AudioParams { breath_hz: 0.1, ..params.clone() }));
+    }
+}
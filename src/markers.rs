@@ -0,0 +1,206 @@
+// Stimulus event markers for external recorders
+// The audio thread stamps events on its own sample clock and hands them to this thread, which
+// sends them as line-delimited JSON to TCP clients and/or a UDP target
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+use serde::Serialize;
+use crate::constants::*;
+use crate::stimulus::StimulusEvent;
+
+/// A stimulus event stamped on the audio sample clock
+#[derive(Clone, Debug)]
+pub struct StimulusMarker {
+    pub event: StimulusEvent,
+    pub stream_frame: u64,   // Frames rendered since the audio stream started (never resets)
+    pub session_sample: u64, // CoherenceSynth session sample (restarts with each session)
+    pub sample_rate: f32,
+}
+
+/// Line layout sent to receivers
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MarkerFormat {
+    #[default]
+    Json, // One JSON object per event with every field
+    Lsl,  // Stream info line, then {"timestamp", "sample": [label]} lines for an LSL string outlet
+}
+
+/// Where markers go (nothing is sent when both addresses are None)
+#[derive(Clone, Debug, Default)]
+pub struct MarkerSettings {
+    pub tcp_addr: Option<String>,   // Address to listen on for TCP clients
+    pub udp_target: Option<String>, // HOST:PORT to send datagrams to
+    pub format: MarkerFormat,
+}
+
+impl MarkerSettings {
+    pub fn enabled(&self) -> bool {
+        self.tcp_addr.is_some() || self.udp_target.is_some()
+    }
+}
+
+#[derive(Serialize)]
+struct JsonMarker<'a> {
+    #[serde(flatten)]
+    event: &'a StimulusEvent,
+    label: String,
+    time: f64, // Seconds on the audio clock since playback started
+    session_secs: f64,
+    session_sample: u64,
+    sample_rate: f32,
+}
+
+#[derive(Serialize)]
+struct LslSample {
+    timestamp: f64,
+    sample: [String; 1],
+}
+
+/// LSL stream description matching a pylsl StreamInfo for a string marker outlet
+pub fn lsl_stream_info() -> String {
+    serde_json::json!({
+        "info": {
+            "name": LSL_MARKER_STREAM_NAME,
+            "type": "Markers",
+            "channel_count": 1,
+            "nominal_srate": 0.0,
+            "channel_format": "string",
+            "source_id": LSL_MARKER_STREAM_NAME,
+        }
+    }).to_string()
+}
+
+/// One marker as a line of JSON (without the newline)
+pub fn format_marker(marker: &StimulusMarker, format: MarkerFormat) -> String {
+    let rate = marker.sample_rate as f64;
+    let time = marker.stream_frame as f64 / rate;
+    let line = match format {
+        MarkerFormat::Json => serde_json::to_string(&JsonMarker {
+            event: &marker.event,
+            label: marker.event.label(),
+            time,
+            session_secs: marker.session_sample as f64 / rate,
+            session_sample: marker.session_sample,
+            sample_rate: marker.sample_rate,
+        }),
+        MarkerFormat::Lsl => serde_json::to_string(&LslSample { timestamp: time, sample: [marker.event.label()] }),
+    };
+    line.unwrap_or_default()
+}
+
+/// Sends markers from the audio thread to TCP clients and a UDP target
+pub struct MarkerServer {
+    rx: Receiver<StimulusMarker>,
+    format: MarkerFormat,
+    listener: Option<TcpListener>,
+    clients: Vec<TcpStream>,
+    udp: Option<(UdpSocket, SocketAddr)>,
+}
+
+impl MarkerServer {
+    pub fn new(settings: &MarkerSettings, rx: Receiver<StimulusMarker>) -> io::Result<Self> {
+        let listener = match &settings.tcp_addr {
+            Some(addr) => {
+                let listener = TcpListener::bind(addr)?;
+                listener.set_nonblocking(true)?;
+                Some(listener)
+            }
+            None => None,
+        };
+        let udp = match &settings.udp_target {
+            Some(target) => {
+                let target = target.to_socket_addrs()?.next()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot resolve {}", target)))?;
+                let local = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+                Some((UdpSocket::bind(local)?, target))
+            }
+            None => None,
+        };
+
+        let server = Self { rx, format: settings.format, listener, clients: Vec::new(), udp };
+        if let (MarkerFormat::Lsl, Some((socket, target))) = (server.format, &server.udp) {
+            socket.send_to(format!("{}\n", lsl_stream_info()).as_bytes(), target)?;
+        }
+        Ok(server)
+    }
+
+    pub fn local_tcp_addr(&self) -> Option<SocketAddr> {
+        self.listener.as_ref().and_then(|l| l.local_addr().ok())
+    }
+
+    /// Forward markers until the audio side hangs up (blocking - run in a separate thread)
+    pub fn run(&mut self) {
+        loop {
+            match self.rx.recv_timeout(Duration::from_millis(MARKER_ACCEPT_INTERVAL_MS)) {
+                Ok(marker) => {
+                    let line = format!("{}\n", format_marker(&marker, self.format));
+                    self.send(line.as_bytes());
+                }
+                Err(RecvTimeoutError::Timeout) => self.accept_clients(),
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+
+    fn accept_clients(&mut self) {
+        let Some(listener) = &self.listener else {
+            return;
+        };
+        while let Ok((mut stream, _)) = listener.accept() {
+            let _ = stream.set_nodelay(true);
+            let _ = stream.set_write_timeout(Some(Duration::from_millis(MARKER_WRITE_TIMEOUT_MS)));
+            if self.format == MarkerFormat::Lsl && stream.write_all(format!("{}\n", lsl_stream_info()).as_bytes()).is_err() {
+                continue;
+            }
+            self.clients.push(stream);
+        }
+    }
+
+    fn send(&mut self, line: &[u8]) {
+        // Clients that connected since the last check get this marker too
+        self.accept_clients();
+        self.clients.retain_mut(|client| client.write_all(line).is_ok());
+        if let Some((socket, target)) = &self.udp {
+            let _ = socket.send_to(line, target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use crate::coherence::SessionPhase;
+
+    #[test]
+    fn test_markers_reach_tcp_client() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let settings = MarkerSettings { tcp_addr: Some("127.0.0.1:0".to_string()), ..Default::default() };
+        let mut server = MarkerServer::new(&settings, rx).unwrap();
+        let addr = server.local_tcp_addr().unwrap();
+        let handle = std::thread::spawn(move || server.run());
+
+        let client = TcpStream::connect(addr).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        tx.send(StimulusMarker {
+            event: StimulusEvent::Phase { phase: SessionPhase::Induction },
+            stream_frame: 96_000,
+            session_sample: 48_000,
+            sample_rate: 48_000.0,
+        }).unwrap();
+
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["event"], "phase");
+        assert_eq!(json["phase"], "Induction");
+        assert_eq!(json["label"], "Phase: Induction");
+        assert_eq!(json["time"], 2.0);
+        assert_eq!(json["session_sample"], 48_000);
+
+        drop(tx);
+        handle.join().unwrap();
+    }
+}
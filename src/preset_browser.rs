@@ -0,0 +1,227 @@
+// Preset browser model: what the list shows about each preset, grouping by category,
+// incremental search and the experimental / headphones / RF filters
+
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use crate::coherence::BeingType;
+use crate::preset_lint::{Diagnostic, LintedPreset};
+
+/// Browser group a preset is listed under
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PresetCategory {
+    Focus,
+    Sleep,
+    Monroe,
+    Animal,
+    Uap,
+    Rf,
+}
+
+impl PresetCategory {
+    pub const ALL: [PresetCategory; 6] = [
+        PresetCategory::Focus,
+        PresetCategory::Sleep,
+        PresetCategory::Monroe,
+        PresetCategory::Animal,
+        PresetCategory::Uap,
+        PresetCategory::Rf,
+    ];
+
+    /// The category a preset's `category` string names (None for one this version doesn't know)
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.label().eq_ignore_ascii_case(name))
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PresetCategory::Focus => "Focus",
+            PresetCategory::Sleep => "Sleep",
+            PresetCategory::Monroe => "Monroe",
+            PresetCategory::Animal => "Animal",
+            PresetCategory::Uap => "UAP",
+            PresetCategory::Rf => "RF",
+        }
+    }
+}
+
+/// Read a preset's category, listing unknown ones under Other instead of rejecting the preset
+/// (the linter warns about them)
+pub fn deserialize_category<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<PresetCategory>, D::Error> {
+    let name: Option<String> = Option::deserialize(deserializer)?;
+    Ok(name.as_deref().and_then(PresetCategory::from_name))
+}
+
+/// Header label for presets without a category
+pub const UNCATEGORIZED_LABEL: &str = "Other";
+
+pub struct PresetInfo {
+    pub filename: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub experimental: Option<bool>,
+    pub category: Option<PresetCategory>,
+    pub extends: Option<String>, // Parent preset it overrides
+    pub tags: Vec<String>,
+    pub headphones: bool, // Plays binaural beats
+    pub uses_rf: bool,
+    pub diagnostics: Vec<Diagnostic>, // Problems found by the preset linter
+    pub update_available: bool, // Edited DEFAULT_ copy with a newer built-in version to merge
+}
+
+impl PresetInfo {
+    pub fn new(filename: String, linted: LintedPreset) -> Self {
+        let params = linted.params.unwrap_or_default();
+        let tags = params.tags.unwrap_or_default();
+        let uses_rf = params.rf_enabled || params.category == Some(PresetCategory::Rf)
+            || tags.iter().any(|t| t.eq_ignore_ascii_case("rf"));
+        Self {
+            filename,
+            title: params.preset_title,
+            description: params.preset_description,
+            experimental: params.experimental,
+            category: params.category,
+            extends: params.extends,
+            headphones: params.coherence.enabled && !matches!(params.coherence.being_type, BeingType::Unknown),
+            uses_rf,
+            tags,
+            diagnostics: linted.diagnostics,
+            update_available: false,
+        }
+    }
+}
+
+/// Search text and flag filters of the browser
+#[derive(Default)]
+pub struct PresetFilter {
+    pub query: String,
+    pub experimental: bool,
+    pub headphones: bool,
+    pub rf: bool,
+}
+
+impl PresetFilter {
+    pub fn is_active(&self) -> bool {
+        !self.query.trim().is_empty() || self.experimental || self.headphones || self.rf
+    }
+
+    /// Every word of the query must be found. Title, filename and tags match fuzzily (the letters
+    /// in order, so "f10" finds "Focus 10"); the description only matches whole substrings.
+    pub fn matches(&self, info: &PresetInfo) -> bool {
+        if (self.experimental && info.experimental != Some(true)) || (self.headphones && !info.headphones) || (self.rf && !info.uses_rf) {
+            return false;
+        }
+        let title = info.title.as_deref().unwrap_or_default().to_lowercase();
+        let filename = info.filename.to_lowercase();
+        let description = info.description.as_deref().unwrap_or_default().to_lowercase();
+        self.query.to_lowercase().split_whitespace().all(|word| {
+            fuzzy_contains(&title, word) || fuzzy_contains(&filename, word) || description.contains(word)
+                || info.tags.iter().any(|tag| fuzzy_contains(&tag.to_lowercase(), word))
+        })
+    }
+
+    /// Short description of the active filters for the list title
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.query.is_empty() {
+            parts.push(format!("\"{}\"", self.query));
+        }
+        for (on, name) in [(self.experimental, "experimental"), (self.headphones, "headphones"), (self.rf, "RF")] {
+            if on {
+                parts.push(name.to_string());
+            }
+        }
+        parts.join(", ")
+    }
+}
+
+/// True if the letters of `needle` appear in `haystack` in order
+fn fuzzy_contains(haystack: &str, needle: &str) -> bool {
+    let mut letters = haystack.chars();
+    needle.chars().all(|c| letters.any(|h| h == c))
+}
+
+/// One line of the browser: a category header or a preset (index into the preset list)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PresetRow {
+    Header { category: Option<PresetCategory>, count: usize, collapsed: bool },
+    Preset(usize),
+}
+
+/// Group the presets that pass the filter by category (uncategorized last). Collapsed groups show
+/// only their header, except while searching so that no match is hidden.
+pub fn build_rows(presets: &[PresetInfo], filter: &PresetFilter, collapsed: &HashSet<Option<PresetCategory>>) -> Vec<PresetRow> {
+    let mut categories: Vec<Option<PresetCategory>> = presets.iter().map(|p| p.category).collect();
+    categories.sort_by_key(|c| (c.is_none(), *c));
+    categories.dedup();
+
+    let searching = !filter.query.trim().is_empty();
+    let mut rows = Vec::new();
+    for category in categories {
+        let members: Vec<usize> = (0..presets.len())
+            .filter(|&i| presets[i].category == category && filter.matches(&presets[i]))
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        let is_collapsed = collapsed.contains(&category) && !searching;
+        rows.push(PresetRow::Header { category, count: members.len(), collapsed: is_collapsed });
+        if !is_collapsed {
+            rows.extend(members.into_iter().map(PresetRow::Preset));
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedded() -> Vec<PresetInfo> {
+        crate::embedded_presets::EMBEDDED_PRESETS.iter()
+            .map(|p| PresetInfo::new(p.filename.to_string(), crate::preset_lint::lint_preset_in(p.content, None)))
+            .collect()
+    }
+
+    fn filenames(presets: &[PresetInfo], rows: &[PresetRow]) -> Vec<String> {
+        rows.iter().filter_map(|row| match row {
+            PresetRow::Preset(i) => Some(presets[*i].filename.clone()),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn test_grouping_search_and_filters() {
+        let presets = embedded();
+        let mut filter = PresetFilter::default();
+        let mut collapsed = HashSet::new();
+
+        let rows = build_rows(&presets, &filter, &collapsed);
+        assert_eq!(rows.len(), presets.len() + 5); // Focus, Monroe, Animal, UAP and Other headers
+        assert!(matches!(rows[0], PresetRow::Header { category: Some(PresetCategory::Focus), .. }));
+
+        collapsed.insert(Some(PresetCategory::Uap));
+        let rows = build_rows(&presets, &filter, &collapsed);
+        assert!(rows.contains(&PresetRow::Header { category: Some(PresetCategory::Uap), count: 6, collapsed: true }));
+        assert!(filenames(&presets, &rows).iter().all(|f| !f.contains("uap")));
+
+        // Searching shows matches even inside collapsed groups
+        filter.query = "f10".to_string();
+        assert_eq!(filenames(&presets, &build_rows(&presets, &filter, &collapsed)),
+            vec!["DEFAULT_deep_focus_calm.json", "DEFAULT_focus_10_mind_awake.json"]); // "Deep Focus ... (10 Hz Alpha)"
+        filter.query = "f10 awake".to_string();
+        assert_eq!(filenames(&presets, &build_rows(&presets, &filter, &collapsed)), vec!["DEFAULT_focus_10_mind_awake.json"]);
+        filter.query = "hydrogen".to_string();
+        assert_eq!(filenames(&presets, &build_rows(&presets, &filter, &collapsed)), vec!["DEFAULT_uap_rf_ultrasonic.json"]);
+
+        filter.query.clear();
+        filter.rf = true;
+        let rf = filenames(&presets, &build_rows(&presets, &filter, &HashSet::new()));
+        assert_eq!(rf, vec!["DEFAULT_uap_rf_ultrasonic.json", "DEFAULT_uap_sweep_24khz.json"]);
+        filter.rf = false;
+        filter.headphones = true;
+        filter.experimental = true;
+        let experimental_binaural = filenames(&presets, &build_rows(&presets, &filter, &HashSet::new()));
+        assert_eq!(experimental_binaural.len(), 3);
+    }
+}
@@ -0,0 +1,308 @@
+// Preset bundles for sharing: any number of presets in one .tar.gz archive, or as a short text
+// code (deflated, base64) that can be pasted into chat. User presets that a bundled preset
+// extends travel with it; built-in parents don't need to. A bundle is preset JSON only: presets
+// have no field that points at an audio file, so there are no samples to pack.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use base64::Engine;
+use flate2::Compression;
+use serde_json::Value;
+use crate::constants::*;
+use crate::embedded_presets::EMBEDDED_PRESETS;
+use crate::preset_files::{is_default, move_to_trash, preset_file_name};
+use crate::preset_schema::migrate_preset;
+
+/// One preset of a bundle
+pub struct BundleEntry {
+    pub name: String, // Filename, e.g. "my_focus.json"
+    pub json: Value,
+}
+
+/// The named presets (user directory first, then built-in) and every user preset they extend
+pub fn collect(dir: &Path, names: &[String]) -> Result<Vec<BundleEntry>, String> {
+    let mut entries: Vec<BundleEntry> = Vec::new();
+    let mut pending: Vec<String> = names.iter()
+        .map(|n| if n.ends_with(".json") { n.clone() } else { format!("{}.json", n) })
+        .collect();
+    pending.reverse();
+
+    while let Some(name) = pending.pop() {
+        if entries.iter().any(|e| e.name == name) {
+            continue;
+        }
+        let content = std::fs::read_to_string(dir.join(&name)).ok()
+            .or_else(|| EMBEDDED_PRESETS.iter().find(|p| p.filename == name).map(|p| p.content.to_string()))
+            .ok_or_else(|| format!("Preset '{}' not found", name))?;
+        let json = migrate_preset(&content).map_err(|e| format!("{} is invalid: {}", name, e))?.json;
+        if let Some(parent) = json.get("extends").and_then(Value::as_str) {
+            if !is_default(parent) {
+                pending.push(parent.to_string());
+            }
+        }
+        entries.push(BundleEntry { name, json });
+    }
+    Ok(entries)
+}
+
+/// Write the presets into a gzipped tar archive (`presets/<name>.json` entries)
+pub fn write_archive(entries: &[BundleEntry], path: &Path) -> Result<(), String> {
+    let file = std::fs::File::create(path).map_err(|e| format!("Could not create {}: {}", path.display(), e))?;
+    let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(file, Compression::default()));
+    for entry in entries {
+        let json = serde_json::to_string_pretty(&entry.json).map_err(|e| e.to_string())?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(json.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(crate::session::unix_now());
+        archive.append_data(&mut header, format!("presets/{}", entry.name), json.as_bytes())
+            .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+    }
+    archive.into_inner().and_then(|gz| gz.finish()).map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// Read the presets from an archive made by `write_archive` (other files in it are ignored)
+pub fn read_archive(path: &Path) -> Result<Vec<BundleEntry>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    let bad = |e: std::io::Error| format!("{} is not a preset bundle: {}", path.display(), e);
+
+    let mut entries = Vec::new();
+    for entry in archive.entries().map_err(bad)? {
+        let entry = entry.map_err(bad)?;
+        // Only the file name is used, so nothing in the archive can point outside the presets folder
+        let name = entry.path().ok()
+            .and_then(|p| p.file_name().and_then(|n| n.to_str()).map(str::to_string))
+            .filter(|n| n.ends_with(".json"));
+        let Some(name) = name.filter(|_| entry.header().entry_type().is_file()) else { continue; };
+        let mut content = String::new();
+        entry.take(PRESET_BUNDLE_MAX_BYTES).read_to_string(&mut content).map_err(bad)?;
+        let json = migrate_preset(&content).map_err(|e| format!("{} in the bundle is invalid: {}", name, e))?.json;
+        entries.push(BundleEntry { name, json });
+    }
+    if entries.is_empty() {
+        return Err(format!("{} has no presets in it", path.display()));
+    }
+    Ok(entries)
+}
+
+/// The presets as one line of text: the prefix, then deflated JSON in URL-safe base64
+pub fn encode_code(entries: &[BundleEntry]) -> Result<String, String> {
+    let map: BTreeMap<&str, &Value> = entries.iter().map(|e| (e.name.as_str(), &e.json)).collect();
+    let json = serde_json::to_vec(&map).map_err(|e| e.to_string())?;
+    let mut deflater = flate2::write::DeflateEncoder::new(Vec::new(), Compression::best());
+    deflater.write_all(&json).map_err(|e| e.to_string())?;
+    let compressed = deflater.finish().map_err(|e| e.to_string())?;
+    Ok(format!("{}{}", PRESET_CODE_PREFIX, base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(compressed)))
+}
+
+/// Read a code made by `encode_code`. Whitespace is ignored, since chat clients wrap long lines.
+pub fn decode_code(code: &str) -> Result<Vec<BundleEntry>, String> {
+    let code: String = code.split_whitespace().collect();
+    let data = code.strip_prefix(PRESET_CODE_PREFIX)
+        .ok_or_else(|| format!("Not a preset code (preset codes start with {})", PRESET_CODE_PREFIX))?;
+    let compressed = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(data)
+        .map_err(|_| "The preset code is damaged or incomplete".to_string())?;
+    let mut json = Vec::new();
+    flate2::read::DeflateDecoder::new(compressed.as_slice()).take(PRESET_BUNDLE_MAX_BYTES).read_to_end(&mut json)
+        .map_err(|_| "The preset code is damaged or incomplete".to_string())?;
+    let map: BTreeMap<String, Value> = serde_json::from_slice(&json).map_err(|e| format!("The preset code is invalid: {}", e))?;
+    map.into_iter()
+        .map(|(name, json)| {
+            let json = migrate_preset(&json.to_string()).map_err(|e| format!("{} in the code is invalid: {}", name, e))?.json;
+            Ok(BundleEntry { name, json })
+        })
+        .collect()
+}
+
+/// The text rendered as a QR code of half-height block characters (light on dark, for terminals)
+pub fn qr_code(text: &str) -> Result<String, String> {
+    use qrcode::render::unicode::Dense1x2;
+    let qr = qrcode::QrCode::new(text.as_bytes()).map_err(|e| format!("Too long for a QR code: {}", e))?;
+    Ok(qr.render::<Dense1x2>().dark_color(Dense1x2::Light).light_color(Dense1x2::Dark).build())
+}
+
+/// What to do with a bundled preset whose filename is already taken by a different preset
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OnConflict {
+    Rename,    // Import it as <name>_imported.json (presets in the bundle that extend it follow)
+    Skip,
+    Overwrite, // The existing file goes to the trash
+}
+
+#[derive(Default)]
+pub struct ImportReport {
+    pub imported: Vec<String>,
+    pub renamed: Vec<(String, String)>,
+    pub overwritten: Vec<String>,
+    pub skipped: Vec<String>, // Skipped on conflict, or identical to the file already there
+}
+
+impl ImportReport {
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("Imported {} preset{}", self.imported.len(), if self.imported.len() == 1 { "" } else { "s" })];
+        if !self.renamed.is_empty() {
+            let renamed: Vec<String> = self.renamed.iter().map(|(from, to)| format!("{} as {}", from, to)).collect();
+            parts.push(format!("renamed {}", renamed.join(", ")));
+        }
+        if !self.overwritten.is_empty() {
+            parts.push(format!("replaced {} (old copies in {}/)", self.overwritten.join(", "), PRESET_TRASH_DIRNAME));
+        }
+        if !self.skipped.is_empty() {
+            parts.push(format!("skipped {}", self.skipped.join(", ")));
+        }
+        parts.join(", ")
+    }
+}
+
+/// The filename each bundled preset imports as (built-in DEFAULT_ names lose the prefix).
+/// Two presets landing on the same name, like `DEFAULT_x.json` and `x.json`, are an error.
+fn target_names(entries: &[BundleEntry]) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = Vec::new();
+    for entry in entries {
+        let stem = entry.name.trim_end_matches(".json");
+        let stem = if is_default(stem) { &stem["DEFAULT_".len()..] } else { stem };
+        let name = preset_file_name(stem).map_err(|e| format!("Bad preset name in the bundle: {}", e))?;
+        if let Some(other) = names.iter().position(|n| *n == name) {
+            return Err(format!("{} and {} in the bundle would both import as {}", entries[other].name, entry.name, name));
+        }
+        names.push(name);
+    }
+    Ok(names)
+}
+
+/// Whether `name` in `dir` holds a different preset than `json` (None when the name is free)
+fn differs_from_existing(dir: &Path, name: &str, json: &Value) -> Option<bool> {
+    let existing = std::fs::read_to_string(dir.join(name)).ok()?;
+    Some(!migrate_preset(&existing).is_ok_and(|m| m.json == *json))
+}
+
+/// The names that importing the bundle into `dir` would conflict on (taken by a different preset)
+pub fn conflicts(dir: &Path, entries: &[BundleEntry]) -> Result<Vec<String>, String> {
+    Ok(target_names(entries)?.into_iter().zip(entries)
+        .filter(|(name, entry)| differs_from_existing(dir, name, &entry.json) == Some(true))
+        .map(|(name, _)| name)
+        .collect())
+}
+
+/// Write the bundled presets into `dir`. Built-in (DEFAULT_) names are imported without the prefix.
+pub fn import(dir: &Path, entries: Vec<BundleEntry>, on_conflict: OnConflict) -> Result<ImportReport, String> {
+    let names = target_names(&entries)?;
+    std::fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+    let mut report = ImportReport::default();
+
+    // Pick every target name first, so references between bundled presets can follow renames
+    let mut targets: Vec<Option<String>> = Vec::new();
+    let mut renames: BTreeMap<String, String> = BTreeMap::new();
+    for (entry, name) in entries.iter().zip(&names) {
+        let target = match differs_from_existing(dir, name, &entry.json) {
+            None => Some(name.clone()),
+            Some(false) => {
+                report.skipped.push(name.clone());
+                None
+            }
+            Some(true) => match on_conflict {
+                OnConflict::Skip => {
+                    report.skipped.push(name.clone());
+                    None
+                }
+                OnConflict::Overwrite => {
+                    report.overwritten.push(name.clone());
+                    Some(name.clone())
+                }
+                OnConflict::Rename => {
+                    let stem = name.trim_end_matches(".json");
+                    let free = (1..).map(|n| if n == 1 { format!("{}_imported.json", stem) } else { format!("{}_imported_{}.json", stem, n) })
+                        .find(|candidate| !dir.join(candidate).exists() && !names.contains(candidate)
+                            && !targets.contains(&Some(candidate.clone())))
+                        .unwrap_or_default();
+                    report.renamed.push((name.clone(), free.clone()));
+                    Some(free)
+                }
+            },
+        };
+        if target.as_deref() != Some(entry.name.as_str()) {
+            if let Some(target) = &target {
+                renames.insert(entry.name.clone(), target.clone());
+            }
+        }
+        targets.push(target);
+    }
+
+    for (mut entry, target) in entries.into_iter().zip(targets) {
+        let Some(target) = target else { continue; };
+        let parent = entry.json.get("extends").and_then(Value::as_str).and_then(|p| renames.get(p)).cloned();
+        if let (Some(parent), Some(obj)) = (parent, entry.json.as_object_mut()) {
+            obj.insert("extends".to_string(), Value::from(parent));
+        }
+        if report.overwritten.contains(&target) {
+            move_to_trash(dir, &target)?;
+        }
+        let json = serde_json::to_string_pretty(&entry.json).map_err(|e| e.to_string())?;
+        std::fs::write(dir.join(&target), json).map_err(|e| format!("Could not write {}: {}", target, e))?;
+        report.imported.push(target);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn test_bundle_round_trips_and_imports() {
+        let dir = TempDir::new("bundle");
+        let (from, to) = (dir.join("from"), dir.join("to"));
+        std::fs::create_dir_all(&from).unwrap();
+        std::fs::write(from.join("base.json"), r#"{"schema_version": 1, "extends": "DEFAULT_focus_10_mind_awake.json", "master_vol": 0.4}"#).unwrap();
+        std::fs::write(from.join("child.json"), r#"{"schema_version": 1, "extends": "base.json", "master_vol": 0.3}"#).unwrap();
+
+        // The user parent comes along, the built-in one doesn't
+        let entries = collect(&from, &["child".to_string()]).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["child.json", "base.json"]);
+
+        let archive = dir.join("bundle.tar.gz");
+        write_archive(&entries, &archive).unwrap();
+        let report = import(&to, read_archive(&archive).unwrap(), OnConflict::Rename).unwrap();
+        assert_eq!(report.imported, vec!["child.json", "base.json"]);
+
+        // The same bundle again changes nothing
+        let code = encode_code(&collect(&from, &["child".to_string()]).unwrap()).unwrap();
+        assert!(code.starts_with(PRESET_CODE_PREFIX) && !code.contains(char::is_whitespace));
+        assert_eq!(import(&to, decode_code(&code).unwrap(), OnConflict::Rename).unwrap().skipped.len(), 2);
+
+        // Changed presets under taken names are renamed, and the child follows its renamed parent
+        std::fs::write(from.join("base.json"), r#"{"schema_version": 1, "master_vol": 0.5}"#).unwrap();
+        std::fs::write(from.join("child.json"), r#"{"schema_version": 1, "extends": "base.json", "master_vol": 0.35}"#).unwrap();
+        let wrapped: String = encode_code(&collect(&from, &["child".to_string()]).unwrap()).unwrap()
+            .chars().enumerate().flat_map(|(i, c)| if i % 40 == 39 { vec![c, '\n'] } else { vec![c] }).collect();
+        let report = import(&to, decode_code(&wrapped).unwrap(), OnConflict::Rename).unwrap();
+        assert_eq!(report.imported, vec!["base_imported.json", "child_imported.json"]);
+        let child = crate::preset_lint::lint_preset_file(&to.join("child_imported.json")).into_params().unwrap();
+        assert_eq!((child.extends.as_deref(), child.master_vol), (Some("base_imported.json"), 0.35));
+
+        let report = import(&to, decode_code(&wrapped).unwrap(), OnConflict::Overwrite).unwrap();
+        assert_eq!(report.overwritten, vec!["base.json", "child.json"]);
+        assert!(to.join(PRESET_TRASH_DIRNAME).read_dir().unwrap().count() == 2);
+
+        // Conflicts are known before importing; two entries can't share a target name
+        let code = encode_code(&collect(&from, &["child".to_string()]).unwrap()).unwrap();
+        assert_eq!(conflicts(&to, &decode_code(&code).unwrap()).unwrap(), Vec::<String>::new());
+        std::fs::write(to.join("base.json"), r#"{"schema_version": 1, "master_vol": 0.6}"#).unwrap();
+        assert_eq!(conflicts(&to, &decode_code(&code).unwrap()).unwrap(), vec!["base.json"]);
+        let clash = vec![
+            BundleEntry { name: "DEFAULT_calm.json".to_string(), json: serde_json::json!({"schema_version": 1}) },
+            BundleEntry { name: "calm.json".to_string(), json: serde_json::json!({"schema_version": 1}) },
+        ];
+        assert!(conflicts(&to, &clash).unwrap_err().contains("both import as calm.json"));
+        assert!(import(&to, clash, OnConflict::Rename).err().unwrap().contains("both import as calm.json"));
+
+        assert!(decode_code("not a code").is_err());
+        assert!(decode_code(&format!("{}AAAA", PRESET_CODE_PREFIX)).is_err());
+        assert!(!qr_code(&code).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+// Preset editor: title, description, flags and filename of the preset being saved
+// Saves never touch DEFAULT_ presets; overwriting any other preset needs a second confirmation.
+// A preset with a parent is saved as overrides of it (see preset_inherit).
+
+use std::path::{Path, PathBuf};
+use crate::audio::AudioParams;
+use crate::constants::*;
+use crate::preset_files::{is_default, preset_file_name};
+use crate::preset_inherit;
+
+/// Fields of the editor form, in display order
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditorField {
+    Title,
+    Description,
+    Extends,
+    Experimental,
+    Locked,
+    Filename,
+}
+
+pub const EDITOR_FIELDS: [EditorField; 6] = [
+    EditorField::Title,
+    EditorField::Description,
+    EditorField::Extends,
+    EditorField::Experimental,
+    EditorField::Locked,
+    EditorField::Filename,
+];
+
+/// Editable preset metadata shown in the TUI editor mode
+pub struct PresetEditor {
+    pub title: String,
+    pub description: String,
+    pub extends: String, // Parent preset filename (empty: save every field)
+    pub experimental: bool,
+    pub locked: bool,
+    pub filename: String, // Without the .json extension
+    pub selected: usize,
+    /// Set once Enter was pressed on an existing file; the next Enter overwrites it
+    pub confirm_overwrite: bool,
+}
+
+impl PresetEditor {
+    /// Start from the playing preset; DEFAULT_ presets are offered under a name of their own
+    pub fn new(params: &AudioParams, current_preset: Option<&str>) -> Self {
+        let stem = current_preset
+            .map(|name| name.trim_end_matches(".json"))
+            .filter(|stem| !stem.is_empty() && *stem != PRESET_FILENAME.trim_end_matches(".json"));
+        let filename = match stem {
+            Some(stem) if is_default(stem) => format!("my_{}", &stem["DEFAULT_".len()..]),
+            Some(stem) => stem.to_string(),
+            None => format!("custom_{}", crate::session::unix_now()),
+        };
+
+        Self {
+            title: params.preset_title.clone().unwrap_or_default(),
+            description: params.preset_description.clone().unwrap_or_default(),
+            extends: params.extends.clone().unwrap_or_default(),
+            experimental: params.experimental == Some(true),
+            locked: params.lock_signal_layer,
+            filename,
+            selected: 0,
+            confirm_overwrite: false,
+        }
+    }
+
+    pub fn field(&self) -> EditorField {
+        EDITOR_FIELDS[self.selected]
+    }
+
+    fn text_mut(&mut self) -> Option<&mut String> {
+        match self.field() {
+            EditorField::Title => Some(&mut self.title),
+            EditorField::Description => Some(&mut self.description),
+            EditorField::Extends => Some(&mut self.extends),
+            EditorField::Filename => Some(&mut self.filename),
+            EditorField::Experimental | EditorField::Locked => None,
+        }
+    }
+
+    /// True if the selected field accepts typed text
+    pub fn editing_text(&self) -> bool {
+        !matches!(self.field(), EditorField::Experimental | EditorField::Locked)
+    }
+
+    /// Append a character to the selected text field
+    pub fn push_char(&mut self, c: char) {
+        if let Some(text) = self.text_mut() {
+            text.push(c);
+            self.confirm_overwrite = false;
+        }
+    }
+
+    /// Remove the last character of the selected text field
+    pub fn pop_char(&mut self) {
+        if let Some(text) = self.text_mut() {
+            text.pop();
+            self.confirm_overwrite = false;
+        }
+    }
+
+    /// Flip the selected flag
+    pub fn toggle(&mut self) {
+        match self.field() {
+            EditorField::Experimental => self.experimental = !self.experimental,
+            EditorField::Locked => self.locked = !self.locked,
+            _ => {}
+        }
+    }
+
+    /// The file to write, or why the name can't be used
+    pub fn file_name(&self) -> Result<String, String> {
+        preset_file_name(&self.filename)
+    }
+
+    /// Copy the edited metadata into the parameters being saved
+    pub fn apply(&self, params: &mut AudioParams) {
+        let text = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+        params.preset_title = text(&self.title);
+        params.preset_description = text(&self.description);
+        params.extends = text(&self.extends).map(|parent| format!("{}.json", parent.trim_end_matches(".json")));
+        params.experimental = self.experimental.then_some(true);
+        params.lock_signal_layer = self.locked;
+        params.schema_version = PRESET_SCHEMA_VERSION;
+    }
+
+    /// Write the preset into `dir`. An existing file is only replaced when the overwrite was
+    /// confirmed; otherwise this asks for confirmation by returning `Ok(None)`.
+    pub fn save(&mut self, dir: &Path, params: &AudioParams) -> Result<Option<PathBuf>, String> {
+        let path = dir.join(self.file_name()?);
+        if path.exists() && !self.confirm_overwrite {
+            self.confirm_overwrite = true;
+            return Ok(None);
+        }
+
+        let mut params = params.clone();
+        self.apply(&mut params);
+        let json = match params.extends {
+            Some(_) => preset_inherit::override_json(&params, dir, &self.file_name()?).map(|v| serde_json::to_string_pretty(&v)),
+            None => Ok(serde_json::to_string_pretty(&params)),
+        };
+        let json = json?.map_err(|e| format!("Error serializing: {}", e))?;
+        std::fs::create_dir_all(dir).map_err(|e| format!("Error creating presets directory: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Error writing file: {}", e))?;
+        Ok(Some(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn test_save_protects_defaults_and_confirms_overwrite() {
+        let params = AudioParams { preset_title: Some("Focus 10".to_string()), ..Default::default() };
+        let mut editor = PresetEditor::new(&params, Some("DEFAULT_focus_10_mind_awake.json"));
+        assert_eq!(editor.filename, "my_focus_10_mind_awake");
+
+        editor.filename = "default_focus_10_mind_awake".to_string();
+        assert!(editor.file_name().unwrap_err().contains("DEFAULT_"));
+        editor.filename = "../escape".to_string();
+        assert!(editor.file_name().is_err());
+
+        let dir = TempDir::new("editor");
+        editor.filename = "mine".to_string();
+        editor.description = "  Evening session ".to_string();
+        editor.experimental = true;
+        let path = editor.save(&dir, &params).unwrap().unwrap();
+        let saved = crate::preset_schema::migrate_preset(&std::fs::read_to_string(&path).unwrap()).unwrap().params;
+        assert_eq!(saved.preset_title.as_deref(), Some("Focus 10"));
+        assert_eq!(saved.preset_description.as_deref(), Some("Evening session"));
+        assert_eq!(saved.experimental, Some(true));
+
+        // The same name again needs a second save to overwrite, and editing cancels the confirmation
+        let mut editor = PresetEditor::new(&saved, Some("mine.json"));
+        assert!(editor.save(&dir, &params).unwrap().is_none());
+        editor.selected = 0;
+        editor.push_char('!');
+        assert!(editor.save(&dir, &params).unwrap().is_none());
+        assert!(editor.save(&dir, &params).unwrap().is_some());
+        let saved = crate::preset_schema::migrate_preset(&std::fs::read_to_string(&path).unwrap()).unwrap().params;
+        assert_eq!(saved.preset_title.as_deref(), Some("Focus 10!"));
+
+        // With a parent only the changes are written
+        let mut editor = PresetEditor::new(&saved, None);
+        editor.filename = "quieter".to_string();
+        editor.extends = "mine".to_string();
+        let quieter = AudioParams { master_vol: 0.3, ..saved.clone() };
+        let path = editor.save(&dir, &quieter).unwrap().unwrap();
+        let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, serde_json::json!({"schema_version": PRESET_SCHEMA_VERSION, "extends": "mine.json", "master_vol": 0.3_f32}));
+        editor.filename = "mine".to_string();
+        editor.confirm_overwrite = true;
+        assert!(editor.save(&dir, &saved).unwrap_err().contains("cycle"));
+    }
+}
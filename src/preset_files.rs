@@ -0,0 +1,180 @@
+// Preset file management in the user presets directory: rename, duplicate, delete into a
+// trash folder (undoable) and revert DEFAULT_ presets to the built-in version
+
+use std::path::{Path, PathBuf};
+use crate::constants::*;
+use crate::embedded_presets::EMBEDDED_PRESETS;
+use crate::preset_inherit;
+
+/// True for the built-in presets (and their copies in the user directory)
+pub fn is_default(name: &str) -> bool {
+    name.to_ascii_uppercase().starts_with("DEFAULT_")
+}
+
+/// Turn a typed name into a preset filename, refusing paths and DEFAULT_ names
+pub fn preset_file_name(input: &str) -> Result<String, String> {
+    let stem = input.trim().trim_end_matches(".json");
+    if stem.is_empty() {
+        return Err("Enter a filename".to_string());
+    }
+    if stem.starts_with('.') || stem.contains(['/', '\\']) {
+        return Err(format!("'{}' is not a plain filename", stem));
+    }
+    if is_default(stem) {
+        return Err("DEFAULT_ presets are built in and can't be overwritten - choose another filename".to_string());
+    }
+    Ok(format!("{}.json", stem))
+}
+
+fn embedded(name: &str) -> Option<&'static str> {
+    EMBEDDED_PRESETS.iter().find(|p| p.filename == name).map(|p| p.content)
+}
+
+/// Rename a user preset, pointing the presets that extend it at the new name.
+/// Returns the new filename and the presets that were re-pointed.
+pub fn rename_preset(dir: &Path, from: &str, to: &str) -> Result<(String, Vec<String>), String> {
+    if is_default(from) {
+        return Err("DEFAULT_ presets can't be renamed - duplicate it instead".to_string());
+    }
+    let to = preset_file_name(to)?;
+    let target = dir.join(&to);
+    if target.exists() {
+        return Err(format!("{} already exists", to));
+    }
+    let children = preset_inherit::dependents(dir, from);
+    std::fs::rename(dir.join(from), &target).map_err(|e| format!("Could not rename {}: {}", from, e))?;
+    for child in &children {
+        preset_inherit::set_parent(dir, child, &to)?;
+    }
+    Ok((to, children))
+}
+
+/// Copy a preset (user or built-in) to a new `<name>_copy.json`; returns the new filename
+pub fn duplicate_preset(dir: &Path, name: &str) -> Result<String, String> {
+    let content = match std::fs::read_to_string(dir.join(name)) {
+        Ok(content) => content,
+        Err(e) => embedded(name).map(str::to_string).ok_or_else(|| format!("Could not read {}: {}", name, e))?,
+    };
+    let stem = name.trim_end_matches(".json");
+    let stem = if is_default(stem) { &stem["DEFAULT_".len()..] } else { stem };
+
+    // First free name: x_copy, x_copy_2, x_copy_3, ...
+    let copy = (1..).map(|n| if n == 1 { format!("{}_copy.json", stem) } else { format!("{}_copy_{}.json", stem, n) })
+        .find(|candidate| !dir.join(candidate).exists())
+        .unwrap_or_default();
+    std::fs::write(dir.join(&copy), content).map_err(|e| format!("Could not write {}: {}", copy, e))?;
+    Ok(copy)
+}
+
+/// A preset moved into the trash folder, kept so the move can be undone
+pub struct Trashed {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Move a preset file into the trash folder (`<trash>/<unix time>_<name>`)
+pub fn move_to_trash(dir: &Path, name: &str) -> Result<Trashed, String> {
+    let trash = dir.join(PRESET_TRASH_DIRNAME);
+    std::fs::create_dir_all(&trash).map_err(|e| format!("Could not create {}: {}", trash.display(), e))?;
+    let now = crate::session::unix_now();
+    let path = (0..).map(|n| trash.join(if n == 0 { format!("{}_{}", now, name) } else { format!("{}-{}_{}", now, n, name) }))
+        .find(|path| !path.exists())
+        .unwrap_or_default();
+    std::fs::rename(dir.join(name), &path).map_err(|e| format!("Could not move {} to the trash: {}", name, e))?;
+    Ok(Trashed { name: name.to_string(), path })
+}
+
+/// Why a user preset can't be deleted: other presets extend it
+pub fn delete_blocker(dir: &Path, name: &str) -> Option<String> {
+    let children = preset_inherit::dependents(dir, name);
+    (!children.is_empty()).then(|| format!("{} is extended by {} - delete or re-parent those first", name, children.join(", ")))
+}
+
+/// Delete a user preset by moving it to the trash folder (refused while other presets extend it)
+pub fn delete_preset(dir: &Path, name: &str) -> Result<Trashed, String> {
+    if is_default(name) {
+        return Err("DEFAULT_ presets can't be deleted - revert it to the built-in version instead".to_string());
+    }
+    if let Some(blocker) = delete_blocker(dir, name) {
+        return Err(blocker);
+    }
+    if !dir.join(name).is_file() {
+        return Err(format!("{} is not in {}", name, dir.display()));
+    }
+    move_to_trash(dir, name)
+}
+
+/// Put a trashed preset back (a file that has since taken its name is trashed in turn)
+pub fn restore_preset(dir: &Path, trashed: &Trashed) -> Result<(), String> {
+    if dir.join(&trashed.name).exists() {
+        move_to_trash(dir, &trashed.name)?;
+    }
+    std::fs::rename(&trashed.path, dir.join(&trashed.name))
+        .map_err(|e| format!("Could not restore {}: {}", trashed.name, e))
+}
+
+/// Replace a modified DEFAULT_ preset with the built-in version; the modified file goes to the trash
+/// Returns None when the file already matches
+pub fn revert_preset(dir: &Path, name: &str) -> Result<Option<Trashed>, String> {
+    let content = embedded(name).ok_or_else(|| format!("{} is not a built-in preset", name))?;
+    let path = dir.join(name);
+    let trashed = match std::fs::read_to_string(&path) {
+        Ok(current) if current == content => return Ok(None),
+        Ok(_) => Some(move_to_trash(dir, name)?),
+        Err(_) => None,
+    };
+    std::fs::write(&path, content).map_err(|e| format!("Could not write {}: {}", name, e))?;
+    Ok(trashed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn test_manage_presets() {
+        let dir = TempDir::new("preset_files");
+        let default = &EMBEDDED_PRESETS[0];
+
+        // Duplicating a built-in drops the DEFAULT_ prefix and numbers repeated copies
+        let copy = duplicate_preset(&dir, default.filename).unwrap();
+        assert_eq!(copy, format!("{}_copy.json", &default.filename["DEFAULT_".len()..default.filename.len() - 5]));
+        assert!(duplicate_preset(&dir, default.filename).unwrap().ends_with("_copy_2.json"));
+
+        let (renamed, _) = rename_preset(&dir, &copy, "mine").unwrap();
+        assert_eq!(renamed, "mine.json");
+        assert!(rename_preset(&dir, &renamed, "DEFAULT_mine").is_err());
+        assert!(rename_preset(&dir, default.filename, "other").is_err());
+
+        // Delete, then undo
+        let trashed = delete_preset(&dir, &renamed).unwrap();
+        assert!(!dir.join(&renamed).exists() && trashed.path.exists());
+        restore_preset(&dir, &trashed).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join(&renamed)).unwrap(), default.content);
+        assert!(delete_preset(&dir, default.filename).is_err());
+
+        // Revert a modified default, keeping the modified file in the trash
+        std::fs::write(dir.join(default.filename), "{}").unwrap();
+        let trashed = revert_preset(&dir, default.filename).unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join(default.filename)).unwrap(), default.content);
+        assert_eq!(std::fs::read_to_string(&trashed.path).unwrap(), "{}");
+        assert!(revert_preset(&dir, default.filename).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parents_follow_renames_and_block_deletes() {
+        let dir = TempDir::new("preset_parents");
+        std::fs::write(dir.join("base.json"), r#"{"schema_version": 1, "master_vol": 0.4}"#).unwrap();
+        std::fs::write(dir.join("child.json"), r#"{"schema_version": 1, "extends": "base.json", "breath_vol": 0.2}"#).unwrap();
+
+        let (renamed, children) = rename_preset(&dir, "base.json", "calm").unwrap();
+        assert_eq!(children, vec!["child.json"]);
+        let child = crate::preset_lint::lint_preset_file(&dir.join("child.json")).into_params().unwrap();
+        assert_eq!((child.extends.as_deref(), child.master_vol, child.breath_vol), (Some("calm.json"), 0.4, 0.2));
+
+        assert!(delete_preset(&dir, &renamed).err().unwrap().contains("child.json"));
+        delete_preset(&dir, "child.json").unwrap();
+        delete_preset(&dir, &renamed).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+// Preset inheritance: a preset with `"extends": "<parent>.json"` only lists the fields it changes.
+// Parents are resolved recursively (they may extend presets of their own) before the preset is
+// checked, and a preset saved with a parent is written back in that override-only form.
+
+use std::path::Path;
+use serde_json::{Map, Value};
+use crate::audio::AudioParams;
+use crate::embedded_presets::EMBEDDED_PRESETS;
+use crate::preset_files::is_default;
+use crate::preset_schema::migrate_preset;
+
+/// A parent's JSON. DEFAULT_ parents are the built-in versions (not the user's possibly edited
+/// copy), anything else is read from `dir`, the directory the child lives in.
+pub fn load_parent(dir: Option<&Path>, name: &str) -> Option<String> {
+    let from_dir = || dir.and_then(|dir| std::fs::read_to_string(dir.join(name)).ok());
+    if is_default(name) {
+        EMBEDDED_PRESETS.iter().find(|p| p.filename == name).map(|p| p.content.to_string()).or_else(from_dir)
+    } else {
+        from_dir()
+    }
+}
+
+/// The presets in `dir` that extend `name` directly
+pub fn dependents(dir: &Path, name: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut children: Vec<String> = entries.flatten().map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "json"))
+        .filter(|p| std::fs::read_to_string(p).ok()
+            .and_then(|json| serde_json::from_str::<Value>(&json).ok())
+            .is_some_and(|json| json.get("extends").and_then(Value::as_str) == Some(name)))
+        .map(|p| p.file_name().unwrap_or_default().to_string_lossy().to_string())
+        .collect();
+    children.sort();
+    children
+}
+
+/// Point a preset in `dir` at a new parent, keeping the rest of its JSON
+pub fn set_parent(dir: &Path, name: &str, parent: &str) -> Result<(), String> {
+    let path = dir.join(name);
+    let mut json: Value = std::fs::read_to_string(&path).ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .ok_or_else(|| format!("Could not read {}", name))?;
+    json["extends"] = Value::from(parent);
+    let json = serde_json::to_string_pretty(&json).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("Could not write {}: {}", name, e))
+}
+
+/// Flatten a (migrated) preset onto its chain of parents. `name` is the preset's own filename,
+/// when known, so a parent that leads back to it is reported as a cycle.
+pub fn resolve(preset: &Value, dir: Option<&Path>, name: Option<&str>) -> Result<Value, String> {
+    let mut chain: Vec<String> = name.map(str::to_string).into_iter().collect();
+    resolve_chain(preset.clone(), dir, &mut chain)
+}
+
+fn resolve_chain(preset: Value, dir: Option<&Path>, chain: &mut Vec<String>) -> Result<Value, String> {
+    let Some(parent) = preset.get("extends") else {
+        return Ok(preset);
+    };
+    let parent = parent.as_str().ok_or("extends must be a preset filename")?.to_string();
+    if chain.contains(&parent) {
+        chain.push(parent);
+        return Err(format!("extends cycle: {}", chain.join(" -> ")));
+    }
+    chain.push(parent.clone());
+
+    let json = load_parent(dir, &parent).ok_or_else(|| format!("extends {} but no such preset exists", parent))?;
+    let migrated = migrate_preset(&json).map_err(|e| format!("parent {} is invalid: {}", parent, e))?;
+    let mut resolved = resolve_chain(migrated.json, dir, chain)?;
+    merge(&mut resolved, preset);
+    Ok(resolved)
+}
+
+/// Lay `over` onto `base`: objects (like `coherence`) are merged field by field, anything else is replaced
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => { base.insert(key, value); }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+/// The fields of `child` that differ from `parent` (None when nothing does). A field the parent
+/// sets but the child leaves out is written as null so it stays unset.
+fn diff(child: &Value, parent: &Value) -> Option<Value> {
+    match (child, parent) {
+        (Value::Object(child), Value::Object(parent)) => {
+            let mut changed: Map<String, Value> = child.iter()
+                .filter_map(|(key, value)| match parent.get(key) {
+                    Some(inherited) => diff(value, inherited).map(|d| (key.clone(), d)),
+                    None => Some((key.clone(), value.clone())),
+                })
+                .collect();
+            for key in parent.keys().filter(|key| !child.contains_key(*key)) {
+                changed.insert(key.clone(), Value::Null);
+            }
+            if changed.is_empty() { None } else { Some(Value::Object(changed)) }
+        }
+        _ if child == parent => None,
+        _ => Some(child.clone()),
+    }
+}
+
+/// The override-only JSON for saving `params` (which extend `params.extends`) as `name` in `dir`
+pub fn override_json(params: &AudioParams, dir: &Path, name: &str) -> Result<Value, String> {
+    let parent = params.extends.clone().ok_or("preset has no parent")?;
+    let inherited = resolve(&serde_json::json!({ "extends": parent }), Some(dir), Some(name))?;
+    let inherited: AudioParams = serde_json::from_value(inherited).map_err(|e| format!("parent {} is invalid: {}", parent, e))?;
+
+    // Compare like with like: both sides with every field written out
+    let full = serde_json::to_value(params).map_err(|e| e.to_string())?;
+    let inherited = serde_json::to_value(&inherited).map_err(|e| e.to_string())?;
+    let mut overrides = match diff(&full, &inherited) {
+        Some(Value::Object(overrides)) => overrides,
+        _ => Map::new(),
+    };
+    overrides.insert("schema_version".to_string(), full["schema_version"].clone());
+    overrides.insert("extends".to_string(), Value::from(parent));
+    Ok(Value::Object(overrides))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+    use crate::preset_lint::{lint_preset_file, lint_preset_in};
+
+    #[test]
+    fn test_extends_resolves_and_saves_overrides() {
+        let dir = TempDir::new("inherit");
+        std::fs::write(dir.join("base.json"), r#"{"schema_version": 1, "extends": "DEFAULT_focus_10_mind_awake.json",
+            "master_vol": 0.4, "coherence": {"volume": 0.3}}"#).unwrap();
+        let child = r#"{"schema_version": 1, "extends": "base.json", "preset_title": "Quiet", "coherence": {"volume": 0.2}}"#;
+
+        // Nested coherence fields override one by one; the rest comes from two levels up
+        let focus10 = lint_preset_in(crate::embedded_presets::EMBEDDED_PRESETS.iter()
+            .find(|p| p.filename == "DEFAULT_focus_10_mind_awake.json").unwrap().content, None).into_params().unwrap();
+        let params = lint_preset_in(child, Some(&dir)).into_params().unwrap();
+        assert_eq!(params.extends.as_deref(), Some("base.json"));
+        assert_eq!(params.preset_title.as_deref(), Some("Quiet"));
+        assert_eq!((params.master_vol, params.coherence.volume), (0.4, 0.2));
+        assert_eq!(params.coherence.left_carrier, focus10.coherence.left_carrier);
+
+        // Saving writes only what differs from the parent
+        let mut edited = params.clone();
+        edited.tags = None;
+        edited.coherence.volume = 0.25;
+        let saved = override_json(&edited, &dir, "child.json").unwrap();
+        assert_eq!(saved, serde_json::json!({"schema_version": 1, "extends": "base.json",
+            "preset_title": "Quiet", "tags": null, "coherence": {"volume": 0.25}}));
+        let reloaded = lint_preset_in(&saved.to_string(), Some(&dir)).into_params().unwrap();
+        assert!(reloaded.tags.is_none() && reloaded.coherence.volume == 0.25);
+
+        // Cycles and missing parents are errors
+        std::fs::write(dir.join("a.json"), r#"{"schema_version": 1, "extends": "b.json"}"#).unwrap();
+        std::fs::write(dir.join("b.json"), r#"{"schema_version": 1, "extends": "a.json"}"#).unwrap();
+        let err = lint_preset_file(&dir.join("a.json")).into_params().err().unwrap();
+        assert_eq!(err, "extends cycle: a.json -> b.json -> a.json");
+        assert!(lint_preset_in(r#"{"extends": "gone.json"}"#, Some(&dir)).into_params().err().unwrap().contains("no such preset"));
+
+        // A DEFAULT_ parent is the built-in version, even when the user's copy was edited
+        let base = || serde_json::to_value(lint_preset_file(&dir.join("base.json")).into_params().unwrap()).unwrap();
+        let before = base();
+        std::fs::write(dir.join("DEFAULT_focus_10_mind_awake.json"), r#"{"schema_version": 1, "coherence": {"left_carrier": 300.0}}"#).unwrap();
+        assert_eq!(base(), before);
+    }
+}
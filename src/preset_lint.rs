@@ -0,0 +1,242 @@
+// Preset validation: everything the loader would otherwise accept silently (unknown keys,
+// values outside the ranges the mixer allows, carriers that disagree with the declared beat)
+
+use std::fmt;
+use std::path::Path;
+use crate::audio::AudioParams;
+use crate::coherence::BeingType;
+use crate::constants::*;
+use crate::preset_inherit;
+use crate::preset_schema::{migrate_preset, MigratedPreset};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    /// The preset can't be loaded (or would play something the mixer never allows)
+    Error,
+    /// The preset loads, but something in it is ignored or inconsistent
+    Warning,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", label, self.message)
+    }
+}
+
+/// Result of checking one preset
+pub struct LintedPreset {
+    /// The parameters, when the JSON could be read at all
+    pub params: Option<AudioParams>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl LintedPreset {
+    /// The parameters if the preset has no errors, else every error on one line
+    pub fn into_params(self) -> Result<AudioParams, String> {
+        let errors: Vec<&str> = self.diagnostics.iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.message.as_str())
+            .collect();
+        match self.params {
+            Some(params) if errors.is_empty() => Ok(params),
+            _ => Err(errors.join("; ")),
+        }
+    }
+}
+
+/// Check preset JSON of any schema version (parents it extends are looked up in the user presets)
+pub fn lint_preset(json: &str) -> LintedPreset {
+    lint_preset_in(json, Some(&get_presets_dir()))
+}
+
+/// Check preset JSON whose parents are in `dir` (None: built-in presets only)
+pub fn lint_preset_in(json: &str, dir: Option<&Path>) -> LintedPreset {
+    lint(json, dir, None)
+}
+
+fn lint(json: &str, dir: Option<&Path>, name: Option<&str>) -> LintedPreset {
+    let migrated = match migrate_preset(json) {
+        Ok(migrated) => migrated,
+        Err(e) => {
+            return LintedPreset { params: None, diagnostics: vec![error(parse_error(json, e))] };
+        }
+    };
+
+    // A preset that extends another is checked with everything it inherits
+    let migrated = if migrated.json.get("extends").is_some() {
+        let resolved = preset_inherit::resolve(&migrated.json, dir, name)
+            .and_then(|flat| migrate_preset(&flat.to_string()));
+        match resolved {
+            Ok(resolved) => resolved,
+            Err(e) => return LintedPreset { params: None, diagnostics: vec![error(e)] },
+        }
+    } else {
+        migrated
+    };
+
+    let mut diagnostics: Vec<Diagnostic> = migrated.notes.iter().cloned().map(warning).collect();
+    check_ranges(&migrated.params, &mut diagnostics);
+    check_beat(&migrated, &mut diagnostics);
+    LintedPreset { params: Some(migrated.params), diagnostics }
+}
+
+/// Check a preset file on disk (parents are looked up next to it)
+pub fn lint_preset_file(path: &Path) -> LintedPreset {
+    let name = path.file_name().and_then(|n| n.to_str());
+    match std::fs::read_to_string(path) {
+        Ok(json) => lint(&json, path.parent(), name),
+        Err(e) => LintedPreset { params: None, diagnostics: vec![error(format!("could not read: {}", e))] },
+    }
+}
+
+/// Syntax and type errors with their line and column. Migration works on a JSON value, which
+/// has lost the positions, so the text is parsed again to find them.
+fn parse_error(json: &str, fallback: String) -> String {
+    let positioned = match serde_json::from_str::<serde_json::Value>(json) {
+        Err(e) => Some(e),
+        Ok(_) => serde_json::from_str::<AudioParams>(json).err(),
+    };
+    match positioned {
+        Some(e) if e.line() > 0 => {
+            let message = e.to_string();
+            let message = message.rsplit_once(" at line ").map_or(message.as_str(), |(m, _)| m).to_string();
+            format!("line {}, column {}: {}", e.line(), e.column(), message)
+        }
+        _ => fallback,
+    }
+}
+
+/// Values the mixer would never produce; it clamps these when adjusting, so a file is the only way in
+fn check_ranges(params: &AudioParams, diagnostics: &mut Vec<Diagnostic>) {
+    let volumes = [
+        ("master_vol", params.master_vol),
+        ("carrier_vol", params.carrier_vol),
+        ("harmonic_vol", params.harmonic_vol),
+        ("ping_vol", params.ping_vol),
+        ("chirp_vol", params.chirp_vol),
+        ("pad_vol", params.pad_vol),
+        ("breath_vol", params.breath_vol),
+        ("coherence.volume", params.coherence.volume),
+    ];
+    for (name, value) in volumes {
+        if !(0.0..=1.0).contains(&value) {
+            diagnostics.push(error(format!("{} is {} but volumes must be between 0 and 1", name, value)));
+        }
+    }
+
+    // A custom beat outside the mixer's range plays, but the first adjustment snaps it into range
+    let c = &params.coherence;
+    if matches!(c.being_type, BeingType::HumanCustom) && !(BEAT_MIN_HZ..=BEAT_MAX_HZ).contains(&c.custom_binaural_hz) {
+        diagnostics.push(warning(format!(
+            "coherence.custom_binaural_hz is {} Hz, outside the {}-{} Hz the mixer adjusts (adjusting it jumps into that range)",
+            c.custom_binaural_hz, BEAT_MIN_HZ, BEAT_MAX_HZ)));
+    }
+    if !(PING_FREQ_MIN_HZ..=PING_FREQ_MAX_HZ).contains(&params.ping_freq_hz) {
+        diagnostics.push(error(format!("ping_freq_hz is {} but must be between {} and {} Hz",
+            params.ping_freq_hz, PING_FREQ_MIN_HZ, PING_FREQ_MAX_HZ)));
+    }
+    if !(PORT_MIN..=PORT_MAX).contains(&params.stream_port) {
+        diagnostics.push(error(format!("stream_port is {} but must be between {} and {}",
+            params.stream_port, PORT_MIN, PORT_MAX)));
+    }
+    if params.rf_gain > RF_GAIN_MAX_DB {
+        diagnostics.push(error(format!("rf_gain is {} but the maximum is {} dB", params.rf_gain, RF_GAIN_MAX_DB)));
+    }
+
+    let phases = [
+        ("startup_duration_min", c.startup_duration_min),
+        ("induction_duration_min", c.induction_duration_min),
+        ("stabilization_duration_min", c.stabilization_duration_min),
+        ("return_duration_min", c.return_duration_min),
+    ];
+    for (name, value) in phases {
+        if value < 0.0 || !value.is_finite() {
+            diagnostics.push(error(format!("coherence.{} is {} but must be zero or more minutes", name, value)));
+        }
+    }
+}
+
+/// A custom beat that disagrees with its carriers plays the carriers' beat, not the declared one
+fn check_beat(migrated: &MigratedPreset, diagnostics: &mut Vec<Diagnostic>) {
+    let c = &migrated.params.coherence;
+    let declared = migrated.json.get("coherence").and_then(|c| c.get("custom_binaural_hz")).is_some();
+    if !c.enabled || !(declared || matches!(c.being_type, BeingType::HumanCustom)) {
+        return;
+    }
+    let beat = c.binaural_beat_hz();
+    if (beat - c.custom_binaural_hz).abs() > PRESET_LINT_BEAT_TOL_HZ {
+        diagnostics.push(warning(format!(
+            "coherence.custom_binaural_hz is {} Hz but the carriers ({} / {} Hz) play a {:.2} Hz beat",
+            c.custom_binaural_hz, c.left_carrier, c.right_carrier, beat)));
+    }
+}
+
+fn error(message: String) -> Diagnostic {
+    Diagnostic { severity: Severity::Error, message }
+}
+
+fn warning(message: String) -> Diagnostic {
+    Diagnostic { severity: Severity::Warning, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(json: &str) -> Vec<String> {
+        lint_preset(json).diagnostics.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn test_embedded_presets_are_clean() {
+        for preset in crate::embedded_presets::EMBEDDED_PRESETS {
+            let found: Vec<String> = lint_preset_in(preset.content, None).diagnostics.iter().map(|d| d.to_string()).collect();
+            if preset.filename == "DEFAULT_deep_focus_peak.json" {
+                // 40 Hz gamma is above what the mixer's beat control reaches
+                assert!(found.len() == 1 && found[0].starts_with("warning: coherence.custom_binaural_hz is 40 Hz"));
+            } else {
+                assert!(found.is_empty(), "{}: {:?}", preset.filename, found);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reports_problems() {
+        assert_eq!(messages("{\n  \"master_vol\": 3.0,\n  \"harmonc_vol\": 0.2\n}"), vec![
+            "warning: unknown field harmonc_vol was ignored (did you mean harmonic_vol?)",
+            "error: master_vol is 3 but volumes must be between 0 and 1",
+        ]);
+        assert_eq!(messages("{\n  \"master_vol\": \"loud\"\n}"),
+            vec!["error: line 2, column 22: invalid type: string \"loud\", expected f32"]);
+        assert_eq!(messages("{\n  \"master_vol\": 0.5,\n}"),
+            vec!["error: line 3, column 1: trailing comma"]);
+
+        let custom = r#"{"coherence": {"enabled": true, "being_type": "HumanCustom",
+            "left_carrier": 400.0, "right_carrier": 412.0, "custom_binaural_hz": 10.0}}"#;
+        assert_eq!(messages(custom),
+            vec!["warning: coherence.custom_binaural_hz is 10 Hz but the carriers (400 / 412 Hz) play a 12.00 Hz beat"]);
+        assert!(lint_preset(custom).into_params().is_ok());
+        assert!(lint_preset(r#"{"stream_port": 80}"#).into_params().err().unwrap().contains("stream_port is 80"));
+    }
+
+    #[test]
+    fn test_unknown_category_is_a_warning() {
+        let linted = lint_preset(r#"{"category": "meditation", "master_vol": 0.4}"#);
+        assert_eq!(linted.diagnostics.iter().map(|d| d.to_string()).collect::<Vec<_>>(),
+            vec!["warning: unknown category \"meditation\" is listed under Other (known: focus, sleep, monroe, animal, uap, rf)"]);
+        let params = linted.into_params().expect("preset still loads");
+        assert_eq!(params.category, None);
+        assert_eq!(params.master_vol, 0.4);
+        assert!(messages(r#"{"category": "sleep"}"#).is_empty());
+    }
+}
@@ -0,0 +1,324 @@
+// Preset schema versions: every preset carries `schema_version`, and older JSON is upgraded one
+// version at a time before it is deserialized, so reshaped fields are carried over instead of
+// silently falling back to defaults
+
+use std::path::Path;
+use serde_json::{Map, Value};
+use crate::audio::AudioParams;
+use crate::constants::*;
+use crate::preset_browser::PresetCategory;
+
+/// Upgrades a preset object by one schema version, noting anything it could not carry over
+type Migration = fn(&mut Map<String, Value>, &mut Vec<String>);
+
+/// `MIGRATIONS[n]` takes a version `n` preset to version `n + 1`
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1];
+
+/// A preset brought up to the current schema
+pub struct MigratedPreset {
+    pub params: AudioParams,
+    /// The upgraded JSON (unknown fields kept), as written back to disk
+    pub json: Value,
+    pub from_version: u32,
+    /// Fields dropped or ignored on the way
+    pub notes: Vec<String>,
+}
+
+impl MigratedPreset {
+    pub fn upgraded(&self) -> bool {
+        self.from_version < PRESET_SCHEMA_VERSION
+    }
+}
+
+/// Run every migration between the preset's schema version and the current one
+pub fn migrate_preset(json: &str) -> Result<MigratedPreset, String> {
+    let mut value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let obj = value.as_object_mut().ok_or("Preset is not a JSON object")?;
+
+    // Presets from before versioning have no schema_version at all
+    let from_version = match obj.get("schema_version") {
+        None => 0,
+        Some(v) => v.as_u64().ok_or("schema_version is not a whole number")? as u32,
+    };
+    if from_version > PRESET_SCHEMA_VERSION {
+        return Err(format!("Preset uses schema v{} but this build only reads up to v{} - update soulwhistle",
+            from_version, PRESET_SCHEMA_VERSION));
+    }
+
+    let mut notes = Vec::new();
+    for migration in &MIGRATIONS[from_version as usize..] {
+        migration(obj, &mut notes);
+    }
+    obj.insert("schema_version".to_string(), Value::from(PRESET_SCHEMA_VERSION));
+
+    let params: AudioParams = serde_json::from_value(value.clone()).map_err(|e| e.to_string())?;
+    let parsed = serde_json::to_value(&params).map_err(|e| e.to_string())?;
+    ignored_fields(&value, &parsed, "", &mut notes);
+
+    Ok(MigratedPreset { params, json: value, from_version, notes })
+}
+
+/// v0 -> v1: versioning starts here. Both earlier layouts (the flat signal-layer presets of v0.2
+/// and the current fields without a version) load as they are; the decoded-map format documented
+/// in data/README.md (name / binaural_beat_hz / carriers / focus_level) is rebuilt as coherence params.
+fn migrate_v0_to_v1(obj: &mut Map<String, Value>, notes: &mut Vec<String>) {
+    let decoded = ["carriers", "binaural_beat_hz", "focus_level"].iter().any(|k| obj.contains_key(*k));
+    if !decoded {
+        return;
+    }
+
+    if let Some(name) = obj.remove("name") {
+        obj.entry("preset_title").or_insert(name);
+    }
+    if let Some(Value::Array(lines)) = obj.remove("usage_notes") {
+        let text: Vec<&str> = lines.iter().filter_map(|l| l.as_str()).collect();
+        if !text.is_empty() {
+            obj.entry("preset_description").or_insert_with(|| Value::from(text.join(" ")));
+        }
+    }
+    if obj.remove("technical_details").is_some() {
+        notes.push("technical_details has no equivalent and was dropped".to_string());
+    }
+
+    let beat = obj.remove("binaural_beat_hz").and_then(|v| v.as_f64());
+    let carriers = obj.remove("carriers");
+    let carrier = |key: &str| carriers.as_ref().and_then(|c| c.get(key)).and_then(|v| v.as_f64());
+    let left = carrier("left_hz").unwrap_or(OPTIMAL_CARRIER_HZ as f64);
+    let right = carrier("right_hz").unwrap_or(left + beat.unwrap_or(0.0));
+    let being_type = match obj.remove("focus_level").and_then(|v| v.as_u64()) {
+        Some(10) => "HumanFocus10",
+        Some(12) => "HumanFocus12",
+        Some(15) => "HumanFocus15",
+        Some(21) => "HumanFocus21",
+        _ => "HumanCustom",
+    };
+
+    let coherence = obj.entry("coherence").or_insert_with(|| Value::Object(Map::new()));
+    if let Some(coherence) = coherence.as_object_mut() {
+        coherence.insert("enabled".to_string(), Value::from(true));
+        coherence.insert("being_type".to_string(), Value::from(being_type));
+        coherence.insert("left_carrier".to_string(), Value::from(left));
+        coherence.insert("right_carrier".to_string(), Value::from(right));
+        coherence.insert("custom_binaural_hz".to_string(), Value::from(beat.unwrap_or((right - left).abs())));
+    }
+    // Decoded maps are binaural-only, like the built-in Focus presets
+    obj.entry("lock_signal_layer").or_insert(Value::from(true));
+}
+
+/// Note every field of the input that did not survive parsing (unknown or renamed fields)
+fn ignored_fields(input: &Value, parsed: &Value, prefix: &str, notes: &mut Vec<String>) {
+    let (Some(input), Some(parsed)) = (input.as_object(), parsed.as_object()) else {
+        return;
+    };
+    for (key, value) in input {
+        match parsed.get(key) {
+            // A category string this version doesn't know is read as no category
+            None if prefix.is_empty() && key == "category" && value.is_string() => {
+                let known: Vec<String> = PresetCategory::ALL.iter().map(|c| c.label().to_lowercase()).collect();
+                notes.push(format!("unknown category {} is listed under Other (known: {})", value, known.join(", ")));
+            }
+            None if !value.is_null() => {
+                // Most unknown fields are typos of a real one
+                let suggestion = parsed.keys().filter(|k| !input.contains_key(*k))
+                    .map(|k| (edit_distance(key, k), k))
+                    .filter(|(d, _)| *d <= 2)
+                    .min()
+                    .map(|(_, k)| format!(" (did you mean {}{}?)", prefix, k))
+                    .unwrap_or_default();
+                notes.push(format!("unknown field {}{} was ignored{}", prefix, key, suggestion));
+            }
+            Some(inner) => ignored_fields(value, inner, &format!("{}{}.", prefix, key), notes),
+            None => {}
+        }
+    }
+}
+
+/// Levenshtein distance between two field names
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Presets rewritten (or left alone) by `upgrade_preset_dir`
+#[derive(Default)]
+pub struct UpgradeReport {
+    pub upgraded: Vec<String>,
+    pub failed: Vec<String>,
+    /// Fields the upgraded presets lost, as "file: note" (still in the backups)
+    pub notes: Vec<String>,
+}
+
+impl UpgradeReport {
+    /// One-line summary for the TUI status bar (None when there was nothing to do)
+    pub fn status_message(&self) -> Option<String> {
+        if !self.failed.is_empty() {
+            Some(format!("⚠️  Could not upgrade {}", self.failed.join(", ")))
+        } else if !self.upgraded.is_empty() {
+            let dropped = if self.notes.is_empty() {
+                String::new()
+            } else {
+                format!(" - {} field{} not carried over", self.notes.len(), if self.notes.len() == 1 { "" } else { "s" })
+            };
+            Some(format!("Upgraded {} preset{} to schema v{} (originals in {}/): {}{}",
+                self.upgraded.len(), if self.upgraded.len() == 1 { "" } else { "s" },
+                PRESET_SCHEMA_VERSION, PRESET_BACKUP_DIRNAME, self.upgraded.join(", "), dropped))
+        } else {
+            None
+        }
+    }
+}
+
+/// Migrate every outdated preset in a directory on disk, copying the original into the
+/// backup subdirectory first. A preset is only rewritten once its backup exists.
+pub fn upgrade_preset_dir(dir: &Path) -> UpgradeReport {
+    let mut report = UpgradeReport::default();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return report;
+    };
+    let mut paths: Vec<_> = entries.flatten().map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let Ok(json) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        match migrate_preset(&json) {
+            Ok(migrated) if migrated.upgraded() => match rewrite_with_backup(dir, &path, &migrated) {
+                Ok(()) => {
+                    report.notes.extend(migrated.notes.iter().map(|n| format!("{}: {}", name, n)));
+                    report.upgraded.push(name);
+                }
+                Err(e) => report.failed.push(format!("{}: {}", name, e)),
+            },
+            Ok(_) => {}
+            // Broken files are reported when loaded; only a too-new schema is worth flagging here
+            Err(e) if e.contains("schema v") => report.failed.push(format!("{}: {}", name, e)),
+            Err(_) => {}
+        }
+    }
+    report
+}
+
+fn rewrite_with_backup(dir: &Path, path: &Path, migrated: &MigratedPreset) -> Result<(), String> {
+    let backup_dir = dir.join(PRESET_BACKUP_DIRNAME);
+    std::fs::create_dir_all(&backup_dir).map_err(|e| e.to_string())?;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let backup = backup_dir.join(format!("{}.v{}-{}.json", stem, migrated.from_version, crate::session::unix_now()));
+    std::fs::copy(path, &backup).map_err(|e| format!("backup failed: {}", e))?;
+
+    // Write next to the original and rename over it so a failed write never truncates the preset
+    let json = serde_json::to_string_pretty(&migrated.json).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+    use crate::coherence::BeingType;
+
+    #[test]
+    fn test_flat_signal_preset() {
+        // v0.2 presets: signal layer only, no coherence section
+        let migrated = migrate_preset(include_str!("../data/preset.json")).unwrap();
+        assert_eq!(migrated.from_version, 0);
+        assert!(migrated.notes.is_empty());
+        assert_eq!(migrated.params.carrier_type, crate::audio::SignalType::Square);
+        assert_eq!(migrated.params.rf_freq_hz, 1_420_405_752);
+        assert!(!migrated.params.coherence.enabled);
+        assert_eq!(migrated.json["schema_version"], PRESET_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_decoded_map_preset() {
+        let json = r#"{
+            "name": "Focus 12 - Expanded Awareness",
+            "binaural_beat_hz": 1.5,
+            "carriers": { "left_hz": 350.0, "right_hz": 348.5 },
+            "focus_level": 12,
+            "technical_details": { "source": "Wave II" },
+            "usage_notes": ["Use headphones.", "Start after Focus 10."]
+        }"#;
+        let migrated = migrate_preset(json).unwrap();
+        let params = &migrated.params;
+        assert_eq!(params.preset_title.as_deref(), Some("Focus 12 - Expanded Awareness"));
+        assert_eq!(params.preset_description.as_deref(), Some("Use headphones. Start after Focus 10."));
+        assert!(params.coherence.enabled && params.lock_signal_layer);
+        assert!(matches!(params.coherence.being_type, BeingType::HumanFocus12));
+        assert_eq!((params.coherence.left_carrier, params.coherence.right_carrier), (350.0, 348.5));
+        assert_eq!(params.coherence.custom_binaural_hz, 1.5);
+        assert_eq!(migrated.notes, vec!["technical_details has no equivalent and was dropped"]);
+
+        // Without carriers the beat is placed on the optimal carrier
+        let params = migrate_preset(r#"{"name": "Beat", "binaural_beat_hz": 6.0, "focus_level": 3}"#).unwrap().params;
+        assert!(matches!(params.coherence.being_type, BeingType::HumanCustom));
+        assert_eq!(params.coherence.right_carrier - params.coherence.left_carrier, 6.0);
+    }
+
+    #[test]
+    fn test_unversioned_current_layout() {
+        let json = r#"{
+            "preset_title": "Old",
+            "master_vol": 0.4,
+            "coherence": { "enabled": true, "being_type": "HumanFocus10", "left_carrier": 400.0,
+                           "right_carrier": 404.11, "harmonic_220hz": true, "carier_volume": 0.3 },
+            "session_timer": 12.0
+        }"#;
+        let migrated = migrate_preset(json).unwrap();
+        assert!(migrated.upgraded());
+        assert_eq!(migrated.params.master_vol, 0.4);
+        assert!(migrated.params.coherence.harmonic_220hz);
+        assert_eq!(migrated.notes, vec![
+            "unknown field coherence.carier_volume was ignored",
+            "unknown field session_timer was ignored",
+        ]);
+    }
+
+    #[test]
+    fn test_embedded_presets_are_current() {
+        for preset in crate::embedded_presets::EMBEDDED_PRESETS {
+            let migrated = migrate_preset(preset.content).unwrap();
+            assert!(!migrated.upgraded(), "{} has no schema_version", preset.filename);
+            assert!(migrated.notes.is_empty(), "{}: {:?}", preset.filename, migrated.notes);
+        }
+    }
+
+    #[test]
+    fn test_newer_schema_rejected() {
+        let json = format!(r#"{{"schema_version": {}}}"#, PRESET_SCHEMA_VERSION + 1);
+        assert!(migrate_preset(&json).err().unwrap().contains("update soulwhistle"));
+    }
+
+    #[test]
+    fn test_upgrade_dir_backs_up_first() {
+        let dir = TempDir::new("schema");
+        std::fs::write(dir.join("old.json"), r#"{"master_vol": 0.3}"#).unwrap();
+        std::fs::write(dir.join("new.json"), format!(r#"{{"schema_version": {}}}"#, PRESET_SCHEMA_VERSION)).unwrap();
+
+        let report = upgrade_preset_dir(&dir);
+        assert_eq!(report.upgraded, vec!["old.json"]);
+        assert!(report.failed.is_empty());
+        let backups: Vec<_> = std::fs::read_dir(dir.join(PRESET_BACKUP_DIRNAME)).unwrap().flatten().collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(std::fs::read_to_string(backups[0].path()).unwrap(), r#"{"master_vol": 0.3}"#);
+        assert!(!migrate_preset(&std::fs::read_to_string(dir.join("old.json")).unwrap()).unwrap().upgraded());
+
+        // Nothing left to do on the next start
+        assert!(upgrade_preset_dir(&dir).status_message().is_none());
+    }
+}
@@ -0,0 +1,362 @@
+// Updates of the built-in presets: the user directory keeps copies of the DEFAULT_ presets, so
+// each copy's origin is recorded and a newer built-in version replaces copies the user never
+// touched. Edited copies get a field-by-field merge instead.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde_json::{Map, Value};
+use crate::constants::*;
+use crate::embedded_presets::EMBEDDED_PRESETS;
+use crate::preset_files::{move_to_trash, Trashed};
+use crate::preset_schema::migrate_preset;
+
+/// Fingerprint of a preset's content. Hashes the migrated JSON with sorted keys, so
+/// reformatting or a schema upgrade of an untouched copy doesn't count as an edit.
+pub fn preset_hash(json: &str) -> Option<String> {
+    let canonical = migrate_preset(json).ok()?.json.to_string();
+    // FNV-1a: stable across builds and Rust versions, unlike std's hasher
+    let hash = canonical.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+    Some(format!("{:016x}", hash))
+}
+
+/// Hashes of the built-in presets in releases before installs were recorded (v0.2.0). An
+/// unrecorded copy matching one of these is an untouched old install, not an edit.
+const RELEASED_DEFAULTS: &[(&str, &str)] = &[
+    ("DEFAULT_actual_dog_whistle.json", "a0e9d0c6ef0859e5"),
+    ("DEFAULT_deep_focus_active.json", "8a4d883f0d8c3df9"),
+    ("DEFAULT_deep_focus_adhd.json", "a843b29ebe2b842e"),
+    ("DEFAULT_deep_focus_calm.json", "b72d296e54ddf832"),
+    ("DEFAULT_deep_focus_peak.json", "594fbebf255c71cf"),
+    ("DEFAULT_explore_all.json", "6af7ea9b93d63509"),
+    ("DEFAULT_focus_10_mind_awake.json", "d6b82f3ad30b6de5"),
+    ("DEFAULT_focus_12_expanded.json", "8a995975d3a642d2"),
+    ("DEFAULT_focus_15_no_time.json", "5f08b36ba7e932ce"),
+    ("DEFAULT_focus_21_bridge.json", "0ef26d4a48da1979"),
+    ("DEFAULT_uap_brycehelm.json", "c39eab9143bb45b0"),
+    ("DEFAULT_uap_enigmatic_ideas.json", "702539ff2bed6caf"),
+    ("DEFAULT_uap_frequencies.json", "175ba712fed73636"),
+    ("DEFAULT_uap_rf_ultrasonic.json", "dded2f0ebf7d2f29"),
+    ("DEFAULT_uap_sweep_18khz.json", "323b3eb0d4f1050c"),
+    ("DEFAULT_uap_sweep_24khz.json", "f399d84c115c7768"),
+];
+
+/// Built-in version each DEFAULT_ copy was installed from (filename -> preset hash)
+fn load_installed(dir: &Path) -> BTreeMap<String, String> {
+    std::fs::read_to_string(dir.join(PRESET_DEFAULTS_MANIFEST))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_installed(dir: &Path, installed: &BTreeMap<String, String>) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(installed).map_err(std::io::Error::other)?;
+    std::fs::write(dir.join(PRESET_DEFAULTS_MANIFEST), json)
+}
+
+/// How a DEFAULT_ copy in the user directory relates to the built-in version
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum CopyState {
+    Missing,
+    Current,
+    /// Untouched copy of an older built-in version
+    Outdated,
+    /// Edited by the user; the built-in version hasn't changed since it was installed
+    Edited,
+    /// Differs from a built-in version that changed (or, unrecorded, from every released version)
+    NeedsMerge,
+}
+
+fn copy_state(dir: &Path, installed: &BTreeMap<String, String>, name: &str, builtin_hash: &str) -> CopyState {
+    let Ok(content) = std::fs::read_to_string(dir.join(name)) else {
+        return CopyState::Missing;
+    };
+    let copy_hash = preset_hash(&content);
+    let recorded = installed.get(name).map(String::as_str);
+    if copy_hash.as_deref() == Some(builtin_hash) {
+        CopyState::Current
+    } else if recorded == Some(builtin_hash) {
+        CopyState::Edited
+    } else if copy_hash.as_deref().is_some_and(|hash| match recorded {
+        Some(recorded) => hash == recorded,
+        None => RELEASED_DEFAULTS.contains(&(name, hash)),
+    }) {
+        CopyState::Outdated
+    } else {
+        CopyState::NeedsMerge
+    }
+}
+
+/// What `sync_defaults` did
+#[derive(Default)]
+pub struct DefaultsSync {
+    pub installed: Vec<String>,
+    pub updated: Vec<String>,
+    pub needs_merge: Vec<String>,
+}
+
+impl DefaultsSync {
+    /// One-line summary for the TUI status bar (None when nothing changed)
+    pub fn status_message(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if !self.updated.is_empty() {
+            parts.push(format!("Updated {} built-in preset{}: {}", self.updated.len(),
+                if self.updated.len() == 1 { "" } else { "s" }, self.updated.join(", ")));
+        }
+        if !self.needs_merge.is_empty() {
+            parts.push(format!("{} edited built-in preset{} have updates - press m on them in the preset list to merge",
+                self.needs_merge.len(), if self.needs_merge.len() == 1 { "" } else { "s" }));
+        }
+        if parts.is_empty() { None } else { Some(parts.join(" | ")) }
+    }
+}
+
+/// Install missing built-in presets and update untouched copies of older versions.
+/// Edited copies are left alone and listed for merging.
+pub fn sync_defaults(dir: &Path) -> std::io::Result<DefaultsSync> {
+    let mut installed = load_installed(dir);
+    let mut sync = DefaultsSync::default();
+
+    for preset in EMBEDDED_PRESETS {
+        let Some(builtin_hash) = preset_hash(preset.content) else { continue; };
+        match copy_state(dir, &installed, preset.filename, &builtin_hash) {
+            CopyState::Missing => {
+                std::fs::write(dir.join(preset.filename), preset.content)?;
+                sync.installed.push(preset.filename.to_string());
+            }
+            CopyState::Outdated => {
+                std::fs::write(dir.join(preset.filename), preset.content)?;
+                sync.updated.push(preset.filename.to_string());
+            }
+            CopyState::Current => {}
+            CopyState::Edited => continue,
+            CopyState::NeedsMerge => {
+                sync.needs_merge.push(preset.filename.to_string());
+                continue;
+            }
+        }
+        installed.insert(preset.filename.to_string(), builtin_hash);
+    }
+
+    save_installed(dir, &installed)?;
+    Ok(sync)
+}
+
+/// Built-in presets whose edited copies have an update waiting to be merged
+pub fn pending_merges(dir: &Path) -> Vec<String> {
+    let installed = load_installed(dir);
+    EMBEDDED_PRESETS.iter()
+        .filter(|p| preset_hash(p.content).is_some_and(|hash| copy_state(dir, &installed, p.filename, &hash) == CopyState::NeedsMerge))
+        .map(|p| p.filename.to_string())
+        .collect()
+}
+
+/// A field whose value differs between the user's copy and the built-in version
+pub struct FieldDiff {
+    pub path: String, // Dotted, e.g. "coherence.volume"
+    pub mine: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+/// Leaf fields of a preset by dotted path (nested objects are walked, arrays are values)
+fn flatten(value: &Value, prefix: &str, out: &mut BTreeMap<String, Value>) {
+    match value.as_object() {
+        Some(obj) => {
+            for (key, inner) in obj {
+                flatten(inner, &format!("{}{}.", prefix, key), out);
+            }
+        }
+        None => {
+            out.insert(prefix.trim_end_matches('.').to_string(), value.clone());
+        }
+    }
+}
+
+pub fn field_diffs(mine: &Value, theirs: &Value) -> Vec<FieldDiff> {
+    let (mut a, mut b) = (BTreeMap::new(), BTreeMap::new());
+    flatten(mine, "", &mut a);
+    flatten(theirs, "", &mut b);
+    let mut paths: Vec<&String> = a.keys().chain(b.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    paths.into_iter()
+        .filter(|path| a.get(*path) != b.get(*path))
+        .map(|path| FieldDiff { path: path.clone(), mine: a.get(path).cloned(), theirs: b.get(path).cloned() })
+        .collect()
+}
+
+/// Set (or with None, remove) a field by dotted path
+fn set_path(root: &mut Value, path: &str, value: Option<Value>) {
+    let mut node = root;
+    let mut keys: Vec<&str> = path.split('.').collect();
+    let last = keys.pop().unwrap_or_default();
+    for key in keys {
+        let Some(obj) = node.as_object_mut() else { return; };
+        node = obj.entry(key).or_insert_with(|| Value::Object(Map::new()));
+    }
+    if let Some(obj) = node.as_object_mut() {
+        match value {
+            Some(value) => { obj.insert(last.to_string(), value); }
+            None => { obj.remove(last); }
+        }
+    }
+}
+
+/// Field-by-field merge of an edited DEFAULT_ copy with the new built-in version
+pub struct PresetMerge {
+    pub filename: String,
+    mine: Value,
+    builtin_hash: String,
+    pub diffs: Vec<FieldDiff>,
+    pub take_theirs: Vec<bool>, // Per diff: use the built-in value (default: keep the user's)
+    pub selected: usize,
+}
+
+impl PresetMerge {
+    pub fn open(dir: &Path, filename: &str) -> Result<Self, String> {
+        let builtin = EMBEDDED_PRESETS.iter().find(|p| p.filename == filename)
+            .ok_or_else(|| format!("{} is not a built-in preset", filename))?;
+        let content = std::fs::read_to_string(dir.join(filename)).map_err(|e| format!("Could not read {}: {}", filename, e))?;
+        let mine = migrate_preset(&content).map_err(|e| format!("{} is invalid: {}", filename, e))?.json;
+        let theirs = migrate_preset(builtin.content).map_err(|e| format!("Built-in {} is invalid: {}", filename, e))?.json;
+        let diffs = field_diffs(&mine, &theirs);
+        Ok(Self {
+            filename: filename.to_string(),
+            mine,
+            builtin_hash: preset_hash(builtin.content).unwrap_or_default(),
+            take_theirs: vec![false; diffs.len()],
+            diffs,
+            selected: 0,
+        })
+    }
+
+    pub fn toggle(&mut self) {
+        if let Some(take) = self.take_theirs.get_mut(self.selected) {
+            *take = !*take;
+        }
+    }
+
+    /// Take every built-in value (or keep every one of the user's, when all were taken)
+    pub fn toggle_all(&mut self) {
+        let all = self.take_theirs.iter().all(|t| *t);
+        self.take_theirs.iter_mut().for_each(|t| *t = !all);
+    }
+
+    pub fn merged(&self) -> Value {
+        let mut merged = self.mine.clone();
+        for (diff, take) in self.diffs.iter().zip(&self.take_theirs) {
+            if *take {
+                set_path(&mut merged, &diff.path, diff.theirs.clone());
+            }
+        }
+        merged
+    }
+
+    /// Write the merge (the user's previous copy goes to the trash) and record the built-in
+    /// version it was merged with, so the same update isn't offered again
+    pub fn apply(&self, dir: &Path) -> Result<Trashed, String> {
+        let json = serde_json::to_string_pretty(&self.merged()).map_err(|e| e.to_string())?;
+        let trashed = move_to_trash(dir, &self.filename)?;
+        std::fs::write(dir.join(&self.filename), json).map_err(|e| format!("Could not write {}: {}", self.filename, e))?;
+        let mut installed = load_installed(dir);
+        installed.insert(self.filename.clone(), self.builtin_hash.clone());
+        save_installed(dir, &installed).map_err(|e| e.to_string())?;
+        Ok(trashed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedded_presets::EmbeddedPreset;
+    use crate::utils::TempDir;
+
+    /// Installed defaults where both copies came from an older release and the user edited the second
+    fn stale_defaults(name: &str) -> (TempDir, &'static EmbeddedPreset, &'static EmbeddedPreset) {
+        let dir = TempDir::new(name);
+        let (first, second) = (&EMBEDDED_PRESETS[0], &EMBEDDED_PRESETS[1]);
+        sync_defaults(&dir).unwrap();
+
+        let old = first.content.replace("\"master_vol\": 0.2", "\"master_vol\": 0.25");
+        assert_ne!(old, first.content);
+        std::fs::write(dir.join(first.filename), &old).unwrap();
+        let edited = second.content.replace("\"schema_version\": 1,", "\"schema_version\": 1, \"seed\": 7,");
+        std::fs::write(dir.join(second.filename), &edited).unwrap();
+        let mut installed = load_installed(&dir);
+        installed.insert(first.filename.to_string(), preset_hash(&old).unwrap());
+        installed.insert(second.filename.to_string(), "0000000000000000".to_string());
+        save_installed(&dir, &installed).unwrap();
+        (dir, first, second)
+    }
+
+    #[test]
+    fn test_first_sync_installs_every_default() {
+        let dir = TempDir::new("defaults_install");
+        let sync = sync_defaults(&dir).unwrap();
+        assert_eq!(sync.installed.len(), EMBEDDED_PRESETS.len());
+        assert!(sync_defaults(&dir).unwrap().status_message().is_none());
+    }
+
+    #[test]
+    fn test_sync_updates_untouched_copies_and_flags_edited_ones() {
+        let (dir, first, second) = stale_defaults("defaults_update");
+        let sync = sync_defaults(&dir).unwrap();
+        assert_eq!(sync.updated, vec![first.filename]);
+        assert_eq!(std::fs::read_to_string(dir.join(first.filename)).unwrap(), first.content);
+        assert_eq!(sync.needs_merge, vec![second.filename]);
+        assert_eq!(pending_merges(&dir), vec![second.filename]);
+    }
+
+    #[test]
+    fn test_applied_merge_is_not_offered_again() {
+        let (dir, _, second) = stale_defaults("defaults_merge");
+        sync_defaults(&dir).unwrap();
+
+        // Keep the user's seed
+        let merge = PresetMerge::open(&dir, second.filename).unwrap();
+        assert_eq!(merge.diffs.len(), 1);
+        assert_eq!(merge.diffs[0].path, "seed");
+        assert!(merge.diffs[0].theirs.is_none());
+        merge.apply(&dir).unwrap();
+        assert!(pending_merges(&dir).is_empty());
+        assert!(sync_defaults(&dir).unwrap().status_message().is_none());
+    }
+
+    #[test]
+    fn test_updates_untouched_copies_from_before_recording() {
+        let dir = TempDir::new("defaults_legacy");
+        let (first, second) = (&EMBEDDED_PRESETS[0], &EMBEDDED_PRESETS[1]);
+
+        // v0.2.0 copies had no schema version, category or tags, and no install record
+        let released = |content: &str| {
+            let mut json: Value = serde_json::from_str(content).unwrap();
+            for key in ["schema_version", "category", "tags"] {
+                json.as_object_mut().unwrap().remove(key);
+            }
+            serde_json::to_string_pretty(&json).unwrap()
+        };
+        let old = released(first.content);
+        assert!(RELEASED_DEFAULTS.contains(&(first.filename, preset_hash(&old).unwrap().as_str())));
+        std::fs::write(dir.join(first.filename), &old).unwrap();
+        let edited = released(second.content).replace("\"master_vol\": 0.6", "\"master_vol\": 0.55");
+        std::fs::write(dir.join(second.filename), &edited).unwrap();
+
+        let sync = sync_defaults(&dir).unwrap();
+        assert_eq!(sync.updated, vec![first.filename]);
+        assert_eq!(std::fs::read_to_string(dir.join(first.filename)).unwrap(), first.content);
+        assert_eq!(sync.needs_merge, vec![second.filename]);
+    }
+
+    #[test]
+    fn test_merge_takes_chosen_fields() {
+        let mine = serde_json::json!({"master_vol": 0.5, "coherence": {"volume": 0.3, "enabled": true}, "seed": 4});
+        let theirs = serde_json::json!({"master_vol": 0.6, "coherence": {"volume": 0.8, "enabled": true}});
+        let diffs = field_diffs(&mine, &theirs);
+        let paths: Vec<&str> = diffs.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec!["coherence.volume", "master_vol", "seed"]);
+
+        let mut merged = mine.clone();
+        set_path(&mut merged, "coherence.volume", Some(serde_json::json!(0.8)));
+        set_path(&mut merged, "seed", None);
+        assert_eq!(merged, serde_json::json!({"master_vol": 0.5, "coherence": {"volume": 0.8, "enabled": true}}));
+    }
+}
@@ -0,0 +1,343 @@
+// Experiment protocols: a whole study visit (silent baseline, presets, questionnaires) described in
+// one JSON file and run step by step, with everything the visit produces written to one run folder
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::audio::AudioParams;
+use crate::coherence::BeingType;
+use crate::constants::*;
+use crate::hrv::HrvSummary;
+use crate::session::{unix_now, ReportQuestion, SessionReport};
+
+/// One step of a protocol file
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProtocolStep {
+    /// Audio off (e.g. a resting baseline)
+    Silence {
+        minutes: f32,
+        #[serde(default)]
+        label: Option<String>,
+    },
+    /// Play a preset, for its own session length unless `minutes` is given
+    Preset {
+        preset: String,
+        #[serde(default)]
+        minutes: Option<f32>,
+        #[serde(default)]
+        label: Option<String>,
+    },
+    /// The post-session report form plus any extra questions; waits for the participant
+    Questionnaire {
+        #[serde(default)]
+        questions: Vec<ReportQuestion>,
+        #[serde(default)]
+        label: Option<String>,
+    },
+}
+
+/// A protocol file
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Protocol {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub steps: Vec<ProtocolStep>,
+}
+
+/// A step checked and ready to run (preset steps already loaded)
+pub struct PlannedStep {
+    pub step: ProtocolStep,
+    pub label: String,
+    pub duration_secs: Option<f32>, // None for questionnaires, which wait for the participant
+    pub params: Option<AudioParams>,
+}
+
+impl PlannedStep {
+    fn kind(&self) -> &'static str {
+        match self.step {
+            ProtocolStep::Silence { .. } => "silence",
+            ProtocolStep::Preset { .. } => "preset",
+            ProtocolStep::Questionnaire { .. } => "questionnaire",
+        }
+    }
+}
+
+/// Read a protocol file and load every preset it uses, so a typo fails before the visit starts
+/// Preset names are looked up next to the protocol file first, then like `--preset`
+pub fn load_protocol(path: &Path) -> Result<(Protocol, Vec<PlannedStep>), String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read protocol {}: {}", path.display(), e))?;
+    let protocol: Protocol = serde_json::from_str(&json).map_err(|e| format!("Invalid protocol {}: {}", path.display(), e))?;
+    if protocol.steps.is_empty() {
+        return Err(format!("Protocol {} has no steps", path.display()));
+    }
+
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let valid_minutes = |minutes: f32, n: usize| {
+        if minutes.is_finite() && minutes > 0.0 && minutes <= PROTOCOL_STEP_MAX_MIN {
+            Ok(minutes * 60.0)
+        } else {
+            Err(format!("Step {}: minutes must be between 0 and {}", n, PROTOCOL_STEP_MAX_MIN))
+        }
+    };
+
+    let mut planned = Vec::with_capacity(protocol.steps.len());
+    for (i, step) in protocol.steps.iter().enumerate() {
+        let n = i + 1;
+        let (label, duration_secs, params) = match step {
+            ProtocolStep::Silence { minutes, label } => {
+                (label.clone().unwrap_or_else(|| "Silence".to_string()), Some(valid_minutes(*minutes, n)?), None)
+            }
+            ProtocolStep::Preset { preset, minutes, label } => {
+                let local = base_dir.join(preset);
+                let name = if local.is_file() { local.display().to_string() } else { preset.clone() };
+                let params = crate::headless::load_preset_params(Some(&name)).map_err(|e| format!("Step {}: {}", n, e))?;
+                let minutes = match minutes {
+                    Some(m) => *m,
+                    None if params.coherence.enabled && params.coherence.being_type != BeingType::Unknown => {
+                        params.coherence.total_session_min()
+                    }
+                    None => return Err(format!("Step {}: preset '{}' has no session length, give \"minutes\"", n, preset)),
+                };
+                let label = label.clone().or_else(|| params.preset_title.clone()).unwrap_or_else(|| preset.clone());
+                (label, Some(valid_minutes(minutes, n)?), Some(params))
+            }
+            ProtocolStep::Questionnaire { label, .. } => {
+                (label.clone().unwrap_or_else(|| "Questionnaire".to_string()), None, None)
+            }
+        };
+        planned.push(PlannedStep { step: step.clone(), label, duration_secs, params });
+    }
+    Ok((protocol, planned))
+}
+
+/// How a run ended (or that it is still going)
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Completed,
+    Aborted,
+}
+
+/// What happened in one step
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct StepResult {
+    pub step: usize, // 1-based
+    pub kind: String,
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    pub started_at_unix: u64,
+    pub ended_at_unix: u64,
+    pub elapsed_secs: f32, // Excluding pauses
+    pub skipped: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<SessionReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hrv: Option<HrvSummary>,
+    /// Manifest to regenerate the step's audio (steps with sound only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<String>,
+}
+
+/// The run log saved as run.json in the run folder (rewritten after every step)
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RunLog {
+    pub protocol: String,
+    pub title: Option<String>,
+    pub started_at_unix: u64,
+    pub ended_at_unix: Option<u64>,
+    pub status: RunStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eeg_recording: Option<String>,
+    pub steps: Vec<StepResult>,
+}
+
+/// A protocol being run: the current step, its pausable timer and the log so far
+pub struct ProtocolRun {
+    pub dir: PathBuf,
+    pub steps: Vec<PlannedStep>,
+    pub current: usize,
+    pub log: RunLog,
+    step_started_at: u64,
+    step_elapsed: f32,               // Seconds run before the last pause
+    step_resumed: Option<Instant>,   // None while paused
+}
+
+impl ProtocolRun {
+    /// Create the run folder (runs directory, named after the protocol and start time) with a copy
+    /// of the protocol, and start the first step
+    pub fn start(path: &Path, protocol: &Protocol, steps: Vec<PlannedStep>) -> std::io::Result<Self> {
+        let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "protocol".to_string());
+        let started_at = unix_now();
+        let dir = get_runs_dir().join(format!("{}_{}", name, started_at));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::copy(path, dir.join(PROTOCOL_COPY_FILENAME))?;
+
+        let run = Self {
+            dir,
+            steps,
+            current: 0,
+            log: RunLog {
+                protocol: name,
+                title: protocol.title.clone(),
+                started_at_unix: started_at,
+                ended_at_unix: None,
+                status: RunStatus::Running,
+                eeg_recording: None,
+                steps: Vec::new(),
+            },
+            step_started_at: started_at,
+            step_elapsed: 0.0,
+            step_resumed: Some(Instant::now()),
+        };
+        run.save_log()?;
+        Ok(run)
+    }
+
+    pub fn current_step(&self) -> Option<&PlannedStep> {
+        self.steps.get(self.current)
+    }
+
+    /// Seconds the current step has run, not counting pauses
+    pub fn elapsed_secs(&self, now: Instant) -> f32 {
+        self.step_elapsed + self.step_resumed.map_or(0.0, |t| now.duration_since(t).as_secs_f32())
+    }
+
+    /// True when a timed step has run its length
+    pub fn step_due(&self, now: Instant) -> bool {
+        self.current_step()
+            .and_then(|s| s.duration_secs)
+            .is_some_and(|duration| self.elapsed_secs(now) >= duration)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.step_resumed.is_none()
+    }
+
+    /// Pause or resume the step timer; returns true if now paused
+    pub fn toggle_pause(&mut self, now: Instant) -> bool {
+        match self.step_resumed.take() {
+            Some(resumed) => {
+                self.step_elapsed += now.duration_since(resumed).as_secs_f32();
+                true
+            }
+            None => {
+                self.step_resumed = Some(now);
+                false
+            }
+        }
+    }
+
+    /// Log the current step and move on to the next; returns false when the protocol is over
+    pub fn finish_step(&mut self, now: Instant, skipped: bool, report: Option<SessionReport>, hrv: Option<HrvSummary>) -> bool {
+        let Some(step) = self.steps.get(self.current) else {
+            return false;
+        };
+        let ended_at = unix_now();
+        self.log.steps.push(StepResult {
+            step: self.current + 1,
+            kind: step.kind().to_string(),
+            label: step.label.clone(),
+            preset: match &step.step {
+                ProtocolStep::Preset { preset, .. } => Some(preset.clone()),
+                _ => None,
+            },
+            started_at_unix: self.step_started_at,
+            ended_at_unix: ended_at,
+            elapsed_secs: self.elapsed_secs(now),
+            skipped,
+            report,
+            hrv,
+            manifest: None,
+        });
+
+        self.current += 1;
+        self.step_started_at = ended_at;
+        self.step_elapsed = 0.0;
+        self.step_resumed = Some(now);
+        self.current < self.steps.len()
+    }
+
+    /// Close the log as completed or aborted
+    pub fn end(&mut self, status: RunStatus) {
+        self.log.status = status;
+        self.log.ended_at_unix = Some(unix_now());
+    }
+
+    pub fn save_log(&self) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.log)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        std::fs::write(self.dir.join(PROTOCOL_RUN_LOG_FILENAME), json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::utils::TempDir;
+
+    fn silence(minutes: f32, label: &str) -> PlannedStep {
+        PlannedStep {
+            step: ProtocolStep::Silence { minutes, label: Some(label.to_string()) },
+            label: label.to_string(),
+            duration_secs: Some(minutes * 60.0),
+            params: None,
+        }
+    }
+
+    #[test]
+    fn test_load_protocol_steps() {
+        let json = r#"{
+            "title": "Visit 1",
+            "steps": [
+                { "type": "silence", "minutes": 5, "label": "Baseline" },
+                { "type": "preset", "preset": "DEFAULT_deep_focus_calm" },
+                { "type": "questionnaire", "questions": [{ "prompt": "Felt vibrations", "kind": "yes_no" }] }
+            ]
+        }"#;
+        let dir = TempDir::new("protocol");
+        let path = dir.join("visit.json");
+        std::fs::write(&path, json).unwrap();
+        let (_, steps) = load_protocol(&path).unwrap();
+        assert_eq!(steps[0].label, "Baseline");
+        assert_eq!(steps[0].duration_secs, Some(300.0));
+        assert!(steps[1].params.is_some() && steps[1].duration_secs.unwrap() > 0.0);
+        assert_eq!(steps[2].duration_secs, None);
+
+        std::fs::write(&path, r#"{ "steps": [{ "type": "preset", "preset": "no_such_preset" }] }"#).unwrap();
+        assert!(load_protocol(&path).is_err());
+    }
+
+    #[test]
+    fn test_pauses_do_not_count_towards_the_step() {
+        let steps = vec![
+            silence(5.0, "Baseline"),
+            silence(1.0, "Rest"),
+        ];
+        let t0 = Instant::now();
+        let mut run = ProtocolRun {
+            dir: PathBuf::new(),
+            steps,
+            current: 0,
+            log: RunLog {
+                protocol: "visit".to_string(), title: None, started_at_unix: 0, ended_at_unix: None,
+                status: RunStatus::Running, eeg_recording: None, steps: Vec::new(),
+            },
+            step_started_at: 0,
+            step_elapsed: 0.0,
+            step_resumed: Some(t0),
+        };
+        assert!(run.toggle_pause(t0 + Duration::from_secs(200)));
+        assert!(!run.step_due(t0 + Duration::from_secs(400)));
+        run.toggle_pause(t0 + Duration::from_secs(400));
+        assert!(run.step_due(t0 + Duration::from_secs(500)));
+        assert!(run.finish_step(t0 + Duration::from_secs(500), false, None, None));
+        assert_eq!(run.log.steps[0].elapsed_secs, 300.0);
+        assert_eq!(run.current_step().unwrap().label, "Rest");
+    }
+}
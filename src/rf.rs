@@ -32,6 +32,7 @@ pub struct RfWorker {
 impl RfWorker {
     pub fn new(params: Arc<Mutex<AudioParams>>, error_tx: std::sync::mpsc::Sender<String>, audio_sample_rate: f32) -> Self {
         let hackrf_available = Self::check_hackrf_available();
+        let seed = params.lock().session_seed;
         Self {
             params,
             child: None,
@@ -40,7 +41,7 @@ impl RfWorker {
             running: false,
             hackrf_available,
             last_check_time: std::time::Instant::now(),
-            synth: Synthesizer::new(audio_sample_rate),
+            synth: Synthesizer::new(audio_sample_rate, seed),
             phase_accumulator: 0.0,
             audio_sample_rate,
             error_tx,
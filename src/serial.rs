@@ -0,0 +1,59 @@
+// Serial port access for biosignal devices (OpenBCI dongles, heart-rate bridges)
+// Ports are plain files; on Unix the line is switched to raw mode at the requested baud rate
+
+use std::fs::{File, OpenOptions};
+use std::io;
+
+/// Open a serial device for reading and writing
+/// Reads time out after SERIAL_READ_TIMEOUT_DS tenths of a second and return 0 bytes,
+/// so reader threads can check their stop flags on a silent line
+#[cfg(unix)]
+pub fn open_serial(path: &str, baud: u32) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use crate::constants::SERIAL_READ_TIMEOUT_DS;
+
+    let speed = match baud {
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unsupported baud rate {}", baud))),
+    };
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(path)?;
+
+    // SAFETY: fd is a valid open descriptor for the lifetime of `file`; termios is fully
+    // initialised by tcgetattr before being modified
+    unsafe {
+        let fd = file.as_raw_fd();
+        let mut tio: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut tio) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        libc::cfmakeraw(&mut tio);
+        libc::cfsetispeed(&mut tio, speed);
+        libc::cfsetospeed(&mut tio, speed);
+        tio.c_cflag |= libc::CLOCAL | libc::CREAD;
+        tio.c_cc[libc::VMIN] = 0;
+        tio.c_cc[libc::VTIME] = SERIAL_READ_TIMEOUT_DS;
+        if libc::tcsetattr(fd, libc::TCSANOW, &tio) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(file)
+}
+
+/// Open a serial device for reading and writing
+/// Line settings are left as configured by the OS (set the baud rate with the device manager)
+#[cfg(not(unix))]
+pub fn open_serial(path: &str, _baud: u32) -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).open(path)
+}
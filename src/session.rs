@@ -0,0 +1,331 @@
+// Session records and post-session subjective reports
+// Turns a listening session into a data point for small n-of-1 studies
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use crate::constants::*;
+use crate::blind::BlindRun;
+use crate::hrv::HrvSummary;
+
+/// Kind of answer a report question expects
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum QuestionKind {
+    Scale,  // Integer rating between REPORT_SCALE_MIN and REPORT_SCALE_MAX
+    YesNo,  // Boolean answer
+    Text,   // Free-text answer
+}
+
+/// A question asked in the post-session report (built-in or defined by a preset)
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct ReportQuestion {
+    pub prompt: String,
+    #[serde(default = "default_question_kind")]
+    pub kind: QuestionKind,
+}
+
+fn default_question_kind() -> QuestionKind {
+    QuestionKind::Scale
+}
+
+/// Read an experiment's report questions: a JSON list of questions, or an object with `report_questions`
+pub fn load_questions(path: &Path) -> Result<Vec<ReportQuestion>, String> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum QuestionFile {
+        List(Vec<ReportQuestion>),
+        Experiment { report_questions: Vec<ReportQuestion> },
+    }
+
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read questions {}: {}", path.display(), e))?;
+    match serde_json::from_str(&json) {
+        Ok(QuestionFile::List(questions) | QuestionFile::Experiment { report_questions: questions }) => Ok(questions),
+        Err(e) => Err(format!("Invalid questions {}: {}", path.display(), e)),
+    }
+}
+
+/// Answer to a single report question
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum ReportValue {
+    Scale(u8),
+    YesNo(bool),
+    Text(String),
+}
+
+/// Answer to a custom (preset-defined) question
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ReportAnswer {
+    pub question: String,
+    pub answer: ReportValue,
+}
+
+/// Structured self-report collected after a session
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SessionReport {
+    pub depth: u8,
+    pub sleepiness: u8,
+    pub imagery: u8,
+    pub note: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub answers: Vec<ReportAnswer>,
+}
+
+/// One listening session as saved to the sessions directory
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SessionRecord {
+    pub preset: Option<String>,
+    pub preset_title: Option<String>,
+    pub started_at_unix: u64,
+    pub ended_at_unix: u64,
+    pub duration_secs: f32,
+    pub completed: bool, // True if the session ran its full length, false if stopped early
+    pub report: Option<SessionReport>,
+    /// EDF+/BDF+ file the EEG was being recorded to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eeg_recording: Option<String>,
+    /// Heart rate, HRV and breath pacing, when a heart sensor was connected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hrv: Option<HrvSummary>,
+    /// Study, participant and neutral code of a blinded session (the condition stays in the study key)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blind: Option<BlindRun>,
+    /// Manifest to regenerate the session's audio (soulwhistle render)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<String>,
+}
+
+impl SessionRecord {
+    /// Write the record as `session_<started_at>.json` in the sessions directory
+    pub fn save(&self) -> std::io::Result<PathBuf> {
+        self.save_in(&get_sessions_dir())
+    }
+
+    /// Write the record to `dir`, numbering it (`session_<started_at>_2.json`, ...) rather than
+    /// replacing another session that started in the same second
+    pub fn save_in(&self, dir: &Path) -> std::io::Result<PathBuf> {
+        use std::io::Write;
+        std::fs::create_dir_all(dir)?;
+
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        for n in 1.. {
+            let path = match n {
+                1 => dir.join(format!("session_{}.json", self.started_at_unix)),
+                n => dir.join(format!("session_{}_{}.json", self.started_at_unix, n)),
+            };
+            match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => return file.write_all(json.as_bytes()).map(|_| path),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        unreachable!()
+    }
+}
+
+/// Current time as seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// A report question together with the answer being edited
+pub struct ReportField {
+    pub question: ReportQuestion,
+    pub value: ReportValue,
+}
+
+/// Editable report form shown in the TUI report mode
+pub struct ReportForm {
+    pub fields: Vec<ReportField>,
+    pub selected: usize,
+    pub completed: bool,
+}
+
+// Indices of the built-in questions (custom questions follow)
+const DEPTH_FIELD: usize = 0;
+const SLEEPINESS_FIELD: usize = 1;
+const IMAGERY_FIELD: usize = 2;
+const NOTE_FIELD: usize = 3;
+const BUILTIN_FIELD_COUNT: usize = 4;
+
+impl ReportForm {
+    /// Build a form with the built-in questions followed by any custom questions
+    pub fn new(custom_questions: &[ReportQuestion], completed: bool) -> Self {
+        let builtin = [
+            ("Depth of state", QuestionKind::Scale),
+            ("Sleepiness", QuestionKind::Scale),
+            ("Imagery vividness", QuestionKind::Scale),
+            ("Note", QuestionKind::Text),
+        ];
+
+        let mut fields: Vec<ReportField> = builtin.iter()
+            .map(|(prompt, kind)| ReportField {
+                question: ReportQuestion { prompt: prompt.to_string(), kind: *kind },
+                value: Self::initial_value(*kind),
+            })
+            .collect();
+
+        for question in custom_questions {
+            fields.push(ReportField {
+                question: question.clone(),
+                value: Self::initial_value(question.kind),
+            });
+        }
+
+        Self { fields, selected: 0, completed }
+    }
+
+    fn initial_value(kind: QuestionKind) -> ReportValue {
+        match kind {
+            QuestionKind::Scale => ReportValue::Scale(REPORT_SCALE_DEFAULT),
+            QuestionKind::YesNo => ReportValue::YesNo(false),
+            QuestionKind::Text => ReportValue::Text(String::new()),
+        }
+    }
+
+    /// Select the next (1) or previous (-1) question, wrapping around
+    pub fn select(&mut self, direction: i32) {
+        self.selected = crate::utils::cycle_index(self.selected, self.fields.len(), direction);
+    }
+
+    /// Adjust the selected scale (or flip the selected yes/no) by one step
+    pub fn adjust(&mut self, delta: i32) {
+        if let Some(field) = self.fields.get_mut(self.selected) {
+            match &mut field.value {
+                ReportValue::Scale(v) => {
+                    *v = (*v as i32 + delta).clamp(REPORT_SCALE_MIN as i32, REPORT_SCALE_MAX as i32) as u8;
+                },
+                ReportValue::YesNo(b) => *b = !*b,
+                ReportValue::Text(_) => {}
+            }
+        }
+    }
+
+    /// Append a character to the selected text field
+    pub fn push_char(&mut self, c: char) {
+        if let Some(ReportField { value: ReportValue::Text(text), .. }) = self.fields.get_mut(self.selected) {
+            text.push(c);
+        }
+    }
+
+    /// Remove the last character of the selected text field
+    pub fn pop_char(&mut self) {
+        if let Some(ReportField { value: ReportValue::Text(text), .. }) = self.fields.get_mut(self.selected) {
+            text.pop();
+        }
+    }
+
+    /// True if the selected field accepts typed text
+    pub fn editing_text(&self) -> bool {
+        matches!(self.fields.get(self.selected), Some(ReportField { value: ReportValue::Text(_), .. }))
+    }
+
+    /// Convert the form into a report
+    pub fn to_report(&self) -> SessionReport {
+        let scale = |idx: usize| match self.fields[idx].value {
+            ReportValue::Scale(v) => v,
+            _ => REPORT_SCALE_DEFAULT,
+        };
+        let note = match &self.fields[NOTE_FIELD].value {
+            ReportValue::Text(text) => text.trim().to_string(),
+            _ => String::new(),
+        };
+
+        SessionReport {
+            depth: scale(DEPTH_FIELD),
+            sleepiness: scale(SLEEPINESS_FIELD),
+            imagery: scale(IMAGERY_FIELD),
+            note,
+            answers: self.fields[BUILTIN_FIELD_COUNT..].iter()
+                .map(|field| ReportAnswer {
+                    question: field.question.prompt.clone(),
+                    answer: field.value.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn test_report_form_round_trip() {
+        let custom = [
+            ReportQuestion { prompt: "Felt vibrations".to_string(), kind: QuestionKind::YesNo },
+            ReportQuestion { prompt: "Body awareness".to_string(), kind: QuestionKind::Scale },
+        ];
+        let mut form = ReportForm::new(&custom, true);
+        assert_eq!(form.fields.len(), BUILTIN_FIELD_COUNT + 2);
+
+        // Up from the first field wraps to the last custom question
+        form.select(-1);
+        assert_eq!(form.selected, BUILTIN_FIELD_COUNT + 1);
+        form.adjust(3);
+        form.select(-1);
+        form.adjust(1);
+        form.select(1);
+        form.select(1);
+        assert_eq!(form.selected, DEPTH_FIELD);
+        for _ in 0..20 {
+            form.adjust(1); // Clamped at the top of the scale
+        }
+        (0..NOTE_FIELD).for_each(|_| form.select(1));
+        assert!(form.editing_text());
+        "calm ".chars().for_each(|c| form.push_char(c));
+        form.push_char('x');
+        form.pop_char();
+        form.select(-1);
+        assert!(!form.editing_text());
+        form.push_char('z'); // Ignored outside text answers
+
+        let report = form.to_report();
+        assert_eq!((report.depth, report.sleepiness, report.imagery, report.note.as_str()),
+            (REPORT_SCALE_MAX, REPORT_SCALE_DEFAULT, REPORT_SCALE_DEFAULT, "calm"));
+        let answers: Vec<_> = report.answers.iter().map(|a| (a.question.as_str(), a.answer.clone())).collect();
+        assert_eq!(answers, vec![("Felt vibrations", ReportValue::YesNo(true)),
+            ("Body awareness", ReportValue::Scale(REPORT_SCALE_DEFAULT + 3))]);
+    }
+
+    #[test]
+    fn test_sessions_in_the_same_second_get_their_own_files() {
+        let report = SessionReport {
+            depth: REPORT_SCALE_MAX, sleepiness: REPORT_SCALE_DEFAULT, imagery: REPORT_SCALE_DEFAULT, note: "calm".to_string(),
+            answers: vec![
+                ReportAnswer { question: "Felt vibrations".to_string(), answer: ReportValue::YesNo(true) },
+                ReportAnswer { question: "Body awareness".to_string(), answer: ReportValue::Scale(REPORT_SCALE_DEFAULT + 3) },
+            ],
+        };
+        let dir = TempDir::new("sessions");
+        let record = SessionRecord {
+            preset: Some("calm.json".to_string()), preset_title: None, started_at_unix: 1_760_000_000,
+            ended_at_unix: 1_760_000_600, duration_secs: 600.0, completed: true, report: Some(report),
+            eeg_recording: None, hrv: None, blind: None, manifest: None,
+        };
+        let first = record.save_in(&dir).unwrap();
+        let second = record.save_in(&dir).unwrap();
+        assert_eq!(second.file_name().unwrap(), "session_1760000000_2.json");
+        let loaded: SessionRecord = serde_json::from_str(&std::fs::read_to_string(&first).unwrap()).unwrap();
+        let answers: Vec<_> = loaded.report.unwrap().answers.into_iter().map(|a| a.answer).collect();
+        assert_eq!(answers, vec![ReportValue::YesNo(true), ReportValue::Scale(REPORT_SCALE_DEFAULT + 3)]);
+    }
+
+    #[test]
+    fn test_question_files() {
+        let custom = [
+            ReportQuestion { prompt: "Felt vibrations".to_string(), kind: QuestionKind::YesNo },
+            ReportQuestion { prompt: "Body awareness".to_string(), kind: QuestionKind::Scale },
+        ];
+        // Experiment question files: a plain list, or an object with report_questions
+        let dir = TempDir::new("questions");
+        let questions = dir.join("questions.json");
+        std::fs::write(&questions, r#"{"title": "Pilot", "report_questions": [{"prompt": "Felt vibrations", "kind": "yes_no"}]}"#).unwrap();
+        assert_eq!(load_questions(&questions).unwrap(), custom[..1]);
+        std::fs::write(&questions, r#"[{"prompt": "Body awareness"}]"#).unwrap();
+        assert_eq!(load_questions(&questions).unwrap(), custom[1..]);
+    }
+}
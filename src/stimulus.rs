@@ -0,0 +1,147 @@
+// Stimulus events: changes in what the listener hears (preset, session phase, gamma bursts, beat,
+// breath pace, playback, RF) plus the user's keypresses, so recordings can be epoched around them
+// Parameter-driven events are found by comparing successive AudioParams snapshots; phase and
+// gamma burst changes come either from the snapshot (UI thread) or from the synthesizer itself
+
+use serde::Serialize;
+use crate::audio::AudioParams;
+use crate::coherence::{BeingType, SessionPhase};
+use crate::constants::*;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StimulusEvent {
+    PresetLoaded { preset: String },
+    Phase { phase: SessionPhase },
+    GammaBurstStart,
+    GammaBurstEnd,
+    BeatChange { beat_hz: f32 },
+    BreathRate { breath_hz: f32 },
+    Playback { playing: bool },
+    Rf { enabled: bool, freq_hz: u64 },
+    Key { key: String },
+    ProtocolStep { step: usize, label: String },
+}
+
+impl StimulusEvent {
+    /// Short human-readable label (used as the EDF+ annotation text)
+    pub fn label(&self) -> String {
+        match self {
+            StimulusEvent::PresetLoaded { preset } => format!("Preset: {}", preset),
+            StimulusEvent::Phase { phase } => format!("Phase: {:?}", phase),
+            StimulusEvent::GammaBurstStart => "Gamma burst start".to_string(),
+            StimulusEvent::GammaBurstEnd => "Gamma burst end".to_string(),
+            StimulusEvent::BeatChange { beat_hz } => format!("Beat: {:.2} Hz", beat_hz),
+            StimulusEvent::BreathRate { breath_hz } => format!("Breath: {:.1}/min", breath_hz * 60.0),
+            StimulusEvent::Playback { playing: true } => "Audio on".to_string(),
+            StimulusEvent::Playback { playing: false } => "Audio off".to_string(),
+            StimulusEvent::Rf { enabled: true, freq_hz } => format!("RF on: {:.3} MHz", *freq_hz as f64 / 1e6),
+            StimulusEvent::Rf { enabled: false, .. } => "RF off".to_string(),
+            StimulusEvent::Key { key } => format!("Key: {}", key),
+            StimulusEvent::ProtocolStep { step, label } => format!("Step {}: {}", step, label),
+        }
+    }
+}
+
+/// Turns parameter snapshots into events; a fresh tracker reports the current state on its first update
+#[derive(Default)]
+pub struct StimulusTracker {
+    phase: Option<SessionPhase>,
+    gamma_burst: bool,
+    beat_hz: Option<f32>,
+    breath_hz: Option<f32>,
+    playing: Option<bool>,
+    rf_enabled: bool,
+}
+
+impl StimulusTracker {
+    /// Events since the previous update
+    pub fn update(&mut self, params: &AudioParams) -> Vec<StimulusEvent> {
+        let mut events = self.session_events(params.session_phase, params.gamma_burst);
+        events.extend(self.param_events(params, params.session_timer));
+        events
+    }
+
+    /// Session phase and gamma burst changes
+    pub fn session_events(&mut self, phase: SessionPhase, gamma_burst: bool) -> Vec<StimulusEvent> {
+        let mut events = Vec::new();
+        if self.phase != Some(phase) {
+            self.phase = Some(phase);
+            events.push(StimulusEvent::Phase { phase });
+        }
+        if self.gamma_burst != gamma_burst {
+            self.gamma_burst = gamma_burst;
+            events.push(if gamma_burst { StimulusEvent::GammaBurstStart } else { StimulusEvent::GammaBurstEnd });
+        }
+        events
+    }
+
+    /// Playback, beat, breath pacing and RF changes (`session_secs` positions sleep and wake-up ramps)
+    pub fn param_events(&mut self, params: &AudioParams, session_secs: f32) -> Vec<StimulusEvent> {
+        let mut events = Vec::new();
+
+        if self.playing != Some(params.playing) {
+            self.playing = Some(params.playing);
+            events.push(StimulusEvent::Playback { playing: params.playing });
+        }
+
+        // The beat only exists with a binaural preset
+        let binaural = params.coherence.enabled && !matches!(params.coherence.being_type, BeingType::Unknown);
+        if binaural {
+            let beat_hz = params.coherence.current_beat_hz(session_secs / 60.0);
+            if self.beat_hz.is_none_or(|last| (beat_hz - last).abs() >= STIMULUS_BEAT_CHANGE_HZ) {
+                self.beat_hz = Some(beat_hz);
+                events.push(StimulusEvent::BeatChange { beat_hz });
+            }
+        } else {
+            self.beat_hz = None;
+        }
+
+        // Breath pacing is only heard with the breath layer up
+        if params.breath_vol > 0.0 {
+            if self.breath_hz != Some(params.breath_hz) {
+                self.breath_hz = Some(params.breath_hz);
+                events.push(StimulusEvent::BreathRate { breath_hz: params.breath_hz });
+            }
+        } else {
+            self.breath_hz = None;
+        }
+
+        if self.rf_enabled != params.rf_enabled {
+            self.rf_enabled = params.rf_enabled;
+            events.push(StimulusEvent::Rf { enabled: params.rf_enabled, freq_hz: params.rf_freq_hz });
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_reports_changes_once() {
+        let mut params = AudioParams::default();
+        params.coherence.enabled = true;
+        params.coherence.being_type = BeingType::HumanCustom;
+        params.coherence.apply_custom_binaural(10.0);
+        let mut tracker = StimulusTracker::default();
+
+        let first = tracker.update(&params);
+        assert!(first.contains(&StimulusEvent::Phase { phase: SessionPhase::Startup }));
+        assert!(first.iter().any(|e| matches!(e, StimulusEvent::BeatChange { beat_hz } if (beat_hz - 10.0).abs() < 0.01)));
+        assert!(tracker.update(&params).is_empty());
+
+        // Below the reporting threshold, then a real change
+        params.coherence.apply_custom_binaural(10.05);
+        assert!(tracker.update(&params).is_empty());
+        params.coherence.apply_custom_binaural(6.0);
+        params.gamma_burst = true;
+        params.rf_enabled = true;
+        let events = tracker.update(&params);
+        assert_eq!(events.len(), 3);
+        assert!(events.contains(&StimulusEvent::GammaBurstStart));
+        assert_eq!(events.iter().find(|e| matches!(e, StimulusEvent::BeatChange { .. })).unwrap().label(), "Beat: 6.00 Hz");
+    }
+}
@@ -102,6 +102,156 @@ pub fn cycle_index(current: usize, list_len: usize, direction: i32) -> usize {
     }
 }
 
+/// Local wall-clock time as minutes since midnight
+#[cfg(unix)]
+pub fn local_minutes_of_day() -> u32 {
+    // SAFETY: time() with a null pointer and localtime_r() into a zeroed tm are always valid
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return 0;
+        }
+        (tm.tm_hour * 60 + tm.tm_min) as u32
+    }
+}
+
+/// Local wall-clock time as minutes since midnight (UTC where no timezone lookup is available)
+#[cfg(not(unix))]
+pub fn local_minutes_of_day() -> u32 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    ((secs / 60) % (24 * 60)) as u32
+}
+
+/// Calendar date and wall-clock time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LocalDateTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+/// Current local date and time
+#[cfg(unix)]
+pub fn local_date_time() -> LocalDateTime {
+    // SAFETY: as in local_minutes_of_day
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return utc_date_time(now as u64);
+        }
+        LocalDateTime {
+            year: tm.tm_year + 1900,
+            month: (tm.tm_mon + 1) as u32,
+            day: tm.tm_mday as u32,
+            hour: tm.tm_hour as u32,
+            minute: tm.tm_min as u32,
+            second: tm.tm_sec as u32,
+        }
+    }
+}
+
+/// Current date and time (UTC where no timezone lookup is available)
+#[cfg(not(unix))]
+pub fn local_date_time() -> LocalDateTime {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    utc_date_time(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0))
+}
+
+/// UTC date and time for seconds since the Unix epoch
+pub fn utc_date_time(unix_secs: u64) -> LocalDateTime {
+    // Civil-from-days (proleptic Gregorian), counting from 0000-03-01
+    let days = (unix_secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153; // March = 0
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    let secs_of_day = unix_secs % 86_400;
+    LocalDateTime {
+        year: year as i32,
+        month: month as u32,
+        day: (day_of_year - (153 * month_index + 2) / 5 + 1) as u32,
+        hour: (secs_of_day / 3600) as u32,
+        minute: (secs_of_day / 60 % 60) as u32,
+        second: (secs_of_day % 60) as u32,
+    }
+}
+
+/// Parse a 24-hour clock time ("HH:MM") into minutes since midnight
+pub fn parse_clock_time(text: &str) -> Option<u32> {
+    let (hours, minutes) = text.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    if hours < 24 && minutes < 60 {
+        Some(hours * 60 + minutes)
+    } else {
+        None
+    }
+}
+
+/// Format minutes since midnight as "HH:MM"
+pub fn format_clock_time(minutes_of_day: u32) -> String {
+    let minutes_of_day = minutes_of_day % (24 * 60);
+    format!("{:02}:{:02}", minutes_of_day / 60, minutes_of_day % 60)
+}
+
+/// Minutes from `now` until the next occurrence of `target` (both minutes since midnight)
+/// A target equal to now is treated as tomorrow
+pub fn minutes_until(now: u32, target: u32) -> u32 {
+    let day = 24 * 60;
+    let delta = (target + day - now % day) % day;
+    if delta == 0 { day } else { delta }
+}
+
+/// Format a duration in minutes as "7h05m" (or "42m" under an hour)
+pub fn format_duration_min(minutes: f32) -> String {
+    let total = minutes.max(0.0).round() as u32;
+    if total >= 60 {
+        format!("{}h{:02}m", total / 60, total % 60)
+    } else {
+        format!("{}m", total)
+    }
+}
+
+/// Scratch directory for tests, removed on drop so a failing test cleans up too
+#[cfg(test)]
+pub struct TempDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// A fresh, empty directory unique to `name` and this test process
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("soulwhistle_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,4 +291,28 @@ mod tests {
         assert_eq!(cycle_index(2, 5, -1), 1);
         assert_eq!(cycle_index(0, 5, -1), 4); // Wrap around
     }
+
+    #[test]
+    fn test_parse_clock_time() {
+        assert_eq!(parse_clock_time("06:45"), Some(6 * 60 + 45));
+        assert_eq!(parse_clock_time("23:59"), Some(23 * 60 + 59));
+        assert_eq!(parse_clock_time("24:00"), None);
+        assert_eq!(parse_clock_time("7"), None);
+    }
+
+    #[test]
+    fn test_utc_date_time() {
+        let leap_day = utc_date_time(951_827_696);
+        assert_eq!((leap_day.year, leap_day.month, leap_day.day), (2000, 2, 29));
+        assert_eq!((leap_day.hour, leap_day.minute, leap_day.second), (12, 34, 56));
+        let later = utc_date_time(1_792_378_995);
+        assert_eq!((later.year, later.month, later.day, later.hour), (2026, 10, 19, 3));
+    }
+
+    #[test]
+    fn test_minutes_until_wraps_midnight() {
+        assert_eq!(minutes_until(22 * 60, 6 * 60 + 30), 8 * 60 + 30);
+        assert_eq!(minutes_until(6 * 60, 7 * 60), 60);
+        assert_eq!(minutes_until(7 * 60, 7 * 60), 24 * 60);
+    }
 }
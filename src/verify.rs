@@ -0,0 +1,420 @@
+// Preset self-verification: renders a preset offline through the synthesizer and measures what it
+// actually plays (carriers, beat, harmonics, gamma bursts, phase envelope, signal carrier modulation)
+// against what it declares
+
+use crate::audio::{AudioParams, SignalType, Synthesizer};
+use crate::coherence::{BeingType, CoherenceParams};
+use crate::constants::*;
+use crate::dsp::DftPlan;
+
+/// One measured claim
+pub struct Check {
+    pub name: &'static str,
+    pub expected: String,
+    pub measured: String,
+    pub pass: bool,
+}
+
+/// All checks of one preset
+pub struct PresetReport {
+    pub preset: String,
+    pub checks: Vec<Check>,
+}
+
+impl PresetReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.pass)
+    }
+}
+
+/// Render and check a preset; presets without a binaural layer or modulated signal carrier get no checks
+pub fn verify_preset(preset: &str, params: &AudioParams) -> PresetReport {
+    let mut checks = Vec::new();
+    if params.coherence.enabled && params.coherence.being_type != BeingType::Unknown {
+        check_binaural(params, &mut checks);
+    }
+    if !params.lock_signal_layer && params.carrier_vol > 0.0 {
+        check_signal_carrier(params, &mut checks);
+    }
+    PresetReport { preset: preset.to_string(), checks }
+}
+
+/// What the binaural layer played over a whole session
+struct BinauralRender {
+    envelope: Vec<f32>,        // Left channel RMS per envelope block
+    bursts: Vec<(f32, f32)>,   // Gamma bursts (start, length) in seconds
+    window: Option<(Vec<f64>, Vec<f64>)>, // First full-volume stretch without gamma bursts
+}
+
+fn render_binaural(params: &AudioParams) -> BinauralRender {
+    let rate = VERIFY_SAMPLE_RATE_HZ;
+    let coherence = &params.coherence;
+    let mut params = params.clone();
+    params.lock_signal_layer = true; // Only the binaural layer
+    let mut synth = Synthesizer::new(rate, 0);
+
+    let block_len = (rate * VERIFY_BLOCK_SECS) as usize;
+    let blocks_per_envelope = (VERIFY_ENVELOPE_SECS / VERIFY_BLOCK_SECS).round() as usize;
+    let total_blocks = (coherence.total_session_min() * 60.0 / VERIFY_BLOCK_SECS) as usize;
+    let window_len = (rate * VERIFY_WINDOW_SECS) as usize;
+    let gamma_hz = (GAMMA_CARRIER_HZ + GAMMA_BEAT_HZ) as f64; // Right ear during a burst
+
+    let mut render = BinauralRender { envelope: Vec::new(), bursts: Vec::new(), window: None };
+    let (mut left, mut right) = (Vec::with_capacity(block_len), Vec::with_capacity(block_len));
+    let (mut capture_left, mut capture_right) = (Vec::new(), Vec::new());
+    let mut burst_start = None;
+    let mut envelope_energy = 0.0;
+
+    for block in 0..total_blocks {
+        left.clear();
+        right.clear();
+        for _ in 0..block_len {
+            let (l, r) = synth.next_sample(&params);
+            left.push(l);
+            right.push(r);
+        }
+        let t = block as f32 * VERIFY_BLOCK_SECS;
+
+        // Share of the right channel's amplitude that is the gamma burst tone
+        let right_peak = rms(&right) * std::f64::consts::SQRT_2;
+        let gamma = if right_peak > 1e-9 { tone_amplitude(&right, gamma_hz, rate as f64) / right_peak } else { 0.0 };
+        match (gamma > VERIFY_GAMMA_ON_RATIO, burst_start) {
+            (true, None) => burst_start = Some(t),
+            (false, Some(start)) => {
+                render.bursts.push((start, t - start));
+                burst_start = None;
+            }
+            _ => {}
+        }
+
+        envelope_energy += left.iter().map(|&x| x as f64 * x as f64).sum::<f64>();
+        if (block + 1) % blocks_per_envelope == 0 {
+            render.envelope.push((envelope_energy / (block_len * blocks_per_envelope) as f64).sqrt() as f32);
+            envelope_energy = 0.0;
+        }
+
+        if render.window.is_none() {
+            let full_volume = t / 60.0 >= coherence.startup_end_min()
+                && (t + VERIFY_BLOCK_SECS) / 60.0 <= coherence.stabilization_end_min();
+            if full_volume && gamma < VERIFY_GAMMA_CLEAN_RATIO {
+                capture_left.extend(left.iter().map(|&x| x as f64));
+                capture_right.extend(right.iter().map(|&x| x as f64));
+            } else {
+                capture_left.clear();
+                capture_right.clear();
+            }
+            if capture_left.len() >= window_len {
+                capture_left.truncate(window_len);
+                capture_right.truncate(window_len);
+                render.window = Some((std::mem::take(&mut capture_left), std::mem::take(&mut capture_right)));
+            }
+        }
+    }
+    render
+}
+
+fn check_binaural(params: &AudioParams, checks: &mut Vec<Check>) {
+    let coherence = &params.coherence;
+    let render = render_binaural(params);
+
+    match &render.window {
+        Some((left, right)) => {
+            let spectra = Spectra::new(left, right, VERIFY_SAMPLE_RATE_HZ as f64);
+            let (left_hz, right_hz) = (spectra.peak_hz(&spectra.left), spectra.peak_hz(&spectra.right));
+            let declared = (coherence.left_carrier as f64, coherence.right_carrier as f64);
+            checks.push(Check {
+                name: "carriers",
+                expected: format!("{:.2} / {:.2} Hz", declared.0, declared.1),
+                measured: format!("{:.3} / {:.3} Hz", left_hz, right_hz),
+                pass: (left_hz - declared.0).abs() <= VERIFY_FREQ_TOL_HZ && (right_hz - declared.1).abs() <= VERIFY_FREQ_TOL_HZ,
+            });
+
+            let beat = (left_hz - right_hz).abs();
+            let declared_beat = coherence.binaural_beat_hz() as f64;
+            checks.push(Check {
+                name: "beat",
+                expected: format!("{:.2} Hz", declared_beat),
+                measured: format!("{:.3} Hz", beat),
+                pass: (beat - declared_beat).abs() <= VERIFY_FREQ_TOL_HZ,
+            });
+            if let Some(described) = params.preset_description.as_deref().and_then(described_beat_hz) {
+                checks.push(Check {
+                    name: "described beat",
+                    expected: format!("{:.2} Hz", described),
+                    measured: format!("{:.3} Hz", beat),
+                    pass: (beat - described).abs() <= VERIFY_FREQ_TOL_HZ.max(described * 0.005), // Descriptions round
+                });
+            }
+
+            let carrier = spectra.band_energy(&spectra.left, left_hz);
+            for (name, hz, enabled, relative) in [
+                ("220 Hz harmonic", HARMONIC_220_HZ, coherence.harmonic_220hz, HARMONIC_220_RELATIVE_VOL),
+                ("495 Hz harmonic", HARMONIC_495_HZ, coherence.harmonic_495hz, HARMONIC_495_RELATIVE_VOL),
+            ] {
+                let level = (spectra.band_energy(&spectra.left, hz as f64) / carrier).sqrt();
+                let (expected, pass) = if enabled {
+                    (format!("{:.3} of carrier", relative), (level - relative as f64).abs() <= relative as f64 * VERIFY_LEVEL_TOL)
+                } else {
+                    ("off".to_string(), level < VERIFY_ABSENT_LEVEL)
+                };
+                checks.push(Check { name, expected, measured: format!("{:.3} of carrier", level), pass });
+            }
+        }
+        None => checks.push(Check {
+            name: "carriers",
+            expected: format!("{:.0} s at full volume without gamma bursts", VERIFY_WINDOW_SECS),
+            measured: "no such stretch".to_string(),
+            pass: false,
+        }),
+    }
+
+    checks.push(check_gamma_bursts(coherence, &render.bursts));
+    checks.push(check_envelope(params, &render.envelope));
+}
+
+/// Focus 10 interrupts the theta beat with regular gamma bursts; nothing else should have any
+fn check_gamma_bursts(coherence: &CoherenceParams, bursts: &[(f32, f32)]) -> Check {
+    if coherence.being_type != BeingType::HumanFocus10 {
+        return Check {
+            name: "gamma bursts",
+            expected: "none".to_string(),
+            measured: format!("{}", bursts.len()),
+            pass: bursts.is_empty(),
+        };
+    }
+
+    let period = GAMMA_BURST_INTERVAL_SECS + GAMMA_BURST_DURATION_SECS; // The interval restarts after each burst
+    let expected = format!("first at {:.1} s, {:.1} s long, every {:.1} s", GAMMA_BURST_INTERVAL_SECS, GAMMA_BURST_DURATION_SECS, period);
+    let Some(&(first, _)) = bursts.first() else {
+        return Check { name: "gamma bursts", expected, measured: "none".to_string(), pass: false };
+    };
+    let lengths = bursts.iter().map(|b| b.1);
+    let gaps: Vec<f32> = bursts.windows(2).map(|pair| pair[1].0 - pair[0].0).collect();
+    let spread = |values: &mut dyn Iterator<Item = f32>| {
+        values.fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)))
+    };
+    let (short, long) = spread(&mut lengths.clone());
+    let (min_gap, max_gap) = if gaps.is_empty() { (period, period) } else { spread(&mut gaps.iter().copied()) };
+
+    Check {
+        name: "gamma bursts",
+        expected,
+        measured: format!("{} bursts, first at {:.2} s, {:.2}-{:.2} s long, every {:.2}-{:.2} s",
+            bursts.len(), first, short, long, min_gap, max_gap),
+        pass: (first - GAMMA_BURST_INTERVAL_SECS).abs() <= VERIFY_GAMMA_TOL_SECS
+            && lengths.clone().all(|l| (l - GAMMA_BURST_DURATION_SECS).abs() <= VERIFY_GAMMA_TOL_SECS)
+            && gaps.iter().all(|g| (g - period).abs() <= VERIFY_GAMMA_TOL_SECS),
+    }
+}
+
+/// The measured envelope against the declared phases: startup ramp from silence, full volume, return to 30%
+fn check_envelope(params: &AudioParams, envelope: &[f32]) -> Check {
+    let coherence = &params.coherence;
+    // Sines at different frequencies add in power
+    let mut power = 1.0;
+    if coherence.harmonic_220hz {
+        power += HARMONIC_220_RELATIVE_VOL * HARMONIC_220_RELATIVE_VOL;
+    }
+    if coherence.harmonic_495hz {
+        power += HARMONIC_495_RELATIVE_VOL * HARMONIC_495_RELATIVE_VOL;
+    }
+    let full_rms = params.master_vol * coherence.volume * (power / 2.0).sqrt();
+
+    let worst = envelope.iter().enumerate()
+        .map(|(i, &rms)| {
+            let minutes = (i as f32 + 0.5) * VERIFY_ENVELOPE_SECS / 60.0;
+            (minutes, rms / full_rms - declared_phase_volume(coherence, minutes))
+        })
+        .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()));
+
+    let expected = format!("ramp {:.1} min, full to {:.1} min, down to 30% by {:.1} min",
+        coherence.startup_end_min(), coherence.stabilization_end_min(), coherence.total_session_min());
+    match worst {
+        Some((minutes, error)) => Check {
+            name: "phase envelope",
+            expected,
+            measured: format!("largest error {:+.1}% at {:.1} min", error * 100.0, minutes),
+            pass: error.abs() <= VERIFY_ENVELOPE_TOL,
+        },
+        None => Check { name: "phase envelope", expected, measured: "no session length".to_string(), pass: false },
+    }
+}
+
+fn declared_phase_volume(coherence: &CoherenceParams, minutes: f32) -> f32 {
+    let startup = coherence.startup_duration_min;
+    let return_start = coherence.stabilization_end_min();
+    if minutes < startup {
+        minutes / startup
+    } else if minutes < return_start {
+        1.0
+    } else if coherence.return_duration_min > 0.0 {
+        1.0 - 0.7 * ((minutes - return_start) / coherence.return_duration_min).min(1.0)
+    } else {
+        0.3
+    }
+}
+
+/// The modulated signal carriers: AM puts a sideband each side at the Schumann rate,
+/// FM (20 Hz deviation) moves most of the energy from the carrier into its sidebands
+fn check_signal_carrier(params: &AudioParams, checks: &mut Vec<Check>) {
+    let carrier_hz = match params.carrier_type {
+        SignalType::SchumannAM | SignalType::SchumannFM => CARRIER_BASE_HZ,
+        SignalType::Schumann783AM => CARRIER_783_HZ,
+        _ => return,
+    } as f64;
+
+    let solo = AudioParams {
+        carrier_vol: 1.0,
+        harmonic_vol: 0.0,
+        ping_vol: 0.0,
+        chirp_vol: 0.0,
+        pad_vol: 0.0,
+        breath_vol: 0.0,
+        master_vol: 1.0,
+        coherence: CoherenceParams { enabled: false, being_type: BeingType::Unknown, ..params.coherence },
+        ..params.clone()
+    };
+    let rate = VERIFY_SAMPLE_RATE_HZ;
+    let mut synth = Synthesizer::new(rate, 0);
+    let samples: Vec<f64> = (0..(rate * VERIFY_WINDOW_SECS) as usize).map(|_| synth.next_sample(&solo).0 as f64).collect();
+    let spectra = Spectra::new(&samples, &samples, rate as f64);
+
+    let schumann = SCHUMANN_RESONANCE_HZ as f64;
+    let sidebands: f64 = (1..=VERIFY_SIDEBANDS)
+        .map(|k| {
+            let offset = k as f64 * schumann;
+            spectra.band_energy(&spectra.left, carrier_hz - offset) + spectra.band_energy(&spectra.left, carrier_hz + offset)
+        })
+        .sum();
+    let ratio = (sidebands / spectra.band_energy(&spectra.left, carrier_hz)).sqrt();
+
+    let (expected, pass) = if params.carrier_type == SignalType::SchumannFM {
+        ("sidebands stronger than the carrier".to_string(), ratio > 1.0)
+    } else {
+        // Carrier AM_MODULATION_MIN, one sideband AM_MODULATION_MIN / 2 each side
+        let am = std::f64::consts::FRAC_1_SQRT_2;
+        (format!("sidebands {:.3} of carrier", am), (ratio - am).abs() <= am * VERIFY_LEVEL_TOL)
+    };
+    checks.push(Check {
+        name: "signal carrier",
+        expected: format!("{:?} at {:.0} Hz: {}", params.carrier_type, carrier_hz, expected),
+        measured: format!("sidebands {:.3} of carrier", ratio),
+        pass,
+    });
+}
+
+/// Hann-windowed spectra of a stereo stretch
+struct Spectra {
+    left: Vec<f64>, // Power per bin
+    right: Vec<f64>,
+    bin_hz: f64,
+}
+
+impl Spectra {
+    fn new(left: &[f64], right: &[f64], sample_rate: f64) -> Self {
+        let n = left.len();
+        let window = |x: &[f64]| -> Vec<f64> {
+            x.iter().enumerate()
+                .map(|(i, v)| v * (0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / (n - 1) as f64).cos()))
+                .collect()
+        };
+        let (l, r) = DftPlan::new(n).real_pair(&window(left), &window(right));
+        Self {
+            left: l.iter().map(|c| c.norm_sqr()).collect(),
+            right: r.iter().map(|c| c.norm_sqr()).collect(),
+            bin_hz: sample_rate / n as f64,
+        }
+    }
+
+    /// Frequency of the strongest bin, refined by a parabola through the log power around it
+    fn peak_hz(&self, power: &[f64]) -> f64 {
+        let k = (1..power.len() - 1).max_by(|&a, &b| power[a].total_cmp(&power[b])).unwrap_or(1);
+        let (a, b, c) = (power[k - 1].ln(), power[k].ln(), power[k + 1].ln());
+        let denominator = a - 2.0 * b + c;
+        let offset = if denominator.abs() > 0.0 { 0.5 * (a - c) / denominator } else { 0.0 };
+        (k as f64 + offset) * self.bin_hz
+    }
+
+    /// Energy within VERIFY_BAND_HZ of a frequency
+    fn band_energy(&self, power: &[f64], hz: f64) -> f64 {
+        let low = ((hz - VERIFY_BAND_HZ) / self.bin_hz).ceil().max(0.0) as usize;
+        let high = (((hz + VERIFY_BAND_HZ) / self.bin_hz).floor() as usize).min(power.len() - 1);
+        power.get(low..=high).map_or(0.0, |bins| bins.iter().sum())
+    }
+}
+
+fn rms(samples: &[f32]) -> f64 {
+    (samples.iter().map(|&x| x as f64 * x as f64).sum::<f64>() / samples.len().max(1) as f64).sqrt()
+}
+
+/// Amplitude of one frequency in a block (Goertzel)
+fn tone_amplitude(samples: &[f32], hz: f64, sample_rate: f64) -> f64 {
+    let coeff = 2.0 * (2.0 * std::f64::consts::PI * hz / sample_rate).cos();
+    let (mut s1, mut s2) = (0.0, 0.0);
+    for &x in samples {
+        let s = x as f64 + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+    2.0 * (s1 * s1 + s2 * s2 - coeff * s1 * s2).max(0.0).sqrt() / samples.len() as f64
+}
+
+/// The beat a description opens with ("4.11 Hz theta ...", "18 Hz low-beta ..."), if any
+fn described_beat_hz(description: &str) -> Option<f64> {
+    let words: Vec<&str> = description.split_whitespace().take(2).collect();
+    match words.as_slice() {
+        [number, unit] if unit.starts_with("Hz") => number.parse().ok(),
+        [first, ..] => first.strip_suffix("Hz").and_then(|n| n.parse().ok()),
+        [] => None,
+    }
+    .filter(|hz| *hz > 0.0 && *hz <= BEAT_MAX_HZ as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verifies_focus_10_session() {
+        let mut params = AudioParams {
+            lock_signal_layer: true,
+            preset_description: Some("4.11 Hz theta".to_string()),
+            ..Default::default()
+        };
+        params.coherence = CoherenceParams {
+            enabled: true,
+            being_type: BeingType::HumanFocus10,
+            left_carrier: 400.0,
+            right_carrier: 404.11,
+            harmonic_220hz: true,
+            startup_duration_min: 0.5,
+            induction_duration_min: 1.5,
+            stabilization_duration_min: 0.5,
+            return_duration_min: 0.5,
+            ..Default::default()
+        };
+        let report = verify_preset("focus 10", &params);
+        for check in &report.checks {
+            assert!(check.pass, "{}: expected {}, measured {}", check.name, check.expected, check.measured);
+        }
+        assert_eq!(report.checks.len(), 7);
+
+        // A description that claims another beat fails
+        params.preset_description = Some("4.5 Hz theta".to_string());
+        let report = verify_preset("focus 10", &params);
+        let failed: Vec<&str> = report.checks.iter().filter(|c| !c.pass).map(|c| c.name).collect();
+        assert_eq!(failed, ["described beat"]);
+
+        let signal = AudioParams { carrier_type: SignalType::Schumann783AM, carrier_vol: 0.5, ..Default::default() };
+        let report = verify_preset("signal", &signal);
+        assert!(report.checks.len() == 1 && report.passed(), "{}", report.checks[0].measured);
+    }
+
+    #[test]
+    fn test_schumann_fm_carrier_is_modulated() {
+        let signal = AudioParams { carrier_type: SignalType::SchumannFM, carrier_vol: 0.5, ..Default::default() };
+        let report = verify_preset("signal", &signal);
+        assert_eq!(report.checks.len(), 1);
+        assert!(report.passed(), "{}", report.checks[0].measured);
+    }
+}
@@ -0,0 +1,133 @@
+// Minimal WAV file I/O: 16-bit PCM output for offline rendering, PCM or float input for analysis
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use crate::constants::*;
+
+const WAV_HEADER_BYTES: u32 = 44;
+const WAV_FORMAT_PCM: u16 = 1;
+const WAV_FORMAT_FLOAT: u16 = 3;
+const WAV_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Streams stereo frames to a 16-bit PCM WAV file; sizes are patched in by `finish`
+pub struct WavWriter {
+    file: BufWriter<File>,
+    frames: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: &str, sample_rate: u32) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write_header(&mut file, sample_rate, 0)?;
+        Ok(Self { file, frames: 0 })
+    }
+
+    pub fn write_frame(&mut self, left: f32, right: f32) -> std::io::Result<()> {
+        for sample in [left, right] {
+            let value = (sample.clamp(AUDIO_CLAMP_MIN, AUDIO_CLAMP_MAX) * PCM_I16_MAX) as i16;
+            self.file.write_all(&value.to_le_bytes())?;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Fill in the chunk sizes and flush
+    pub fn finish(mut self, sample_rate: u32) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.file, sample_rate, self.frames)?;
+        self.file.flush()
+    }
+}
+
+fn write_header<W: Write>(out: &mut W, sample_rate: u32, frames: u32) -> std::io::Result<()> {
+    let channels: u16 = 2;
+    let bits: u16 = 16;
+    let block_align = channels * bits / 8;
+    let data_bytes = frames * block_align as u32;
+
+    out.write_all(b"RIFF")?;
+    out.write_all(&(WAV_HEADER_BYTES - 8 + data_bytes).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&WAV_FORMAT_PCM.to_le_bytes())?;
+    out.write_all(&channels.to_le_bytes())?;
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+    out.write_all(&block_align.to_le_bytes())?;
+    out.write_all(&bits.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_bytes.to_le_bytes())
+}
+
+/// Stereo audio read from a WAV file, on the 16-bit PCM scale whatever the file's format
+pub struct WavAudio {
+    pub sample_rate: u32,
+    pub left: Vec<f32>,
+    pub right: Vec<f32>,
+}
+
+impl WavAudio {
+    pub fn duration_secs(&self) -> f64 {
+        self.left.len() as f64 / self.sample_rate as f64
+    }
+}
+
+/// Read the first two channels of an 8/16/24/32-bit PCM or 32/64-bit float WAV file
+pub fn read_stereo_wav(path: &str) -> Result<WavAudio, String> {
+    let mut bytes = Vec::new();
+    File::open(path).and_then(|mut f| f.read_to_end(&mut bytes)).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(format!("{} is not a WAV file", path));
+    }
+
+    let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+    let u32_at = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+    let mut format = None; // (format tag, channels, sample rate, bits)
+    let mut data = None;
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let size = u32_at(pos + 4) as usize;
+        let body = pos + 8;
+        let end = (body + size).min(bytes.len());
+        match &bytes[pos..pos + 4] {
+            b"fmt " if size >= 16 && end >= body + 16 => {
+                let mut tag = u16_at(body);
+                if tag == WAV_FORMAT_EXTENSIBLE && end >= body + 26 {
+                    tag = u16_at(body + 24); // First bytes of the sub-format GUID
+                }
+                format = Some((tag, u16_at(body + 2) as usize, u32_at(body + 4), u16_at(body + 14)));
+            }
+            b"data" => data = Some(&bytes[body..end]),
+            _ => {}
+        }
+        pos = body + size + size % 2; // Chunks are word-aligned
+    }
+
+    let (tag, channels, sample_rate, bits) = format.ok_or_else(|| format!("{} has no format chunk", path))?;
+    let data = data.ok_or_else(|| format!("{} has no audio data", path))?;
+    if channels < 2 {
+        return Err(format!("{} must be stereo (2 channels)", path));
+    }
+    if sample_rate == 0 {
+        return Err(format!("{} has an invalid sample rate", path));
+    }
+    let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+        (WAV_FORMAT_PCM, 8) => |b| (b[0] as f32 - 128.0) * 256.0,
+        (WAV_FORMAT_PCM, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32,
+        (WAV_FORMAT_PCM, 24) => |b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 256.0,
+        (WAV_FORMAT_PCM, 32) => |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 65536.0,
+        (WAV_FORMAT_FLOAT, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) * 32768.0,
+        (WAV_FORMAT_FLOAT, 64) => |b| (f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) * 32768.0) as f32,
+        _ => return Err(format!("{}: unsupported WAV encoding (format {}, {} bits)", path, tag, bits)),
+    };
+
+    let sample_bytes = bits as usize / 8;
+    let frame_bytes = sample_bytes * channels;
+    let frames = data.len() / frame_bytes;
+    let mut audio = WavAudio { sample_rate, left: Vec::with_capacity(frames), right: Vec::with_capacity(frames) };
+    for frame in data.chunks_exact(frame_bytes) {
+        audio.left.push(decode(&frame[..sample_bytes]));
+        audio.right.push(decode(&frame[sample_bytes..2 * sample_bytes]));
+    }
+    Ok(audio)
+}